@@ -0,0 +1,53 @@
+#version 150
+
+// Sprite effects. See SpriteEffects in src/sprite_effects.rs
+// The palette size has to match MAX_PALETTE_COLORS.
+
+in vec2 v_uv;
+in vec4 v_color;
+
+uniform sampler2D u_texture;
+uniform vec4 u_diffuse;
+
+uniform float u_flash;
+uniform vec4 u_flash_color;
+uniform float u_dissolve;
+uniform vec2 u_texture_size;
+uniform int u_palette_size;
+uniform vec4 u_palette_from[8];
+uniform vec4 u_palette_to[8];
+
+out vec4 o_color;
+
+float noise(vec2 texel) {
+    return fract(sin(dot(texel, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    vec4 texel = texture(u_texture, v_uv);
+
+    for (int i = 0; i < 8; i++) {
+        if (i >= u_palette_size) {
+            break;
+        }
+        if (distance(texel.rgb, u_palette_from[i].rgb) < 0.01) {
+            texel.rgb = u_palette_to[i].rgb;
+            break;
+        }
+    }
+
+    // Every texel of the sprite is removed at its own dissolve value.
+    if (u_dissolve > 0.0) {
+        float value = noise(floor(v_uv * u_texture_size));
+        if (value < u_dissolve) {
+            discard;
+        }
+        if (value < u_dissolve + 0.08) {
+            texel.rgb = u_flash_color.rgb;
+        }
+    }
+
+    vec4 color = texel * u_diffuse * v_color;
+    color.rgb = mix(color.rgb, u_flash_color.rgb, u_flash);
+    o_color = color;
+}
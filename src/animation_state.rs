@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+
+use tetra::graphics::Color;
+use tetra::math::Vec2;
+use tetra::Context;
+
+use crate::image_assets::ImageAssets;
+use crate::sprite::Sprite;
+
+/// Condition of a transition. Every condition of the transition has to be true.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransitionCondition {
+    /// Condition that is set by AnimationStateMachine::set_condition() is true.
+    When(&'static str),
+    /// Condition that is set by AnimationStateMachine::set_condition() is false or not set.
+    Unless(&'static str),
+    /// Animation of the state reached its last frame. (is_loop = false) or looped.
+    AnimationEnd,
+    /// Animation frame event in the last update_sprites(). See AnimationMultiTextures::events
+    Event(&'static str),
+}
+
+struct AnimationState {
+    animation: String,
+    /// None: use loop flag of the animation.
+    is_loop: Option<bool>,
+}
+
+struct AnimationTransition {
+    /// State names. Empty: from any state.
+    from: Vec<&'static str>,
+    to: &'static str,
+    conditions: Vec<TransitionCondition>,
+}
+
+/// States and transitions of a sprite. Declare it once and share it with every AnimationStateMachine.
+///
+/// ```text
+/// AnimationGraph::new("stand")
+///     .state("stand", "player-stand", None)
+///     .state("run", "player-run", None)
+///     .transition(&["stand"], "run", vec![TransitionCondition::When("running")])
+/// ```
+///
+/// Transitions are checked in the order they are added. The first one that matches is used.
+pub struct AnimationGraph {
+    initial_state: &'static str,
+    states: HashMap<&'static str, AnimationState>,
+    transitions: Vec<AnimationTransition>,
+}
+
+impl AnimationGraph {
+    pub fn new(initial_state: &'static str) -> AnimationGraph {
+        AnimationGraph {
+            initial_state: initial_state,
+            states: HashMap::new(),
+            transitions: vec![],
+        }
+    }
+
+    /// Add state that plays "animation". is_loop: None uses loop flag of the animation.
+    pub fn state(
+        mut self,
+        name: &'static str,
+        animation: &str,
+        is_loop: Option<bool>,
+    ) -> AnimationGraph {
+        self.states.insert(
+            name,
+            AnimationState {
+                animation: String::from(animation),
+                is_loop: is_loop,
+            },
+        );
+        self
+    }
+
+    /// Add transition from states in "from" (empty: any state) to "to".
+    pub fn transition(
+        mut self,
+        from: &[&'static str],
+        to: &'static str,
+        conditions: Vec<TransitionCondition>,
+    ) -> AnimationGraph {
+        self.transitions.push(AnimationTransition {
+            from: from.to_vec(),
+            to: to,
+            conditions: conditions,
+        });
+        self
+    }
+}
+
+/// One-shot sprite that plays on top of the state animation. Ex: slash
+struct Overlay {
+    name: &'static str,
+    sprite: Sprite,
+}
+
+/// Current state of an AnimationGraph with one-shot overlays, hit flash and damage tint.
+///
+/// Call update_sprites() once per frame instead of Sprite::update().
+/// Then set conditions and call update_state() for changing state.
+pub struct AnimationStateMachine {
+    state: &'static str,
+    conditions: HashMap<&'static str, bool>,
+    /// Events of the state animation in the last update_sprites().
+    events: Vec<String>,
+    overlays: Vec<Overlay>,
+
+    /// Time left of hit flash. (ms)
+    flash_time: u128,
+    /// Length of the last hit flash. (ms) Flash fades out over it.
+    flash_duration: u128,
+    flash_color: Color,
+    /// Time left of damage tint. (ms)
+    tint_time: u128,
+    tint_color: Color,
+}
+
+impl AnimationStateMachine {
+    pub fn new() -> AnimationStateMachine {
+        AnimationStateMachine {
+            state: "",
+            conditions: HashMap::new(),
+            events: vec![],
+            overlays: vec![],
+            flash_time: 0,
+            flash_duration: 0,
+            flash_color: Color::rgb(1.0, 1.0, 1.0),
+            tint_time: 0,
+            tint_color: Color::rgb(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Clear state, conditions, overlays, flash and tint. Use it with pooled objects.
+    pub fn reset(&mut self) {
+        self.state = "";
+        self.conditions.clear();
+        self.events.clear();
+        self.overlays.clear();
+        self.flash_time = 0;
+        self.tint_time = 0;
+    }
+
+    /// Name of the current state. "" before start().
+    pub fn state(&self) -> &str {
+        self.state
+    }
+
+    pub fn set_condition(&mut self, name: &'static str, value: bool) {
+        self.conditions.insert(name, value);
+    }
+
+    pub fn condition(&self, name: &str) -> bool {
+        match self.conditions.get(name) {
+            Some(v) => *v,
+            None => false,
+        }
+    }
+
+    /// Play the initial state of the graph.
+    ///
+    /// # Return:
+    ///
+    /// False: animation of the state is missing.
+    ///
+    pub fn start(
+        &mut self,
+        graph: &AnimationGraph,
+        sprite: &mut Sprite,
+        image_assets: &ImageAssets,
+    ) -> bool {
+        self.enter(graph, graph.initial_state, sprite, image_assets)
+    }
+
+    /// Change to the state without checking transitions.
+    ///
+    /// # Return:
+    ///
+    /// False: no state or animation with the name. The state does not change.
+    ///
+    pub fn enter(
+        &mut self,
+        graph: &AnimationGraph,
+        name: &'static str,
+        sprite: &mut Sprite,
+        image_assets: &ImageAssets,
+    ) -> bool {
+        let state = match graph.states.get(name) {
+            Some(v) => v,
+            None => {
+                println!("Animation state error: unknown state {}", name);
+                return false;
+            }
+        };
+
+        match image_assets.get_animation_object(state.animation.as_str()) {
+            Some(mut animation) => {
+                match state.is_loop {
+                    Some(v) => animation.is_loop = Some(v),
+                    None => (),
+                };
+                sprite.play(&animation);
+                self.state = name;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move sprite, overlays, flash and tint by one frame of the game.
+    /// Flash and tint are set into SpriteEffects of the sprite.
+    ///
+    /// # Return:
+    ///
+    /// Events of the state animation. See Sprite::update()
+    ///
+    pub fn update_sprites(&mut self, sprite: &mut Sprite) -> Vec<String> {
+        self.events = sprite.update();
+
+        for overlay in self.overlays.iter_mut() {
+            overlay.sprite.update();
+        }
+        // One-shot overlay is removed after its last frame.
+        self.overlays
+            .retain(|overlay| overlay.sprite.is_end_of_animation() == false);
+
+        match self.flash_time.checked_sub(crate::ONE_FRAME.as_millis()) {
+            Some(v) => self.flash_time = v,
+            None => self.flash_time = 0,
+        };
+        if self.flash_time > 0 {
+            sprite.effects.flash = self.flash_time as f32 / self.flash_duration.max(1) as f32;
+            sprite.effects.flash_color = self.flash_color;
+        } else {
+            sprite.effects.flash = 0.0;
+        }
+
+        match self.tint_time.checked_sub(crate::ONE_FRAME.as_millis()) {
+            Some(v) => self.tint_time = v,
+            None => self.tint_time = 0,
+        };
+        if self.tint_time > 0 {
+            sprite.effects.tint = self.tint_color;
+        } else {
+            sprite.effects.tint = Color::rgb(1.0, 1.0, 1.0);
+        }
+
+        self.events.clone()
+    }
+
+    /// Change state with the first transition that matches.
+    ///
+    /// # Return:
+    ///
+    /// True: state changed.
+    ///
+    pub fn update_state(
+        &mut self,
+        graph: &AnimationGraph,
+        sprite: &mut Sprite,
+        image_assets: &ImageAssets,
+    ) -> bool {
+        let mut next_state = None;
+        for transition in graph.transitions.iter() {
+            if transition.to == self.state {
+                continue;
+            }
+
+            if transition.from.len() > 0 && transition.from.contains(&self.state) == false {
+                continue;
+            }
+
+            if transition
+                .conditions
+                .iter()
+                .all(|condition| self.is_true(condition, sprite))
+            {
+                next_state = Some(transition.to);
+                break;
+            }
+        }
+
+        match next_state {
+            Some(name) => self.enter(graph, name, sprite, image_assets),
+            None => false,
+        }
+    }
+
+    fn is_true(&self, condition: &TransitionCondition, sprite: &Sprite) -> bool {
+        match condition {
+            TransitionCondition::When(name) => self.condition(name),
+            TransitionCondition::Unless(name) => self.condition(name) == false,
+            TransitionCondition::AnimationEnd => sprite.is_end_of_animation(),
+            TransitionCondition::Event(name) => self.events.iter().any(|v| v == name),
+        }
+    }
+
+    /// Play one-shot animation on top of the state animation. Overlay with the same name restarts.
+    pub fn play_overlay(
+        &mut self,
+        name: &'static str,
+        animation_name: &str,
+        image_assets: &ImageAssets,
+    ) {
+        let mut animation = match image_assets.get_animation_object(animation_name) {
+            Some(v) => v,
+            None => return,
+        };
+        animation.is_loop = Some(false);
+
+        self.overlays.retain(|overlay| overlay.name != name);
+
+        let mut sprite = Sprite::new();
+        sprite.play(&animation);
+        self.overlays.push(Overlay {
+            name: name,
+            sprite: sprite,
+        });
+    }
+
+    pub fn has_overlay(&self, name: &str) -> bool {
+        self.overlays.iter().any(|overlay| overlay.name == name)
+    }
+
+    pub fn draw_overlay(
+        &mut self,
+        ctx: &mut Context,
+        name: &str,
+        position: Vec2<f32>,
+        flip_x: bool,
+        image_assets: &ImageAssets,
+    ) {
+        match self
+            .overlays
+            .iter_mut()
+            .find(|overlay| overlay.name == name)
+        {
+            Some(overlay) => {
+                overlay.sprite.flip_x(flip_x);
+                overlay.sprite.draw(ctx, position, 0.0, image_assets);
+            }
+            None => (),
+        };
+    }
+
+    /// Fill the sprite with "color" that fades out in "duration" (ms). Ex: white flash on hit.
+    /// It runs alongside the state animation.
+    pub fn flash(&mut self, duration: u128, color: Color) {
+        self.flash_time = duration;
+        self.flash_duration = duration;
+        self.flash_color = color;
+    }
+
+    pub fn is_flashing(&self) -> bool {
+        self.flash_time > 0
+    }
+
+    /// Multiply the sprite color with "color" for "duration" (ms). Ex: red tint on damage.
+    pub fn tint(&mut self, duration: u128, color: Color) {
+        self.tint_time = self.tint_time.max(duration);
+        self.tint_color = color;
+    }
+
+    pub fn is_tinted(&self) -> bool {
+        self.tint_time > 0
+    }
+}
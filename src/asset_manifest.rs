@@ -0,0 +1,640 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use tetra::graphics::Color;
+
+use crate::image_assets::ContentPath;
+use crate::sprite_effects::parse_hex_color;
+use crate::sprite_sheet::SpriteSheet;
+
+/// Frames per second of an animation that has no "frame_length" in its sidecar file.
+pub const DEFAULT_FRAME_LENGTH: u64 = 18;
+/// Sidecar file in an animation directory. It keeps frame length, loop flag and frame order.
+pub const SIDECAR_FILE_NAME: &str = "animation.txt";
+
+/// Error from manifest, sidecar or texture files.
+/// line starts from 1. 0: error is not from a line in the file.
+#[derive(Debug)]
+pub struct AssetError {
+    pub path: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path, self.line, self.message)
+    }
+}
+
+/// Animation that is built after its textures are loaded.
+#[derive(Debug, Clone)]
+pub struct AnimationDefinition {
+    pub name: String,
+    /// Texture key of every frame.
+    pub frames: Vec<String>,
+    /// Frames per second. Same as frame length in ImageAssets::load_animations()
+    pub frame_length: u64,
+    /// None: Sprite keeps its loop flag.
+    pub is_loop: Option<bool>,
+    /// Duration of every frame. (ms) Empty: every frame uses frame_length.
+    pub durations: Vec<u64>,
+    /// (frame index, event name) See AnimationMultiTextures::events
+    pub events: Vec<(usize, String)>,
+}
+
+/// Textures and animations from a manifest file.
+///
+/// Manifest format. One entry per line. Paths are relative to the manifest file.
+///
+/// ```text
+/// # comment
+/// texture bg bg.png
+/// scan enemy enemies
+/// sheet boss boss/boss.json
+/// shader sprite-effects shaders/sprite-effects.frag
+/// palette pop-corn-red f8e8b0=f07060 c8a050=a03030
+/// sound player_hit sfx/player_hit.mp3
+/// ```
+///
+/// "scan <key prefix> <directory>" adds every png file under the directory.
+/// * "<directory>/N.png" (N = 1, 2, 3, ...) are frames of animation "<key prefix>". Texture keys are "<key prefix>-N".
+/// * "<directory>/name.png" is texture "<key prefix>-name". It is also a one frame animation
+///   when no animation has the same name.
+/// * "<directory>/sub/" is scanned with key prefix "<key prefix>-sub".
+///
+/// "sheet <key> <json>" adds a sheet image and its animations from Aseprite JSON data. See SpriteSheet.
+///
+/// "shader <key> <file>" adds a fragment shader. See ImageAssets::load_shaders()
+///
+/// "palette <name> <RRGGBB>=<RRGGBB> ..." adds color pairs for SpriteEffects::palette.
+///
+/// "sound <key> <file>" adds a sound file for AudioManager. Missing file is an error, same as texture.
+///
+pub struct AssetManifest {
+    pub textures: Vec<ContentPath>,
+    pub animations: Vec<AnimationDefinition>,
+    /// Sheets from "sheet" entries. Their images are in "textures".
+    pub sheets: Vec<SpriteSheet>,
+    /// (key, fragment shader path) from "shader" entries.
+    pub shaders: Vec<(String, String)>,
+    /// (name, colors) from "palette" entries.
+    pub palettes: Vec<(String, Vec<(Color, Color)>)>,
+    /// (key, sound path) from "sound" entries. Only files that exist.
+    pub sounds: Vec<(String, String)>,
+    /// Problems that do not stop loading. Ex: missing frame, broken sidecar file.
+    pub errors: Vec<AssetError>,
+}
+
+impl AssetManifest {
+    pub fn new() -> AssetManifest {
+        AssetManifest {
+            textures: vec![],
+            animations: vec![],
+            sheets: vec![],
+            shaders: vec![],
+            palettes: vec![],
+            sounds: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// # Arguments:
+    ///
+    /// * path - path to manifest file. Ex: "./resources/assets.txt"
+    ///
+    pub fn load(path: &str) -> Result<AssetManifest, AssetError> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                let base = match Path::new(path).parent() {
+                    Some(v) => v,
+                    None => Path::new("."),
+                };
+                AssetManifest::parse(path, source.as_str(), base)
+            }
+            Err(e) => Err(AssetError {
+                path: String::from(path),
+                line: 0,
+                message: format!("Can not read manifest file. {}", e),
+            }),
+        }
+    }
+
+    /// Parse manifest from text. "path" only use for error message.
+    /// Files in the manifest are relative to "base".
+    pub fn parse(path: &str, source: &str, base: &Path) -> Result<AssetManifest, AssetError> {
+        let mut manifest = AssetManifest::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let text = line.trim();
+            if text.len() == 0 || text.starts_with("#") {
+                continue;
+            }
+
+            let values: Vec<&str> = text.split_whitespace().collect();
+            match (values[0], values.len()) {
+                ("texture", 3) => manifest.add_texture(values[1], &base.join(values[2])),
+                ("scan", 3) => {
+                    let directory = base.join(values[2]);
+                    if directory.is_dir() {
+                        manifest.scan(values[1], &directory);
+                    } else {
+                        manifest.errors.push(AssetError {
+                            path: String::from(path),
+                            line: index + 1,
+                            message: format!("Directory missing ({})", directory.display()),
+                        });
+                    }
+                }
+                ("sheet", 3) => match SpriteSheet::load(values[1], &base.join(values[2])) {
+                    Ok(sheet) => {
+                        manifest
+                            .textures
+                            .push(ContentPath::new(sheet.key.clone(), sheet.image.clone()));
+                        manifest.sheets.push(sheet);
+                    }
+                    Err(e) => manifest.errors.push(e),
+                },
+                ("sound", 3) => manifest.add_sound(values[1], &base.join(values[2])),
+                ("shader", 3) => manifest.shaders.push((
+                    String::from(values[1]),
+                    base.join(values[2]).display().to_string(),
+                )),
+                ("palette", n) if n > 2 => {
+                    let mut colors = vec![];
+                    for pair in values[2..].iter() {
+                        let color = pair.split_once('=').and_then(|(from, to)| {
+                            Some((parse_hex_color(from)?, parse_hex_color(to)?))
+                        });
+                        match color {
+                            Some(v) => colors.push(v),
+                            None => {
+                                return Err(AssetError {
+                                    path: String::from(path),
+                                    line: index + 1,
+                                    message: format!(
+                                        "Palette color \"{}\" is not RRGGBB=RRGGBB",
+                                        pair
+                                    ),
+                                });
+                            }
+                        };
+                    }
+                    manifest.palettes.push((String::from(values[1]), colors));
+                }
+                ("palette", _) => {
+                    return Err(AssetError {
+                        path: String::from(path),
+                        line: index + 1,
+                        message: String::from("\"palette\" needs a name and colors"),
+                    });
+                }
+                ("texture", _) | ("scan", _) | ("sheet", _) | ("shader", _) | ("sound", _) => {
+                    return Err(AssetError {
+                        path: String::from(path),
+                        line: index + 1,
+                        message: format!("\"{}\" needs a key and a path", values[0]),
+                    });
+                }
+                _ => {
+                    return Err(AssetError {
+                        path: String::from(path),
+                        line: index + 1,
+                        message: format!("Unknown entry \"{}\"", values[0]),
+                    });
+                }
+            };
+        }
+
+        Ok(manifest)
+    }
+
+    /// Add every png file under "directory". See AssetManifest for the key of each file.
+    pub fn scan(&mut self, prefix: &str, directory: &Path) {
+        let entries = match std::fs::read_dir(directory) {
+            Ok(v) => v,
+            Err(e) => {
+                self.errors.push(AssetError {
+                    path: directory.display().to_string(),
+                    line: 0,
+                    message: format!("Can not read directory. {}", e),
+                });
+                return;
+            }
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|e| e.path())
+            .collect();
+        paths.sort();
+
+        // Frame number -> path
+        let mut frames = BTreeMap::new();
+        for path in paths.iter() {
+            let name = match path.file_name() {
+                Some(v) => v.to_string_lossy().to_string(),
+                None => continue,
+            };
+
+            if path.is_dir() {
+                self.scan(join_key(prefix, name.as_str()).as_str(), path);
+                continue;
+            }
+
+            if name.ends_with(".png") == false {
+                continue;
+            }
+            let stem = &name[..name.len() - 4];
+
+            match stem.parse::<usize>() {
+                Ok(number) => {
+                    frames.insert(number, path.clone());
+                }
+                Err(_) => self.add_texture(join_key(prefix, stem).as_str(), path),
+            };
+        }
+
+        if frames.len() == 0 {
+            return;
+        }
+
+        for (number, path) in frames.iter() {
+            self.textures.push(ContentPath::new(
+                join_key(prefix, number.to_string().as_str()),
+                path.display().to_string(),
+            ));
+        }
+
+        let mut animation = AnimationDefinition {
+            name: String::from(prefix),
+            frames: vec![],
+            frame_length: DEFAULT_FRAME_LENGTH,
+            is_loop: None,
+            durations: vec![],
+            events: vec![],
+        };
+
+        let mut order: Vec<usize> = (1..=*frames.keys().last().unwrap()).collect();
+        let sidecar_path = directory.join(SIDECAR_FILE_NAME);
+        if sidecar_path.is_file() {
+            match self.parse_sidecar(&sidecar_path, &mut animation) {
+                Some(v) => order = v,
+                None => (),
+            };
+        }
+
+        for number in order.iter() {
+            if frames.contains_key(number) {
+                animation
+                    .frames
+                    .push(join_key(prefix, number.to_string().as_str()));
+            } else {
+                self.errors.push(AssetError {
+                    path: directory.display().to_string(),
+                    line: 0,
+                    message: format!("Missing frame {}.png of animation \"{}\"", number, prefix),
+                });
+            }
+        }
+
+        if animation.durations.len() > 0 && animation.durations.len() != animation.frames.len() {
+            self.errors.push(AssetError {
+                path: sidecar_path.display().to_string(),
+                line: 0,
+                message: format!(
+                    "{} durations for {} frames of animation \"{}\"",
+                    animation.durations.len(),
+                    animation.frames.len(),
+                    prefix
+                ),
+            });
+            animation.durations.clear();
+        }
+
+        let frame_count = animation.frames.len();
+        for (index, name) in animation.events.iter() {
+            if *index >= frame_count {
+                self.errors.push(AssetError {
+                    path: sidecar_path.display().to_string(),
+                    line: 0,
+                    message: format!(
+                        "Event \"{}\" at frame {} of animation \"{}\" that has {} frames",
+                        name,
+                        index + 1,
+                        prefix,
+                        frame_count
+                    ),
+                });
+            }
+        }
+        animation.events.retain(|(index, _)| *index < frame_count);
+
+        // Animation from frames wins over one frame animation with the same name.
+        self.animations.retain(|v| v.name != animation.name);
+        self.animations.push(animation);
+    }
+
+    /// Add texture and one frame animation with the same key. Missing file is an error.
+    fn add_texture(&mut self, key: &str, path: &Path) {
+        if path.is_file() == false {
+            self.errors.push(AssetError {
+                path: path.display().to_string(),
+                line: 0,
+                message: format!("Missing texture \"{}\"", key),
+            });
+            return;
+        }
+
+        self.textures.push(ContentPath::new(
+            String::from(key),
+            path.display().to_string(),
+        ));
+        if self.animations.iter().any(|v| v.name == key) == false {
+            self.animations.push(AnimationDefinition {
+                name: String::from(key),
+                frames: vec![String::from(key)],
+                frame_length: 1,
+                is_loop: None,
+                durations: vec![],
+                events: vec![],
+            });
+        }
+    }
+
+    /// Add sound file. Missing file is an error.
+    fn add_sound(&mut self, key: &str, path: &Path) {
+        if path.is_file() == false {
+            self.errors.push(AssetError {
+                path: path.display().to_string(),
+                line: 0,
+                message: format!("Missing sound \"{}\"", key),
+            });
+            return;
+        }
+
+        self.sounds
+            .push((String::from(key), path.display().to_string()));
+    }
+
+    /// Read sidecar file into "animation". Return frame order when the file has "frames".
+    ///
+    /// ```text
+    /// frame_length=14
+    /// loop=false
+    /// frames=1,2,2,3
+    /// durations=100,50,50,200
+    /// events=2:spawn,4:sound:spawner
+    /// ```
+    ///
+    /// "durations" are in ms, one per frame in play order.
+    /// "events" are "N:name". N is the position in play order. (1 = first frame)
+    fn parse_sidecar(
+        &mut self,
+        path: &Path,
+        animation: &mut AnimationDefinition,
+    ) -> Option<Vec<usize>> {
+        let source = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) => {
+                self.errors.push(AssetError {
+                    path: path.display().to_string(),
+                    line: 0,
+                    message: format!("Can not read sidecar file. {}", e),
+                });
+                return None;
+            }
+        };
+
+        let mut order = None;
+        for (index, line) in source.lines().enumerate() {
+            let text = line.trim();
+            if text.len() == 0 || text.starts_with("#") {
+                continue;
+            }
+
+            let values: Vec<&str> = text.splitn(2, '=').collect();
+            let value = match values.len() {
+                2 => values[1].trim(),
+                _ => "",
+            };
+
+            let result = match values[0].trim() {
+                "frame_length" => match value.parse::<u64>() {
+                    Ok(v) if v > 0 => {
+                        animation.frame_length = v;
+                        Ok(())
+                    }
+                    _ => Err(format!("Invalid frame_length \"{}\"", value)),
+                },
+                "loop" => match value.parse::<bool>() {
+                    Ok(v) => {
+                        animation.is_loop = Some(v);
+                        Ok(())
+                    }
+                    Err(_) => Err(format!("Invalid loop \"{}\"", value)),
+                },
+                "frames" => {
+                    let numbers: Result<Vec<usize>, _> = value
+                        .split(',')
+                        .map(|v| v.trim().parse::<usize>())
+                        .collect();
+                    match numbers {
+                        Ok(v) => {
+                            order = Some(v);
+                            Ok(())
+                        }
+                        Err(_) => Err(format!("Invalid frames \"{}\"", value)),
+                    }
+                }
+                "durations" => {
+                    let numbers: Result<Vec<u64>, _> =
+                        value.split(',').map(|v| v.trim().parse::<u64>()).collect();
+                    match numbers {
+                        Ok(v) => {
+                            animation.durations = v;
+                            Ok(())
+                        }
+                        Err(_) => Err(format!("Invalid durations \"{}\"", value)),
+                    }
+                }
+                "events" => {
+                    let mut result = Ok(());
+                    for event in value.split(',') {
+                        let event_values: Vec<&str> = event.trim().splitn(2, ':').collect();
+                        match (event_values[0].parse::<usize>(), event_values.len()) {
+                            (Ok(number), 2) if number > 0 && event_values[1].len() > 0 => {
+                                animation
+                                    .events
+                                    .push((number - 1, String::from(event_values[1])));
+                            }
+                            _ => result = Err(format!("Invalid event \"{}\"", event.trim())),
+                        };
+                    }
+                    result
+                }
+                _ => Err(format!("Unknown setting \"{}\"", text)),
+            };
+
+            match result {
+                Ok(_) => (),
+                Err(message) => self.errors.push(AssetError {
+                    path: path.display().to_string(),
+                    line: index + 1,
+                    message: message,
+                }),
+            };
+        }
+
+        order
+    }
+}
+
+fn join_key(prefix: &str, name: &str) -> String {
+    match prefix.len() {
+        0 => String::from(name),
+        _ => format!("{}-{}", prefix, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{AssetManifest, DEFAULT_FRAME_LENGTH, SIDECAR_FILE_NAME};
+
+    /// Empty directory in the temp directory with "files". (path, content)
+    fn fixture_directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("one-man-assets-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&directory);
+        for (path, content) in files.iter() {
+            let path = directory.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        directory
+    }
+
+    fn error_of(source: &str) -> (usize, String) {
+        let e = AssetManifest::parse("assets.txt", source, Path::new("."))
+            .err()
+            .unwrap();
+        (e.line, e.message)
+    }
+
+    #[test]
+    fn every_kind_of_manifest_line_is_read() {
+        let sidecar =
+            "frame_length=14\nloop=false\nframes=2,1,2\ndurations=100,50,100\nevents=2:spawn\n";
+        let sheet = r#"{"frames": [{"frame": {"x": 0, "y": 0, "w": 16, "h": 16}}], "meta": {"image": "boss.png"}}"#;
+        let directory = fixture_directory(
+            "kinds",
+            &[
+                ("bg.png", ""),
+                ("enemy/1.png", ""),
+                ("enemy/2.png", ""),
+                ("enemy/hit.png", ""),
+                (format!("enemy/{}", SIDECAR_FILE_NAME).as_str(), sidecar),
+                ("boss.json", sheet),
+                ("hit.mp3", ""),
+            ],
+        );
+        let source = "# comment\n\ntexture bg bg.png\nscan enemy enemy\nsheet boss boss.json\n\
+                      shader flash flash.frag\npalette red f8e8b0=f07060 c8a050=a03030\n\
+                      sound hit hit.mp3\n";
+        let manifest = AssetManifest::parse("assets.txt", source, &directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let manifest = manifest.unwrap();
+
+        let keys: Vec<&str> = manifest.textures.iter().map(|v| v.key.as_str()).collect();
+        if cfg!(feature = "sprite-sheets") {
+            assert_eq!(manifest.errors.len(), 0, "{:?}", manifest.errors);
+            assert_eq!(keys, vec!["bg", "enemy-hit", "enemy-1", "enemy-2", "boss"]);
+            assert_eq!(manifest.sheets.len(), 1);
+        } else {
+            assert_eq!(keys, vec!["bg", "enemy-hit", "enemy-1", "enemy-2"]);
+        }
+
+        let enemy = manifest
+            .animations
+            .iter()
+            .find(|v| v.name == "enemy")
+            .unwrap();
+        assert_eq!(enemy.frames, vec!["enemy-2", "enemy-1", "enemy-2"]);
+        assert_eq!(enemy.frame_length, 14);
+        assert_eq!(enemy.is_loop, Some(false));
+        assert_eq!(enemy.durations, vec![100, 50, 100]);
+        assert_eq!(enemy.events, vec![(1, String::from("spawn"))]);
+        assert!(manifest.animations.iter().any(|v| v.name == "enemy-hit"));
+
+        assert_eq!(manifest.shaders.len(), 1);
+        assert_eq!(manifest.shaders[0].0, "flash");
+        assert_eq!(manifest.palettes.len(), 1);
+        assert_eq!(manifest.palettes[0].0, "red");
+        assert_eq!(manifest.palettes[0].1.len(), 2);
+        assert_eq!(manifest.sounds.len(), 1);
+        assert_eq!(manifest.sounds[0].0, "hit");
+    }
+
+    #[test]
+    fn unknown_or_malformed_manifest_line_is_an_error() {
+        assert_eq!(
+            error_of("texture bg bg.png\nimage bg bg.png\n"),
+            (2, String::from("Unknown entry \"image\""))
+        );
+        assert_eq!(
+            error_of("# comment\ntexture bg\n"),
+            (2, String::from("\"texture\" needs a key and a path"))
+        );
+        assert_eq!(
+            error_of("palette red\n"),
+            (1, String::from("\"palette\" needs a name and colors"))
+        );
+        assert_eq!(
+            error_of("palette red f8e8b0=f07060 c8a050-a03030\n"),
+            (
+                1,
+                String::from("Palette color \"c8a050-a03030\" is not RRGGBB=RRGGBB")
+            )
+        );
+    }
+
+    #[test]
+    fn broken_sidecar_lines_are_errors_of_their_lines() {
+        let sidecar = "frame_length=0\nspeed=2\nloop=maybe\nevents=0:spawn\nframes=1,2\n";
+        let directory = fixture_directory(
+            "sidecar",
+            &[
+                ("enemy/1.png", ""),
+                ("enemy/2.png", ""),
+                (format!("enemy/{}", SIDECAR_FILE_NAME).as_str(), sidecar),
+            ],
+        );
+        let manifest = AssetManifest::parse("assets.txt", "scan enemy enemy\n", &directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let manifest = manifest.unwrap();
+
+        let errors: Vec<(usize, &str)> = manifest
+            .errors
+            .iter()
+            .map(|e| (e.line, e.message.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (1, "Invalid frame_length \"0\""),
+                (2, "Unknown setting \"speed=2\""),
+                (3, "Invalid loop \"maybe\""),
+                (4, "Invalid event \"0:spawn\""),
+            ]
+        );
+
+        // Lines that can be read are still used.
+        let enemy = &manifest.animations[0];
+        assert_eq!(enemy.frames, vec!["enemy-1", "enemy-2"]);
+        assert_eq!(enemy.frame_length, DEFAULT_FRAME_LENGTH);
+        assert_eq!(enemy.is_loop, None);
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+
+use tetra::audio::{Sound, SoundInstance, SoundState};
+use tetra::Context;
+
+/// Mixer bus. Volume of a sound = volume * bus volume * master volume.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AudioBus {
+    Music,
+    Sfx,
+}
+
+/// Sound that AudioManager can play. One object per file.
+#[derive(Debug, Clone)]
+pub struct SoundDefinition {
+    /// Key for play the sound after loaded. Ex: "player_hit"
+    pub key: String,
+    pub path: String,
+    pub bus: AudioBus,
+    /// Maximum instances of the sound that play at the same time. The oldest one is stopped for the new one.
+    pub max_instances: usize,
+    /// Random speed between 1.0 - pitch_variation and 1.0 + pitch_variation.
+    pub pitch_variation: f32,
+    /// (music volume, duration in ms) when the sound starts. None: no ducking.
+    pub duck_music: Option<(f32, u128)>,
+}
+
+impl SoundDefinition {
+    pub fn new(
+        key: &str,
+        path: &str,
+        bus: AudioBus,
+        max_instances: usize,
+        pitch_variation: f32,
+    ) -> SoundDefinition {
+        SoundDefinition {
+            key: String::from(key),
+            path: String::from(path),
+            bus: bus,
+            max_instances: max_instances.max(1),
+            pitch_variation: pitch_variation,
+            duck_music: None,
+        }
+    }
+
+    /// Lower music bus to "volume" for "duration" (ms) when the sound starts.
+    pub fn with_ducking(mut self, volume: f32, duration: u128) -> SoundDefinition {
+        self.duck_music = Some((volume, duration));
+        self
+    }
+}
+
+/// Device that plays the sounds. Every sound has "max_instances" voices. (index 0 .. max_instances)
+pub trait AudioBackend {
+    fn play(&mut self, key: &str, voice: usize, volume: f32, speed: f32, repeating: bool);
+    fn set_volume(&mut self, key: &str, voice: usize, volume: f32);
+    fn stop(&mut self, key: &str, voice: usize);
+    fn is_playing(&self, key: &str, voice: usize) -> bool;
+}
+
+/// Play sounds with tetra. All files are loaded when the backend is created.
+pub struct TetraAudioBackend {
+    voices: HashMap<String, Vec<SoundInstance>>,
+}
+
+impl TetraAudioBackend {
+    /// Load every sound file and spawn voices for it. Missing file is printed and skipped.
+    pub fn new(ctx: &Context, definitions: &Vec<SoundDefinition>) -> TetraAudioBackend {
+        let mut voices = HashMap::new();
+        for definition in definitions.iter() {
+            match TetraAudioBackend::load(ctx, definition) {
+                Ok(instances) => {
+                    voices.insert(definition.key.clone(), instances);
+                }
+                Err(e) => println!("Load sound error: {} {}", definition.path, e),
+            };
+        }
+
+        TetraAudioBackend { voices: voices }
+    }
+
+    fn load(ctx: &Context, definition: &SoundDefinition) -> tetra::Result<Vec<SoundInstance>> {
+        let sound = Sound::new(definition.path.as_str())?;
+        let mut instances = Vec::new();
+        for _ in 0..definition.max_instances {
+            instances.push(sound.spawn(ctx)?);
+        }
+
+        Ok(instances)
+    }
+
+    fn get(&self, key: &str, voice: usize) -> Option<&SoundInstance> {
+        match self.voices.get(key) {
+            Some(instances) => instances.get(voice),
+            None => None,
+        }
+    }
+}
+
+impl AudioBackend for TetraAudioBackend {
+    fn play(&mut self, key: &str, voice: usize, volume: f32, speed: f32, repeating: bool) {
+        match self.get(key, voice) {
+            Some(instance) => {
+                instance.stop();
+                instance.set_volume(volume);
+                instance.set_speed(speed);
+                instance.set_repeating(repeating);
+                instance.play();
+            }
+            None => (),
+        };
+    }
+
+    fn set_volume(&mut self, key: &str, voice: usize, volume: f32) {
+        match self.get(key, voice) {
+            Some(instance) => instance.set_volume(volume),
+            None => (),
+        };
+    }
+
+    fn stop(&mut self, key: &str, voice: usize) {
+        match self.get(key, voice) {
+            Some(instance) => instance.stop(),
+            None => (),
+        };
+    }
+
+    fn is_playing(&self, key: &str, voice: usize) -> bool {
+        match self.get(key, voice) {
+            Some(instance) => instance.state() == SoundState::Playing,
+            None => false,
+        }
+    }
+}
+
+/// Backend without audio device. Use it in headless mode. Every sound finishes immediately.
+pub struct NullAudioBackend {
+    /// Total sounds that started.
+    pub played: u64,
+}
+
+impl NullAudioBackend {
+    pub fn new() -> NullAudioBackend {
+        NullAudioBackend { played: 0 }
+    }
+}
+
+impl AudioBackend for NullAudioBackend {
+    fn play(&mut self, _key: &str, _voice: usize, _volume: f32, _speed: f32, _repeating: bool) {
+        self.played += 1;
+    }
+
+    fn set_volume(&mut self, _key: &str, _voice: usize, _volume: f32) {}
+
+    fn stop(&mut self, _key: &str, _voice: usize) {}
+
+    fn is_playing(&self, _key: &str, _voice: usize) -> bool {
+        false
+    }
+}
+
+/// A sound instance that AudioManager started.
+struct Voice {
+    /// Voice is unused when it is false or the backend finished playing it.
+    active: bool,
+    /// Tick when the voice started. The oldest voice is replaced first.
+    started: u128,
+    volume: f32,
+}
+
+/// Play sounds by key with bus volumes, polyphony limit, pitch variation and music ducking.
+pub struct AudioManager {
+    backend: Box<dyn AudioBackend>,
+    definitions: HashMap<String, SoundDefinition>,
+    voices: HashMap<String, Vec<Voice>>,
+
+    master_volume: f32,
+    music_volume: f32,
+    sfx_volume: f32,
+
+    /// Music volume multiplier while ducking.
+    duck_volume: f32,
+    /// Time left of ducking. (ms)
+    duck_time: u128,
+    /// Current music volume multiplier. It moves toward duck_volume or 1.0 every update.
+    duck_level: f32,
+
+    tick: u128,
+}
+
+impl AudioManager {
+    pub fn new(backend: Box<dyn AudioBackend>, definitions: Vec<SoundDefinition>) -> AudioManager {
+        let mut definition_map = HashMap::new();
+        let mut voices = HashMap::new();
+        for definition in definitions.into_iter() {
+            let mut list = Vec::new();
+            for _ in 0..definition.max_instances {
+                list.push(Voice {
+                    active: false,
+                    started: 0,
+                    volume: 0.0,
+                });
+            }
+            voices.insert(definition.key.clone(), list);
+            definition_map.insert(definition.key.clone(), definition);
+        }
+
+        AudioManager {
+            backend: backend,
+            definitions: definition_map,
+            voices: voices,
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            duck_volume: 1.0,
+            duck_time: 0,
+            duck_level: 1.0,
+            tick: 0,
+        }
+    }
+
+    pub fn set_volumes(&mut self, master_volume: f32, music_volume: f32, sfx_volume: f32) {
+        self.master_volume = master_volume.max(0.0).min(1.0);
+        self.music_volume = music_volume.max(0.0).min(1.0);
+        self.sfx_volume = sfx_volume.max(0.0).min(1.0);
+    }
+
+    pub fn bus_volume(&self, bus: AudioBus) -> f32 {
+        match bus {
+            AudioBus::Music => self.master_volume * self.music_volume * self.duck_level,
+            AudioBus::Sfx => self.master_volume * self.sfx_volume,
+        }
+    }
+
+    /// Play the sound once. Unknown key is printed and ignored.
+    pub fn play(&mut self, key: &str, volume: f32) {
+        self.start(key, volume, false);
+    }
+
+    /// Play the sound repeatedly until stop() is called.
+    pub fn play_repeating(&mut self, key: &str, volume: f32) {
+        self.start(key, volume, true);
+    }
+
+    /// Stop every instance of the sound.
+    pub fn stop(&mut self, key: &str) {
+        match self.voices.get_mut(key) {
+            Some(voices) => {
+                for (index, voice) in voices.iter_mut().enumerate() {
+                    if voice.active {
+                        self.backend.stop(key, index);
+                        voice.active = false;
+                    }
+                }
+            }
+            None => (),
+        };
+    }
+
+    /// Change volume of every instance of the sound that is playing.
+    pub fn set_volume(&mut self, key: &str, volume: f32) {
+        match self.voices.get_mut(key) {
+            Some(voices) => {
+                for voice in voices.iter_mut() {
+                    voice.volume = volume;
+                }
+            }
+            None => (),
+        };
+    }
+
+    pub fn is_playing(&self, key: &str) -> bool {
+        match self.voices.get(key) {
+            Some(voices) => voices
+                .iter()
+                .enumerate()
+                .any(|(index, voice)| voice.active && self.backend.is_playing(key, index)),
+            None => false,
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        let keys: Vec<String> = self.voices.keys().cloned().collect();
+        for key in keys.iter() {
+            self.stop(key);
+        }
+    }
+
+    /// Lower music bus to "volume" for "duration" (ms). The lowest request is used when ducking overlaps.
+    pub fn duck_music(&mut self, volume: f32, duration: u128) {
+        if self.duck_time == 0 || volume < self.duck_volume {
+            self.duck_volume = volume;
+        }
+        self.duck_time = self.duck_time.max(duration);
+    }
+
+    /// Move time by "dt" (ms). Update ducking and volume of playing sounds.
+    pub fn update(&mut self, dt: u128) {
+        self.tick += dt;
+
+        match self.duck_time.checked_sub(dt) {
+            Some(v) => self.duck_time = v,
+            None => self.duck_time = 0,
+        };
+
+        let target = if self.duck_time > 0 {
+            self.duck_volume
+        } else {
+            1.0
+        };
+        // Fade in about 0.25 second.
+        let step = dt as f32 / 250.0;
+        if self.duck_level < target {
+            self.duck_level = (self.duck_level + step).min(target);
+        } else {
+            self.duck_level = (self.duck_level - step).max(target);
+        }
+
+        for (key, voices) in self.voices.iter_mut() {
+            let bus_volume = match self.definitions.get(key) {
+                Some(definition) => match definition.bus {
+                    AudioBus::Music => self.master_volume * self.music_volume * self.duck_level,
+                    AudioBus::Sfx => self.master_volume * self.sfx_volume,
+                },
+                None => continue,
+            };
+
+            for (index, voice) in voices.iter_mut().enumerate() {
+                if voice.active {
+                    if self.backend.is_playing(key, index) {
+                        self.backend
+                            .set_volume(key, index, voice.volume * bus_volume);
+                    } else {
+                        voice.active = false;
+                    }
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, key: &str, volume: f32, repeating: bool) {
+        let definition = match self.definitions.get(key) {
+            Some(v) => v.clone(),
+            None => {
+                println!("Play sound error: unknown sound {}", key);
+                return;
+            }
+        };
+
+        match definition.duck_music {
+            Some((duck_volume, duration)) => self.duck_music(duck_volume, duration),
+            None => (),
+        };
+
+        let speed = if definition.pitch_variation > 0.0 {
+            1.0 + rand::random::<f32>() * definition.pitch_variation * 2.0
+                - definition.pitch_variation
+        } else {
+            1.0
+        };
+        let bus_volume = self.bus_volume(definition.bus);
+
+        let voices = match self.voices.get_mut(key) {
+            Some(v) => v,
+            None => return,
+        };
+
+        // Use an unused voice or replace the oldest one.
+        let mut selected = 0;
+        for (index, voice) in voices.iter().enumerate() {
+            if voice.active == false || self.backend.is_playing(key, index) == false {
+                selected = index;
+                break;
+            }
+
+            if voice.started < voices[selected].started {
+                selected = index;
+            }
+        }
+
+        voices[selected] = Voice {
+            active: true,
+            started: self.tick,
+            volume: volume,
+        };
+        self.backend
+            .play(key, selected, volume * bus_volume, speed, repeating);
+    }
+}
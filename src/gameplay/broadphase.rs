@@ -0,0 +1,176 @@
+use tetra::graphics::Rectangle;
+use tetra::math::Vec2;
+
+use crate::gameplay::bullet_pool::Bullet;
+use crate::gameplay::collider;
+use crate::gameplay::enemy_manager::Enemy;
+
+/// Size of a grid cell. Most enemies and bullets fit in one cell.
+const CELL_SIZE: f32 = 32.0;
+/// Extra radius of every query. Some hit checks round positions to integer with distance_sqr().
+const QUERY_MARGIN: f32 = 1.0;
+
+/// Uniform grid over the camera area. A cell keeps indices of the objects that touch it.
+/// Objects outside the camera area are not in the grid.
+pub struct Grid {
+    origin: Vec2<f32>,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+    /// Every object in insert order.
+    objects: Vec<usize>,
+    /// False: queries give every object, same as a loop over the whole list.
+    use_cells: bool,
+}
+
+impl Grid {
+    pub fn new() -> Grid {
+        Grid {
+            origin: Vec2::zero(),
+            columns: 0,
+            rows: 0,
+            cells: vec![],
+            objects: vec![],
+            use_cells: true,
+        }
+    }
+
+    /// Remove every object and cover "area" with cells. Cells keep their memory.
+    pub fn reset(&mut self, area: Rectangle) {
+        self.origin = Vec2::new(area.x, area.y);
+        self.columns = ((area.width / CELL_SIZE).ceil() as usize).max(1);
+        self.rows = ((area.height / CELL_SIZE).ceil() as usize).max(1);
+
+        self.cells.resize_with(self.columns * self.rows, Vec::new);
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        self.objects.clear();
+    }
+
+    /// Add object "index" to every cell under its bounding box.
+    pub fn insert(&mut self, index: usize, position: Vec2<f32>, radius: f32) {
+        self.objects.push(index);
+        if self.use_cells == false {
+            return;
+        }
+
+        let (left, top, right, bottom) = self.cell_range(position, radius);
+        for row in top..=bottom {
+            for column in left..=right {
+                self.cells[row * self.columns + column].push(index);
+            }
+        }
+    }
+
+    /// Find objects that share a cell with the circle.
+    ///
+    /// # Arguments:
+    ///
+    /// * result - cleared and filled with object indices. No duplicate and in ascending order,
+    ///            so a hit check visits objects in the same order as a loop over the whole list.
+    ///
+    pub fn query(&self, position: Vec2<f32>, radius: f32, result: &mut Vec<usize>) {
+        result.clear();
+        if self.use_cells == false {
+            result.extend_from_slice(&self.objects);
+            return;
+        }
+
+        if self.cells.len() == 0 {
+            return;
+        }
+
+        let (left, top, right, bottom) = self.cell_range(position, radius + QUERY_MARGIN);
+        for row in top..=bottom {
+            for column in left..=right {
+                result.extend_from_slice(&self.cells[row * self.columns + column]);
+            }
+        }
+
+        result.sort_unstable();
+        result.dedup();
+    }
+
+    /// Cells under the bounding box of the circle. (left, top, right, bottom) Clamped inside the grid.
+    fn cell_range(&self, position: Vec2<f32>, radius: f32) -> (usize, usize, usize, usize) {
+        let cell = |value: f32, origin: f32, count: usize| {
+            (((value - origin) / CELL_SIZE).floor().max(0.0) as usize).min(count - 1)
+        };
+
+        (
+            cell(position.x - radius, self.origin.x, self.columns),
+            cell(position.y - radius, self.origin.y, self.rows),
+            cell(position.x + radius, self.origin.x, self.columns),
+            cell(position.y + radius, self.origin.y, self.rows),
+        )
+    }
+}
+
+/// Broadphase of the hit checks. Rebuild it once per frame before Simulation::call_hit_checks().
+/// Player bullets, player hit points and melee attacks query "enemies" and "enemy_bullets"
+/// instead of looping over every active enemy and bullet.
+pub struct Broadphase {
+    camera_area: Rectangle,
+    /// Indices in EnemyManager::get_mut_active_enemy()
+    pub enemies: Grid,
+    /// Indices in BulletPool::enemy_active_bullets
+    pub enemy_bullets: Grid,
+    /// Result of the last query. Keep it here, so queries do not allocate.
+    pub candidates: Vec<usize>,
+}
+
+impl Broadphase {
+    pub fn new() -> Broadphase {
+        Broadphase {
+            camera_area: Rectangle::new(0.0, 0.0, 0.0, 0.0),
+            enemies: Grid::new(),
+            enemy_bullets: Grid::new(),
+            candidates: vec![],
+        }
+    }
+
+    /// False: queries skip the cells and give every object inside the camera area.
+    /// Tests compare hit checks with and without the grid.
+    pub fn set_use_grid(&mut self, use_grid: bool) {
+        self.enemies.use_cells = use_grid;
+        self.enemy_bullets.use_cells = use_grid;
+    }
+
+    /// Put active enemies and enemy bullets inside the camera area in the grids.
+    /// Enemies use Enemy::hit_radius() and bullets cover their path of this frame.
+    /// Query bullet paths with collider::sweep_bounds().
+    pub fn rebuild(
+        &mut self,
+        camera_position: Vec2<f32>,
+        enemies: &[Enemy],
+        enemy_bullets: &[Bullet],
+    ) {
+        self.camera_area = crate::gameplay::utils::camera_area(camera_position);
+        self.enemies.reset(self.camera_area);
+        self.enemy_bullets.reset(self.camera_area);
+
+        for (index, enemy) in enemies.iter().enumerate() {
+            let radius = enemy.hit_radius();
+            if enemy.active && self.is_inside_camera_area(&enemy.position, radius) {
+                self.enemies.insert(index, enemy.position, radius);
+            }
+        }
+
+        for (index, bullet) in enemy_bullets.iter().enumerate() {
+            if bullet.active && self.is_inside_camera_area(&bullet.position, bullet.radius) {
+                let (center, radius) = collider::sweep_bounds(
+                    bullet.previous_position,
+                    bullet.position,
+                    bullet.radius,
+                );
+                self.enemy_bullets.insert(index, center, radius);
+            }
+        }
+    }
+
+    /// Same as utils::is_inside_camera_area() with the camera area of the last rebuild().
+    pub fn is_inside_camera_area(&self, position: &Vec2<f32>, radius: f32) -> bool {
+        crate::gameplay::utils::is_inside_area(&self.camera_area, position, radius)
+    }
+}
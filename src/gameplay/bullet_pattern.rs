@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+
+use tetra::math::Vec2;
+
+use crate::gameplay::bullet_pool::{Bullet, BulletOwner, BulletSpawnNode, BulletTypeBank};
+use crate::gameplay::world::World;
+use crate::image_assets::ImageAssets;
+
+/// Bullet patterns that are loaded over the built-in patterns.
+pub const BULLET_PATTERN_PATH: &str = "./resources/patterns/bullet-patterns.ron";
+
+/// How the bullets of one shot are placed. Rotation is 0.0 - 1.0 like bullet.rotation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternShape {
+    /// "count" bullets on a full circle.
+    Ring { count: u32 },
+    /// "count" bullets on an arc of "angle" around the direction. Count 1 is a single bullet.
+    Spread { count: u32, angle: f32 },
+}
+
+/// Bullet that a volley fires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternBullet {
+    pub bullet_type: i32,
+    pub speed: f32,
+    pub radius: f32,
+    /// bullet.extra of every bullet. Ex: "idle_animation=enemy-bullet-1-idle|"
+    pub extra: String,
+}
+
+/// One step of a pattern. It fires "repeat" + 1 shots of "shape".
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternVolley {
+    /// Waiting time after the previous volley. (ms)
+    pub delay: u128,
+    pub shape: PatternShape,
+    /// Direction of the shot. With "aim" it is added to the direction to the target.
+    pub direction: f32,
+    /// True: shots turn to the target when they are fired.
+    pub aim: bool,
+    /// Extra shots after the first one.
+    pub repeat: u32,
+    /// Time between shots. (ms)
+    pub interval: u128,
+    /// Rotation that is added every shot. Ring or spread with "turn" is a spiral.
+    pub turn: f32,
+    /// Speed that is added every shot.
+    pub speed_step: f32,
+    /// Bullets start this far from the origin along their direction. (pixel)
+    pub distance: f32,
+    pub bullet: PatternBullet,
+    /// Pattern that every bullet fires at its position after "sub_delay". (ms) "": none.
+    /// The bullet is removed when it fires.
+    pub sub_pattern: String,
+    pub sub_delay: u128,
+}
+
+impl PatternVolley {
+    pub fn new(shape: PatternShape, bullet: PatternBullet) -> PatternVolley {
+        PatternVolley {
+            delay: 0,
+            shape: shape,
+            direction: 0.0,
+            aim: false,
+            repeat: 0,
+            interval: 0,
+            turn: 0.0,
+            speed_step: 0.0,
+            distance: 0.0,
+            bullet: bullet,
+            sub_pattern: String::new(),
+            sub_delay: 0,
+        }
+    }
+}
+
+/// Volleys that run one after another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BulletPattern {
+    pub volleys: Vec<PatternVolley>,
+}
+
+/// BulletPatternBank keeps every pattern by name. Enemies, levels and bullets fire them by name.
+pub struct BulletPatternBank {
+    patterns: HashMap<String, BulletPattern>,
+}
+
+impl BulletPatternBank {
+    pub fn new() -> BulletPatternBank {
+        BulletPatternBank {
+            patterns: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BulletPattern> {
+        self.patterns.get(name)
+    }
+
+    /// Add pattern. It replaces the pattern with the same name.
+    pub fn add(&mut self, name: &str, pattern: BulletPattern) {
+        self.patterns.insert(String::from(name), pattern);
+    }
+
+    /// Pattern names in order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.patterns.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Setup built-in patterns, then patterns from BULLET_PATTERN_PATH.
+    /// The built-in patterns are kept when the file has error.
+    pub fn setup(&mut self) {
+        self.clear();
+        setup_builtin_patterns(self);
+
+        #[cfg(feature = "level-files")]
+        match crate::gameplay::bullet_pattern_file::load_patterns(BULLET_PATTERN_PATH) {
+            Ok(bank) => self.patterns.extend(bank.patterns),
+            Err(e) => {
+                println!("Load bullet pattern error: {}", e);
+                println!("Use built-in bullet patterns");
+            }
+        };
+    }
+
+    pub fn clear(&mut self) {
+        self.patterns.clear();
+    }
+}
+
+/// The aimed burst of the boss. It is the only pattern without pattern file.
+fn setup_builtin_patterns(bank: &mut BulletPatternBank) {
+    let bullet = PatternBullet {
+        bullet_type: 1,
+        speed: 1.0,
+        radius: 4.0,
+        extra: String::from("idle_animation=enemy-bullet-1-idle|firing_animation=enemy-bullet-1-firing|hit_animation=enemy-bullet-1-hit|kill_animation=enemy-bullet-1-kill|scale=1.2|"),
+    };
+
+    let mut volley = PatternVolley::new(
+        PatternShape::Spread {
+            count: 2,
+            angle: 0.02,
+        },
+        bullet,
+    );
+    volley.aim = true;
+    volley.repeat = 26;
+    volley.interval = 15 * crate::ONE_FRAME.as_millis();
+    volley.distance = 28.0;
+
+    bank.add(
+        "boss-aimed",
+        BulletPattern {
+            volleys: vec![volley],
+        },
+    );
+}
+
+/// Pattern that waits for "delay" and fires from "position". World keeps them for Simulation.
+#[derive(Debug, Clone)]
+pub struct PatternSpawnNode {
+    /// Waiting time before the first volley. (ms)
+    pub delay: u128,
+    pub pattern: String,
+    pub position: Vec2<f32>,
+}
+
+impl PatternSpawnNode {
+    pub fn new(delay: u128, pattern: &str, position: Vec2<f32>) -> PatternSpawnNode {
+        PatternSpawnNode {
+            delay: delay,
+            pattern: String::from(pattern),
+            position: position,
+        }
+    }
+}
+
+/// Running pattern. The owner moves the origin and picks the target on every update.
+#[derive(Debug, Clone)]
+pub struct PatternEmitter {
+    pattern: String,
+    volley_index: usize,
+    /// Shots of the current volley that were fired.
+    shot: u32,
+    /// Time until the next shot. (ms)
+    wait: u128,
+    started: bool,
+    finished: bool,
+}
+
+impl PatternEmitter {
+    /// # Arguments:
+    ///
+    /// * pattern - name in BulletPatternBank
+    /// * delay - waiting time before the first volley (ms)
+    ///
+    pub fn new(pattern: &str, delay: u128) -> PatternEmitter {
+        PatternEmitter {
+            pattern: String::from(pattern),
+            volley_index: 0,
+            shot: 0,
+            wait: delay,
+            started: false,
+            finished: false,
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    /// True: every volley is fired or the pattern is missing.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Index of the volley that fires next.
+    pub fn volley_index(&self) -> usize {
+        self.volley_index
+    }
+
+    /// Time until the next shot. (ms)
+    pub fn next_shot_time(&self) -> u128 {
+        self.wait
+    }
+
+    /// Fire the shots of this frame. Call it once per frame.
+    ///
+    /// # Arguments:
+    ///
+    /// * origin - world position of the emitter
+    /// * target - world position that aimed volleys turn to
+    ///
+    /// # Return:
+    ///
+    /// * Enemy bullets for World::spawn_bullet()
+    ///
+    pub fn update(
+        &mut self,
+        bank: &BulletPatternBank,
+        origin: Vec2<f32>,
+        target: Vec2<f32>,
+    ) -> Vec<BulletSpawnNode> {
+        let mut nodes = vec![];
+        if self.finished {
+            return nodes;
+        }
+
+        let pattern = match bank.get(self.pattern.as_str()) {
+            Some(v) => v,
+            None => {
+                println!("No bullet pattern: {}", self.pattern);
+                self.finished = true;
+                return nodes;
+            }
+        };
+
+        if self.started == false {
+            self.started = true;
+            match pattern.volleys.get(0) {
+                Some(volley) => self.wait += volley.delay,
+                None => (),
+            };
+        }
+
+        while self.wait == 0 {
+            let volley = match pattern.volleys.get(self.volley_index) {
+                Some(v) => v,
+                None => break,
+            };
+
+            fire_volley(volley, self.shot, origin, target, &mut nodes);
+
+            self.shot += 1;
+            if self.shot > volley.repeat {
+                self.shot = 0;
+                self.volley_index += 1;
+                self.wait = match pattern.volleys.get(self.volley_index) {
+                    Some(next_volley) => next_volley.delay,
+                    None => 0,
+                };
+            } else {
+                self.wait = volley.interval;
+            }
+        }
+
+        if self.volley_index >= pattern.volleys.len() {
+            self.finished = true;
+        }
+
+        match self.wait.checked_sub(crate::ONE_FRAME.as_millis()) {
+            Some(v) => self.wait = v,
+            None => self.wait = 0,
+        };
+
+        nodes
+    }
+}
+
+/// Put bullets of shot number "shot" of "volley" into "nodes".
+fn fire_volley(
+    volley: &PatternVolley,
+    shot: u32,
+    origin: Vec2<f32>,
+    target: Vec2<f32>,
+    nodes: &mut Vec<BulletSpawnNode>,
+) {
+    let mut direction = volley.direction + volley.turn * shot as f32;
+    if volley.aim {
+        direction += (target.y - origin.y)
+            .atan2(target.x - origin.x)
+            .to_degrees()
+            / 360.0;
+    }
+
+    let mut rotations = vec![];
+    match volley.shape {
+        PatternShape::Ring { count } => {
+            for index in 0..count {
+                rotations.push(direction + index as f32 / count as f32);
+            }
+        }
+        PatternShape::Spread { count, angle } => {
+            if count == 1 {
+                rotations.push(direction);
+            } else {
+                for index in 0..count {
+                    rotations
+                        .push(direction - angle / 2.0 + angle * index as f32 / (count - 1) as f32);
+                }
+            }
+        }
+    };
+
+    let mut extra = volley.bullet.extra.clone();
+    if volley.sub_pattern.len() > 0 {
+        extra.push_str(
+            format!(
+                "pattern={}|pattern_delay={}|",
+                volley.sub_pattern, volley.sub_delay
+            )
+            .as_str(),
+        );
+    }
+
+    for rotation in rotations.iter() {
+        let rotation = rotation.rem_euclid(1.0);
+        let radian = (rotation * 360.0).to_radians();
+        nodes.push(BulletSpawnNode {
+            bullet_type: volley.bullet.bullet_type,
+            position: origin + Vec2::new(radian.cos(), radian.sin()) * volley.distance,
+            owner_type: BulletOwner::ENEMY,
+            rotation: rotation,
+            speed: volley.bullet.speed + volley.speed_step * shot as f32,
+            radius: volley.bullet.radius,
+            extra: extra.clone(),
+        });
+    }
+}
+
+/// Read "pattern" and "pattern_delay" that fire_volley() puts in bullet extra.
+pub fn sub_pattern_of(extra: &str) -> Option<(String, u128)> {
+    let mut pattern = None;
+    let mut delay = 0;
+    for item in extra.split('|') {
+        let mut pair = item.splitn(2, '=');
+        match (pair.next(), pair.next()) {
+            (Some("pattern"), Some(value)) => pattern = Some(String::from(value)),
+            (Some("pattern_delay"), Some(value)) => delay = value.parse::<u128>().unwrap_or(0),
+            _ => (),
+        };
+    }
+
+    pattern.map(|v| (v, delay))
+}
+
+/// Run "pattern" without the game and list every bullet that it fires. (frame, bullet)
+/// Bullets with a sub-pattern move with their BulletType like in BulletPool and fire their
+/// pattern at the end of "pattern_delay". "target" is the only player. Homing bullets and
+/// aimed volleys turn to it. The camera is at "origin".
+///
+/// # Arguments:
+///
+/// * frames - frames to run. Frame 1 is the first update.
+///
+pub fn simulate(
+    bank: &BulletPatternBank,
+    bullet_type_bank: &mut BulletTypeBank,
+    image_assets: &ImageAssets,
+    pattern: &str,
+    frames: u64,
+    origin: Vec2<f32>,
+    target: Vec2<f32>,
+) -> Vec<(u64, BulletSpawnNode)> {
+    let mut spawned = vec![];
+    let mut world = World::new(origin);
+    world.player_positions.push(target);
+    let mut emitters = vec![(origin, PatternEmitter::new(pattern, 0))];
+    let mut carriers: Vec<Bullet> = vec![];
+
+    for frame in 1..=frames {
+        for carrier in carriers.iter_mut() {
+            carrier.update(bullet_type_bank, &mut world);
+        }
+        carriers.retain(|carrier| carrier.active);
+        world.bullet_spawn_nodes.clear();
+
+        for node in world.pattern_spawn_nodes.drain(..) {
+            emitters.push((
+                node.position,
+                PatternEmitter::new(&node.pattern, node.delay),
+            ));
+        }
+
+        for (position, emitter) in emitters.iter_mut() {
+            for node in emitter.update(bank, *position, target) {
+                if sub_pattern_of(node.extra.as_str()).is_some() {
+                    let mut carrier = Bullet::new();
+                    match carrier.setup_from_node(&node, bullet_type_bank, image_assets) {
+                        Ok(_) => carriers.push(carrier),
+                        Err(e) => println!("{}", e),
+                    };
+                }
+                spawned.push((frame, node));
+            }
+        }
+        emitters.retain(|(_, emitter)| emitter.is_finished() == false);
+    }
+
+    spawned
+}
+
+#[cfg(test)]
+mod tests {
+    use tetra::math::Vec2;
+
+    use super::{
+        simulate, sub_pattern_of, BulletPattern, BulletPatternBank, PatternBullet, PatternShape,
+        PatternVolley,
+    };
+    use crate::gameplay::bullet_pool::{BulletSpawnNode, BulletTypeBank};
+    use crate::image_assets::ImageAssets;
+
+    const TARGET_POSITION: Vec2<f32> = Vec2::new(120.0, 60.0);
+
+    fn pattern_bullet(bullet_type: i32, extra: &str) -> PatternBullet {
+        PatternBullet {
+            bullet_type: bullet_type,
+            speed: 2.0,
+            radius: 4.0,
+            extra: String::from(extra),
+        }
+    }
+
+    /// "carrier" fires one bullet to the left. It fires "burst" after 500 ms.
+    fn carrier_bank(bullet_type: i32, extra: &str) -> BulletPatternBank {
+        let mut carrier = PatternVolley::new(
+            PatternShape::Spread {
+                count: 1,
+                angle: 0.0,
+            },
+            pattern_bullet(bullet_type, extra),
+        );
+        carrier.direction = 0.5;
+        carrier.sub_pattern = String::from("burst");
+        carrier.sub_delay = 500;
+
+        let mut bank = BulletPatternBank::new();
+        bank.add(
+            "carrier",
+            BulletPattern {
+                volleys: vec![carrier],
+            },
+        );
+        bank.add(
+            "burst",
+            BulletPattern {
+                volleys: vec![PatternVolley::new(
+                    PatternShape::Ring { count: 1 },
+                    pattern_bullet(1, ""),
+                )],
+            },
+        );
+        bank
+    }
+
+    /// Bullets from the sub-pattern.
+    fn burst_bullets(bank: &BulletPatternBank) -> Vec<BulletSpawnNode> {
+        let image_assets = ImageAssets::new(vec![]);
+        let mut bullet_type_bank = BulletTypeBank::new();
+        bullet_type_bank.setup(&image_assets);
+
+        simulate(
+            bank,
+            &mut bullet_type_bank,
+            &image_assets,
+            "carrier",
+            120,
+            Vec2::zero(),
+            TARGET_POSITION,
+        )
+        .into_iter()
+        .map(|(_, node)| node)
+        .filter(|node| sub_pattern_of(node.extra.as_str()).is_none())
+        .collect()
+    }
+
+    #[test]
+    fn straight_carrier_fires_where_it_is_after_sub_delay() {
+        let bullets = burst_bullets(&carrier_bank(1, ""));
+
+        assert_eq!(bullets.len(), 1);
+        // 32 frames of 2 pixels
+        assert!(Vec2::distance(bullets[0].position, Vec2::new(-64.0, 0.0)) < 0.01);
+    }
+
+    #[test]
+    fn homing_carrier_fires_where_it_turned_to() {
+        let straight = burst_bullets(&carrier_bank(1, ""));
+        let homing = burst_bullets(&carrier_bank(2, "turn_rate=0.05|"));
+
+        assert_eq!(homing.len(), 1);
+        assert!(
+            Vec2::distance(homing[0].position, TARGET_POSITION)
+                < Vec2::distance(straight[0].position, TARGET_POSITION) - 32.0,
+            "{:?}",
+            homing[0].position
+        );
+    }
+
+    #[test]
+    fn carrier_removed_on_the_ground_fires_nothing() {
+        let mut bank = carrier_bank(5, "gravity=0.5|max_bounces=0|");
+        let mut pattern = bank.get("carrier").unwrap().clone();
+        pattern.volleys[0].direction = 0.25;
+        bank.add("carrier", pattern);
+
+        assert_eq!(burst_bullets(&bank).len(), 0);
+    }
+}
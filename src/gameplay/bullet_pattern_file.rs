@@ -0,0 +1,511 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Deserialize;
+use tetra::math::Vec2;
+
+use crate::gameplay::bullet_pattern::{
+    self, BulletPattern, BulletPatternBank, PatternBullet, PatternShape, PatternVolley,
+};
+use crate::gameplay::bullet_pool::{self, BulletTypeBank};
+use crate::gameplay::level_file::{self, Keyed, LevelFileError};
+use crate::gameplay::parameters::Parameters;
+use crate::image_assets::ImageAssets;
+
+/// Directory of golden spawn lists. "<pattern name>.txt" for every pattern.
+pub const GOLDEN_DIRECTORY: &str = "./resources/patterns/golden";
+/// Frames of the golden spawn lists. (4 seconds)
+const GOLDEN_FRAMES: u64 = 240;
+
+#[derive(Deserialize)]
+#[serde(rename = "BulletPatterns")]
+struct BulletPatternsData<'a> {
+    /// Names are borrowed from the source. Validation goes through them in name order.
+    #[serde(default, borrow)]
+    bullets: BTreeMap<&'a str, Keyed<'a, BulletData>>,
+    #[serde(borrow)]
+    patterns: BTreeMap<&'a str, Vec<Keyed<'a, VolleyData>>>,
+}
+
+#[derive(Deserialize)]
+struct BulletData {
+    bullet_type: i32,
+    #[serde(default = "default_speed")]
+    speed: f32,
+    #[serde(default = "default_radius")]
+    radius: f32,
+    #[serde(default)]
+    extra: String,
+}
+
+#[derive(Deserialize)]
+enum ShapeData {
+    Ring {
+        count: u32,
+    },
+    Spread {
+        count: u32,
+        #[serde(default)]
+        angle: f32,
+    },
+}
+
+#[derive(Deserialize)]
+struct VolleyData {
+    #[serde(default)]
+    delay: u64,
+    shape: ShapeData,
+    /// Name in "bullets"
+    bullet: String,
+    /// Replace speed of the bullet.
+    #[serde(default)]
+    speed: Option<f32>,
+    #[serde(default)]
+    direction: f32,
+    #[serde(default)]
+    aim: bool,
+    #[serde(default)]
+    repeat: u32,
+    #[serde(default)]
+    interval: u64,
+    #[serde(default)]
+    turn: f32,
+    #[serde(default)]
+    speed_step: f32,
+    #[serde(default)]
+    distance: f32,
+    #[serde(default)]
+    sub_pattern: Option<String>,
+    #[serde(default)]
+    sub_delay: u64,
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+fn default_radius() -> f32 {
+    4.0
+}
+
+/// Load bullet pattern file (RON format). The bank has only patterns from the file.
+///
+/// # Arguments:
+///
+/// * path - path to pattern file. Ex: "./resources/patterns/bullet-patterns.ron"
+///
+pub fn load_patterns(path: &str) -> Result<BulletPatternBank, LevelFileError> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => parse_patterns(path, source.as_str()),
+        Err(e) => Err(LevelFileError {
+            path: String::from(path),
+            line: 0,
+            column: 0,
+            message: format!("Can not read bullet pattern file. {}", e),
+        }),
+    }
+}
+
+/// Parse bullet patterns from text. "path" only use for error message.
+pub fn parse_patterns(path: &str, source: &str) -> Result<BulletPatternBank, LevelFileError> {
+    let data: BulletPatternsData = match level_file::ron_options().from_str(source) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(LevelFileError {
+                path: String::from(path),
+                line: e.position.line,
+                column: e.position.col,
+                message: format!("{}", e.code),
+            });
+        }
+    };
+
+    validate(path, source, &data)?;
+
+    let mut bank = BulletPatternBank::new();
+    for (name, volleys) in data.patterns.iter() {
+        let mut pattern = BulletPattern { volleys: vec![] };
+        for volley_data in volleys.iter() {
+            // validate() checked the bullet name.
+            let bullet_data = &data.bullets[volley_data.bullet.as_str()];
+            let shape = match volley_data.shape {
+                ShapeData::Ring { count } => PatternShape::Ring { count: count },
+                ShapeData::Spread { count, angle } => PatternShape::Spread {
+                    count: count,
+                    angle: angle,
+                },
+            };
+
+            let mut volley = PatternVolley::new(
+                shape,
+                PatternBullet {
+                    bullet_type: bullet_data.bullet_type,
+                    speed: volley_data.speed.unwrap_or(bullet_data.speed),
+                    radius: bullet_data.radius,
+                    extra: bullet_data.extra.clone(),
+                },
+            );
+            volley.delay = volley_data.delay as u128;
+            volley.direction = volley_data.direction;
+            volley.aim = volley_data.aim;
+            volley.repeat = volley_data.repeat;
+            volley.interval = volley_data.interval as u128;
+            volley.turn = volley_data.turn;
+            volley.speed_step = volley_data.speed_step;
+            volley.distance = volley_data.distance;
+            volley.sub_pattern = volley_data.sub_pattern.clone().unwrap_or_default();
+            volley.sub_delay = volley_data.sub_delay as u128;
+
+            pattern.volleys.push(volley);
+        }
+
+        bank.add(name, pattern);
+    }
+
+    Ok(bank)
+}
+
+/// Check names, counts and bullet extra. Deserializer can not check these.
+/// Errors point to the name of the field or pattern that cause them.
+fn validate(path: &str, source: &str, data: &BulletPatternsData) -> Result<(), LevelFileError> {
+    let error = |key: Option<&str>, message: String| {
+        let (line, column) = match key {
+            Some(key) => level_file::position_of(source, key),
+            None => (0, 0),
+        };
+        LevelFileError {
+            path: String::from(path),
+            line: line,
+            column: column,
+            message: message,
+        }
+    };
+
+    for (name, bullet) in data.bullets.iter() {
+        match BulletTypeBank::parameters(bullet.bullet_type) {
+            Some(specs) => match Parameters::validate(
+                bullet.extra.as_str(),
+                &[&bullet_pool::COMMON_PARAMETERS, specs],
+            ) {
+                Ok(_) => (),
+                Err(e) => {
+                    return Err(error(
+                        bullet.key("extra"),
+                        format!("{} in bullet \"{}\"", e, name),
+                    ));
+                }
+            },
+            None => {
+                return Err(error(
+                    bullet.key("bullet_type"),
+                    format!(
+                        "Unknown bullet_type {} in bullet \"{}\"",
+                        bullet.bullet_type, name
+                    ),
+                ));
+            }
+        };
+    }
+
+    for (name, volleys) in data.patterns.iter() {
+        if volleys.len() == 0 {
+            return Err(error(
+                Some(name),
+                format!("No volley in pattern \"{}\"", name),
+            ));
+        }
+
+        for volley in volleys.iter() {
+            let count = match volley.shape {
+                ShapeData::Ring { count } => count,
+                ShapeData::Spread { count, .. } => count,
+            };
+            if count == 0 {
+                return Err(error(
+                    volley.key("shape"),
+                    format!("count has to be more than 0 in pattern \"{}\"", name),
+                ));
+            }
+
+            if data.bullets.contains_key(volley.bullet.as_str()) == false {
+                return Err(error(
+                    volley.key("bullet"),
+                    format!(
+                        "Unknown bullet \"{}\" in pattern \"{}\"",
+                        volley.bullet, name
+                    ),
+                ));
+            }
+
+            match volley.sub_pattern.as_ref() {
+                Some(sub_pattern) if data.patterns.contains_key(sub_pattern.as_str()) == false => {
+                    return Err(error(
+                        volley.key("sub_pattern"),
+                        format!(
+                            "Unknown sub_pattern \"{}\" in pattern \"{}\"",
+                            sub_pattern, name
+                        ),
+                    ));
+                }
+                _ => (),
+            };
+        }
+    }
+
+    let mut done = BTreeSet::new();
+    for name in data.patterns.keys() {
+        match find_sub_pattern_cycle(data, name, &mut vec![], &mut done) {
+            Some((volley, cycle)) => {
+                return Err(error(
+                    volley.key("sub_pattern"),
+                    format!(
+                        "Cyclic sub_pattern in pattern \"{}\": {}",
+                        cycle[cycle.len() - 2],
+                        cycle.join(" -> ")
+                    ),
+                ));
+            }
+            None => (),
+        };
+    }
+
+    Ok(())
+}
+
+/// Depth first search from pattern "name" through sub_pattern. Sub-patterns have to exist.
+/// "visiting" has the patterns on the current path. "done" has patterns without a cycle.
+///
+/// # Return:
+///
+/// * Some: the volley that closes a cycle and the patterns of the cycle. (first == last)
+///
+fn find_sub_pattern_cycle<'s, 'a>(
+    data: &'s BulletPatternsData<'a>,
+    name: &'s str,
+    visiting: &mut Vec<&'s str>,
+    done: &mut BTreeSet<&'s str>,
+) -> Option<(&'s Keyed<'a, VolleyData>, Vec<&'s str>)> {
+    if done.contains(name) {
+        return None;
+    }
+
+    visiting.push(name);
+    for volley in data.patterns[name].iter() {
+        let sub_pattern = match volley.sub_pattern.as_ref() {
+            Some(v) => v.as_str(),
+            None => continue,
+        };
+
+        match visiting.iter().position(|v| *v == sub_pattern) {
+            Some(start) => {
+                let mut cycle = visiting[start..].to_vec();
+                cycle.push(sub_pattern);
+                return Some((volley, cycle));
+            }
+            None => (),
+        };
+
+        match find_sub_pattern_cycle(data, sub_pattern, visiting, done) {
+            Some(v) => return Some(v),
+            None => (),
+        };
+    }
+    visiting.pop();
+    done.insert(name);
+
+    None
+}
+
+/// Spawn list of "pattern" from bullet_pattern::simulate() in text. One bullet per line.
+/// The emitter is at (0, 0) and the target is at (120, 60).
+pub fn golden_text(bank: &BulletPatternBank, pattern: &str) -> String {
+    let mut text = String::new();
+    let image_assets = ImageAssets::new(vec![]);
+    let mut bullet_type_bank = BulletTypeBank::new();
+    bullet_type_bank.setup(&image_assets);
+    let spawned = bullet_pattern::simulate(
+        bank,
+        &mut bullet_type_bank,
+        &image_assets,
+        pattern,
+        GOLDEN_FRAMES,
+        Vec2::zero(),
+        Vec2::new(120.0, 60.0),
+    );
+
+    for (frame, node) in spawned.iter() {
+        text.push_str(
+            format!(
+                "{} type={} position=({:.2}, {:.2}) rotation={:.4} speed={:.2} radius={:.1}",
+                frame,
+                node.bullet_type,
+                node.position.x,
+                node.position.y,
+                node.rotation,
+                node.speed,
+                node.radius
+            )
+            .as_str(),
+        );
+        match bullet_pattern::sub_pattern_of(node.extra.as_str()) {
+            Some((sub_pattern, delay)) => {
+                text.push_str(format!(" pattern={} delay={}", sub_pattern, delay).as_str())
+            }
+            None => (),
+        };
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Write spawn lists of every pattern in "path" into golden files in GOLDEN_DIRECTORY.
+/// Run it after a pattern is changed on purpose. `cargo test` compares them.
+///
+/// # Return:
+///
+/// * True: every golden file is written.
+///
+pub fn bless_patterns(path: &str) -> bool {
+    let bank = match load_patterns(path) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Bullet pattern error: {}", e);
+            return false;
+        }
+    };
+
+    let mut passed = true;
+    for name in bank.names().iter() {
+        let golden_path = format!("{}/{}.txt", GOLDEN_DIRECTORY, name);
+        match std::fs::write(golden_path.as_str(), golden_text(&bank, name).as_str()) {
+            Ok(_) => println!("Write {}", golden_path),
+            Err(e) => {
+                println!("Can not write {}. {}", golden_path, e);
+                passed = false;
+            }
+        };
+    }
+
+    passed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{golden_text, load_patterns, parse_patterns, GOLDEN_DIRECTORY};
+    use crate::gameplay::bullet_pattern::BULLET_PATTERN_PATH;
+
+    #[test]
+    fn every_pattern_fires_the_same_bullets_as_its_golden_file() {
+        let bank = load_patterns(BULLET_PATTERN_PATH).unwrap();
+        let names = bank.names();
+        assert!(names.len() > 0);
+
+        for name in names.iter() {
+            let golden_path = format!("{}/{}.txt", GOLDEN_DIRECTORY, name);
+            let golden = std::fs::read_to_string(golden_path.as_str()).unwrap_or_else(|e| {
+                panic!("Pattern \"{}\": can not read {}. {}", name, golden_path, e)
+            });
+            let text = golden_text(&bank, name);
+
+            for (index, (expected, actual)) in golden.lines().zip(text.lines()).enumerate() {
+                assert_eq!(
+                    expected,
+                    actual,
+                    "Pattern \"{}\": line {} is different. Run `cargo run -- --bless-patterns` if it is on purpose.",
+                    name,
+                    index + 1
+                );
+            }
+            assert_eq!(
+                golden.lines().count(),
+                text.lines().count(),
+                "Pattern \"{}\": bullets in golden and now",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn every_golden_file_has_a_pattern() {
+        let bank = load_patterns(BULLET_PATTERN_PATH).unwrap();
+        let names = bank.names();
+
+        for entry in std::fs::read_dir(GOLDEN_DIRECTORY).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            assert!(
+                names.iter().any(|v| v.as_str() == name.as_str()),
+                "{} has no pattern",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn optional_fields_need_no_some() {
+        let source = r#"BulletPatterns(
+            bullets: { "bullet-1": (bullet_type: 1) },
+            patterns: {
+                "implicit": [(shape: Ring(count: 4), bullet: "bullet-1", speed: 2.0)],
+                "explicit": [(shape: Ring(count: 4), bullet: "bullet-1", speed: Some(2.0))],
+            },
+        )"#;
+        let bank = parse_patterns("test.ron", source).unwrap();
+
+        assert_eq!(
+            golden_text(&bank, "implicit"),
+            golden_text(&bank, "explicit")
+        );
+    }
+
+    fn cycle_error(patterns: &str) -> (usize, usize, String) {
+        let source = format!(
+            "BulletPatterns(\n    bullets: {{ \"bullet-1\": (bullet_type: 1) }},\n    patterns: {{\n{}    }},\n)",
+            patterns
+        );
+        let e = parse_patterns("test.ron", source.as_str()).err().unwrap();
+        (e.line, e.column, e.message)
+    }
+
+    #[test]
+    fn sub_pattern_of_itself_is_an_error() {
+        let (line, column, message) = cycle_error(
+            "        \"a\": [(shape: Ring(count: 1), bullet: \"bullet-1\", sub_pattern: \"a\")],\n",
+        );
+
+        assert_eq!((line, column), (4, 59));
+        assert_eq!(message, "Cyclic sub_pattern in pattern \"a\": a -> a");
+    }
+
+    #[test]
+    fn sub_pattern_cycle_is_an_error() {
+        let (line, column, message) = cycle_error(concat!(
+            "        \"a\": [(shape: Ring(count: 1), bullet: \"bullet-1\", sub_pattern: \"b\")],\n",
+            "        \"b\": [\n",
+            "            (shape: Ring(count: 1), bullet: \"bullet-1\"),\n",
+            "            (shape: Ring(count: 1), bullet: \"bullet-1\", sub_pattern: \"c\"),\n",
+            "        ],\n",
+            "        \"c\": [(shape: Ring(count: 1), bullet: \"bullet-1\", sub_pattern: \"b\")],\n",
+        ));
+
+        assert_eq!((line, column), (9, 59));
+        assert_eq!(message, "Cyclic sub_pattern in pattern \"c\": b -> c -> b");
+    }
+
+    #[test]
+    fn shared_sub_pattern_is_not_a_cycle() {
+        let source = r#"BulletPatterns(
+            bullets: { "bullet-1": (bullet_type: 1) },
+            patterns: {
+                "a": [
+                    (shape: Ring(count: 1), bullet: "bullet-1", sub_pattern: "b"),
+                    (shape: Ring(count: 1), bullet: "bullet-1", sub_pattern: "c"),
+                ],
+                "b": [(shape: Ring(count: 1), bullet: "bullet-1", sub_pattern: "c")],
+                "c": [(shape: Ring(count: 1), bullet: "bullet-1")],
+            },
+        )"#;
+
+        assert!(parse_patterns("test.ron", source).is_ok());
+    }
+}
@@ -1,27 +1,45 @@
 use std::collections::HashMap;
 
-use rand::prelude::*;
-
 use tetra::math::Vec2;
 use tetra::Context;
 
 use crate::image_assets::ImageAssets;
 use crate::sprite::Sprite;
 
+use crate::gameplay::bullet_pattern::PatternSpawnNode;
+use crate::gameplay::parameters::{ParameterError, ParameterSpec, ParameterType, Parameters};
 use crate::gameplay::particle_manager::{ParticleDrawLayer, ParticleSpawnNode};
+use crate::gameplay::random::GameRng;
+use crate::gameplay::world::World;
+
+/// Keys in bullet.extra that use by every BulletType.
+/// "pattern": bullet pattern that the bullet fires at its position after "pattern_delay". (ms)
+/// The bullet is removed when it fires.
+pub const COMMON_PARAMETERS: [ParameterSpec; 7] = [
+    ParameterSpec::new("idle_animation", ParameterType::Text, None),
+    ParameterSpec::new("firing_animation", ParameterType::Text, None),
+    ParameterSpec::new("hit_animation", ParameterType::Text, None),
+    ParameterSpec::new("kill_animation", ParameterType::Text, None),
+    ParameterSpec::new("scale", ParameterType::Float, None),
+    ParameterSpec::new("pattern", ParameterType::Text, Some("")),
+    ParameterSpec::new("pattern_delay", ParameterType::UInt, Some("0")),
+];
 
 pub trait BulletType {
     /// Return type id of the BulletType.
     /// This value have to be unique between BulletType
     fn bullet_type_id(&self) -> i32;
 
+    /// Keys that this type read from bullet.extra. (Beside COMMON_PARAMETERS)
+    fn parameters(&self) -> &'static [ParameterSpec];
+
     /// Setup bullet data.
-    /// It will use first split('|') of bullet.extra for animation_name.
+    /// It will use "idle_animation" in bullet.extra for animation_name.
     /// If no animation. It will set bullet.active to false
     fn setup(&mut self, bullet: &mut Bullet, image_assets: &ImageAssets);
 
-    /// Use for updating bullet position.
-    fn update(&self, bullet: &mut Bullet);
+    /// Use for updating bullet position. Spawning and sound requests go into "world".
+    fn update(&self, bullet: &mut Bullet, world: &mut World);
 
     /// Draw bullet on screen. It will be called from inside Bullet.update()
     fn draw(&self, ctx: &mut Context, image_assets: &mut ImageAssets, bullet: &mut Bullet);
@@ -54,10 +72,34 @@ impl BulletTypeBank {
         self.types.insert(number, bullet_type);
     }
 
+    /// Keys that bullet type read from "extra". It can use without setup().
+    pub fn parameters(bullet_type_number: i32) -> Option<&'static [ParameterSpec]> {
+        match bullet_type_number {
+            1 => Some(&crate::gameplay::bullet_types::constant_velocity::PARAMETERS),
+            2 => Some(&crate::gameplay::bullet_types::homing::PARAMETERS),
+            3 => Some(&crate::gameplay::bullet_types::accelerating::PARAMETERS),
+            4 => Some(&crate::gameplay::bullet_types::sine_wave::PARAMETERS),
+            5 => Some(&crate::gameplay::bullet_types::bouncing::PARAMETERS),
+            _ => None,
+        }
+    }
+
     /// Setup default BulletTypes in the bank.
-    pub fn setup(&mut self, ctx: &mut Context, image_assets: &ImageAssets) {
+    pub fn setup(&mut self, image_assets: &ImageAssets) {
         let bullet_type =
-            crate::gameplay::bullet_types::constant_velocity::ConstantVelocityBulletType::new(ctx);
+            crate::gameplay::bullet_types::constant_velocity::ConstantVelocityBulletType::new();
+        self.add(bullet_type.bullet_type_id(), Box::new(bullet_type));
+
+        let bullet_type = crate::gameplay::bullet_types::homing::HomingBulletType::new();
+        self.add(bullet_type.bullet_type_id(), Box::new(bullet_type));
+
+        let bullet_type = crate::gameplay::bullet_types::accelerating::AcceleratingBulletType::new();
+        self.add(bullet_type.bullet_type_id(), Box::new(bullet_type));
+
+        let bullet_type = crate::gameplay::bullet_types::sine_wave::SineWaveBulletType::new();
+        self.add(bullet_type.bullet_type_id(), Box::new(bullet_type));
+
+        let bullet_type = crate::gameplay::bullet_types::bouncing::BouncingBulletType::new();
         self.add(bullet_type.bullet_type_id(), Box::new(bullet_type));
     }
 
@@ -110,7 +152,19 @@ pub struct Bullet {
     pub health: i32,
     pub damage: u32,
 
-    pub extra: HashMap<String, String>,
+    /// Time until the bullet fires "pattern" from extra. (ms)
+    pub pattern_time: u128,
+
+    /// Updates since the bullet is spawned.
+    pub frame: u32,
+    /// Movement per frame of BulletTypes that do not move along rotation. Ex: bouncing
+    pub velocity: Vec2<f32>,
+    /// Position on the straight path of BulletTypes that move around it. Ex: sine-wave
+    pub anchor: Vec2<f32>,
+    /// Free value for BulletType. Ex: bounce count
+    pub state: i32,
+
+    pub extra: Parameters,
     pub sprite: Sprite,
 }
 
@@ -129,7 +183,14 @@ impl Bullet {
             health: 1,
             damage: 1,
 
-            extra: HashMap::new(),
+            pattern_time: 0,
+
+            frame: 0,
+            velocity: Vec2::zero(),
+            anchor: Vec2::zero(),
+            state: 0,
+
+            extra: Parameters::new(),
             sprite: Sprite::new(),
         }
     }
@@ -146,27 +207,39 @@ impl Bullet {
         self.radius = 1.0;
         self.health = 1;
         self.damage = 1;
+        self.pattern_time = 0;
+        self.frame = 0;
+        self.velocity = Vec2::zero();
+        self.anchor = Vec2::zero();
+        self.state = 0;
         self.extra.clear();
         self.sprite.reset();
     }
 
-    pub fn update(&mut self) {
-        let mut bullet_type_bank = crate::BULLET_TYPE_BANK.lock().unwrap();
+    pub fn update(&mut self, bullet_type_bank: &BulletTypeBank, world: &mut World) {
         self.previous_position = self.position;
-        match bullet_type_bank.get_mut(self.bullet_type) {
+        match bullet_type_bank.get(self.bullet_type) {
             Some(t) => {
-                t.update(self);
+                t.update(self, world);
             }
             None => {}
         }
-
-        if crate::gameplay::utils::is_inside_camera_area(&self.position, self.radius) == false {
+        self.frame += 1;
+
+        if crate::gameplay::utils::is_inside_camera_area(
+            world.camera_position,
+            &self.position,
+            self.radius,
+        ) == false
+        {
             match self.life_time.checked_sub(crate::ONE_FRAME.as_millis()) {
                 Some(v) => self.life_time = v,
                 None => self.life_time = 0,
             };
         }
 
+        self.update_pattern(world);
+
         if self.life_time == 0 {
             self.active = false;
         }
@@ -176,9 +249,31 @@ impl Bullet {
         }
     }
 
-    pub fn draw(&mut self, ctx: &mut Context, image_assets: &mut ImageAssets) {
-        let mut bullet_type_bank = crate::BULLET_TYPE_BANK.lock().unwrap();
-        match bullet_type_bank.get_mut(self.bullet_type) {
+    /// Fire "pattern" from extra when pattern_time is over and remove the bullet.
+    fn update_pattern(&mut self, world: &mut World) {
+        let pattern = self.extra.get_text("pattern").unwrap_or("");
+        if pattern.len() == 0 || self.active == false {
+            return;
+        }
+
+        match self.pattern_time.checked_sub(crate::ONE_FRAME.as_millis()) {
+            Some(v) => self.pattern_time = v,
+            None => self.pattern_time = 0,
+        };
+
+        if self.pattern_time == 0 {
+            world.fire_pattern(PatternSpawnNode::new(0, pattern, self.position));
+            self.active = false;
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        bullet_type_bank: &BulletTypeBank,
+        image_assets: &mut ImageAssets,
+    ) {
+        match bullet_type_bank.get(self.bullet_type) {
             Some(t) => {
                 t.draw(ctx, image_assets, self);
             }
@@ -186,39 +281,80 @@ impl Bullet {
         }
     }
 
-    pub fn parsing_extra(&mut self, raw_extra: &str) {
-        if raw_extra.len() == 0 {
-            return;
-        }
-
-        let split: Vec<&str> = raw_extra.split('|').collect();
+    /// Make this bullet the bullet of "node" and setup it with its BulletType.
+    /// Call reset() before it when the bullet is reused.
+    ///
+    /// # Return:
+    ///
+    /// * Err: the type is not in "bullet_type_bank" or extra of the node is invalid.
+    ///
+    pub fn setup_from_node(
+        &mut self,
+        node: &BulletSpawnNode,
+        bullet_type_bank: &mut BulletTypeBank,
+        image_assets: &ImageAssets,
+    ) -> Result<(), String> {
+        self.active = true;
+        self.bullet_type = node.bullet_type;
+        self.owner_type = node.owner_type;
+        self.position = node.position;
+        self.previous_position = node.position;
+        self.rotation = node.rotation;
+        self.speed = node.speed;
+        self.radius = node.radius;
+
+        let bullet_type = match bullet_type_bank.get_mut(node.bullet_type) {
+            Some(t) => t,
+            None => return Err(format!("No bullet type: {}", node.bullet_type)),
+        };
 
-        for text in split.iter() {
-            if text.len() == 0 {
-                continue;
+        match self.parsing_extra(node.extra.as_str(), bullet_type.parameters()) {
+            Ok(_) => (),
+            Err(e) => {
+                return Err(format!(
+                    "Spawn bullet type {} error: {}",
+                    node.bullet_type, e
+                ))
             }
+        };
+
+        self.pattern_time = self.extra.get_uint("pattern_delay").unwrap_or(0);
 
-            let parameter: Vec<&str> = text.split('=').collect();
-            if parameter.len() == 2 {
-                self.extra
-                    .insert(String::from(parameter[0]), String::from(parameter[1]));
-            } else {
-                panic!("Incorrect parameter format: {} ({})", text, raw_extra);
+        match self.extra.get_float("scale") {
+            Some(scale) => {
+                self.sprite.scale = Vec2::new(scale, scale);
             }
-        }
+            None => (),
+        };
+
+        bullet_type.setup(self, image_assets);
+        Ok(())
     }
 
-    pub fn spawn_firing_particle(position: Vec2<f32>, raw_extra: &str) {
-        Bullet::spawn_particle(position, ParticleDrawLayer::FiringBullet, raw_extra);
+    /// Parse "raw_extra" into bullet.extra. Accept only COMMON_PARAMETERS and keys in "specs".
+    pub fn parsing_extra(
+        &mut self,
+        raw_extra: &str,
+        specs: &[ParameterSpec],
+    ) -> Result<(), ParameterError> {
+        self.extra.load(raw_extra, &[&COMMON_PARAMETERS, specs])
     }
 
-    pub fn spawn_hitting_particle(position: Vec2<f32>, raw_extra: &str) {
-        Bullet::spawn_particle(position, ParticleDrawLayer::BulletHit, raw_extra);
+    pub fn spawn_firing_particle(world: &mut World, position: Vec2<f32>, raw_extra: &str) {
+        Bullet::spawn_particle(world, position, ParticleDrawLayer::FiringBullet, raw_extra);
     }
 
-    fn spawn_particle(position: Vec2<f32>, draw_layer: ParticleDrawLayer, raw_extra: &str) {
-        let mut particle_spawn_nodes = crate::PARTICLE_SPAWN_NODES.lock().unwrap();
-        particle_spawn_nodes.push(ParticleSpawnNode::new(1, position, draw_layer, raw_extra));
+    pub fn spawn_hitting_particle(world: &mut World, position: Vec2<f32>, raw_extra: &str) {
+        Bullet::spawn_particle(world, position, ParticleDrawLayer::BulletHit, raw_extra);
+    }
+
+    fn spawn_particle(
+        world: &mut World,
+        position: Vec2<f32>,
+        draw_layer: ParticleDrawLayer,
+        raw_extra: &str,
+    ) {
+        world.spawn_particle(ParticleSpawnNode::new(1, position, draw_layer, raw_extra));
     }
 }
 
@@ -333,6 +469,11 @@ impl BulletPool {
         self.pool.push(bullet);
     }
 
+    /// Number of bullets that the pool created. Active and inactive.
+    pub fn capacity(&self) -> usize {
+        self.total_bullets as usize
+    }
+
     /// Pop a bullet from the pool
     pub fn pop(&mut self) -> Option<Bullet> {
         let size = self.pool.len();
@@ -368,44 +509,35 @@ impl BulletPool {
         self.pool.split_off(final_length)
     }
 
-    pub fn spawn_bullets_from_queue(image_assets: &ImageAssets) {
-        let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
-        let mut bullet_spawn_nodes = crate::BULLET_SPAWN_NODES.lock().unwrap();
-        let mut bullet_type_bank = crate::BULLET_TYPE_BANK.lock().unwrap();
+    /// Create bullets from world.bullet_spawn_nodes and clear the queue.
+    pub fn spawn_bullets_from_queue(
+        &mut self,
+        bullet_type_bank: &mut BulletTypeBank,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
+        let bullet_spawn_nodes = std::mem::take(&mut world.bullet_spawn_nodes);
 
         for node in bullet_spawn_nodes.iter() {
-            match bullet_pool.pop() {
+            match self.pop() {
                 Some(mut bullet) => {
                     bullet.reset();
-
-                    let bullet_type_id = node.bullet_type;
-
-                    bullet.active = true;
-                    bullet.bullet_type = node.bullet_type;
-                    bullet.owner_type = node.owner_type;
-                    bullet.position = node.position;
-                    bullet.rotation = node.rotation;
-                    bullet.speed = node.speed;
-                    bullet.radius = node.radius;
-                    bullet.parsing_extra(node.extra.as_str());
-
-                    match bullet.extra.get("scale") {
-                        Some(v) => {
-                            let scale = v.parse::<f32>().unwrap_or(1.0);
-                            bullet.sprite.scale = Vec2::new(scale, scale);
+                    match bullet.setup_from_node(node, bullet_type_bank, image_assets) {
+                        Ok(_) => (),
+                        Err(e) => {
+                            println!("{}", e);
+                            self.push(bullet);
+                            continue;
                         }
-                        None => (),
                     };
 
-                    let firing_animation = match bullet.extra.get("firing_animation") {
-                        Some(value) => value.as_str(),
-                        None => "",
-                    };
+                    let firing_animation = bullet.extra.get_text("firing_animation").unwrap_or("");
 
                     let random_size = 16.0;
                     let random_position = Vec2::new(
-                        random_size / 2.0 - random::<f32>() * random_size,
-                        random_size / 2.0 - random::<f32>() * random_size,
+                        random_size / 2.0 - rng.random::<f32>() * random_size,
+                        random_size / 2.0 - rng.random::<f32>() * random_size,
                     );
 
                     let rotation = if bullet.rotation < 0.0 {
@@ -421,37 +553,32 @@ impl BulletPool {
                     };
 
                     Bullet::spawn_firing_particle(
+                        world,
                         bullet.position + random_position,
                         format!("idle_animation={}|{}|scale=2.5|", firing_animation, flip_x)
                             .as_str(),
                     );
 
-                    match bullet_type_bank.get_mut(bullet_type_id) {
-                        Some(t) => {
-                            t.setup(&mut bullet, image_assets);
-                        }
-                        None => {}
-                    }
-
-                    bullet_pool.use_bullet(bullet);
+                    self.use_bullet(bullet);
                 }
                 None => break,
             };
         }
 
-        bullet_spawn_nodes.clear();
-
         // Spawn bullets from waiting queue. Any firing bullets always put in to the spawning queue.
-        bullet_pool.spawning_bullets_from_waiting_queue();
+        self.spawning_bullets_from_waiting_queue();
     }
 
-    pub fn update_active_player_bullets() {
+    pub fn update_active_player_bullets(
+        &mut self,
+        bullet_type_bank: &BulletTypeBank,
+        world: &mut World,
+    ) {
         let mut remove_active_bullet_list = vec![];
-        let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
         let mut index = 0;
 
-        for bullet in bullet_pool.player_active_bullets.iter_mut() {
-            bullet.update();
+        for bullet in self.player_active_bullets.iter_mut() {
+            bullet.update(bullet_type_bank, world);
 
             if bullet.active == false {
                 remove_active_bullet_list.push(index);
@@ -461,19 +588,22 @@ impl BulletPool {
         }
 
         for index in remove_active_bullet_list.iter().rev() {
-            let inactive_bullet = bullet_pool.player_active_bullets.remove(*index);
-            bullet_pool.push(inactive_bullet);
+            let inactive_bullet = self.player_active_bullets.remove(*index);
+            self.push(inactive_bullet);
         }
 
         remove_active_bullet_list.clear();
     }
 
-    pub fn update_active_enemies_bullets() {
+    pub fn update_active_enemies_bullets(
+        &mut self,
+        bullet_type_bank: &BulletTypeBank,
+        world: &mut World,
+    ) {
         let mut remove_active_bullet_list = vec![];
-        let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
         let mut index = 0;
-        for bullet in bullet_pool.enemy_active_bullets.iter_mut() {
-            bullet.update();
+        for bullet in self.enemy_active_bullets.iter_mut() {
+            bullet.update(bullet_type_bank, world);
 
             if bullet.active == false {
                 remove_active_bullet_list.push(index);
@@ -483,24 +613,32 @@ impl BulletPool {
         }
 
         for index in remove_active_bullet_list.iter().rev() {
-            let inactive_bullet = bullet_pool.enemy_active_bullets.remove(*index);
-            bullet_pool.push(inactive_bullet);
+            let inactive_bullet = self.enemy_active_bullets.remove(*index);
+            self.push(inactive_bullet);
         }
 
         remove_active_bullet_list.clear();
     }
 
-    pub fn draw_active_player_bullets(ctx: &mut Context, image_assets: &mut ImageAssets) {
-        let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
-        for bullet in bullet_pool.player_active_bullets.iter_mut() {
-            bullet.draw(ctx, image_assets);
+    pub fn draw_active_player_bullets(
+        &mut self,
+        ctx: &mut Context,
+        bullet_type_bank: &BulletTypeBank,
+        image_assets: &mut ImageAssets,
+    ) {
+        for bullet in self.player_active_bullets.iter_mut() {
+            bullet.draw(ctx, bullet_type_bank, image_assets);
         }
     }
 
-    pub fn draw_active_enemies_bullets(ctx: &mut Context, image_assets: &mut ImageAssets) {
-        let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
-        for bullet in bullet_pool.enemy_active_bullets.iter_mut() {
-            bullet.draw(ctx, image_assets);
+    pub fn draw_active_enemies_bullets(
+        &mut self,
+        ctx: &mut Context,
+        bullet_type_bank: &BulletTypeBank,
+        image_assets: &mut ImageAssets,
+    ) {
+        for bullet in self.enemy_active_bullets.iter_mut() {
+            bullet.draw(ctx, bullet_type_bank, image_assets);
         }
     }
 }
@@ -0,0 +1,204 @@
+use tetra::math::Vec2;
+
+use crate::image_assets::ImageAssets;
+
+use crate::gameplay::bullet_pool::{Bullet, BulletOwner, BulletTypeBank};
+use crate::gameplay::world::World;
+
+/// Directory of golden trajectories. "<case name>.txt" for every case.
+pub const GOLDEN_DIRECTORY: &str = "./resources/bullets/golden";
+/// The longest trajectory. (3 seconds)
+const TRAJECTORY_FRAMES: u32 = 180;
+/// Bullets start here and the only player stands at TARGET_POSITION.
+const START_POSITION: Vec2<f32> = Vec2::new(-200.0, -60.0);
+const TARGET_POSITION: Vec2<f32> = Vec2::new(100.0, 0.0);
+
+/// Bullet of a golden trajectory.
+struct TrajectoryCase {
+    name: &'static str,
+    bullet_type: i32,
+    rotation: f32,
+    speed: f32,
+    extra: &'static str,
+}
+
+const CASES: [TrajectoryCase; 7] = [
+    TrajectoryCase {
+        name: "constant-velocity",
+        bullet_type: 1,
+        rotation: 0.0,
+        speed: 2.0,
+        extra: "",
+    },
+    TrajectoryCase {
+        name: "homing",
+        bullet_type: 2,
+        rotation: 0.75,
+        speed: 2.0,
+        extra: "turn_rate=0.01|",
+    },
+    TrajectoryCase {
+        name: "homing-delayed",
+        bullet_type: 2,
+        rotation: 0.0,
+        speed: 2.0,
+        extra: "turn_rate=0.03|homing_delay=500|homing_time=1000|",
+    },
+    TrajectoryCase {
+        name: "accelerating",
+        bullet_type: 3,
+        rotation: 0.0,
+        speed: 0.5,
+        extra: "acceleration=0.05|max_speed=4.0|",
+    },
+    TrajectoryCase {
+        name: "decelerating",
+        bullet_type: 3,
+        rotation: 0.05,
+        speed: 4.0,
+        extra: "acceleration=-0.05|min_speed=1.0|",
+    },
+    TrajectoryCase {
+        name: "sine-wave",
+        bullet_type: 4,
+        rotation: 0.02,
+        speed: 2.0,
+        extra: "amplitude=16.0|frequency=1.5|",
+    },
+    TrajectoryCase {
+        name: "bouncing",
+        bullet_type: 5,
+        rotation: 0.9,
+        speed: 3.0,
+        extra: "gravity=0.2|bounce=0.6|max_bounces=2|",
+    },
+];
+
+/// Move the bullet of "case" until it is removed or TRAJECTORY_FRAMES.
+/// Rules of every BulletType are checked by the tests next to the type.
+///
+/// # Return:
+///
+/// * Ok: trajectory in text. One frame per line.
+/// * Err: extra of the case is invalid or the type does not exist.
+///
+fn run_case(
+    case: &TrajectoryCase,
+    bullet_type_bank: &mut BulletTypeBank,
+    image_assets: &ImageAssets,
+) -> Result<String, String> {
+    let mut world = World::new(Vec2::new(0.0, -crate::SCREEN_HEIGHT * 0.3));
+    world.player_positions.push(TARGET_POSITION);
+
+    let mut bullet = Bullet::new();
+    bullet.active = true;
+    bullet.bullet_type = case.bullet_type;
+    bullet.owner_type = BulletOwner::ENEMY;
+    bullet.position = START_POSITION;
+    bullet.rotation = case.rotation;
+    bullet.speed = case.speed;
+    bullet.radius = 4.0;
+
+    match bullet_type_bank.get_mut(case.bullet_type) {
+        Some(t) => {
+            match bullet.parsing_extra(case.extra, t.parameters()) {
+                Ok(_) => (),
+                Err(e) => return Err(format!("{}", e)),
+            };
+            t.setup(&mut bullet, image_assets);
+        }
+        None => return Err(format!("No bullet type: {}", case.bullet_type)),
+    };
+
+    let mut text = String::new();
+    while bullet.active && bullet.frame < TRAJECTORY_FRAMES {
+        bullet.update(bullet_type_bank, &mut world);
+
+        text.push_str(
+            format!(
+                "{} position=({:.2}, {:.2}) rotation={:.4} speed={:.3}\n",
+                bullet.frame, bullet.position.x, bullet.position.y, bullet.rotation, bullet.speed
+            )
+            .as_str(),
+        );
+    }
+
+    Ok(text)
+}
+
+/// Write trajectories of every case into golden files in GOLDEN_DIRECTORY.
+/// Run it after a BulletType is changed on purpose. `cargo test` compares them.
+///
+/// # Return:
+///
+/// * True: every golden file is written.
+///
+pub fn bless_trajectories() -> bool {
+    let image_assets = ImageAssets::new(vec![]);
+    let mut bullet_type_bank = BulletTypeBank::new();
+    bullet_type_bank.setup(&image_assets);
+
+    let mut passed = true;
+    for case in CASES.iter() {
+        let golden_path = format!("{}/{}.txt", GOLDEN_DIRECTORY, case.name);
+        let text = match run_case(case, &mut bullet_type_bank, &image_assets) {
+            Ok(v) => v,
+            Err(message) => {
+                println!("Bullet \"{}\": {}", case.name, message);
+                passed = false;
+                continue;
+            }
+        };
+
+        match std::fs::write(golden_path.as_str(), text.as_str()) {
+            Ok(_) => println!("Write {}", golden_path),
+            Err(e) => {
+                println!("Can not write {}. {}", golden_path, e);
+                passed = false;
+            }
+        };
+    }
+
+    passed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_case, CASES, GOLDEN_DIRECTORY};
+    use crate::gameplay::bullet_pool::BulletTypeBank;
+    use crate::image_assets::ImageAssets;
+
+    #[test]
+    fn every_bullet_type_moves_the_same_as_its_golden_file() {
+        let image_assets = ImageAssets::new(vec![]);
+        let mut bullet_type_bank = BulletTypeBank::new();
+        bullet_type_bank.setup(&image_assets);
+
+        for case in CASES.iter() {
+            let golden_path = format!("{}/{}.txt", GOLDEN_DIRECTORY, case.name);
+            let golden = std::fs::read_to_string(golden_path.as_str()).unwrap_or_else(|e| {
+                panic!(
+                    "Bullet \"{}\": can not read {}. {}",
+                    case.name, golden_path, e
+                )
+            });
+            let text = run_case(case, &mut bullet_type_bank, &image_assets).unwrap();
+
+            for (index, (expected, actual)) in golden.lines().zip(text.lines()).enumerate() {
+                assert_eq!(
+                    expected,
+                    actual,
+                    "Bullet \"{}\": line {} is different. Run `cargo run -- --bless-bullets` if it is on purpose.",
+                    case.name,
+                    index + 1
+                );
+            }
+            assert_eq!(
+                golden.lines().count(),
+                text.lines().count(),
+                "Bullet \"{}\": frames in golden and now",
+                case.name
+            );
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use tetra::Context;
+
+use crate::gameplay::bullet_pool::{Bullet, BulletType};
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
+use crate::gameplay::world::World;
+use crate::image_assets::ImageAssets;
+
+/// "acceleration": speed that is added every frame. Less than 0: slow down.
+/// "min_speed", "max_speed": the speed stays between them.
+pub const PARAMETERS: [ParameterSpec; 3] = [
+    ParameterSpec::new("acceleration", ParameterType::Float, Some("0.05")),
+    ParameterSpec::new("min_speed", ParameterType::Float, Some("0.0")),
+    ParameterSpec::new("max_speed", ParameterType::Float, Some("6.0")),
+];
+
+/// Bullet that speeds up or slows down along its rotation.
+pub struct AcceleratingBulletType {}
+
+impl AcceleratingBulletType {
+    pub fn new() -> AcceleratingBulletType {
+        AcceleratingBulletType {}
+    }
+}
+
+impl BulletType for AcceleratingBulletType {
+    fn bullet_type_id(&self) -> i32 {
+        3
+    }
+
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn setup(&mut self, bullet: &mut Bullet, image_assets: &ImageAssets) {
+        bullet.life_time = 100;
+        bullet.health = 2;
+
+        let idle_animation_name = bullet.extra.get_text("idle_animation").unwrap_or("");
+
+        match image_assets.get_animation_object(idle_animation_name) {
+            Some(animation) => {
+                bullet.sprite.play(&animation);
+            }
+            None => (),
+        };
+    }
+
+    fn update(&self, bullet: &mut Bullet, _world: &mut World) {
+        let acceleration = bullet.extra.get_float("acceleration").unwrap_or(0.0);
+        let min_speed = bullet.extra.get_float("min_speed").unwrap_or(0.0);
+        let max_speed = bullet.extra.get_float("max_speed").unwrap_or(bullet.speed);
+
+        bullet.speed = (bullet.speed + acceleration).max(min_speed).min(max_speed);
+        bullet.position +=
+            crate::gameplay::utils::rotation_to_direction(bullet.rotation) * bullet.speed;
+        bullet.sprite.update();
+    }
+
+    fn draw(&self, ctx: &mut Context, image_assets: &mut ImageAssets, bullet: &mut Bullet) {
+        bullet
+            .sprite
+            .draw(ctx, bullet.position, bullet.rotation, image_assets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tetra::math::Vec2;
+
+    use super::AcceleratingBulletType;
+    use crate::gameplay::bullet_pool::{Bullet, BulletType};
+    use crate::gameplay::world::World;
+
+    /// Speeds of 120 frames from "speed".
+    fn speeds(speed: f32, extra: &str) -> Vec<f32> {
+        let bullet_type = AcceleratingBulletType::new();
+        let mut world = World::new(Vec2::new(0.0, -crate::SCREEN_HEIGHT * 0.3));
+        let mut bullet = Bullet::new();
+        bullet.speed = speed;
+        bullet
+            .parsing_extra(extra, bullet_type.parameters())
+            .unwrap();
+
+        (0..120)
+            .map(|_| {
+                bullet_type.update(&mut bullet, &mut world);
+                bullet.speed
+            })
+            .collect()
+    }
+
+    #[test]
+    fn speeds_up_until_max_speed() {
+        let speeds = speeds(0.5, "acceleration=0.05|max_speed=4.0|");
+
+        assert!(speeds.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(speeds.iter().all(|speed| *speed <= 4.0));
+        assert_eq!(speeds[speeds.len() - 1], 4.0);
+    }
+
+    #[test]
+    fn slows_down_until_min_speed() {
+        let speeds = speeds(4.0, "acceleration=-0.05|min_speed=1.0|");
+
+        assert!(speeds.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(speeds.iter().all(|speed| *speed >= 1.0));
+        assert_eq!(speeds[speeds.len() - 1], 1.0);
+    }
+}
@@ -0,0 +1,170 @@
+use tetra::math::Vec2;
+use tetra::Context;
+
+use crate::gameplay::bullet_pool::{Bullet, BulletType};
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
+use crate::gameplay::world::World;
+use crate::image_assets::ImageAssets;
+
+/// "gravity": falling speed that is added every frame.
+/// "bounce": part of the falling speed that is kept after a bounce. (0.0 - 1.0)
+/// "max_bounces": the bullet is removed when it hits the ground after this many bounces.
+pub const PARAMETERS: [ParameterSpec; 3] = [
+    ParameterSpec::new("gravity", ParameterType::Float, Some("0.15")),
+    ParameterSpec::new("bounce", ParameterType::Float, Some("0.7")),
+    ParameterSpec::new("max_bounces", ParameterType::UInt, Some("3")),
+];
+
+/// Bullet that falls and bounces on the ground. (World::ground())
+/// bullet.velocity starts from rotation and speed. bullet.state counts bounces.
+pub struct BouncingBulletType {}
+
+impl BouncingBulletType {
+    pub fn new() -> BouncingBulletType {
+        BouncingBulletType {}
+    }
+}
+
+impl BulletType for BouncingBulletType {
+    fn bullet_type_id(&self) -> i32 {
+        5
+    }
+
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn setup(&mut self, bullet: &mut Bullet, image_assets: &ImageAssets) {
+        bullet.life_time = 100;
+        bullet.health = 2;
+        bullet.velocity =
+            crate::gameplay::utils::rotation_to_direction(bullet.rotation) * bullet.speed;
+
+        let idle_animation_name = bullet.extra.get_text("idle_animation").unwrap_or("");
+
+        match image_assets.get_animation_object(idle_animation_name) {
+            Some(animation) => {
+                bullet.sprite.play(&animation);
+            }
+            None => (),
+        };
+    }
+
+    fn update(&self, bullet: &mut Bullet, world: &mut World) {
+        let gravity = bullet.extra.get_float("gravity").unwrap_or(0.0);
+        let bounce = bullet.extra.get_float("bounce").unwrap_or(0.0);
+        let max_bounces = bullet.extra.get_uint("max_bounces").unwrap_or(0) as i32;
+
+        bullet.velocity.y += gravity;
+        bullet.position += bullet.velocity;
+
+        let ground = world.ground();
+        if bullet.position.y >= ground && bullet.velocity.y > 0.0 {
+            bullet.position.y = ground;
+            if bullet.state >= max_bounces {
+                bullet.health = 0;
+            } else {
+                bullet.state += 1;
+                bullet.velocity.y = -bullet.velocity.y * bounce;
+            }
+        }
+
+        bullet.rotation = crate::gameplay::utils::rotation_between(Vec2::zero(), bullet.velocity);
+        bullet.speed = bullet.velocity.magnitude();
+        bullet.sprite.update();
+    }
+
+    fn draw(&self, ctx: &mut Context, image_assets: &mut ImageAssets, bullet: &mut Bullet) {
+        bullet
+            .sprite
+            .draw(ctx, bullet.position, bullet.rotation, image_assets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tetra::math::Vec2;
+
+    use super::BouncingBulletType;
+    use crate::gameplay::bullet_pool::{Bullet, BulletType, BulletTypeBank};
+    use crate::gameplay::world::World;
+    use crate::image_assets::ImageAssets;
+
+    /// Bouncing bullet at (0, -60) that falls straight down.
+    fn setup_bullet(extra: &str) -> (Bullet, BulletTypeBank, World) {
+        let mut bullet_type = BouncingBulletType::new();
+        let mut bullet = Bullet::new();
+        bullet.active = true;
+        bullet.bullet_type = bullet_type.bullet_type_id();
+        bullet.position = Vec2::new(0.0, -60.0);
+        bullet.rotation = 0.25;
+        bullet.speed = 3.0;
+        bullet
+            .parsing_extra(extra, bullet_type.parameters())
+            .unwrap();
+        bullet_type.setup(&mut bullet, &ImageAssets::new(vec![]));
+
+        let mut bank = BulletTypeBank::new();
+        bank.add(bullet_type.bullet_type_id(), Box::new(bullet_type));
+
+        (
+            bullet,
+            bank,
+            World::new(Vec2::new(0.0, -crate::SCREEN_HEIGHT * 0.3)),
+        )
+    }
+
+    /// Update until the bullet is removed. Return the number of ground hits.
+    fn run_until_removed(bullet: &mut Bullet, bank: &BulletTypeBank, world: &mut World) -> i32 {
+        let mut hits = 0;
+        for _ in 0..600 {
+            let falling = bullet.velocity.y > 0.0;
+            bullet.update(bank, world);
+            assert!(bullet.position.y <= world.ground(), "under the ground");
+
+            if falling && bullet.position.y == world.ground() {
+                hits += 1;
+            }
+            if bullet.active == false {
+                return hits;
+            }
+        }
+
+        panic!("still bouncing after 600 frames");
+    }
+
+    #[test]
+    fn bounces_max_bounces_times_then_is_removed() {
+        let (mut bullet, bank, mut world) = setup_bullet("gravity=0.2|bounce=0.6|max_bounces=2|");
+
+        let hits = run_until_removed(&mut bullet, &bank, &mut world);
+
+        assert_eq!(hits, 3);
+        assert_eq!(bullet.state, 2);
+        assert_eq!(bullet.health, 0);
+    }
+
+    #[test]
+    fn is_removed_on_the_first_hit_without_bounces() {
+        let (mut bullet, bank, mut world) = setup_bullet("gravity=0.2|bounce=0.6|max_bounces=0|");
+
+        let hits = run_until_removed(&mut bullet, &bank, &mut world);
+
+        assert_eq!(hits, 1);
+        assert_eq!(bullet.state, 0);
+        assert_eq!(bullet.health, 0);
+    }
+
+    #[test]
+    fn bounce_keeps_part_of_the_falling_speed() {
+        let (mut bullet, bank, mut world) = setup_bullet("gravity=0.2|bounce=0.5|max_bounces=1|");
+
+        let mut falling_speed = 0.0;
+        while bullet.state == 0 {
+            falling_speed = bullet.velocity.y + 0.2;
+            bullet.update(&bank, &mut world);
+        }
+
+        assert!((bullet.velocity.y + falling_speed * 0.5).abs() < 0.0001);
+    }
+}
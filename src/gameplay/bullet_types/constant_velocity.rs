@@ -3,19 +3,18 @@ use tetra::math::Vec2;
 use tetra::Context;
 
 use crate::gameplay::bullet_pool::{Bullet, BulletOwner, BulletType};
+use crate::gameplay::parameters::ParameterSpec;
+use crate::gameplay::world::World;
 use crate::image_assets::ImageAssets;
+
+pub const PARAMETERS: [ParameterSpec; 0] = [];
+
 pub struct ConstantVelocityBulletType {
     // skin: Mesh,
 }
 
 impl ConstantVelocityBulletType {
-    pub fn new(ctx: &mut Context) -> ConstantVelocityBulletType {
-        let skin = GeometryBuilder::new()
-            .set_color(Color::BLUE)
-            .circle(ShapeStyle::Fill, Vec2::zero(), 6.0)
-            .unwrap()
-            .build_mesh(ctx)
-            .unwrap();
+    pub fn new() -> ConstantVelocityBulletType {
         ConstantVelocityBulletType{
             // skin: skin
         }
@@ -29,17 +28,19 @@ impl BulletType for ConstantVelocityBulletType {
         1
     }
 
+    /// Use only COMMON_PARAMETERS
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
     /// Setup bullet data.
-    /// It will use first split('|') of bullet.extra for animation_name.
+    /// It will use "idle_animation" in bullet.extra for animation_name.
     /// If no animation. It will set bullet.active to false
     fn setup(&mut self, bullet: &mut Bullet, image_assets: &ImageAssets) {
         bullet.life_time = 100;
         bullet.health = 2;
 
-        let idle_animation_name = match bullet.extra.get("idle_animation") {
-            Some(name) => name.as_str(),
-            None => "",
-        };
+        let idle_animation_name = bullet.extra.get_text("idle_animation").unwrap_or("");
 
         match image_assets.get_animation_object(idle_animation_name) {
             Some(animation) => {
@@ -50,7 +51,7 @@ impl BulletType for ConstantVelocityBulletType {
     }
 
     /// Use for updating bullet position.
-    fn update(&self, bullet: &mut Bullet) {
+    fn update(&self, bullet: &mut Bullet, _world: &mut World) {
         bullet.position.x += (bullet.rotation * 360.0).to_radians().cos() * bullet.speed;
         bullet.position.y += (bullet.rotation * 360.0).to_radians().sin() * bullet.speed;
         bullet.sprite.update();
@@ -63,3 +64,32 @@ impl BulletType for ConstantVelocityBulletType {
             .draw(ctx, bullet.position, bullet.rotation, image_assets);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tetra::math::Vec2;
+
+    use super::ConstantVelocityBulletType;
+    use crate::gameplay::bullet_pool::{Bullet, BulletType};
+    use crate::gameplay::world::World;
+
+    #[test]
+    fn moves_straight_at_the_same_speed() {
+        let bullet_type = ConstantVelocityBulletType::new();
+        let mut world = World::new(Vec2::new(0.0, -crate::SCREEN_HEIGHT * 0.3));
+        world.player_positions.push(Vec2::new(100.0, 0.0));
+
+        let mut bullet = Bullet::new();
+        bullet.rotation = 0.125;
+        bullet.speed = 2.0;
+
+        for _ in 0..30 {
+            bullet_type.update(&mut bullet, &mut world);
+        }
+
+        let expected = crate::gameplay::utils::rotation_to_direction(0.125) * 60.0;
+        assert!(Vec2::distance(bullet.position, expected) < 0.001);
+        assert_eq!(bullet.rotation, 0.125);
+        assert_eq!(bullet.speed, 2.0);
+    }
+}
@@ -0,0 +1,165 @@
+use tetra::Context;
+
+use crate::gameplay::bullet_pool::{Bullet, BulletType};
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
+use crate::gameplay::world::World;
+use crate::image_assets::ImageAssets;
+
+/// "turn_rate": the most rotation per frame. (0.0 - 1.0)
+/// "homing_delay": time before the bullet starts turning. (ms)
+/// "homing_time": time that the bullet turns. It flies straight after that. (ms)
+pub const PARAMETERS: [ParameterSpec; 3] = [
+    ParameterSpec::new("turn_rate", ParameterType::Float, Some("0.01")),
+    ParameterSpec::new("homing_delay", ParameterType::UInt, Some("0")),
+    ParameterSpec::new("homing_time", ParameterType::UInt, Some("2000")),
+];
+
+/// Bullet that turns to the nearest living player.
+pub struct HomingBulletType {}
+
+impl HomingBulletType {
+    pub fn new() -> HomingBulletType {
+        HomingBulletType {}
+    }
+}
+
+impl BulletType for HomingBulletType {
+    fn bullet_type_id(&self) -> i32 {
+        2
+    }
+
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn setup(&mut self, bullet: &mut Bullet, image_assets: &ImageAssets) {
+        bullet.life_time = 100;
+        bullet.health = 2;
+
+        let idle_animation_name = bullet.extra.get_text("idle_animation").unwrap_or("");
+
+        match image_assets.get_animation_object(idle_animation_name) {
+            Some(animation) => {
+                bullet.sprite.play(&animation);
+            }
+            None => (),
+        };
+    }
+
+    fn update(&self, bullet: &mut Bullet, world: &mut World) {
+        let time = bullet.frame as u128 * crate::ONE_FRAME.as_millis();
+        let homing_delay = bullet.extra.get_uint("homing_delay").unwrap_or(0);
+        let homing_time = bullet.extra.get_uint("homing_time").unwrap_or(0);
+
+        if time >= homing_delay && time < homing_delay + homing_time {
+            match world.nearest_player_position(bullet.position) {
+                Some(target) => {
+                    let turn_rate = bullet.extra.get_float("turn_rate").unwrap_or(0.0);
+                    bullet.rotation = crate::gameplay::utils::turn_angle(
+                        bullet.rotation,
+                        crate::gameplay::utils::rotation_between(bullet.position, target),
+                        turn_rate,
+                    );
+                }
+                None => (),
+            };
+        }
+
+        bullet.position +=
+            crate::gameplay::utils::rotation_to_direction(bullet.rotation) * bullet.speed;
+        bullet.sprite.update();
+    }
+
+    fn draw(&self, ctx: &mut Context, image_assets: &mut ImageAssets, bullet: &mut Bullet) {
+        bullet
+            .sprite
+            .draw(ctx, bullet.position, bullet.rotation, image_assets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tetra::math::Vec2;
+
+    use super::HomingBulletType;
+    use crate::gameplay::bullet_pool::{Bullet, BulletType, BulletTypeBank};
+    use crate::gameplay::world::World;
+    use crate::image_assets::ImageAssets;
+
+    /// The only player stands here.
+    const TARGET_POSITION: Vec2<f32> = Vec2::new(100.0, 0.0);
+
+    /// Homing bullet at (0, -60) that flies to the left, away from the player.
+    fn setup_bullet(extra: &str) -> (Bullet, BulletTypeBank, World) {
+        let mut bullet_type = HomingBulletType::new();
+        let mut bullet = Bullet::new();
+        bullet.active = true;
+        bullet.bullet_type = bullet_type.bullet_type_id();
+        bullet.position = Vec2::new(0.0, -60.0);
+        bullet.rotation = 0.5;
+        bullet.speed = 1.0;
+        bullet
+            .parsing_extra(extra, bullet_type.parameters())
+            .unwrap();
+        bullet_type.setup(&mut bullet, &ImageAssets::new(vec![]));
+
+        let mut bank = BulletTypeBank::new();
+        bank.add(bullet_type.bullet_type_id(), Box::new(bullet_type));
+
+        let mut world = World::new(Vec2::new(0.0, -crate::SCREEN_HEIGHT * 0.3));
+        world.player_positions.push(TARGET_POSITION);
+
+        (bullet, bank, world)
+    }
+
+    /// Rotation between "a" and "b" the short way around. (0.0 - 0.5)
+    fn turn_between(a: f32, b: f32) -> f32 {
+        let turn = (b - a).rem_euclid(1.0);
+        turn.min(1.0 - turn)
+    }
+
+    #[test]
+    fn turns_no_more_than_turn_rate_per_frame() {
+        let (mut bullet, bank, mut world) = setup_bullet("turn_rate=0.02|");
+
+        for frame in 0..120 {
+            let previous_rotation = bullet.rotation;
+            bullet.update(&bank, &mut world);
+
+            let turn = turn_between(previous_rotation, bullet.rotation);
+            assert!(turn <= 0.02 + 0.0001, "frame {}: turned {}", frame, turn);
+            if frame == 0 {
+                assert!((turn - 0.02).abs() < 0.0001, "first turn {}", turn);
+            }
+        }
+
+        let to_target = crate::gameplay::utils::rotation_between(bullet.position, TARGET_POSITION);
+        assert!(turn_between(bullet.rotation, to_target) < 0.02);
+    }
+
+    #[test]
+    fn turns_only_from_homing_delay_until_homing_time_is_over() {
+        let (mut bullet, bank, mut world) =
+            setup_bullet("turn_rate=0.005|homing_delay=500|homing_time=500|");
+
+        let mut turned = false;
+        for _ in 0..120 {
+            let time = bullet.frame as u128 * crate::ONE_FRAME.as_millis();
+            let previous_rotation = bullet.rotation;
+            bullet.update(&bank, &mut world);
+
+            if (500..1000).contains(&time) {
+                assert!(
+                    bullet.rotation != previous_rotation,
+                    "straight at {} ms",
+                    time
+                );
+                turned = true;
+            } else {
+                assert_eq!(bullet.rotation, previous_rotation, "turned at {} ms", time);
+            }
+        }
+
+        assert!(turned);
+    }
+}
@@ -0,0 +1,121 @@
+use tetra::math::Vec2;
+use tetra::Context;
+
+use crate::gameplay::bullet_pool::{Bullet, BulletType};
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
+use crate::gameplay::world::World;
+use crate::image_assets::ImageAssets;
+
+/// "amplitude": the farthest distance from the straight path. (pixel)
+/// "frequency": waves per second.
+/// "phase": start of the wave. (0.0 - 1.0) 0.0 and 0.5 start on the path, 0.5 waves to the other side.
+pub const PARAMETERS: [ParameterSpec; 3] = [
+    ParameterSpec::new("amplitude", ParameterType::Float, Some("12.0")),
+    ParameterSpec::new("frequency", ParameterType::Float, Some("1.0")),
+    ParameterSpec::new("phase", ParameterType::Float, Some("0.0")),
+];
+
+/// Bullet that waves across its rotation. bullet.anchor moves along the straight path.
+pub struct SineWaveBulletType {}
+
+impl SineWaveBulletType {
+    pub fn new() -> SineWaveBulletType {
+        SineWaveBulletType {}
+    }
+}
+
+impl BulletType for SineWaveBulletType {
+    fn bullet_type_id(&self) -> i32 {
+        4
+    }
+
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn setup(&mut self, bullet: &mut Bullet, image_assets: &ImageAssets) {
+        bullet.life_time = 100;
+        bullet.health = 2;
+        bullet.anchor = bullet.position;
+
+        let idle_animation_name = bullet.extra.get_text("idle_animation").unwrap_or("");
+
+        match image_assets.get_animation_object(idle_animation_name) {
+            Some(animation) => {
+                bullet.sprite.play(&animation);
+            }
+            None => (),
+        };
+    }
+
+    fn update(&self, bullet: &mut Bullet, _world: &mut World) {
+        let amplitude = bullet.extra.get_float("amplitude").unwrap_or(0.0);
+        let frequency = bullet.extra.get_float("frequency").unwrap_or(0.0);
+        let phase = bullet.extra.get_float("phase").unwrap_or(0.0);
+
+        let direction = crate::gameplay::utils::rotation_to_direction(bullet.rotation);
+        bullet.anchor += direction * bullet.speed;
+
+        let time = (bullet.frame + 1) as f32 * crate::ONE_FRAME.as_millis() as f32 / 1000.0;
+        let wave = ((time * frequency + phase) * std::f32::consts::PI * 2.0).sin();
+        bullet.position = bullet.anchor + Vec2::new(-direction.y, direction.x) * amplitude * wave;
+        bullet.sprite.update();
+    }
+
+    fn draw(&self, ctx: &mut Context, image_assets: &mut ImageAssets, bullet: &mut Bullet) {
+        bullet
+            .sprite
+            .draw(ctx, bullet.position, bullet.rotation, image_assets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tetra::math::Vec2;
+
+    use super::SineWaveBulletType;
+    use crate::gameplay::bullet_pool::{Bullet, BulletType};
+    use crate::gameplay::world::World;
+
+    /// Distances from the straight path of 120 frames. Sides are + and -.
+    fn path_distances(extra: &str) -> Vec<f32> {
+        let bullet_type = SineWaveBulletType::new();
+        let mut world = World::new(Vec2::new(0.0, -crate::SCREEN_HEIGHT * 0.3));
+        let mut bullet = Bullet::new();
+        bullet.rotation = 0.02;
+        bullet.speed = 2.0;
+        bullet
+            .parsing_extra(extra, bullet_type.parameters())
+            .unwrap();
+
+        let direction = crate::gameplay::utils::rotation_to_direction(bullet.rotation);
+        (0..120)
+            .map(|_| {
+                bullet_type.update(&mut bullet, &mut world);
+                bullet.frame += 1;
+                bullet.position.x * -direction.y + bullet.position.y * direction.x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn waves_on_both_sides_within_amplitude() {
+        let distances = path_distances("amplitude=16.0|frequency=1.5|");
+
+        assert!(distances
+            .iter()
+            .all(|distance| distance.abs() <= 16.0 + 0.001));
+        assert!(distances.iter().any(|distance| *distance > 15.0));
+        assert!(distances.iter().any(|distance| *distance < -15.0));
+    }
+
+    #[test]
+    fn half_phase_waves_to_the_other_side_first() {
+        let distances = path_distances("amplitude=16.0|frequency=1.5|");
+        let other_side = path_distances("amplitude=16.0|frequency=1.5|phase=0.5|");
+
+        for (a, b) in distances.iter().zip(other_side.iter()) {
+            assert!((a + b).abs() < 0.001);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use tetra::math::Vec2;
+
+/// Trauma that is removed per second. Trauma 1.0 stops shaking after 1 / TRAUMA_DECAY seconds.
+pub const TRAUMA_DECAY: f32 = 1.4;
+/// Camera offset at trauma 1.0. (pixel)
+pub const MAX_SHAKE_OFFSET: f32 = 10.0;
+/// Camera rotation at trauma 1.0. (radian)
+pub const MAX_SHAKE_ROTATION: f32 = 0.04;
+/// The longest hit-stop. Longer requests are cut. (ms)
+pub const MAX_HIT_STOP: u128 = 250;
+
+/// Camera request from players, enemies, bullets and particles. They are queued in World.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CameraEffect {
+    /// Add trauma. (0.0 - 1.0) Shake is trauma * trauma, so small hits stay small.
+    Trauma(f32),
+    /// Freeze the game for the duration. (ms) The longest request wins.
+    HitStop(u128),
+    /// Zoom in by "amount" (0.1 = 10%) and ease back to 1.0 in "duration". (ms)
+    Zoom { amount: f32, duration: u128 },
+}
+
+/// Easing of the camera travel between nodes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TravelCurve {
+    /// Move 2 pixels per frame on each axis.
+    Step,
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl TravelCurve {
+    pub fn from_name(name: &str) -> Option<TravelCurve> {
+        match name {
+            "step" => Some(TravelCurve::Step),
+            "linear" => Some(TravelCurve::Linear),
+            "ease_in" => Some(TravelCurve::EaseIn),
+            "ease_out" => Some(TravelCurve::EaseOut),
+            "ease_in_out" => Some(TravelCurve::EaseInOut),
+            _ => None,
+        }
+    }
+
+    /// Travel progress at time "t". (0.0 - 1.0)
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.min(1.0).max(0.0);
+        match self {
+            TravelCurve::Step | TravelCurve::Linear => t,
+            TravelCurve::EaseIn => t * t * t,
+            TravelCurve::EaseOut => 1.0 - (1.0 - t).powi(3),
+            TravelCurve::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Shake, hit-stop and zoom state of the camera. Simulation updates it with requests from World.
+/// Everything is driven by the frame counter, so replays get the same camera.
+pub struct CameraEffects {
+    trauma: f32,
+    hit_stop_time: u128,
+    zoom_amount: f32,
+    zoom_time: u128,
+    zoom_duration: u128,
+
+    offset: Vec2<f32>,
+    rotation: f32,
+}
+
+impl CameraEffects {
+    pub fn new() -> CameraEffects {
+        CameraEffects {
+            trauma: 0.0,
+            hit_stop_time: 0,
+            zoom_amount: 0.0,
+            zoom_time: 0,
+            zoom_duration: 0,
+            offset: Vec2::zero(),
+            rotation: 0.0,
+        }
+    }
+
+    pub fn apply(&mut self, effect: CameraEffect) {
+        match effect {
+            CameraEffect::Trauma(amount) => {
+                self.trauma = (self.trauma + amount).min(1.0).max(0.0);
+            }
+            CameraEffect::HitStop(duration) => {
+                self.hit_stop_time = self.hit_stop_time.max(duration.min(MAX_HIT_STOP));
+            }
+            CameraEffect::Zoom { amount, duration } => {
+                // Stronger punch replaces the running one.
+                if amount >= self.current_zoom_amount() || self.zoom_time == 0 {
+                    self.zoom_amount = amount;
+                    self.zoom_time = duration;
+                    self.zoom_duration = duration;
+                }
+            }
+        };
+    }
+
+    /// Move every effect by one frame of the game.
+    ///
+    /// # Arguments:
+    ///
+    /// * frame - frame counter of the simulation. It picks the shake direction.
+    ///
+    pub fn update(&mut self, frame: u64) {
+        let frame_time = crate::ONE_FRAME.as_millis();
+
+        self.trauma = (self.trauma - TRAUMA_DECAY * frame_time as f32 / 1000.0).max(0.0);
+        match self.hit_stop_time.checked_sub(frame_time) {
+            Some(v) => self.hit_stop_time = v,
+            None => self.hit_stop_time = 0,
+        };
+        match self.zoom_time.checked_sub(frame_time) {
+            Some(v) => self.zoom_time = v,
+            None => self.zoom_time = 0,
+        };
+
+        let shake = self.trauma * self.trauma;
+        if shake == 0.0 {
+            self.offset = Vec2::zero();
+            self.rotation = 0.0;
+        } else {
+            let frame = frame as f32;
+            self.offset = Vec2::new(
+                MAX_SHAKE_OFFSET * shake * noise(frame, 1.0),
+                MAX_SHAKE_OFFSET * shake * noise(frame, 2.0),
+            );
+            self.rotation = MAX_SHAKE_ROTATION * shake * noise(frame, 3.0);
+        }
+    }
+
+    /// Offset from shake. Add it to camera position for drawing only.
+    pub fn offset(&self) -> Vec2<f32> {
+        self.offset
+    }
+
+    /// Camera rotation from shake. (radian)
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    /// Camera zoom. 1.0: no zoom.
+    pub fn zoom(&self) -> f32 {
+        1.0 + self.current_zoom_amount()
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// True: the game is frozen by hit-stop.
+    pub fn is_hit_stop(&self) -> bool {
+        self.hit_stop_time > 0
+    }
+
+    fn current_zoom_amount(&self) -> f32 {
+        if self.zoom_time == 0 || self.zoom_duration == 0 {
+            return 0.0;
+        }
+
+        let t = self.zoom_time as f32 / self.zoom_duration as f32;
+        self.zoom_amount * TravelCurve::EaseIn.apply(t)
+    }
+}
+
+/// Smooth value between -1.0 and 1.0. "seed" gives every axis its own wave.
+fn noise(frame: f32, seed: f32) -> f32 {
+    let value = (frame * 0.9 * seed + seed * 12.7).sin() * 0.6
+        + (frame * 2.3 + seed * 4.1).sin() * 0.3
+        + (frame * 5.1 * seed).sin() * 0.1;
+    value.min(1.0).max(-1.0)
+}
@@ -0,0 +1,127 @@
+use tetra::math::Vec2;
+
+use crate::gameplay::camera_effects::TravelCurve;
+
+/// Samples of every spline segment. More samples: the speed along the curve is closer to constant.
+const SPLINE_SAMPLES: usize = 16;
+
+/// Camera speed when the node does not set one. (pixel per frame)
+pub const DEFAULT_CAMERA_SPEED: f32 = 2.0;
+
+/// How the camera moves from the previous node to a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeTravel {
+    pub curve: TravelCurve,
+    /// Waypoints between the previous node and this node. (world position)
+    pub waypoints: Vec<Vec2<f32>>,
+    /// True: smooth curve through the waypoints. False: straight lines.
+    pub spline: bool,
+    /// Average speed. (pixel per frame)
+    pub speed: f32,
+    /// True: the camera leaves this node after waiting_time without waiting for enemies.
+    /// Patterns that have not started keep going at the next node.
+    pub auto_scroll: bool,
+}
+
+impl NodeTravel {
+    pub fn new() -> NodeTravel {
+        NodeTravel {
+            curve: TravelCurve::Step,
+            waypoints: vec![],
+            spline: false,
+            speed: DEFAULT_CAMERA_SPEED,
+            auto_scroll: false,
+        }
+    }
+
+    /// True: travel uses the original movement. (Step on each axis, no waypoints)
+    pub fn is_step(&self) -> bool {
+        self.curve == TravelCurve::Step && self.waypoints.len() == 0 && self.spline == false
+    }
+}
+
+/// Line or Catmull-Rom spline through points. Position is looked up by distance along the path,
+/// so the camera moves at the same speed on long and short segments.
+pub struct CameraPath {
+    /// (distance from the start, position)
+    samples: Vec<(f32, Vec2<f32>)>,
+}
+
+impl CameraPath {
+    /// # Arguments:
+    ///
+    /// * points - start, waypoints and end of the path. At least 1 point.
+    /// * spline - True: smooth curve through every point.
+    ///
+    pub fn new(points: &[Vec2<f32>], spline: bool) -> CameraPath {
+        let mut positions = vec![];
+        if spline && points.len() > 2 {
+            for index in 0..points.len() - 1 {
+                let p0 = points[index.max(1) - 1];
+                let p1 = points[index];
+                let p2 = points[index + 1];
+                let p3 = points[(index + 2).min(points.len() - 1)];
+                for sample in 0..SPLINE_SAMPLES {
+                    let t = sample as f32 / SPLINE_SAMPLES as f32;
+                    positions.push(catmull_rom(p0, p1, p2, p3, t));
+                }
+            }
+            positions.push(points[points.len() - 1]);
+        } else {
+            positions.extend_from_slice(points);
+        }
+
+        let mut samples = vec![];
+        let mut distance = 0.0;
+        for (index, position) in positions.iter().enumerate() {
+            if index > 0 {
+                distance += Vec2::distance(positions[index - 1], *position);
+            }
+            samples.push((distance, *position));
+        }
+
+        CameraPath { samples: samples }
+    }
+
+    /// Length of the path. (pixel)
+    pub fn length(&self) -> f32 {
+        match self.samples.last() {
+            Some(v) => v.0,
+            None => 0.0,
+        }
+    }
+
+    /// Position at "distance" from the start. Distance is clamped to the path.
+    pub fn position_at(&self, distance: f32) -> Vec2<f32> {
+        let first = match self.samples.first() {
+            Some(v) => *v,
+            None => return Vec2::zero(),
+        };
+        if distance <= 0.0 {
+            return first.1;
+        }
+
+        for pair in self.samples.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if distance <= to.0 {
+                let segment = to.0 - from.0;
+                if segment <= 0.0 {
+                    return to.1;
+                }
+                return Vec2::lerp(from.1, to.1, (distance - from.0) / segment);
+            }
+        }
+
+        self.samples[self.samples.len() - 1].1
+    }
+}
+
+fn catmull_rom(p0: Vec2<f32>, p1: Vec2<f32>, p2: Vec2<f32>, p3: Vec2<f32>, t: f32) -> Vec2<f32> {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
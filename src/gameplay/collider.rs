@@ -0,0 +1,277 @@
+use tetra::math::Vec2;
+
+/// Results of Enemy::hit_check().
+/// 0: not hit, 1: hit weak point, 2: hit armour, -1: hit shield. (No damage)
+pub const HIT_NONE: i32 = 0;
+pub const HIT_WEAK_POINT: i32 = 1;
+pub const HIT_ARMOUR: i32 = 2;
+pub const HIT_SHIELD: i32 = -1;
+
+/// Armour divides damage by this. Bullets (1 damage) can not break armour, melee attacks can.
+const ARMOUR_DAMAGE_DIVISOR: u32 = 2;
+/// Iterations of the swept test. Enough for less than 0.001 error of hit time.
+const SWEEP_ITERATIONS: u32 = 24;
+
+/// What a ColliderPart does with hits.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HitZone {
+    /// Full damage.
+    WeakPoint,
+    /// Damage divided by ARMOUR_DAMAGE_DIVISOR.
+    Armour,
+    /// No damage. Bullets are still stopped.
+    Invulnerable,
+}
+
+impl HitZone {
+    pub fn hit_result(&self) -> i32 {
+        match self {
+            HitZone::WeakPoint => HIT_WEAK_POINT,
+            HitZone::Armour => HIT_ARMOUR,
+            HitZone::Invulnerable => HIT_SHIELD,
+        }
+    }
+}
+
+/// Damage after the hit zone of "hit_result". 0: the hit does not hurt.
+pub fn damage_of(hit_result: i32, damage: u32) -> u32 {
+    match hit_result {
+        HIT_WEAK_POINT => damage,
+        HIT_ARMOUR => damage / ARMOUR_DAMAGE_DIVISOR,
+        _ => 0,
+    }
+}
+
+/// Shape of a ColliderPart. Positions are relative to the owner.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColliderShape {
+    Circle {
+        center: Vec2<f32>,
+        radius: f32,
+    },
+    /// Line from "start" to "end" with radius.
+    Capsule {
+        start: Vec2<f32>,
+        end: Vec2<f32>,
+        radius: f32,
+    },
+    /// Axis-aligned box.
+    Rectangle {
+        center: Vec2<f32>,
+        half_size: Vec2<f32>,
+    },
+}
+
+impl ColliderShape {
+    /// Distance from "point" to the shape. 0.0: the point is inside.
+    pub fn distance(&self, point: Vec2<f32>) -> f32 {
+        match self {
+            ColliderShape::Circle { center, radius } => {
+                (Vec2::distance(point, *center) - radius).max(0.0)
+            }
+            ColliderShape::Capsule { start, end, radius } => {
+                (Vec2::distance(point, closest_point_on_line(*start, *end, point)) - radius)
+                    .max(0.0)
+            }
+            ColliderShape::Rectangle { center, half_size } => {
+                let outside = Vec2::new(
+                    ((point.x - center.x).abs() - half_size.x).max(0.0),
+                    ((point.y - center.y).abs() - half_size.y).max(0.0),
+                );
+                outside.magnitude()
+            }
+        }
+    }
+
+    /// Radius of the circle around (0, 0) that covers the shape.
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            ColliderShape::Circle { center, radius } => center.magnitude() + radius,
+            ColliderShape::Capsule { start, end, radius } => {
+                start.magnitude().max(end.magnitude()) + radius
+            }
+            ColliderShape::Rectangle { center, half_size } => {
+                (center.x.abs() + half_size.x).hypot(center.y.abs() + half_size.y)
+            }
+        }
+    }
+
+    /// Move a circle from "from" to "to" and find the first time it touches the shape.
+    /// The shape is placed at "origin".
+    ///
+    /// # Return:
+    ///
+    /// * Some: time of the hit. 0.0: touching at "from", 1.0: touching at "to".
+    /// * None: not hit.
+    ///
+    pub fn sweep(
+        &self,
+        origin: Vec2<f32>,
+        from: Vec2<f32>,
+        to: Vec2<f32>,
+        radius: f32,
+    ) -> Option<f32> {
+        let distance = |time: f32| self.distance(Vec2::lerp(from, to, time) - origin) - radius;
+
+        if distance(0.0) <= 0.0 {
+            return Some(0.0);
+        }
+
+        let length = Vec2::distance(from, to);
+        if length == 0.0 || distance(0.5) > length * 0.5 {
+            return None;
+        }
+
+        // Distance to a convex shape is convex along a line. Find the closest time first.
+        let mut low = 0.0;
+        let mut high = 1.0;
+        for _ in 0..SWEEP_ITERATIONS {
+            let left = low + (high - low) / 3.0;
+            let right = high - (high - low) / 3.0;
+            if distance(left) < distance(right) {
+                high = right;
+            } else {
+                low = left;
+            }
+        }
+
+        let closest = (low + high) / 2.0;
+        if distance(closest) > 0.0 {
+            return None;
+        }
+
+        // Distance only goes down before the closest time. Find where it reaches 0.
+        let mut low = 0.0;
+        let mut high = closest;
+        for _ in 0..SWEEP_ITERATIONS {
+            let middle = (low + high) / 2.0;
+            if distance(middle) > 0.0 {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+
+        Some(high)
+    }
+}
+
+fn closest_point_on_line(start: Vec2<f32>, end: Vec2<f32>, point: Vec2<f32>) -> Vec2<f32> {
+    let line = end - start;
+    let length_squared = line.magnitude_squared();
+    if length_squared == 0.0 {
+        return start;
+    }
+
+    let time = ((point - start).dot(line) / length_squared)
+        .max(0.0)
+        .min(1.0);
+    start + line * time
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColliderPart {
+    pub shape: ColliderShape,
+    pub zone: HitZone,
+}
+
+/// Hit area made of circles, capsules and boxes. Each part is a weak point, armour or invulnerable.
+///
+/// ```text
+/// Collider::new()
+///     .part(HitZone::WeakPoint, ColliderShape::Circle { center: Vec2::new(0.0, -8.0), radius: 6.0 })
+///     .part(HitZone::Armour, ColliderShape::Circle { center: Vec2::zero(), radius: 16.0 })
+/// ```
+///
+/// A moving circle hits the part that it touches first. Parts that are touched at the same time
+/// are checked in the order they are added.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collider {
+    pub parts: Vec<ColliderPart>,
+}
+
+impl Collider {
+    pub fn new() -> Collider {
+        Collider { parts: vec![] }
+    }
+
+    /// One circle at the center.
+    pub fn circle(radius: f32, zone: HitZone) -> Collider {
+        Collider::new().part(
+            zone,
+            ColliderShape::Circle {
+                center: Vec2::zero(),
+                radius: radius,
+            },
+        )
+    }
+
+    pub fn part(mut self, zone: HitZone, shape: ColliderShape) -> Collider {
+        self.parts.push(ColliderPart {
+            shape: shape,
+            zone: zone,
+        });
+        self
+    }
+
+    pub fn clear(&mut self) {
+        self.parts.clear();
+    }
+
+    /// Radius of the circle around the owner that covers every part. 0.0: no part.
+    pub fn bounding_radius(&self) -> f32 {
+        self.parts
+            .iter()
+            .fold(0.0, |radius, part| part.shape.bounding_radius().max(radius))
+    }
+
+    /// Move a circle from "from" to "to" and find the first part that it touches.
+    ///
+    /// # Arguments:
+    ///
+    /// * origin - position of the owner.
+    /// * from, to - path of the circle in this frame. Use the same position for a circle that does not move.
+    ///
+    /// # Return:
+    ///
+    /// * Some: the part and time of the hit. (0.0 - 1.0)
+    /// * None: not hit.
+    ///
+    pub fn sweep(
+        &self,
+        origin: Vec2<f32>,
+        from: Vec2<f32>,
+        to: Vec2<f32>,
+        radius: f32,
+    ) -> Option<(&ColliderPart, f32)> {
+        let mut result: Option<(&ColliderPart, f32)> = None;
+        for part in self.parts.iter() {
+            match part.shape.sweep(origin, from, to, radius) {
+                Some(time) => match result {
+                    Some((_, first_time)) if first_time <= time => (),
+                    _ => result = Some((part, time)),
+                },
+                None => (),
+            };
+        }
+
+        result
+    }
+
+    /// Same as sweep() but return one of HIT_NONE, HIT_WEAK_POINT, HIT_ARMOUR and HIT_SHIELD.
+    pub fn hit_check(&self, origin: Vec2<f32>, from: Vec2<f32>, to: Vec2<f32>, radius: f32) -> i32 {
+        match self.sweep(origin, from, to, radius) {
+            Some((part, _)) => part.zone.hit_result(),
+            None => HIT_NONE,
+        }
+    }
+}
+
+/// Center and radius of the circle that covers a circle moving from "from" to "to".
+/// Use it for finding candidates in Broadphase.
+pub fn sweep_bounds(from: Vec2<f32>, to: Vec2<f32>, radius: f32) -> (Vec2<f32>, f32) {
+    (
+        Vec2::lerp(from, to, 0.5),
+        radius + Vec2::distance(from, to) * 0.5,
+    )
+}
@@ -0,0 +1,412 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tetra::graphics::text::{Font, Text};
+use tetra::graphics::{self, Color, DrawParams, GeometryBuilder, Rectangle, ShapeStyle};
+use tetra::math::Vec2;
+use tetra::Context;
+
+use crate::gameplay::collider::{ColliderShape, HitZone};
+use crate::gameplay::player::Player;
+use crate::gameplay::simulation::Simulation;
+
+/// Frames in the frame time graph.
+const GRAPH_FRAMES: usize = 120;
+/// Frame time at the top of the graph. (ms)
+const GRAPH_MAX_TIME: f32 = 33.4;
+const GRAPH_HEIGHT: f32 = 40.0;
+/// Frame time of 60 fps. (ms) The graph draws a line at this time.
+const TARGET_FRAME_TIME: f32 = 16.7;
+
+const WEAK_POINT_COLOR: Color = Color::rgb(1.0, 0.0, 0.0);
+const ARMOUR_COLOR: Color = Color::rgb(1.0, 0.55, 0.0);
+const SHIELD_COLOR: Color = Color::rgb(0.2, 0.4, 1.0);
+const RADIUS_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.35);
+const PLAYER_BULLET_COLOR: Color = Color::rgb(0.0, 0.7, 0.2);
+const ENEMY_BULLET_COLOR: Color = Color::rgb(0.9, 0.0, 0.9);
+const MELEE_COLOR: Color = Color::rgb(0.9, 0.8, 0.0);
+const NODE_COLOR: Color = Color::rgb(0.0, 0.5, 0.6);
+const TEXT_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+
+/// Hitboxes, camera nodes, pool usage, pattern timers and frame time graph on top of the game.
+/// Toggle it with GamePlayScene's debug key. It only reads the simulation.
+pub struct DebugOverlay {
+    visible: bool,
+    /// Latest frames. (frame time, simulation step time) in ms.
+    frame_times: VecDeque<(f32, f32)>,
+    /// Time of the last Simulation::step(). (ms)
+    step_time: f32,
+
+    text: Option<Text>,
+    node_text: Option<Text>,
+}
+
+impl DebugOverlay {
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            visible: false,
+            frame_times: VecDeque::new(),
+            step_time: 0.0,
+            text: None,
+            node_text: None,
+        }
+    }
+
+    pub fn setup(&mut self, ctx: &mut Context) {
+        let font = match Font::vector(ctx, "./resources/fonts/D-DINCondensed.ttf", 14.0) {
+            Ok(v) => v,
+            Err(_) => panic!("Load font error for debug overlay"),
+        };
+        self.text = Some(Text::new("", font.clone()));
+        self.node_text = Some(Text::new("", font));
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Keep time of the last Simulation::step() for the graph.
+    pub fn set_step_time(&mut self, time: Duration) {
+        self.step_time = time.as_secs_f32() * 1000.0;
+    }
+
+    /// Keep time of this frame for the graph. Call it once per draw, even when the overlay is hidden.
+    pub fn record_frame(&mut self, ctx: &Context) {
+        let frame_time = tetra::time::get_delta_time(ctx).as_secs_f32() * 1000.0;
+        self.frame_times.push_back((frame_time, self.step_time));
+        while self.frame_times.len() > GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+    }
+
+    /// Colliders, radii and camera nodes. Draw it with the camera transform.
+    pub fn draw_world(&mut self, ctx: &mut Context, simulation: &Simulation) {
+        if self.visible == false {
+            return;
+        }
+
+        let mut builder = GeometryBuilder::new();
+
+        for enemy in simulation.enemy_manager.get_active_enemy().iter() {
+            circle(&mut builder, RADIUS_COLOR, enemy.position, enemy.radius);
+
+            for part in enemy.collider.parts.iter() {
+                let color = match part.zone {
+                    HitZone::WeakPoint => WEAK_POINT_COLOR,
+                    HitZone::Armour => ARMOUR_COLOR,
+                    HitZone::Invulnerable => SHIELD_COLOR,
+                };
+                shape(&mut builder, color, enemy.position, &part.shape);
+            }
+        }
+
+        for bullet in simulation.bullet_pool.player_active_bullets.iter() {
+            circle(
+                &mut builder,
+                PLAYER_BULLET_COLOR,
+                bullet.position,
+                bullet.radius,
+            );
+            line(
+                &mut builder,
+                PLAYER_BULLET_COLOR,
+                bullet.previous_position,
+                bullet.position,
+            );
+        }
+
+        for bullet in simulation.bullet_pool.enemy_active_bullets.iter() {
+            circle(
+                &mut builder,
+                ENEMY_BULLET_COLOR,
+                bullet.position,
+                bullet.radius,
+            );
+            line(
+                &mut builder,
+                ENEMY_BULLET_COLOR,
+                bullet.previous_position,
+                bullet.position,
+            );
+        }
+
+        for player in simulation.players.iter() {
+            if player.alive() == false {
+                continue;
+            }
+
+            circle(
+                &mut builder,
+                WEAK_POINT_COLOR,
+                player.get_hit_point_position(),
+                player.get_hit_point_radius(),
+            );
+
+            let melee_color = match player.is_attacking() {
+                true => MELEE_COLOR,
+                false => RADIUS_COLOR,
+            };
+            circle(
+                &mut builder,
+                melee_color,
+                player.get_melee_attack_position(),
+                Player::get_melee_attack_radius(),
+            );
+        }
+
+        let current_node = simulation
+            .level
+            .get_current_node()
+            .map(|node| node.name.as_str());
+        for (_, node) in simulation.level.all_nodes().iter() {
+            let size = match current_node == Some(node.name.as_str()) {
+                true => 12.0,
+                false => 6.0,
+            };
+            line(
+                &mut builder,
+                NODE_COLOR,
+                node.position - Vec2::new(size, 0.0),
+                node.position + Vec2::new(size, 0.0),
+            );
+            line(
+                &mut builder,
+                NODE_COLOR,
+                node.position - Vec2::new(0.0, size),
+                node.position + Vec2::new(0.0, size),
+            );
+
+            match node.next_node.as_ref() {
+                Some(name) => match simulation.level.get_node(name) {
+                    Some(next_node) => line(
+                        &mut builder,
+                        RADIUS_COLOR,
+                        node.position,
+                        next_node.position,
+                    ),
+                    None => (),
+                },
+                None => (),
+            };
+        }
+
+        draw_builder(ctx, &builder, Vec2::zero());
+
+        let node_text = self.node_text.as_mut().unwrap();
+        for (_, node) in simulation.level.all_nodes().iter() {
+            node_text.set_content(node.name.as_str());
+            graphics::draw(
+                ctx,
+                &*node_text,
+                DrawParams::new()
+                    .position(node.position + Vec2::new(4.0, 2.0))
+                    .color(NODE_COLOR),
+            );
+        }
+    }
+
+    /// Pool usage, pattern timers and frame time graph. Draw it without the camera transform.
+    pub fn draw_screen(&mut self, ctx: &mut Context, simulation: &Simulation) {
+        if self.visible == false {
+            return;
+        }
+
+        let (frame_time, step_time) = self.frame_times.back().cloned().unwrap_or((0.0, 0.0));
+        let mut lines = vec![format!(
+            "FPS {:.0}  FRAME {:.1} ms  STEP {:.2} ms",
+            tetra::time::get_fps(ctx),
+            frame_time,
+            step_time
+        )];
+
+        let bullet_pool = &simulation.bullet_pool;
+        lines.push(format!(
+            "BULLETS {}/{} (player {}, enemy {})",
+            bullet_pool.player_active_bullets.len() + bullet_pool.enemy_active_bullets.len(),
+            bullet_pool.capacity(),
+            bullet_pool.player_active_bullets.len(),
+            bullet_pool.enemy_active_bullets.len()
+        ));
+        lines.push(format!(
+            "ENEMIES {}/{}",
+            simulation.enemy_manager.active_enemy_count(),
+            simulation.enemy_manager.capacity()
+        ));
+        lines.push(format!(
+            "PARTICLES {}/{}",
+            simulation.particle_manager.active_particle_count(),
+            simulation.particle_manager.capacity()
+        ));
+
+        match simulation.level.get_current_node() {
+            Some(node) => lines.push(format!(
+                "NODE {} -> {}",
+                node.name,
+                node.next_node
+                    .as_ref()
+                    .map(|name| name.as_str())
+                    .unwrap_or("-")
+            )),
+            None => lines.push(String::from("NODE -")),
+        };
+
+        for (pattern, time) in simulation.level.upcoming_patterns().iter() {
+            lines.push(format!("PATTERN {} in {:.2} s", pattern, seconds(*time)));
+        }
+
+        for time in simulation.level.next_enemy_spawn_times().iter() {
+            lines.push(format!("SPAWNING next enemy in {:.2} s", seconds(*time)));
+        }
+
+        for (_, emitter) in simulation.pattern_emitters().iter() {
+            lines.push(format!(
+                "BULLET PATTERN {} volley {} in {:.2} s",
+                emitter.pattern(),
+                emitter.volley_index(),
+                seconds(emitter.next_shot_time())
+            ));
+        }
+
+        for enemy in simulation.enemy_manager.get_active_enemy().iter() {
+            match enemy.bullet_pattern.as_ref() {
+                Some(emitter) if emitter.is_finished() == false => lines.push(format!(
+                    "BULLET PATTERN {} volley {} in {:.2} s (enemy {})",
+                    emitter.pattern(),
+                    emitter.volley_index(),
+                    seconds(emitter.next_shot_time()),
+                    enemy.enemy_type
+                )),
+                _ => (),
+            };
+        }
+
+        let text = self.text.as_mut().unwrap();
+        text.set_content(lines.join("\n"));
+        graphics::draw(
+            ctx,
+            &*text,
+            DrawParams::new()
+                .position(Vec2::new(16.0, 64.0))
+                .color(TEXT_COLOR),
+        );
+
+        self.draw_graph(ctx);
+    }
+
+    /// Frame times (black) and simulation step times (orange) at the bottom left corner.
+    fn draw_graph(&self, ctx: &mut Context) {
+        let width = GRAPH_FRAMES as f32 * 2.0;
+        let position = Vec2::new(16.0, crate::SCREEN_HEIGHT - GRAPH_HEIGHT - 8.0);
+        let height_of = |time: f32| GRAPH_HEIGHT - (time / GRAPH_MAX_TIME).min(1.0) * GRAPH_HEIGHT;
+
+        let mut builder = GeometryBuilder::new();
+        builder.set_color(Color::rgba(1.0, 1.0, 1.0, 0.6));
+        builder
+            .rectangle(
+                ShapeStyle::Fill,
+                Rectangle::new(0.0, 0.0, width, GRAPH_HEIGHT),
+            )
+            .ok();
+
+        let target = height_of(TARGET_FRAME_TIME);
+        line(
+            &mut builder,
+            RADIUS_COLOR,
+            Vec2::new(0.0, target),
+            Vec2::new(width, target),
+        );
+
+        if self.frame_times.len() > 1 {
+            let frame_points: Vec<Vec2<f32>> = self
+                .frame_times
+                .iter()
+                .enumerate()
+                .map(|(index, (time, _))| Vec2::new(index as f32 * 2.0, height_of(*time)))
+                .collect();
+            builder.set_color(TEXT_COLOR);
+            builder.polyline(1.0, &frame_points).ok();
+
+            let step_points: Vec<Vec2<f32>> = self
+                .frame_times
+                .iter()
+                .enumerate()
+                .map(|(index, (_, time))| Vec2::new(index as f32 * 2.0, height_of(*time)))
+                .collect();
+            builder.set_color(ARMOUR_COLOR);
+            builder.polyline(1.0, &step_points).ok();
+        }
+
+        draw_builder(ctx, &builder, position);
+    }
+}
+
+fn seconds(time: u128) -> f32 {
+    time as f32 / 1000.0
+}
+
+fn circle(builder: &mut GeometryBuilder, color: Color, center: Vec2<f32>, radius: f32) {
+    if radius <= 0.0 {
+        return;
+    }
+
+    builder.set_color(color);
+    builder.circle(ShapeStyle::Stroke(1.0), center, radius).ok();
+}
+
+fn line(builder: &mut GeometryBuilder, color: Color, from: Vec2<f32>, to: Vec2<f32>) {
+    if from == to {
+        return;
+    }
+
+    builder.set_color(color);
+    builder.polyline(1.0, &[from, to]).ok();
+}
+
+/// Outline of a collider shape of an owner at "origin".
+fn shape(builder: &mut GeometryBuilder, color: Color, origin: Vec2<f32>, shape: &ColliderShape) {
+    match shape {
+        ColliderShape::Circle { center, radius } => {
+            circle(builder, color, origin + *center, *radius)
+        }
+        ColliderShape::Capsule { start, end, radius } => {
+            circle(builder, color, origin + *start, *radius);
+            circle(builder, color, origin + *end, *radius);
+
+            let direction = *end - *start;
+            if direction.magnitude_squared() > 0.0 {
+                let side = Vec2::new(-direction.y, direction.x).normalized() * *radius;
+                line(builder, color, origin + *start + side, origin + *end + side);
+                line(builder, color, origin + *start - side, origin + *end - side);
+            }
+        }
+        ColliderShape::Rectangle { center, half_size } => {
+            builder.set_color(color);
+            builder
+                .rectangle(
+                    ShapeStyle::Stroke(1.0),
+                    Rectangle::new(
+                        origin.x + center.x - half_size.x,
+                        origin.y + center.y - half_size.y,
+                        half_size.x * 2.0,
+                        half_size.y * 2.0,
+                    ),
+                )
+                .ok();
+        }
+    };
+}
+
+fn draw_builder(ctx: &mut Context, builder: &GeometryBuilder, position: Vec2<f32>) {
+    if builder.vertices().len() == 0 {
+        return;
+    }
+
+    match builder.build_mesh(ctx) {
+        Ok(mesh) => graphics::draw(ctx, &mesh, position),
+        Err(e) => println!("Debug overlay mesh error: {}", e),
+    };
+}
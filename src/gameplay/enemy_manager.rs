@@ -1,17 +1,35 @@
 use std::collections::HashMap;
 
-use rand::prelude::*;
-
+use tetra::graphics::Color;
 use tetra::math::Vec2;
 use tetra::Context;
 
+use crate::animation_state::AnimationStateMachine;
+use crate::gameplay::bullet_pattern::PatternEmitter;
 use crate::gameplay::bullet_pool::{BulletOwner, BulletSpawnNode};
+use crate::gameplay::collider::Collider;
+use crate::gameplay::parameters::{ParameterError, ParameterSpec, ParameterType, Parameters};
 use crate::gameplay::particle_manager::{ParticleDrawLayer, ParticleSpawnNode};
 use crate::gameplay::player::Player;
+use crate::gameplay::random::GameRng;
+use crate::gameplay::world::World;
 
 use crate::image_assets::ImageAssets;
 use crate::sprite::Sprite;
 
+/// Frames that enemy sprite flashes white after hit. Hits during the flash do not restart it.
+const HIT_FLASH_FRAMES: i32 = 8;
+/// Time that dying enemy sprite dissolves. (ms)
+const DISSOLVE_TIME: u128 = 400;
+
+/// Keys in enemy.extra that use by every EnemyType.
+/// "palette": palette of the enemy variant from the asset manifest.
+pub const COMMON_PARAMETERS: [ParameterSpec; 1] =
+    [ParameterSpec::new("palette", ParameterType::Text, Some(""))];
+
+/// Enemy types that EnemyTypeBank::setup() can create. Level files can spawn only these.
+pub const ENEMY_TYPES: [i32; 4] = [0, 1, 2, 3];
+
 pub struct EnemyManager {
     active_enemies: Vec<Enemy>,
     inactive_enemies: Vec<Enemy>,
@@ -37,21 +55,32 @@ impl EnemyManager {
         enemy_type_number: i32,
         position: Vec2<f32>,
         raw_extra: &str,
+        enemy_type_bank: &mut EnemyTypeBank,
+        world: &mut World,
         image_assets: &ImageAssets,
+        rng: &mut GameRng,
     ) -> bool {
         if self.inactive_enemies.len() > 0 {
-            let mut enemy_type_bank = crate::ENEMY_TYPE_BANK.lock().unwrap();
             match enemy_type_bank.get_mut(enemy_type_number) {
                 Some(enemy_type) => match self.inactive_enemies.pop() {
                     Some(mut v) => {
                         let enemy = &mut v;
                         enemy.reset();
                         enemy.position = position;
-                        enemy.parsing_extra(raw_extra);
-                        enemy_type.init(enemy, image_assets);
-                        self.active_enemies.push(v);
-
-                        return true;
+                        match enemy.parsing_extra(raw_extra, enemy_type.parameters()) {
+                            Ok(_) => {
+                                enemy_type.init(enemy, world, image_assets, rng);
+                                enemy.sprite.effects.palette =
+                                    String::from(enemy.extra.get_text("palette").unwrap_or(""));
+                                self.active_enemies.push(v);
+
+                                return true;
+                            }
+                            Err(e) => {
+                                println!("Spawn enemy type {} error: {}", enemy_type_number, e);
+                                self.inactive_enemies.push(v);
+                            }
+                        }
                     }
                     None => println!("No inactive enemy for use"),
                 },
@@ -62,12 +91,19 @@ impl EnemyManager {
         false
     }
 
-    pub fn update_active_enemies(&mut self, player: Option<&Player>, image_assets: &ImageAssets) {
+    pub fn update_active_enemies(
+        &mut self,
+        players: &[Player],
+        enemy_type_bank: &EnemyTypeBank,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
         self.remove_active_enemy_list.clear();
 
         let mut index = 0;
         for enemy in self.active_enemies.iter_mut() {
-            enemy.update(player, image_assets);
+            enemy.update(players, enemy_type_bank, world, image_assets, rng);
             if enemy.active == false {
                 self.remove_active_enemy_list.push(index);
             }
@@ -83,12 +119,21 @@ impl EnemyManager {
         }
     }
 
-    pub fn draw(&mut self, ctx: &mut Context, image_assets: &ImageAssets) {
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        enemy_type_bank: &EnemyTypeBank,
+        image_assets: &ImageAssets,
+    ) {
         for enemy in self.active_enemies.iter_mut() {
-            enemy.draw(ctx, image_assets);
+            enemy.draw(ctx, enemy_type_bank, image_assets);
         }
     }
 
+    pub fn get_active_enemy(&self) -> &Vec<Enemy> {
+        &self.active_enemies
+    }
+
     pub fn get_mut_active_enemy(&mut self) -> &mut Vec<Enemy> {
         &mut self.active_enemies
     }
@@ -96,6 +141,15 @@ impl EnemyManager {
     pub fn has_active_enemy(&self) -> bool {
         self.active_enemies.len() > 0
     }
+
+    pub fn active_enemy_count(&self) -> usize {
+        self.active_enemies.len()
+    }
+
+    /// Number of enemies that the manager created. Active and inactive.
+    pub fn capacity(&self) -> usize {
+        self.active_enemies.len() + self.inactive_enemies.len()
+    }
 }
 
 pub struct Enemy {
@@ -104,11 +158,14 @@ pub struct Enemy {
 
     pub health: u32,
     pub max_health: u32,
+    /// Frames left of the hit flash.
     pub hit_frame: i32,
 
     pub position: Vec2<f32>,
     pub rotation: f32,
     pub radius: f32,
+    /// Hit area for bullets, melee attacks and players. EnemyType sets it in init() and can change it in update().
+    pub collider: Collider,
 
     pub frame: u128,
     pub tick: u128,
@@ -116,9 +173,16 @@ pub struct Enemy {
     pub weapon_tick: u128,
     pub life_time: u128,
     pub state: i32,
-    pub extra: HashMap<String, String>,
+    pub extra: Parameters,
     pub target_position: Vec<Vec2<f32>>,
     pub sprite: Sprite,
+    /// Animation events of the sprite in this update. EnemyType::update() can use them.
+    /// "sound:<key>" events play the sound before EnemyType::update().
+    pub animation_events: Vec<String>,
+    /// State of "sprite" for EnemyType that has AnimationGraph. It also runs hit flash.
+    pub animation: AnimationStateMachine,
+    /// Bullet pattern that the enemy is firing. See fire_bullet_pattern()
+    pub bullet_pattern: Option<PatternEmitter>,
 }
 
 impl Enemy {
@@ -132,15 +196,19 @@ impl Enemy {
             position: Vec2::zero(),
             rotation: 0.0,
             radius: 0.0,
+            collider: Collider::new(),
             frame:0,
             tick: 0,
             maximum_tick: 1,
             weapon_tick: 0,
             life_time: 0,
             state: 0,
-            extra: HashMap::new(),
+            extra: Parameters::new(),
             target_position: vec![],
             sprite: Sprite::new(),
+            animation_events: vec![],
+            animation: AnimationStateMachine::new(),
+            bullet_pattern: None,
         }
     }
 
@@ -149,9 +217,11 @@ impl Enemy {
         self.enemy_type = 0;
         self.health = 0;
         self.max_health = 1;
+        self.hit_frame = 0;
         self.position = Vec2::zero();
         self.rotation = 0.0;
         self.radius = 0.0;
+        self.collider.clear();
         self.frame = 0;
         self.tick = 0;
         self.maximum_tick = 1;
@@ -161,55 +231,95 @@ impl Enemy {
         self.extra.clear();
         self.target_position.clear();
         self.sprite.reset();
+        self.animation_events.clear();
+        self.animation.reset();
+        self.bullet_pattern = None;
     }
 
-    pub fn update(&mut self, player: Option<&Player>, image_assets: &ImageAssets) {
-        self.sprite.update();
-
-        {
-            let enemy_type_bank = crate::ENEMY_TYPE_BANK.lock().unwrap();
-            match enemy_type_bank.get(self.enemy_type) {
-                Some(t) => {
-                    t.update(self, player, image_assets);
-                }
-                None => (),
-            };
+    /// EnemyType get the nearest living player in "players" as its target.
+    pub fn update(
+        &mut self,
+        players: &[Player],
+        enemy_type_bank: &EnemyTypeBank,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
+        if self.hit_frame > 0 {
+            self.hit_frame -= 1;
         }
-        
-
-        if self.health == 0 {
-            self.die();
+        self.animation_events = self.animation.update_sprites(&mut self.sprite);
+        for event in self.animation_events.iter() {
+            if event.starts_with("sound:") {
+                world.play_sound(&event["sound:".len()..], 1.0);
+            }
         }
-    }
 
-    pub fn draw(&mut self, ctx: &mut Context, image_assets: &ImageAssets) {
-        let enemy_type_bank = crate::ENEMY_TYPE_BANK.lock().unwrap();
+        let player = crate::gameplay::player::nearest_living_player(players, self.position);
+
         match enemy_type_bank.get(self.enemy_type) {
             Some(t) => {
-                t.draw(ctx, image_assets, self);
+                t.update(self, player, world, image_assets, rng);
             }
             None => (),
         };
+
+        if self.health == 0 {
+            self.die(enemy_type_bank, world, rng);
+        }
     }
 
-    pub fn hit_check(&self, position: &Vec2<f32>, radius: f32) -> i32 {
-        let enemy_type_bank = crate::ENEMY_TYPE_BANK.lock().unwrap();
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        enemy_type_bank: &EnemyTypeBank,
+        image_assets: &ImageAssets,
+    ) {
         match enemy_type_bank.get(self.enemy_type) {
             Some(t) => {
-                let result = t.hit_check(self, position, radius);
-                return result;
-            }
-            None => {
-                println!("Not enemy type for hit_check")
+                t.draw(ctx, image_assets, self);
             }
+            None => (),
         };
+    }
 
-        0
+    /// Check a circle that moves from "from" to "to" in this frame with the collider.
+    ///
+    /// # Return:
+    ///
+    /// * One of collider::HIT_NONE, HIT_WEAK_POINT, HIT_ARMOUR and HIT_SHIELD.
+    ///
+    pub fn hit_check(&self, from: &Vec2<f32>, to: &Vec2<f32>, radius: f32) -> i32 {
+        self.collider.hit_check(self.position, *from, *to, radius)
     }
 
-    pub fn get_hit(&mut self, hit_position: &Vec2<f32>, damage: u32) {
+    /// Radius that covers "radius" and every collider part. Use it for broadphase.
+    pub fn hit_radius(&self) -> f32 {
+        self.radius.max(self.collider.bounding_radius())
+    }
+
+    /// Points for killing this enemy. 0: no enemy type.
+    pub fn score_value(&self, enemy_type_bank: &EnemyTypeBank) -> u32 {
+        match enemy_type_bank.get(self.enemy_type) {
+            Some(t) => t.score_value(),
+            None => 0,
+        }
+    }
+
+    pub fn get_hit(
+        &mut self,
+        hit_position: &Vec2<f32>,
+        damage: u32,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
         if self.hit_frame == 0 {
-            self.hit_frame = 8;
+            self.hit_frame = HIT_FLASH_FRAMES;
+            // Flash counts down with hit_frame. (hit_frame / HIT_FLASH_FRAMES)
+            self.animation.flash(
+                HIT_FLASH_FRAMES as u128 * crate::ONE_FRAME.as_millis(),
+                Color::rgb(1.0, 1.0, 1.0),
+            );
         }
 
         let distance = crate::gameplay::utils::distance_sqr(
@@ -231,34 +341,74 @@ impl Enemy {
             None => self.health = 0,
         }
 
-        self.spawn_splash(*hit_position, 0.9);
+        self.spawn_splash(*hit_position, 0.9, world, rng);
     }
 
-    fn die(&mut self) {
-        self.spawn_splash(self.position, 1.6);
+    fn die(&mut self, enemy_type_bank: &EnemyTypeBank, world: &mut World, rng: &mut GameRng) {
+        self.spawn_splash(self.position, 1.6, world, rng);
+        self.spawn_dissolve(world);
         self.active = false;
 
-        let enemy_type_bank = crate::ENEMY_TYPE_BANK.lock().unwrap();
         match enemy_type_bank.get(self.enemy_type) {
             Some(t) => {
-                t.die(self);
+                t.die(self, world, rng);
             }
             None => (),
         };
     }
 
-    pub fn spawn_splash(&self, offset_position: Vec2<f32>, scale_value: f32) {
-        Enemy::spawn_random_splash_particle(offset_position, scale_value);
+    /// Leave the current sprite frame that dissolves. See DissolveParticleType
+    pub fn spawn_dissolve(&self, world: &mut World) {
+        let animation_name = self.sprite.get_current_animation_name();
+        if animation_name.len() == 0 {
+            return;
+        }
+
+        let palette = self.extra.get_text("palette").unwrap_or("");
+        let raw_extra = format!(
+            "idle_animation={}|frame={}|flip_x={}|scale={}|time={}|{}",
+            animation_name,
+            self.sprite.get_current_frame_index(),
+            self.sprite.scale.x < 0.0,
+            self.sprite.scale.x.abs(),
+            DISSOLVE_TIME,
+            match palette.len() > 0 {
+                true => format!("palette={}|", palette),
+                false => String::new(),
+            },
+        );
+
+        world.spawn_particle(ParticleSpawnNode::new(
+            crate::gameplay::particle_types::dissolve::DISSOLVE_PARTICLE_TYPE,
+            self.position,
+            ParticleDrawLayer::Bottomest,
+            raw_extra.as_str(),
+        ));
     }
 
-    pub fn spawn_random_splash_particle(offset_position: Vec2<f32>, scale_value: f32) {
+    pub fn spawn_splash(
+        &self,
+        offset_position: Vec2<f32>,
+        scale_value: f32,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
+        Enemy::spawn_random_splash_particle(offset_position, scale_value, world, rng);
+    }
+
+    pub fn spawn_random_splash_particle(
+        offset_position: Vec2<f32>,
+        scale_value: f32,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
         let random_size = 24.0;
         let random_position = Vec2::new(
-            random_size / 2.0 - random::<f32>() * random_size,
-            random_size / 2.0 - random::<f32>() * random_size,
+            random_size / 2.0 - rng.random::<f32>() * random_size,
+            random_size / 2.0 - rng.random::<f32>() * random_size,
         );
 
-        let flip_x = if random::<u8>() % 2 == 0 {
+        let flip_x = if rng.random::<u8>() % 2 == 0 {
             "flip_x=1"
         } else {
             "flip_x=0"
@@ -266,12 +416,11 @@ impl Enemy {
 
         let splashes = ["splash-1", "splash-2", "splash-3", "splash-4"];
 
-        let name = splashes[random::<usize>() % splashes.len()];
+        let name = splashes[rng.random::<usize>() % splashes.len()];
 
         let position = random_position + offset_position;
 
-        let mut particle_spawn_nodes = crate::PARTICLE_SPAWN_NODES.lock().unwrap();
-        particle_spawn_nodes.push(ParticleSpawnNode::new(
+        world.spawn_particle(ParticleSpawnNode::new(
             1,
             position,
             ParticleDrawLayer::Explosion,
@@ -280,6 +429,7 @@ impl Enemy {
     }
 
     pub fn spawn_bullet(
+        world: &mut World,
         from: Vec2<f32>,
         target: Vec2<f32>,
         bullet_type: i32,
@@ -289,8 +439,7 @@ impl Enemy {
     ) {
         let rotation = (target.y - from.y).atan2(target.x - from.x).to_degrees() / 360.0;
 
-        let mut bullet_spawn_nodes = crate::BULLET_SPAWN_NODES.lock().unwrap();
-        bullet_spawn_nodes.push(BulletSpawnNode {
+        world.spawn_bullet(BulletSpawnNode {
             bullet_type: bullet_type,
             position: from,
             owner_type: BulletOwner::ENEMY,
@@ -301,38 +450,68 @@ impl Enemy {
         })
     }
 
-    pub fn parsing_extra(&mut self, raw_extra: &str) {
-        if raw_extra.len() == 0 {
-            return;
-        }
+    /// Run the bullet pattern from world.bullet_pattern_bank. It replaces the running pattern.
+    pub fn start_bullet_pattern(&mut self, pattern: &str) {
+        self.bullet_pattern = Some(PatternEmitter::new(pattern, 0));
+    }
 
-        let split: Vec<&str> = raw_extra.split('|').collect();
+    /// Fire the running bullet pattern. Call it on every update. The pattern is removed when it finishes.
+    ///
+    /// # Arguments:
+    ///
+    /// * origin - world position of the gun
+    /// * target - world position that aimed volleys turn to
+    ///
+    pub fn fire_bullet_pattern(&mut self, origin: Vec2<f32>, target: Vec2<f32>, world: &mut World) {
+        let emitter = match self.bullet_pattern.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
 
-        for text in split.iter() {
-            if text.len() == 0 {
-                continue;
-            }
+        for node in emitter.update(&world.bullet_pattern_bank, origin, target) {
+            world.spawn_bullet(node);
+        }
 
-            let parameter: Vec<&str> = text.split('=').collect();
-            if parameter.len() == 2 {
-                self.extra
-                    .insert(String::from(parameter[0]), String::from(parameter[1]));
-            } else {
-                panic!("Incorrect parameter format: {} ({})", text, raw_extra);
-            }
+        if emitter.is_finished() {
+            self.bullet_pattern = None;
         }
     }
+
+    /// Parse "raw_extra" into enemy.extra. Accept only keys in "specs".
+    pub fn parsing_extra(
+        &mut self,
+        raw_extra: &str,
+        specs: &[ParameterSpec],
+    ) -> Result<(), ParameterError> {
+        self.extra.load(raw_extra, &[&COMMON_PARAMETERS, specs])
+    }
 }
 
 pub trait EnemyType {
     fn enemy_type_id(&self) -> i32;
-    fn init(&mut self, enemy: &mut Enemy, image_assets: &ImageAssets);
-    fn update(&self, enemy: &mut Enemy, player: Option<&Player>, image_assets: &ImageAssets);
+    /// Keys that this type read from enemy.extra
+    fn parameters(&self) -> &'static [ParameterSpec];
+    /// Points for killing this enemy before combo multiplier.
+    fn score_value(&self) -> u32;
+    fn init(
+        &mut self,
+        enemy: &mut Enemy,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    );
+    /// player: the nearest living player. None: every player is dead.
+    /// Spawning, sound and camera requests go into "world".
+    fn update(
+        &self,
+        enemy: &mut Enemy,
+        player: Option<&Player>,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    );
     fn draw(&self, ctx: &mut Context, image_assets: &ImageAssets, enemy: &mut Enemy);
-    fn die(&self, enemy: &mut Enemy);
-
-    /// 0: not hit, 1: hit weakpoint, -1: hit shield. (No damage)
-    fn hit_check(&self, enemy: &Enemy, position: &Vec2<f32>, radius: f32) -> i32;
+    fn die(&self, enemy: &mut Enemy, world: &mut World, rng: &mut GameRng);
 }
 
 pub struct EnemyTypeBank {
@@ -346,7 +525,7 @@ impl EnemyTypeBank {
         }
     }
 
-    pub fn setup(&mut self, image_assets: &ImageAssets, required_list: &Vec<i32>) {
+    pub fn setup(&mut self, image_assets: &ImageAssets, required_list: &[i32]) {
         for enemy_type_number in required_list {
             match enemy_type_number {
                 0 => {
@@ -380,6 +559,23 @@ impl EnemyTypeBank {
         }
     }
 
+    /// Keys that enemy type read from "extra". None: the type is not in the bank.
+    pub fn parameters(&self, enemy_type_number: i32) -> Option<&'static [ParameterSpec]> {
+        self.get(enemy_type_number)
+            .map(|enemy_type| enemy_type.parameters())
+    }
+
+    /// Display name of enemy type. It can use without setup().
+    pub fn name(enemy_type_number: i32) -> &'static str {
+        match enemy_type_number {
+            0 => "SPAWNER",
+            1 => "FLYING POP CORN",
+            2 => "CRAWLING POP CORN",
+            3 => "BOSS",
+            _ => "UNKNOWN",
+        }
+    }
+
     pub fn add(&mut self, enemy_type: Box<dyn EnemyType + Send + Sync>) {
         self.types.insert(enemy_type.enemy_type_id(), enemy_type);
     }
@@ -1,125 +1,189 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
-use rand::prelude::*;
-
 use tetra::graphics::{self, Color, GeometryBuilder, Mesh, Rectangle, ShapeStyle};
 use tetra::math::Vec2;
 use tetra::Context;
 
+use crate::animation_state::AnimationGraph;
 use crate::image_assets::ImageAssets;
 use crate::sprite::AnimationMultiTextures;
 
+use crate::gameplay::collider::{Collider, ColliderShape, HitZone};
 use crate::gameplay::enemy_manager::{Enemy, EnemyType};
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
 use crate::gameplay::player::Player;
+use crate::gameplay::random::GameRng;
 use crate::gameplay::level::EnemySpawnNode;
+use crate::gameplay::world::World;
+
+/// "bullet_patterns": names in BulletPatternBank separated by ",". The boss picks one at every attack.
+pub const PARAMETERS: [ParameterSpec; 1] = [ParameterSpec::new(
+    "bullet_patterns",
+    ParameterType::Text,
+    Some("boss-aimed"),
+)];
 
 pub struct BossEnemyType {
+    animation_graph: AnimationGraph,
 }
 
 impl BossEnemyType {
     pub fn new(image_assets: &ImageAssets) -> BossEnemyType {
-        
-
         BossEnemyType {
-            
+            animation_graph: AnimationGraph::new("idle").state(
+                "idle",
+                "enemy-boss-idle",
+                Some(true),
+            ),
         }
     }
 
-    fn random_target_position(&self, enemy: &mut Enemy,) {
+    fn random_target_position(&self, enemy: &mut Enemy, world: &World, rng: &mut GameRng) {
         enemy.target_position.clear();
         for _ in 0..3 {
             enemy
                 .target_position
                 .push(crate::gameplay::utils::random_position_inside_camera_area(
-                    0.1, 0.1, 0.8, 0.4,
+                    world.camera_position,
+                    0.1,
+                    0.1,
+                    0.8,
+                    0.4,
+                    rng,
                 ));
         }
     }
 
-    fn random_weapon_tick(&self) -> u128 {
-        2500 + (random::<f32>() * 1000.0) as u128
+    /// Hit area on the upper body of the sprite. The eye is a weak point, the back is armour and
+    /// a shield covers the belly under the back. The mouth is armour while moving (state 0),
+    /// shielded while spawning enemies (state 1) and a weak point while firing bullet patterns (state 2).
+    /// Nothing reaches lower than 26 under the center, so players on the ground only touch the boss
+    /// when it flies low or they jump.
+    fn collider(&self, state: i32) -> Collider {
+        let mouth = match state {
+            1 => HitZone::Invulnerable,
+            2 => HitZone::WeakPoint,
+            _ => HitZone::Armour,
+        };
+
+        Collider::new()
+            .part(
+                HitZone::WeakPoint,
+                ColliderShape::Circle {
+                    center: Vec2::new(-28.0, -50.0),
+                    radius: 18.0,
+                },
+            )
+            .part(
+                mouth,
+                ColliderShape::Circle {
+                    center: Vec2::new(-24.0, 4.0),
+                    radius: 16.0,
+                },
+            )
+            .part(
+                HitZone::Armour,
+                ColliderShape::Capsule {
+                    start: Vec2::new(20.0, -44.0),
+                    end: Vec2::new(20.0, 0.0),
+                    radius: 26.0,
+                },
+            )
+            .part(
+                HitZone::Invulnerable,
+                ColliderShape::Rectangle {
+                    center: Vec2::new(24.0, 20.0),
+                    half_size: Vec2::new(22.0, 6.0),
+                },
+            )
+    }
+
+    fn random_weapon_tick(&self, rng: &mut GameRng) -> u128 {
+        2500 + (rng.random::<f32>() * 1000.0) as u128
     }
 
-    fn do_action(&self, enemy: &mut Enemy, player: Option<&Player>)
-    {
+    fn do_action(
+        &self,
+        enemy: &mut Enemy,
+        player: Option<&Player>,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
         if enemy.state == 1 && enemy.weapon_tick == 0
         {
-            let mut need_to_spawn_enemy_list = crate::ENEMY_SPAWN_NODES.lock().unwrap();
-
             let spawn_position = enemy.position
             + Vec2::new(
-                8.0 - random::<f32>() * 16.0,
-                (8.0 - random::<f32>() * 16.0) + 48.0,
+                8.0 - rng.random::<f32>() * 16.0,
+                (8.0 - rng.random::<f32>() * 16.0) + 48.0,
             );
 
-            if random::<u8>() % 3 == 0
+            if rng.random::<u8>() % 3 == 0
             {
-                need_to_spawn_enemy_list.push(EnemySpawnNode::new(
+                world.spawn_enemy(EnemySpawnNode::new(
                     0,
                     2,
                     spawn_position,
-                    format!("rotation={}|", random::<f32>()).as_str(),
+                    format!("rotation={}|", rng.random::<f32>()).as_str(),
                 ));
             }
             else
             {
-                need_to_spawn_enemy_list.push(EnemySpawnNode::new(
+                world.spawn_enemy(EnemySpawnNode::new(
                     0,
                     1,
                     spawn_position,
-                    format!("rotation={}|", random::<f32>()).as_str(),
+                    "",
                 ));
-                need_to_spawn_enemy_list.push(EnemySpawnNode::new(
+                world.spawn_enemy(EnemySpawnNode::new(
                     0,
                     1,
                     spawn_position,
-                    format!("rotation={}|", random::<f32>()).as_str(),
+                    "",
                 ));
-                need_to_spawn_enemy_list.push(EnemySpawnNode::new(
+                world.spawn_enemy(EnemySpawnNode::new(
                     0,
                     1,
                     spawn_position,
-                    format!("rotation={}|", random::<f32>()).as_str(),
+                    "",
                 ));
             }
 
-            {
-                let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-                play_sound_nodes.insert(String::from("boss_spawn"), (String::from("./resources/sfx/boss_spawn.mp3"), 0.6 ) );
-            }
-
-            {
-                let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-                play_sound_nodes.insert(String::from("boss_enter"), (String::from("./resources/sfx/boss_enter.mp3"), 0.8 ) );
-            }
+            world.play_sound("boss_spawn", 0.6);
+            world.play_sound("boss_enter", 0.8);
 
-            enemy.spawn_splash(spawn_position, 2.5);
-            enemy.spawn_splash(spawn_position, 2.5);
+            enemy.spawn_splash(spawn_position, 2.5, world, rng);
+            enemy.spawn_splash(spawn_position, 2.5, world, rng);
         }
         else if enemy.state == 2  
         {
-            if enemy.frame <= 400 && enemy.frame % 15 == 0 
+            if enemy.frame == 1
             {
-                Enemy::spawn_bullet(
-                    enemy.position + Vec2::new(28.0, -4.0),
-                    player.unwrap().get_hit_point_position() + Vec2::new(8.0 - random::<f32>() * 16.0, (8.0 - random::<f32>() * 16.0)),
-                    1,
-                    1.0,
-                    4.0,
-                    "idle_animation=enemy-bullet-1-idle|firing_animation=enemy-bullet-1-firing|hit_animation=enemy-bullet-1-hit|kill_animation=enemy-bullet-1-kill|scale=1.2|",
-                );
-                
-                Enemy::spawn_bullet(
-                    enemy.position + Vec2::new(32.0, -16.0),
-                    player.unwrap().get_hit_point_position() + Vec2::new(8.0 - random::<f32>() * 16.0, (8.0 - random::<f32>() * 16.0)),
-                    1,
-                    1.0,
-                    4.0,
-                    "idle_animation=enemy-bullet-1-idle|firing_animation=enemy-bullet-1-firing|hit_animation=enemy-bullet-1-hit|kill_animation=enemy-bullet-1-kill|scale=1.2|",
-                );
+                let names: Vec<&str> = enemy
+                    .extra
+                    .get_text("bullet_patterns")
+                    .unwrap_or("")
+                    .split(',')
+                    .filter(|name| name.len() > 0)
+                    .collect();
+                if names.len() > 0 {
+                    let name = String::from(names[rng.random::<usize>() % names.len()]);
+                    enemy.start_bullet_pattern(name.as_str());
+                }
             }
-            else if enemy.frame > 450
+
+            match player {
+                Some(player) => {
+                    enemy.fire_bullet_pattern(
+                        enemy.position + Vec2::new(4.0, -10.0),
+                        player.get_hit_point_position(),
+                        world,
+                    );
+                }
+                None => (),
+            };
+
+            if enemy.frame > 450
             {
                 enemy.frame = 0;
             }
@@ -134,33 +198,48 @@ impl EnemyType for BossEnemyType {
         3
     }
 
-    fn init(&mut self, enemy: &mut Enemy, image_assets: &ImageAssets) {
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn score_value(&self) -> u32 {
+        5000
+    }
+
+    fn init(
+        &mut self,
+        enemy: &mut Enemy,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
         enemy.enemy_type = self.enemy_type_id();
-        enemy.radius = 24.0;
+        enemy.collider = self.collider(0);
+        enemy.radius = enemy.collider.bounding_radius();
         enemy.active = true;
         enemy.health = 300;
         enemy.life_time = 100;
         enemy.maximum_tick = 2000;
-        enemy.weapon_tick = self.random_weapon_tick();
+        enemy.weapon_tick = self.random_weapon_tick(rng);
     
-        {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.insert(String::from("boss_enter"), (String::from("./resources/sfx/boss_enter.mp3"), 0.7 ) );
-        }
+        world.play_sound("boss_enter", 0.7);
 
-        match image_assets.get_animation_object("enemy-boss-idle") {
-            Some(animation) => {
-                enemy.sprite.scale = Vec2::new(1.0, 1.0);
-                enemy.sprite.set_loop(true);
-                enemy.sprite.play(&animation);
-            }
-            None => (),
-        };
+        enemy.sprite.scale = Vec2::new(1.0, 1.0);
+        enemy
+            .animation
+            .start(&self.animation_graph, &mut enemy.sprite, image_assets);
 
-        self.random_target_position(enemy);
+        self.random_target_position(enemy, world, rng);
     }
 
-    fn update(&self, enemy: &mut Enemy, player: Option<&Player>, image_assets: &ImageAssets) {
+    fn update(
+        &self,
+        enemy: &mut Enemy,
+        player: Option<&Player>,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
         match enemy.tick.checked_add(crate::ONE_FRAME.as_millis()) {
             Some(v) => enemy.tick = v,
             None => enemy.tick = 0,
@@ -172,14 +251,16 @@ impl EnemyType for BossEnemyType {
         };
 
         enemy.frame += 1;
-        self.do_action(enemy, player);
+        self.do_action(enemy, player, world, rng);
 
         if enemy.weapon_tick == 0 {
-            enemy.weapon_tick = self.random_weapon_tick();
+            enemy.weapon_tick = self.random_weapon_tick(rng);
+            enemy.bullet_pattern = None;
 
             enemy.frame = 0;
             enemy.state += 1;
             enemy.state = enemy.state % 3;
+            enemy.collider = self.collider(enemy.state);
 
             
         }
@@ -188,7 +269,7 @@ impl EnemyType for BossEnemyType {
 
         if enemy.tick > enemy.maximum_tick {
             enemy.tick = 0;
-            self.random_target_position(enemy);
+            self.random_target_position(enemy, world, rng);
         }
 
         let mut actual_position = enemy.position;
@@ -204,32 +285,15 @@ impl EnemyType for BossEnemyType {
 
     /// This function will called internally in die().
     /// It will decide that what it should do with the bullets on screen when this enemy die
-    fn die(&self, enemy: &mut Enemy) {
-        enemy.spawn_splash(enemy.position, 3.5);
-        enemy.spawn_splash(enemy.position, 3.5);
-        enemy.spawn_splash(enemy.position, 3.5);
-        enemy.spawn_splash(enemy.position, 3.5);
-
-
-        {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.insert(String::from("boss_explode"), (String::from("./resources/sfx/boss_explode.mp3"), 0.8 ) );
-        }
-    }
-
-    /// Return: 0: not hit, 1: hit weakpoint, -1: hit shield. (No damage)
-    fn hit_check(&self, enemy: &Enemy, position: &Vec2<f32>, radius: f32) -> i32 {
-        let distance = crate::gameplay::utils::distance_sqr(
-            enemy.position.x as i128,
-            enemy.position.y as i128,
-            position.x as i128,
-            position.y as i128,
-        );
-        let total_radius = (enemy.radius + radius) as i128;
-        if distance <= total_radius * total_radius {
-            return 1;
-        }
-
-        0
+    fn die(&self, enemy: &mut Enemy, world: &mut World, rng: &mut GameRng) {
+        enemy.spawn_splash(enemy.position, 3.5, world, rng);
+        enemy.spawn_splash(enemy.position, 3.5, world, rng);
+        enemy.spawn_splash(enemy.position, 3.5, world, rng);
+        enemy.spawn_splash(enemy.position, 3.5, world, rng);
+
+        world.play_sound("boss_explode", 0.8);
+        world.add_trauma(0.9);
+        world.hit_stop(180);
+        world.zoom_punch(0.15, 600);
     }
 }
@@ -1,23 +1,40 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
-use rand::prelude::*;
-
 use tetra::graphics::{self, Color, GeometryBuilder, Mesh, Rectangle, ShapeStyle};
 use tetra::math::Vec2;
 use tetra::Context;
 
+use crate::animation_state::{AnimationGraph, TransitionCondition};
 use crate::image_assets::ImageAssets;
 use crate::sprite::AnimationMultiTextures;
 
+use crate::gameplay::collider::{Collider, HitZone};
 use crate::gameplay::enemy_manager::{Enemy, EnemyType};
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
 use crate::gameplay::player::Player;
-
-pub struct CrawlingPopCornEnemyType {}
+use crate::gameplay::random::GameRng;
+use crate::gameplay::world::World;
+
+pub const PARAMETERS: [ParameterSpec; 1] = [ParameterSpec::new(
+    "rotation",
+    ParameterType::Float,
+    Some("0.0"),
+)];
+
+pub struct CrawlingPopCornEnemyType {
+    /// Conditions: "grounded"
+    animation_graph: AnimationGraph,
+}
 
 impl CrawlingPopCornEnemyType {
     pub fn new(image_assets: &ImageAssets) -> CrawlingPopCornEnemyType {
-        CrawlingPopCornEnemyType {}
+        CrawlingPopCornEnemyType {
+            animation_graph: AnimationGraph::new("air")
+                .state("air", "enemy-crawler-air", None)
+                .state("idle", "enemy-crawler-idle", None)
+                .transition(&["air"], "idle", vec![TransitionCondition::When("grounded")]),
+        }
     }
 
     fn apply_gravity(&self, enemy: &mut Enemy, offset: f32) {
@@ -29,16 +46,22 @@ impl CrawlingPopCornEnemyType {
         enemy.position.y >= -10.0
     }
 
-    fn random_weapon_tick(&self, enemy: &mut Enemy) {
-        enemy.weapon_tick = 800 + random::<u128>() % 800;
+    fn random_weapon_tick(&self, enemy: &mut Enemy, rng: &mut GameRng) {
+        enemy.weapon_tick = 800 + rng.random::<u128>() % 800;
     }
 
-    fn update_rotation(&self, enemy: &mut Enemy, player_position: Vec2<f32>) {
-        if enemy.position.x < player_position.x {
-            enemy.rotation = 0.05;
-        } else {
-            enemy.rotation = 0.45;
-        }
+    /// Turn to "player". Keep the current rotation when no player.
+    fn update_rotation(&self, enemy: &mut Enemy, player: Option<&Player>) {
+        match player {
+            Some(player) => {
+                if enemy.position.x < player.get_hit_point_position().x {
+                    enemy.rotation = 0.05;
+                } else {
+                    enemy.rotation = 0.45;
+                }
+            }
+            None => (),
+        };
     }
 }
 
@@ -47,32 +70,50 @@ impl EnemyType for CrawlingPopCornEnemyType {
         2
     }
 
-    fn init(&mut self, enemy: &mut Enemy, image_assets: &ImageAssets) {
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn score_value(&self) -> u32 {
+        150
+    }
+
+    fn init(
+        &mut self,
+        enemy: &mut Enemy,
+        _world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
         enemy.enemy_type = self.enemy_type_id();
         enemy.radius = 20.0;
+        enemy.collider = Collider::circle(enemy.radius, HitZone::WeakPoint);
         enemy.active = true;
         enemy.health = 40;
         enemy.life_time = 1000;
         enemy.sprite.scale = Vec2::new(2.0, 2.0);
 
-        enemy.rotation = match enemy.extra.get("rotation") {
-            Some(v) => v.parse::<f32>().unwrap_or(0.0),
-            None => 0.0,
-        };
+        enemy.rotation = enemy.extra.get_float("rotation").unwrap_or(0.0);
 
-        self.random_weapon_tick(enemy);
+        self.random_weapon_tick(enemy, rng);
 
-        match image_assets.get_animation_object("enemy-crawler-air") {
-            Some(animation) => {
-                enemy.sprite.play(&animation);
-            }
-            None => {
-                enemy.active = false;
-            }
+        if enemy
+            .animation
+            .start(&self.animation_graph, &mut enemy.sprite, image_assets)
+            == false
+        {
+            enemy.active = false;
         }
     }
 
-    fn update(&self, enemy: &mut Enemy, player: Option<&Player>, image_assets: &ImageAssets) {
+    fn update(
+        &self,
+        enemy: &mut Enemy,
+        player: Option<&Player>,
+        _world: &mut World,
+        image_assets: &ImageAssets,
+        _rng: &mut GameRng,
+    ) {
         if enemy.state == 0 {
             let speed = 4.0;
             if enemy.weapon_tick <= 500 {
@@ -86,14 +127,15 @@ impl EnemyType for CrawlingPopCornEnemyType {
             if self.on_the_ground(enemy) {
                 enemy.state = 1;
                 enemy.weapon_tick = 1500;
-                self.update_rotation(enemy, player.unwrap().get_hit_point_position());
-                match image_assets.get_animation_object("enemy-crawler-idle") {
-                    Some(animation) => {
-                        enemy.sprite.play(&animation);
-                    }
-                    None => {
-                        enemy.active = false;
-                    }
+                self.update_rotation(enemy, player);
+
+                enemy.animation.set_condition("grounded", true);
+                if enemy
+                    .animation
+                    .update_state(&self.animation_graph, &mut enemy.sprite, image_assets)
+                    == false
+                {
+                    enemy.active = false;
                 }
             }
 
@@ -105,7 +147,7 @@ impl EnemyType for CrawlingPopCornEnemyType {
 
             if enemy.weapon_tick == 0 {
                 enemy.weapon_tick = 1500;
-                self.update_rotation(enemy, player.unwrap().get_hit_point_position());
+                self.update_rotation(enemy, player);
             }
         }
 
@@ -131,26 +173,7 @@ impl EnemyType for CrawlingPopCornEnemyType {
         );
     }
 
-    fn die(&self, enemy: &mut Enemy) {
-        {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.insert(String::from("crawl_explode"), (String::from("./resources/sfx/crawl_explode.mp3"), 0.8 ) );
-        }
-    }
-
-    /// 0: not hit, 1: hit weakpoint, -1: hit shield. (No damage)
-    fn hit_check(&self, enemy: &Enemy, position: &Vec2<f32>, radius: f32) -> i32 {
-        let distance = crate::gameplay::utils::distance_sqr(
-            enemy.position.x as i128,
-            enemy.position.y as i128,
-            position.x as i128,
-            position.y as i128,
-        );
-        let total_radius = (enemy.radius + radius) as i128;
-        if distance <= total_radius * total_radius {
-            return 1;
-        }
-
-        0
+    fn die(&self, enemy: &mut Enemy, world: &mut World, _rng: &mut GameRng) {
+        world.play_sound("crawl_explode", 0.8);
     }
 }
@@ -1,20 +1,26 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
-use rand::prelude::*;
-
 use tetra::graphics::{self, Color, GeometryBuilder, Mesh, Rectangle, ShapeStyle};
 use tetra::math::Vec2;
 use tetra::Context;
 
+use crate::animation_state::{AnimationGraph, TransitionCondition};
 use crate::image_assets::ImageAssets;
 use crate::sprite::AnimationMultiTextures;
 
+use crate::gameplay::collider::{Collider, HitZone};
 use crate::gameplay::enemy_manager::{Enemy, EnemyType};
+use crate::gameplay::parameters::ParameterSpec;
 use crate::gameplay::player::Player;
+use crate::gameplay::random::GameRng;
+use crate::gameplay::world::World;
+
+pub const PARAMETERS: [ParameterSpec; 0] = [];
 
 pub struct FlyingPopCornEnemyType {
     animations: HashMap<String, AnimationMultiTextures>,
+    animation_graph: AnimationGraph,
 }
 
 impl FlyingPopCornEnemyType {
@@ -36,10 +42,14 @@ impl FlyingPopCornEnemyType {
 
         FlyingPopCornEnemyType {
             animations: animations,
+            animation_graph: AnimationGraph::new("spawn")
+                .state("spawn", "enemy-flying-spawn", Some(false))
+                .state("idle", "enemy-flying-idle", Some(true))
+                .transition(&["spawn"], "idle", vec![TransitionCondition::AnimationEnd]),
         }
     }
 
-    fn random_target_position(&self, enemy: &mut Enemy) {
+    fn random_target_position(&self, enemy: &mut Enemy, world: &World, rng: &mut GameRng) {
         enemy.target_position.clear();
         for _ in 0..3 {
             // enemy.target_position.push(if enemy.state % 2 == 0 {
@@ -51,13 +61,18 @@ impl FlyingPopCornEnemyType {
             enemy
                 .target_position
                 .push(crate::gameplay::utils::random_position_inside_camera_area(
-                    0.1, 0.1, 0.8, 0.4,
+                    world.camera_position,
+                    0.1,
+                    0.1,
+                    0.8,
+                    0.4,
+                    rng,
                 ));
         }
     }
 
-    fn random_weapon_tick(&self) -> u128 {
-        2500 + (random::<f32>() * 1000.0) as u128
+    fn random_weapon_tick(&self, rng: &mut GameRng) -> u128 {
+        2500 + (rng.random::<f32>() * 1000.0) as u128
     }
 }
 
@@ -66,17 +81,35 @@ impl EnemyType for FlyingPopCornEnemyType {
         1
     }
 
-    fn init(&mut self, enemy: &mut Enemy, image_assets: &ImageAssets) {
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn score_value(&self) -> u32 {
+        100
+    }
+
+    fn init(
+        &mut self,
+        enemy: &mut Enemy,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
         enemy.enemy_type = self.enemy_type_id();
         enemy.radius = 8.0;
+        enemy.collider = Collider::circle(enemy.radius, HitZone::WeakPoint);
         enemy.active = true;
         enemy.health = 5;
         enemy.life_time = 100;
         enemy.maximum_tick = 3000;
-        enemy.weapon_tick = self.random_weapon_tick();
+        enemy.weapon_tick = self.random_weapon_tick(rng);
         if enemy.position.x
-            < crate::gameplay::utils::convert_screen_position_to_world_position(Vec2::new(0.0, 0.0))
-                .x
+            < crate::gameplay::utils::convert_screen_position_to_world_position(
+                world.camera_position,
+                Vec2::new(0.0, 0.0),
+            )
+            .x
         {
             if enemy.state % 2 == 1 {
                 enemy.state += 1;
@@ -87,19 +120,22 @@ impl EnemyType for FlyingPopCornEnemyType {
             }
         }
 
-        match image_assets.get_animation_object("enemy-flying-spawn") {
-            Some(animation) => {
-                enemy.sprite.scale = Vec2::new(1.4, 1.4);
-                enemy.sprite.set_loop(false);
-                enemy.sprite.play(&animation);
-            }
-            None => (),
-        };
+        enemy.sprite.scale = Vec2::new(1.4, 1.4);
+        enemy
+            .animation
+            .start(&self.animation_graph, &mut enemy.sprite, image_assets);
 
-        self.random_target_position(enemy);
+        self.random_target_position(enemy, world, rng);
     }
 
-    fn update(&self, enemy: &mut Enemy, player: Option<&Player>, image_assets: &ImageAssets) {
+    fn update(
+        &self,
+        enemy: &mut Enemy,
+        player: Option<&Player>,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
         match enemy.tick.checked_add(crate::ONE_FRAME.as_millis()) {
             Some(v) => enemy.tick = v,
             None => enemy.tick = 0,
@@ -111,33 +147,31 @@ impl EnemyType for FlyingPopCornEnemyType {
         };
 
         if enemy.weapon_tick == 0 {
-            enemy.weapon_tick = self.random_weapon_tick();
-            Enemy::spawn_bullet(
-                enemy.position,
-                player.unwrap().get_hit_point_position(),
-                1,
-                1.0,
-                4.0,
-                "idle_animation=enemy-bullet-1-idle|firing_animation=enemy-bullet-1-firing|hit_animation=enemy-bullet-1-hit|kill_animation=enemy-bullet-1-kill|scale=1.2|",
-            );
-        }
-
-        if enemy.sprite.get_current_animation_name() == "enemy-flying-spawn"
-            && enemy.sprite.is_end_of_animation()
-        {
-            match image_assets.get_animation_object("enemy-flying-idle") {
-                Some(animation) => {
-                    enemy.sprite.set_loop(true);
-                    enemy.sprite.play(&animation);
+            enemy.weapon_tick = self.random_weapon_tick(rng);
+            match player {
+                Some(player) => {
+                    Enemy::spawn_bullet(
+                        world,
+                        enemy.position,
+                        player.get_hit_point_position(),
+                        1,
+                        1.0,
+                        4.0,
+                        "idle_animation=enemy-bullet-1-idle|firing_animation=enemy-bullet-1-firing|hit_animation=enemy-bullet-1-hit|kill_animation=enemy-bullet-1-kill|scale=1.2|",
+                    );
                 }
                 None => (),
             };
         }
 
+        enemy
+            .animation
+            .update_state(&self.animation_graph, &mut enemy.sprite, image_assets);
+
         if enemy.tick > enemy.maximum_tick {
             enemy.state += 1;
             enemy.tick = 0;
-            self.random_target_position(enemy);
+            self.random_target_position(enemy, world, rng);
         }
 
         let mut actual_position = enemy.position;
@@ -153,26 +187,7 @@ impl EnemyType for FlyingPopCornEnemyType {
 
     /// This function will called internally in die().
     /// It will decide that what it should do with the bullets on screen when this enemy die
-    fn die(&self, enemy: &mut Enemy) {
-        {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.insert(String::from("flying_explode"), (String::from("./resources/sfx/flying_explode.mp3"), 0.6 ) );
-        }
-    }
-
-    /// Return: 0: not hit, 1: hit weakpoint, -1: hit shield. (No damage)
-    fn hit_check(&self, enemy: &Enemy, position: &Vec2<f32>, radius: f32) -> i32 {
-        let distance = crate::gameplay::utils::distance_sqr(
-            enemy.position.x as i128,
-            enemy.position.y as i128,
-            position.x as i128,
-            position.y as i128,
-        );
-        let total_radius = (enemy.radius + radius) as i128;
-        if distance <= total_radius * total_radius {
-            return 1;
-        }
-
-        0
+    fn die(&self, enemy: &mut Enemy, world: &mut World, _rng: &mut GameRng) {
+        world.play_sound("flying_explode", 0.6);
     }
 }
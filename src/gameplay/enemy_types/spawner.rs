@@ -1,8 +1,6 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
-use rand::prelude::*;
-
 use tetra::graphics::{self, Color, GeometryBuilder, Mesh, Rectangle, ShapeStyle};
 use tetra::math::Vec2;
 use tetra::Context;
@@ -12,12 +10,27 @@ use crate::sprite::AnimationMultiTextures;
 
 use crate::gameplay::enemy_manager::{Enemy, EnemyManager, EnemyType};
 use crate::gameplay::level::EnemySpawnNode;
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
 use crate::gameplay::player::Player;
+use crate::gameplay::random::GameRng;
+use crate::gameplay::world::World;
+
+pub const PARAMETERS: [ParameterSpec; 8] = [
+    ParameterSpec::new("spawn_time", ParameterType::UInt, Some("123456")),
+    ParameterSpec::new("spawn_interval", ParameterType::UInt, Some("0")),
+    ParameterSpec::new("spawn_queue", ParameterType::Text, None),
+    ParameterSpec::new("idle_animation", ParameterType::Text, None),
+    ParameterSpec::new("spawning_animation", ParameterType::Text, None),
+    ParameterSpec::new("scale", ParameterType::Float, Some("2.5")),
+    ParameterSpec::new("flip_x", ParameterType::Bool, Some("0")),
+    ParameterSpec::new("spawn_event", ParameterType::Text, None),
+];
 
 pub struct SpawnerEnemyType {
     animations: HashMap<String, AnimationMultiTextures>,
 }
 /// spawn_time, spawn_interval, spawn_queue
+/// spawn_event: spawn on the animation event of spawning_animation instead of spawn_interval.
 impl SpawnerEnemyType {
     pub fn new(image_assets: &ImageAssets) -> SpawnerEnemyType {
         let mut animations = HashMap::new();
@@ -27,53 +40,24 @@ impl SpawnerEnemyType {
         }
     }
 
-    fn spawn_tick(&self, enemy: &mut Enemy) {
-        if enemy.tick == 0 {
-            enemy.tick = match enemy.extra.get("spawn_interval") {
-                Some(v) => v.parse::<u128>().unwrap_or(0),
-                None => 0,
-            };
-
-            let spawn_enemy_type_id = match enemy.extra.get_mut("spawn_queue") {
-                Some(v) => {
-                    if v.len() != 0 {
-                        let first_letter = v.chars().nth(0).unwrap();
-                        SpawnerEnemyType::crop_letters(v, 1);
-                        String::from(first_letter).parse::<i32>().unwrap_or(0)
-                    } else {
-                        0
-                    }
-                }
-                None => {
-                    println!("No spawn_queue key");
-                    0
-                }
-            };
-
-            if spawn_enemy_type_id != 0 {
-                let mut need_to_spawn_enemy_list = crate::ENEMY_SPAWN_NODES.lock().unwrap();
-                need_to_spawn_enemy_list.push(EnemySpawnNode::new(
-                    0,
-                    spawn_enemy_type_id,
-                    enemy.position
-                        + Vec2::new(
-                            8.0 - random::<f32>() * 16.0,
-                            (8.0 - random::<f32>() * 16.0) - 24.0,
-                        ),
-                    "",
-                ));
-
-                {
-                    let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-                    play_sound_nodes.insert(String::from("spawner"), (String::from("./resources/sfx/spawner.mp3"), 0.4 ) );
-                }
+    fn spawn_tick(&self, enemy: &mut Enemy, world: &mut World, rng: &mut GameRng) {
+        let spawn_event = match enemy.extra.get_text("spawn_event") {
+            Some(event) => Some(enemy.animation_events.iter().any(|v| v == event)),
+            None => None,
+        };
 
-                enemy.spawn_splash(enemy.position, 0.5);
-                enemy.spawn_splash(enemy.position, 0.5);
-                enemy.spawn_splash(enemy.position, 0.5);
-            } else {
-                enemy.active = false;
+        match spawn_event {
+            Some(true) => {
+                self.spawn_next(enemy, world, rng);
+                return;
             }
+            Some(false) => return,
+            None => (),
+        };
+
+        if enemy.tick == 0 {
+            enemy.tick = enemy.extra.get_uint("spawn_interval").unwrap_or(0);
+            self.spawn_next(enemy, world, rng);
         } else {
             match enemy.tick.checked_sub(crate::ONE_FRAME.as_millis()) {
                 Some(v) => enemy.tick = v,
@@ -82,6 +66,46 @@ impl SpawnerEnemyType {
         }
     }
 
+    /// Spawn the next enemy in spawn_queue. Spawner is removed when the queue is empty.
+    fn spawn_next(&self, enemy: &mut Enemy, world: &mut World, rng: &mut GameRng) {
+        let spawn_enemy_type_id = match enemy.extra.get_mut_text("spawn_queue") {
+            Some(v) => {
+                if v.len() != 0 {
+                    let first_letter = v.chars().nth(0).unwrap();
+                    SpawnerEnemyType::crop_letters(v, 1);
+                    String::from(first_letter).parse::<i32>().unwrap_or(0)
+                } else {
+                    0
+                }
+            }
+            None => {
+                println!("No spawn_queue key");
+                0
+            }
+        };
+
+        if spawn_enemy_type_id != 0 {
+            world.spawn_enemy(EnemySpawnNode::new(
+                0,
+                spawn_enemy_type_id,
+                enemy.position
+                    + Vec2::new(
+                        8.0 - rng.random::<f32>() * 16.0,
+                        (8.0 - rng.random::<f32>() * 16.0) - 24.0,
+                    ),
+                "",
+            ));
+
+            world.play_sound("spawner", 0.4);
+
+            enemy.spawn_splash(enemy.position, 0.5, world, rng);
+            enemy.spawn_splash(enemy.position, 0.5, world, rng);
+            enemy.spawn_splash(enemy.position, 0.5, world, rng);
+        } else {
+            enemy.active = false;
+        }
+    }
+
     fn crop_letters(s: &mut String, pos: usize) {
         match s.char_indices().nth(pos) {
             Some((pos, _)) => {
@@ -99,43 +123,41 @@ impl EnemyType for SpawnerEnemyType {
         0
     }
 
-    fn init(&mut self, enemy: &mut Enemy, image_assets: &ImageAssets) {
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn score_value(&self) -> u32 {
+        500
+    }
+
+    fn init(
+        &mut self,
+        enemy: &mut Enemy,
+        _world: &mut World,
+        image_assets: &ImageAssets,
+        _rng: &mut GameRng,
+    ) {
         enemy.enemy_type = self.enemy_type_id();
         enemy.radius = 32.0;
+        // No collider. Bullets and players pass through spawners.
         enemy.active = true;
         enemy.health = 100000;
         enemy.life_time = 100;
         enemy.maximum_tick = 3000;
 
-        enemy.weapon_tick = match enemy.extra.get("spawn_time") {
-            Some(v) => v.parse::<u128>().unwrap_or(123456),
-            None => 123456,
-        };
+        enemy.weapon_tick = enemy.extra.get_uint("spawn_time").unwrap_or(123456);
 
-        match enemy.extra.get("scale") {
-            Some(v) => {
-                let scale = v.parse::<f32>().unwrap_or(2.5);
-                enemy.sprite.scale = Vec2::new(scale, scale);
-            }
-            None => {
-                enemy.sprite.scale = Vec2::new(2.5, 2.5);
-            }
-        }
+        let scale = enemy.extra.get_float("scale").unwrap_or(2.5);
+        enemy.sprite.scale = Vec2::new(scale, scale);
 
-        match enemy.extra.get("flip_x") {
-            Some(v) => {
-                if v == "1" {
-                    enemy.sprite.flip_x(true);
-                } else {
-                    enemy.sprite.flip_x(false);
-                }
-            }
-            None => {}
-        }
+        enemy
+            .sprite
+            .flip_x(enemy.extra.get_bool("flip_x").unwrap_or(false));
 
-        match enemy.extra.get("idle_animation") {
+        match enemy.extra.get_text("idle_animation") {
             Some(animation_name) => {
-                match image_assets.get_animation_object(animation_name.as_str()) {
+                match image_assets.get_animation_object(animation_name) {
                     Some(animation) => {
                         enemy.sprite.play(&animation);
                     }
@@ -148,7 +170,14 @@ impl EnemyType for SpawnerEnemyType {
         };
     }
 
-    fn update(&self, enemy: &mut Enemy, player: Option<&Player>, image_assets: &ImageAssets) {
+    fn update(
+        &self,
+        enemy: &mut Enemy,
+        player: Option<&Player>,
+        world: &mut World,
+        image_assets: &ImageAssets,
+        rng: &mut GameRng,
+    ) {
         if enemy.weapon_tick != 123456 {
             match enemy.weapon_tick.checked_sub(crate::ONE_FRAME.as_millis()) {
                 Some(v) => enemy.weapon_tick = v,
@@ -161,10 +190,10 @@ impl EnemyType for SpawnerEnemyType {
         }
 
         if enemy.weapon_tick == 0 {
-            match enemy.extra.get("spawning_animation") {
+            match enemy.extra.get_text("spawning_animation") {
                 Some(animation_name) => {
                     if enemy.sprite.get_current_animation_name() != animation_name {
-                        match image_assets.get_animation_object(animation_name.as_str()) {
+                        match image_assets.get_animation_object(animation_name) {
                             Some(animation) => {
                                 enemy.sprite.play(&animation);
                             }
@@ -175,7 +204,7 @@ impl EnemyType for SpawnerEnemyType {
                 None => (),
             };
 
-            self.spawn_tick(enemy);
+            self.spawn_tick(enemy, world, rng);
         }
     }
 
@@ -183,15 +212,9 @@ impl EnemyType for SpawnerEnemyType {
         enemy.sprite.draw(ctx, enemy.position, 0.0, image_assets);
     }
 
-    fn die(&self, enemy: &mut Enemy) {
-        {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.insert(String::from("spawner_explode"), (String::from("./resources/sfx/spawner_explode.mp3"), 0.8 ) );
-        }
-    }
-
-    /// 0: not hit, 1: hit weakpoint, -1: hit shield. (No damage)
-    fn hit_check(&self, enemy: &Enemy, position: &Vec2<f32>, radius: f32) -> i32 {
-        0
+    fn die(&self, enemy: &mut Enemy, world: &mut World, _rng: &mut GameRng) {
+        world.play_sound("spawner_explode", 0.8);
+        world.add_trauma(0.45);
+        world.hit_stop(50);
     }
 }
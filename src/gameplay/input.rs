@@ -1,6 +1,14 @@
-use tetra::input::{self, Key};
+use std::fmt;
+
+use tetra::input::{self, GamepadAxis, GamepadButton, Key};
 use tetra::Context;
 
+#[cfg(feature = "config-files")]
+use serde::{Deserialize, Serialize};
+
+/// File that keep player controls. It is created with preset bindings when it does not exist.
+pub const BINDINGS_PATH: &str = "./controls.ron";
+
 pub trait Input {
     fn up(&self) -> bool;
     fn down(&self) -> bool;
@@ -9,21 +17,332 @@ pub trait Input {
     fn attack(&self) -> bool;
     fn attack_holding(&self) -> u128;
     fn switch(&self) -> bool;
+    /// Read device and update buttons for one frame.
     fn update(&mut self, ctx: &mut Context);
+    /// Update buttons for one frame without device. (Headless, scripted input)
+    fn update_with_state(&mut self, state: &InputState);
+    /// Button states of the last update.
+    fn state(&self) -> InputState;
+    /// The run is over. Close anything the input writes. Ex: replay file
+    fn finish(&mut self) {}
+}
+
+/// Button states of one frame. true: button is down.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct InputState {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub attack: bool,
+    pub switch: bool,
+}
+
+/// A way to press a button.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "config-files", derive(Serialize, Deserialize))]
+pub enum Binding {
+    Key(Key),
+    GamepadButton(GamepadButton),
+    /// Analog stick or trigger pass the threshold.
+    /// Positive threshold: axis value >= threshold. Negative threshold: axis value <= threshold.
+    GamepadAxis(GamepadAxis, f32),
+}
+
+impl Binding {
+    /// Key bindings ignore "gamepad_id".
+    pub fn is_down(&self, ctx: &Context, gamepad_id: usize) -> bool {
+        match self {
+            Binding::Key(key) => input::is_key_down(ctx, *key),
+            Binding::GamepadButton(button) => {
+                input::is_gamepad_button_down(ctx, gamepad_id, *button)
+            }
+            Binding::GamepadAxis(axis, threshold) => {
+                let value = input::get_gamepad_axis_position(ctx, gamepad_id, *axis);
+                if *threshold < 0.0 {
+                    value <= *threshold
+                } else if *threshold > 0.0 {
+                    value >= *threshold
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Bindings of every action. A button is down when one of its bindings is down.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "config-files", derive(Serialize, Deserialize))]
+pub struct Bindings {
+    pub up: Vec<Binding>,
+    pub down: Vec<Binding>,
+    pub left: Vec<Binding>,
+    pub right: Vec<Binding>,
+    pub attack: Vec<Binding>,
+    pub switch: Vec<Binding>,
+    /// Start the level again after game over.
+    pub restart: Vec<Binding>,
+}
+
+/// Error from loading or saving bindings file.
+#[derive(Debug)]
+pub struct BindingsError {
+    pub path: String,
+    pub message: String,
 }
 
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Bindings {
+    /// Arrow keys, X: attack, Z: switch. Gamepad: d-pad or left stick, X: attack, A: switch.
+    pub fn preset() -> Bindings {
+        Bindings {
+            up: vec![
+                Binding::Key(Key::Up),
+                Binding::GamepadButton(GamepadButton::Up),
+                Binding::GamepadAxis(GamepadAxis::LeftStickY, -0.5),
+            ],
+            down: vec![
+                Binding::Key(Key::Down),
+                Binding::GamepadButton(GamepadButton::Down),
+                Binding::GamepadAxis(GamepadAxis::LeftStickY, 0.5),
+            ],
+            left: vec![
+                Binding::Key(Key::Left),
+                Binding::GamepadButton(GamepadButton::Left),
+                Binding::GamepadAxis(GamepadAxis::LeftStickX, -0.5),
+            ],
+            right: vec![
+                Binding::Key(Key::Right),
+                Binding::GamepadButton(GamepadButton::Right),
+                Binding::GamepadAxis(GamepadAxis::LeftStickX, 0.5),
+            ],
+            attack: vec![
+                Binding::Key(Key::X),
+                Binding::GamepadButton(GamepadButton::X),
+            ],
+            switch: vec![
+                Binding::Key(Key::Z),
+                Binding::GamepadButton(GamepadButton::A),
+            ],
+            restart: vec![
+                Binding::Key(Key::Z),
+                Binding::GamepadButton(GamepadButton::Start),
+            ],
+        }
+    }
+
+    /// Same bindings without gamepad. Use it when gamepads belong to other players.
+    pub fn keys_only(&self) -> Bindings {
+        self.filter(|binding| match binding {
+            Binding::Key(_) => true,
+            _ => false,
+        })
+    }
+
+    /// Same bindings without keys. Use it for the gamepads of player 2, 3, ...
+    pub fn gamepad_only(&self) -> Bindings {
+        self.filter(|binding| match binding {
+            Binding::Key(_) => false,
+            _ => true,
+        })
+    }
+
+    /// Keep only the bindings that "keep" returns true.
+    fn filter(&self, keep: impl Fn(&Binding) -> bool) -> Bindings {
+        let filter_list = |list: &Vec<Binding>| -> Vec<Binding> {
+            list.iter()
+                .filter(|binding| keep(binding))
+                .copied()
+                .collect()
+        };
+
+        Bindings {
+            up: filter_list(&self.up),
+            down: filter_list(&self.down),
+            left: filter_list(&self.left),
+            right: filter_list(&self.right),
+            attack: filter_list(&self.attack),
+            switch: filter_list(&self.switch),
+            restart: filter_list(&self.restart),
+        }
+    }
+
+    /// Load bindings from "path". Create the file with preset bindings if it does not exist.
+    /// Use preset bindings when the file has error.
+    pub fn load_or_preset(path: &str) -> Bindings {
+        if cfg!(feature = "config-files") == false {
+            return Bindings::preset();
+        }
+
+        if std::path::Path::new(path).exists() == false {
+            let bindings = Bindings::preset();
+            match bindings.save(path) {
+                Ok(_) => (),
+                Err(e) => println!("Save bindings error: {}", e),
+            };
+            return bindings;
+        }
+
+        match Bindings::load(path) {
+            Ok(bindings) => bindings,
+            Err(e) => {
+                println!("Load bindings error: {}", e);
+                println!("Use preset bindings");
+                Bindings::preset()
+            }
+        }
+    }
+
+    #[cfg(feature = "config-files")]
+    pub fn load(path: &str) -> Result<Bindings, BindingsError> {
+        let error = |message: String| BindingsError {
+            path: String::from(path),
+            message: message,
+        };
+
+        match std::fs::read_to_string(path) {
+            Ok(source) => match ron::de::from_str(source.as_str()) {
+                Ok(bindings) => Ok(bindings),
+                Err(e) => Err(error(format!(
+                    "{}:{}: {}",
+                    e.position.line, e.position.col, e.code
+                ))),
+            },
+            Err(e) => Err(error(format!("Can not read bindings file. {}", e))),
+        }
+    }
+
+    #[cfg(feature = "config-files")]
+    pub fn save(&self, path: &str) -> Result<(), BindingsError> {
+        let error = |message: String| BindingsError {
+            path: String::from(path),
+            message: message,
+        };
+
+        let text = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new()) {
+            Ok(v) => v,
+            Err(e) => return Err(error(format!("{}", e))),
+        };
+
+        match std::fs::write(path, text) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(error(format!("Can not write bindings file. {}", e))),
+        }
+    }
+
+    #[cfg(not(feature = "config-files"))]
+    pub fn load(path: &str) -> Result<Bindings, BindingsError> {
+        Err(BindingsError {
+            path: String::from(path),
+            message: String::from("Bindings file need feature \"config-files\""),
+        })
+    }
+
+    #[cfg(not(feature = "config-files"))]
+    pub fn save(&self, path: &str) -> Result<(), BindingsError> {
+        Err(BindingsError {
+            path: String::from(path),
+            message: String::from("Bindings file need feature \"config-files\""),
+        })
+    }
+}
+
+/// Buttons of every player action from bindings. Keyboard and GamepadInput share it.
+pub struct ActionButtons {
+    pub up: Button,
+    pub down: Button,
+    pub left: Button,
+    pub right: Button,
+    pub attack: Button,
+    pub switch: Button,
+}
+
+impl ActionButtons {
+    pub fn new(bindings: &Bindings) -> ActionButtons {
+        ActionButtons {
+            up: Button::new(bindings.up.clone()),
+            down: Button::new(bindings.down.clone()),
+            left: Button::new(bindings.left.clone()),
+            right: Button::new(bindings.right.clone()),
+            attack: Button::new(bindings.attack.clone()),
+            switch: Button::new(bindings.switch.clone()),
+        }
+    }
+
+    /// Read the keyboard and gamepad "gamepad_id".
+    pub fn update(&mut self, ctx: &mut Context, gamepad_id: usize) {
+        self.up.update(ctx, gamepad_id);
+        self.down.update(ctx, gamepad_id);
+        self.left.update(ctx, gamepad_id);
+        self.right.update(ctx, gamepad_id);
+        self.attack.update(ctx, gamepad_id);
+        self.switch.update(ctx, gamepad_id);
+    }
+
+    pub fn update_with_state(&mut self, state: &InputState) {
+        self.up.update_with_state(state.up);
+        self.down.update_with_state(state.down);
+        self.left.update_with_state(state.left);
+        self.right.update_with_state(state.right);
+        self.attack.update_with_state(state.attack);
+        self.switch.update_with_state(state.switch);
+    }
+
+    pub fn state(&self) -> InputState {
+        InputState {
+            up: self.up.is_down(),
+            down: self.down.is_down(),
+            left: self.left.is_down(),
+            right: self.right.is_down(),
+            attack: self.attack.is_down(),
+            switch: self.switch.is_down(),
+        }
+    }
+
+    pub fn up(&self) -> bool {
+        self.up.is_down()
+    }
+
+    pub fn down(&self) -> bool {
+        self.down.is_down()
+    }
+
+    pub fn left(&self) -> bool {
+        self.left.is_down()
+    }
+
+    pub fn right(&self) -> bool {
+        self.right.is_down()
+    }
+
+    /// Attack and switch are pressed in the first frames only. Move actions are pressed while holding.
+    pub fn attack(&self) -> bool {
+        self.attack.hold_time < 50 && self.attack.idle_time == 0
+    }
+
+    pub fn attack_holding(&self) -> u128 {
+        self.attack.hold_time
+    }
+
+    pub fn switch(&self) -> bool {
+        self.switch.hold_time < 50 && self.switch.idle_time == 0
+    }
+}
+
+/// Keyboard and the first gamepad.
 pub struct Keyboard {
-    pub up_key: Button,
-    pub down_key: Button,
-    pub left_key: Button,
-    pub right_key: Button,
-    pub attack_key: Button,
-    pub switch_key: Button,
+    pub buttons: ActionButtons,
 }
 
 impl Keyboard {
     pub fn new_with_preset_keys() -> Keyboard {
-        Keyboard::new(Key::Up, Key::Down, Key::Left, Key::Right, Key::X, Key::Z)
+        Keyboard::new_with_bindings(&Bindings::preset())
     }
 
     pub fn new(
@@ -34,75 +353,159 @@ impl Keyboard {
         attack_key: Key,
         switch_key: Key,
     ) -> Keyboard {
+        Keyboard::new_with_bindings(&Bindings {
+            up: vec![Binding::Key(up)],
+            down: vec![Binding::Key(down)],
+            left: vec![Binding::Key(left)],
+            right: vec![Binding::Key(right)],
+            attack: vec![Binding::Key(attack_key)],
+            switch: vec![Binding::Key(switch_key)],
+            restart: vec![],
+        })
+    }
+
+    pub fn new_with_bindings(bindings: &Bindings) -> Keyboard {
         Keyboard {
-            up_key: Button::new(up),
-            down_key: Button::new(down),
-            left_key: Button::new(left),
-            right_key: Button::new(right),
-            attack_key: Button::new(attack_key),
-            switch_key: Button::new(switch_key),
+            buttons: ActionButtons::new(bindings),
         }
     }
 }
 
 impl Input for Keyboard {
     fn up(&self) -> bool {
-        self.up_key.hold_time > 0
+        self.buttons.up()
+    }
+
+    fn down(&self) -> bool {
+        self.buttons.down()
+    }
+
+    fn left(&self) -> bool {
+        self.buttons.left()
+    }
+
+    fn right(&self) -> bool {
+        self.buttons.right()
+    }
+
+    fn attack(&self) -> bool {
+        self.buttons.attack()
+    }
+
+    fn attack_holding(&self) -> u128 {
+        self.buttons.attack_holding()
+    }
+
+    fn switch(&self) -> bool {
+        self.buttons.switch()
+    }
+
+    fn update(&mut self, ctx: &mut Context) {
+        self.buttons.update(ctx, 0);
+    }
+
+    fn update_with_state(&mut self, state: &InputState) {
+        self.buttons.update_with_state(state);
+    }
+
+    fn state(&self) -> InputState {
+        self.buttons.state()
+    }
+}
+
+/// Only gamepad bindings of "bindings" are used. Key bindings are ignored.
+pub struct GamepadInput {
+    gamepad_id: usize,
+    pub buttons: ActionButtons,
+}
+
+impl GamepadInput {
+    pub fn new(gamepad_id: usize, bindings: &Bindings) -> GamepadInput {
+        GamepadInput {
+            gamepad_id: gamepad_id,
+            buttons: ActionButtons::new(&bindings.gamepad_only()),
+        }
+    }
+
+    pub fn gamepad_id(&self) -> usize {
+        self.gamepad_id
+    }
+}
+
+impl Input for GamepadInput {
+    fn up(&self) -> bool {
+        self.buttons.up()
     }
 
     fn down(&self) -> bool {
-        self.down_key.hold_time > 0
+        self.buttons.down()
     }
 
     fn left(&self) -> bool {
-        self.left_key.hold_time > 0
+        self.buttons.left()
     }
 
     fn right(&self) -> bool {
-        self.right_key.hold_time > 0
+        self.buttons.right()
     }
 
     fn attack(&self) -> bool {
-        self.attack_key.hold_time < 50 && self.attack_key.idle_time == 0
+        self.buttons.attack()
     }
 
     fn attack_holding(&self) -> u128 {
-        self.attack_key.hold_time
+        self.buttons.attack_holding()
     }
 
     fn switch(&self) -> bool {
-        self.switch_key.hold_time < 50 && self.switch_key.idle_time == 0
+        self.buttons.switch()
     }
 
     fn update(&mut self, ctx: &mut Context) {
-        self.up_key.update(ctx);
-        self.down_key.update(ctx);
-        self.left_key.update(ctx);
-        self.right_key.update(ctx);
-        self.attack_key.update(ctx);
-        self.switch_key.update(ctx);
+        self.buttons.update(ctx, self.gamepad_id);
+    }
+
+    fn update_with_state(&mut self, state: &InputState) {
+        self.buttons.update_with_state(state);
+    }
+
+    fn state(&self) -> InputState {
+        self.buttons.state()
     }
 }
 
 pub struct Button {
-    button: Key,
+    bindings: Vec<Binding>,
     pub hold_time: u128,
     pub idle_time: u128,
+    released: bool,
 }
 
 impl Button {
-    pub fn new(button: Key) -> Button {
+    pub fn new(bindings: Vec<Binding>) -> Button {
         Button {
-            button: button,
+            bindings: bindings,
             idle_time: 200,
             hold_time: 0,
+            released: false,
         }
     }
 
-    pub fn update(&mut self, ctx: &mut Context) {
+    /// Button is down when one of the bindings is down.
+    pub fn update(&mut self, ctx: &mut Context, gamepad_id: usize) {
+        let is_down = self
+            .bindings
+            .iter()
+            .any(|binding| binding.is_down(ctx, gamepad_id));
+        self.update_with_state(is_down);
+    }
+
+    /// Update hold_time and idle_time with button state of this frame.
+    pub fn update_with_state(&mut self, is_down: bool) {
         let delta_time = 1000 / 60;
         self.idle_time += delta_time;
-        if input::is_key_down(ctx, self.button) {
+        self.released = self.hold_time > 0 && is_down == false;
+        if is_down {
             if self.hold_time == 0 {
                 self.idle_time = 0;
             }
@@ -112,6 +515,15 @@ impl Button {
         }
     }
 
+    pub fn is_down(&self) -> bool {
+        self.hold_time > 0
+    }
+
+    /// Button was down in the previous frame and up in this frame.
+    pub fn is_released(&self) -> bool {
+        self.released
+    }
+
     pub fn consume(&mut self) {
         self.idle_time = 200;
     }
@@ -4,6 +4,9 @@ use std::time::Duration;
 use tetra::math::Vec2;
 use tetra::Context;
 
+use crate::gameplay::bullet_pattern::PatternSpawnNode;
+use crate::gameplay::camera_path::NodeTravel;
+use crate::gameplay::world::World;
 use crate::sprite::Sprite;
 
 pub struct NodePoint {
@@ -12,6 +15,14 @@ pub struct NodePoint {
     pub waiting_time: u128,
     pub next_node: Option<String>,
     pub spawn_patterns: VecDeque<PatternNode>,
+    /// Music track that starts when the camera reaches the node. None: keep the current music.
+    pub music: Option<String>,
+    /// Sound that plays once when the camera reaches the node.
+    pub stinger: Option<String>,
+    /// Camera path, speed and easing of the travel to this node.
+    pub travel: NodeTravel,
+    /// Bullet patterns that fire when the camera starts moving to this node.
+    pub bullet_patterns: Vec<PatternSpawnNode>,
 }
 
 pub struct Level {
@@ -24,6 +35,8 @@ pub struct Level {
     spawn_duration: u128,
     current_node_spawn_patterns: VecDeque<PatternNode>,
     active_patterns: Vec<VecDeque<EnemySpawnNode>>,
+    /// Bullet patterns of the current node that are not in the world yet.
+    pending_bullet_patterns: Vec<PatternSpawnNode>,
 }
 
 impl Level {
@@ -37,6 +50,7 @@ impl Level {
             spawn_duration: 0,
             current_node_spawn_patterns: VecDeque::new(),
             active_patterns: vec![],
+            pending_bullet_patterns: vec![],
         }
     }
 
@@ -59,12 +73,55 @@ impl Level {
             next_node: next_node,
             waiting_time: waiting_time,
             spawn_patterns: spawn_patterns,
+            music: None,
+            stinger: None,
+            travel: NodeTravel::new(),
+            bullet_patterns: vec![],
         };
 
         self.all_nodes.insert(String::from(name), node);
     }
 
-    pub fn update(&mut self) {
+    /// Attach music track and stinger sound to the node. Empty string: none.
+    pub fn set_node_music(&mut self, name: &str, music: &str, stinger: &str) {
+        match self.all_nodes.get_mut(name) {
+            Some(node) => {
+                node.music = match music.len() {
+                    0 => None,
+                    _ => Some(String::from(music)),
+                };
+                node.stinger = match stinger.len() {
+                    0 => None,
+                    _ => Some(String::from(stinger)),
+                };
+            }
+            None => println!("set_node_music()::Node missing ({})", name),
+        };
+    }
+
+    /// Set camera path, speed and easing of the travel to the node.
+    pub fn set_node_travel(&mut self, name: &str, travel: NodeTravel) {
+        match self.all_nodes.get_mut(name) {
+            Some(node) => node.travel = travel,
+            None => println!("set_node_travel()::Node missing ({})", name),
+        };
+    }
+
+    /// Set bullet patterns of the node. Their delay starts when the camera starts moving to the node.
+    pub fn set_node_bullet_patterns(&mut self, name: &str, bullet_patterns: Vec<PatternSpawnNode>) {
+        match self.all_nodes.get_mut(name) {
+            Some(node) => node.bullet_patterns = bullet_patterns,
+            None => println!("set_node_bullet_patterns()::Node missing ({})", name),
+        };
+    }
+
+    /// Put enemies of active patterns into world.enemy_spawn_nodes when their delay is over.
+    /// Bullet patterns of the current node go into the world at once. Their emitter waits for the delay.
+    pub fn update(&mut self, world: &mut World) {
+        for bullet_pattern in self.pending_bullet_patterns.drain(..) {
+            world.fire_pattern(bullet_pattern);
+        }
+
         if self.current_node_spawn_patterns.len() > 0 {
             loop {
                 let some_node = self.current_node_spawn_patterns.get(0);
@@ -113,8 +170,7 @@ impl Level {
                     }
                     None => {
                         // println!("Spawn enemy: {}", node.enemy_type);
-                        let mut need_to_spawn_enemy_list = crate::ENEMY_SPAWN_NODES.lock().unwrap();
-                        need_to_spawn_enemy_list.push(node.clone());
+                        world.spawn_enemy(node.clone());
                         node.delay = 0;
                     }
                 }
@@ -157,6 +213,35 @@ impl Level {
             && self.is_start == true
     }
 
+    /// Spawn patterns of the current node that are not started yet.
+    ///
+    /// # Return:
+    ///
+    /// * (pattern name, time until the pattern starts in ms) in starting order.
+    ///
+    pub fn upcoming_patterns(&self) -> Vec<(&str, u128)> {
+        let mut time: u128 = 0;
+        let mut elapsed = self.spawn_duration;
+        let mut list = vec![];
+        for node in self.current_node_spawn_patterns.iter() {
+            // Delays count from the previous pattern. spawn_duration counts from the first one.
+            let wait = node.delay.saturating_sub(elapsed);
+            elapsed = elapsed.saturating_sub(node.delay);
+            time += wait;
+            list.push((node.pattern.as_str(), time));
+        }
+
+        list
+    }
+
+    /// Time until the next enemy of every running spawn pattern. (ms)
+    pub fn next_enemy_spawn_times(&self) -> Vec<u128> {
+        self.active_patterns
+            .iter()
+            .filter_map(|pattern| pattern.get(0).map(|node| node.delay))
+            .collect()
+    }
+
     fn get_pattern(&self, pattern: &String) -> Option<VecDeque<EnemySpawnNode>> {
         match self.patterns.get(pattern) {
             Some(list) => {
@@ -185,7 +270,11 @@ impl Level {
             self.current_camera_target_node_name = Some(String::from(name));
             match self.get_node(name) {
                 Some(node) => {
-                    self.current_node_spawn_patterns = node.spawn_patterns.clone();
+                    // Patterns from an auto-scroll node that have not started are kept.
+                    let mut spawn_patterns = node.spawn_patterns.clone();
+                    let bullet_patterns = node.bullet_patterns.clone();
+                    self.current_node_spawn_patterns.append(&mut spawn_patterns);
+                    self.pending_bullet_patterns = bullet_patterns;
                     self.is_start = true;
                 }
                 None => (),
@@ -0,0 +1,581 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use serde::de::value::{BorrowedStrDeserializer, MapAccessDeserializer};
+use serde::de::{DeserializeSeed, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use tetra::math::Vec2;
+
+use crate::gameplay::bullet_pattern::PatternSpawnNode;
+use crate::gameplay::camera_effects::TravelCurve;
+use crate::gameplay::camera_path::{NodeTravel, DEFAULT_CAMERA_SPEED};
+use crate::gameplay::enemy_manager::{self, EnemyTypeBank};
+use crate::gameplay::level::{EnemySpawnNode, Level, PatternNode};
+use crate::gameplay::parameters::Parameters;
+
+/// Error from loading level file.
+/// line and column start from 1 and point to the text that cause the error.
+#[derive(Debug)]
+pub struct LevelFileError {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LevelFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.path, self.line, self.column, self.message
+        )
+    }
+}
+
+/// RON struct with the names of its fields. The names are slices of the source from the parser,
+/// so an error can point to the field that cause it.
+pub struct Keyed<'a, T> {
+    value: T,
+    keys: Vec<&'a str>,
+}
+
+impl<'a, T> Keyed<'a, T> {
+    /// Name of field "key" in the source. The first field when "key" is not written.
+    /// None: the struct is empty.
+    pub fn key(&self, key: &str) -> Option<&'a str> {
+        match self.keys.iter().find(|name| **name == key) {
+            Some(name) => Some(name),
+            None => self.keys.get(0).copied(),
+        }
+    }
+}
+
+impl<'a, T> Deref for Keyed<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'de: 'a, 'a, T: Deserialize<'de>> Deserialize<'de> for Keyed<'a, T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(
+            "",
+            &[],
+            KeyedVisitor {
+                marker: PhantomData,
+            },
+        )
+    }
+}
+
+struct KeyedVisitor<'a, T> {
+    marker: PhantomData<Keyed<'a, T>>,
+}
+
+impl<'de: 'a, 'a, T: Deserialize<'de>> Visitor<'de> for KeyedVisitor<'a, T> {
+    type Value = Keyed<'a, T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("struct")
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut keys = vec![];
+        let value = T::deserialize(MapAccessDeserializer::new(KeyRecorder {
+            map: map,
+            keys: &mut keys,
+        }))?;
+
+        Ok(Keyed {
+            value: value,
+            keys: keys,
+        })
+    }
+}
+
+/// Pass fields of "map" to the struct and keep their names.
+struct KeyRecorder<'k, 'a, A> {
+    map: A,
+    keys: &'k mut Vec<&'a str>,
+}
+
+impl<'de: 'a, 'k, 'a, A: MapAccess<'de>> MapAccess<'de> for KeyRecorder<'k, 'a, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.map.next_key::<&'de str>()? {
+            Some(key) => {
+                self.keys.push(key);
+                seed.deserialize(BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, A::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.map.next_value_seed(seed)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Level")]
+struct LevelData<'a> {
+    #[serde(borrow)]
+    nodes: Vec<Keyed<'a, NodePointData<'a>>>,
+    /// Validation goes through them in name order.
+    /// Names are owned. A name with escape (\" or \\) can not borrow from the source.
+    #[serde(default, borrow)]
+    patterns: BTreeMap<String, Vec<Keyed<'a, EnemySpawnNodeData>>>,
+}
+
+#[derive(Deserialize)]
+struct NodePointData<'a> {
+    name: String,
+    position: (f32, f32),
+    #[serde(default)]
+    waiting_time: u64,
+    #[serde(default)]
+    next_node: Option<String>,
+    #[serde(default, borrow)]
+    spawn_patterns: Vec<Keyed<'a, PatternNodeData>>,
+    #[serde(default)]
+    music: Option<String>,
+    #[serde(default)]
+    stinger: Option<String>,
+    /// "step", "linear", "ease_in", "ease_out" or "ease_in_out". See TravelCurve
+    #[serde(default)]
+    travel: Option<String>,
+    /// Waypoints of the camera from the previous node. See NodeTravel
+    #[serde(default)]
+    path: Vec<(f32, f32)>,
+    #[serde(default)]
+    spline: bool,
+    /// Camera speed. (pixel per frame)
+    #[serde(default)]
+    speed: Option<f32>,
+    #[serde(default)]
+    auto_scroll: bool,
+    /// Patterns in ./resources/patterns/bullet-patterns.ron
+    #[serde(default)]
+    bullet_patterns: Vec<BulletPatternNodeData>,
+}
+
+#[derive(Deserialize)]
+struct BulletPatternNodeData {
+    #[serde(default)]
+    delay: u64,
+    pattern: String,
+    position: (f32, f32),
+}
+
+#[derive(Deserialize)]
+struct PatternNodeData {
+    #[serde(default)]
+    delay: u64,
+    pattern: String,
+}
+
+#[derive(Deserialize)]
+struct EnemySpawnNodeData {
+    #[serde(default)]
+    delay: u64,
+    enemy_type: i32,
+    position: (f32, f32),
+    #[serde(default)]
+    extra: String,
+}
+
+/// Load level file (RON format) and create Level object from it.
+///
+/// # Arguments:
+///
+/// * path - path to level file. Ex: "./resources/levels/level-1.ron"
+/// * enemy_types - enemy types that the level can spawn. Spawn nodes are checked with their parameters.
+///
+pub fn load_level(path: &str, enemy_types: &EnemyTypeBank) -> Result<Level, LevelFileError> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => parse_level(path, source.as_str(), enemy_types),
+        Err(e) => Err(LevelFileError {
+            path: String::from(path),
+            line: 0,
+            column: 0,
+            message: format!("Can not read level file. {}", e),
+        }),
+    }
+}
+
+/// Parse level from text. "path" only use for error message.
+pub fn parse_level(
+    path: &str,
+    source: &str,
+    enemy_types: &EnemyTypeBank,
+) -> Result<Level, LevelFileError> {
+    let data: LevelData = match ron_options().from_str(source) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(LevelFileError {
+                path: String::from(path),
+                line: e.position.line,
+                column: e.position.col,
+                message: format!("{}", e.code),
+            });
+        }
+    };
+
+    validate(path, source, &data, enemy_types)?;
+
+    let mut level = Level::new();
+
+    for node in data.nodes.iter() {
+        let mut spawn_patterns = VecDeque::new();
+        for pattern_node in node.spawn_patterns.iter() {
+            spawn_patterns.push_back(PatternNode {
+                delay: pattern_node.delay as u128,
+                pattern: pattern_node.pattern.clone(),
+            });
+        }
+
+        let next_node_name = match node.next_node.as_ref() {
+            Some(name) => name.as_str(),
+            None => "",
+        };
+
+        level.add_camera_target_node(
+            node.name.as_str(),
+            Vec2::new(node.position.0, node.position.1),
+            node.waiting_time as u128,
+            next_node_name,
+            spawn_patterns,
+        );
+
+        let music = match node.music.as_ref() {
+            Some(name) => name.as_str(),
+            None => "",
+        };
+        let stinger = match node.stinger.as_ref() {
+            Some(name) => name.as_str(),
+            None => "",
+        };
+        level.set_node_music(node.name.as_str(), music, stinger);
+
+        let mut travel = NodeTravel::new();
+        match node
+            .travel
+            .as_ref()
+            .and_then(|name| TravelCurve::from_name(name))
+        {
+            Some(curve) => travel.curve = curve,
+            None => (),
+        };
+        travel.waypoints = node
+            .path
+            .iter()
+            .map(|position| Vec2::new(position.0, position.1))
+            .collect();
+        travel.spline = node.spline;
+        travel.speed = node.speed.unwrap_or(DEFAULT_CAMERA_SPEED);
+        travel.auto_scroll = node.auto_scroll;
+        level.set_node_travel(node.name.as_str(), travel);
+
+        level.set_node_bullet_patterns(
+            node.name.as_str(),
+            node.bullet_patterns
+                .iter()
+                .map(|bullet_pattern| {
+                    PatternSpawnNode::new(
+                        bullet_pattern.delay as u128,
+                        bullet_pattern.pattern.as_str(),
+                        Vec2::new(bullet_pattern.position.0, bullet_pattern.position.1),
+                    )
+                })
+                .collect(),
+        );
+    }
+
+    for (name, spawn_nodes) in data.patterns.iter() {
+        let mut pattern = VecDeque::new();
+        for spawn_node in spawn_nodes.iter() {
+            pattern.push_back(EnemySpawnNode::new(
+                spawn_node.delay as u128,
+                spawn_node.enemy_type,
+                Vec2::new(spawn_node.position.0, spawn_node.position.1),
+                spawn_node.extra.as_str(),
+            ));
+        }
+
+        level.add_pattern(name.as_str(), pattern);
+    }
+
+    Ok(level)
+}
+
+/// Check references between nodes and patterns. Deserializer can not check these.
+/// Errors point to the name of the field that cause them.
+fn validate(
+    path: &str,
+    source: &str,
+    data: &LevelData,
+    enemy_types: &EnemyTypeBank,
+) -> Result<(), LevelFileError> {
+    let error = |key: Option<&str>, message: String| {
+        let (line, column) = match key {
+            Some(key) => position_of(source, key),
+            None => (0, 0),
+        };
+        LevelFileError {
+            path: String::from(path),
+            line: line,
+            column: column,
+            message: message,
+        }
+    };
+
+    let mut node_names = Vec::new();
+    for node in data.nodes.iter() {
+        if node_names.contains(&node.name.as_str()) {
+            return Err(error(
+                node.key("name"),
+                format!("Duplicate node name \"{}\"", node.name),
+            ));
+        }
+        node_names.push(node.name.as_str());
+    }
+
+    if node_names.contains(&"start") == false {
+        return Err(error(
+            data.nodes.get(0).and_then(|node| node.key("name")),
+            String::from("No node name \"start\""),
+        ));
+    }
+
+    for node in data.nodes.iter() {
+        match node.next_node.as_ref() {
+            Some(next_node) => {
+                if node_names.contains(&next_node.as_str()) == false {
+                    return Err(error(
+                        node.key("next_node"),
+                        format!(
+                            "Unknown next_node \"{}\" in node \"{}\"",
+                            next_node, node.name
+                        ),
+                    ));
+                }
+            }
+            None => (),
+        };
+
+        match node.travel.as_ref() {
+            Some(travel) if TravelCurve::from_name(travel).is_none() => {
+                return Err(error(
+                    node.key("travel"),
+                    format!("Unknown travel \"{}\" in node \"{}\"", travel, node.name),
+                ));
+            }
+            _ => (),
+        };
+
+        match node.speed {
+            Some(speed) if speed <= 0.0 => {
+                return Err(error(
+                    node.key("speed"),
+                    format!("speed has to be more than 0 in node \"{}\"", node.name),
+                ));
+            }
+            _ => (),
+        };
+
+        for pattern_node in node.spawn_patterns.iter() {
+            if data.patterns.contains_key(pattern_node.pattern.as_str()) == false {
+                return Err(error(
+                    pattern_node.key("pattern"),
+                    format!(
+                        "Unknown pattern \"{}\" in node \"{}\"",
+                        pattern_node.pattern, node.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    for (name, spawn_nodes) in data.patterns.iter() {
+        for spawn_node in spawn_nodes.iter() {
+            match enemy_types.parameters(spawn_node.enemy_type) {
+                Some(specs) => match Parameters::validate(
+                    spawn_node.extra.as_str(),
+                    &[&enemy_manager::COMMON_PARAMETERS, specs],
+                ) {
+                    Ok(_) => (),
+                    Err(e) => {
+                        return Err(error(
+                            spawn_node.key("extra"),
+                            format!("{} in pattern \"{}\"", e, name),
+                        ));
+                    }
+                },
+                None => {
+                    return Err(error(
+                        spawn_node.key("enemy_type"),
+                        format!(
+                            "Unknown enemy_type {} in pattern \"{}\"",
+                            spawn_node.enemy_type, name
+                        ),
+                    ));
+                }
+            };
+        }
+    }
+
+    Ok(())
+}
+
+/// RON options of level and bullet pattern files.
+/// "implicit_some" lets Option fields skip Some(). Ex: speed: 1.5
+pub fn ron_options() -> ron::Options {
+    ron::Options::default().with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+}
+
+/// (line, column) of "text" in "source". Both start from 1.
+/// "text" has to be a slice of "source" from the deserializer. (0, 0): it is not.
+pub fn position_of(source: &str, text: &str) -> (usize, usize) {
+    let offset = (text.as_ptr() as usize).wrapping_sub(source.as_ptr() as usize);
+    match offset <= source.len() {
+        true => line_and_column(source, offset),
+        false => (0, 0),
+    }
+}
+
+/// Convert byte offset into (line, column). Both start from 1.
+pub fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for (index, c) in source.char_indices() {
+        if index >= offset {
+            break;
+        }
+
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_level, LevelFileError};
+    use crate::gameplay::enemy_manager::{EnemyTypeBank, ENEMY_TYPES};
+    use crate::image_assets::ImageAssets;
+
+    const LEVEL: &str = r#"Level(
+    nodes: [
+        (name: "start", position: (0.0, 0.0), next_node: "end", spawn_patterns: [(pattern: "wave")]),
+        (name: "end", position: (100.0, 0.0), travel: "linear", speed: 2.0),
+    ],
+    patterns: {
+        "wave": [(enemy_type: 1, position: (10.0, 0.0), extra: "")],
+    },
+)"#;
+
+    /// Error of LEVEL with "from" replaced by "to".
+    fn error_of(from: &str, to: &str) -> LevelFileError {
+        assert!(LEVEL.contains(from), "{}", from);
+
+        let mut enemy_types = EnemyTypeBank::new();
+        enemy_types.setup(&ImageAssets::new(vec![]), &ENEMY_TYPES);
+        match parse_level("test.ron", LEVEL.replace(from, to).as_str(), &enemy_types) {
+            Ok(_) => panic!("no error after {} -> {}", from, to),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn valid_level_has_no_error() {
+        let mut enemy_types = EnemyTypeBank::new();
+        enemy_types.setup(&ImageAssets::new(vec![]), &ENEMY_TYPES);
+
+        assert!(parse_level("test.ron", LEVEL, &enemy_types).is_ok());
+    }
+
+    #[test]
+    fn duplicate_node_name_points_to_the_second_name() {
+        let e = error_of(r#"(name: "end""#, r#"(name: "start""#);
+        assert_eq!((e.line, e.column), (4, 10), "{}", e);
+    }
+
+    #[test]
+    fn missing_start_points_to_the_first_node_name() {
+        let e = error_of(r#"name: "start""#, r#"name: "begin""#);
+        assert_eq!((e.line, e.column), (3, 10), "{}", e);
+    }
+
+    #[test]
+    fn unknown_next_node_points_to_next_node() {
+        let e = error_of(r#"next_node: "end""#, r#"next_node: "exit""#);
+        assert_eq!((e.line, e.column), (3, 47), "{}", e);
+    }
+
+    #[test]
+    fn unknown_travel_points_to_travel() {
+        let e = error_of(r#"travel: "linear""#, r#"travel: "zigzag""#);
+        assert_eq!((e.line, e.column), (4, 47), "{}", e);
+    }
+
+    #[test]
+    fn zero_speed_points_to_speed() {
+        let e = error_of("speed: 2.0", "speed: 0.0");
+        assert_eq!((e.line, e.column), (4, 65), "{}", e);
+    }
+
+    #[test]
+    fn unknown_pattern_points_to_pattern() {
+        let e = error_of(r#"(pattern: "wave")"#, r#"(pattern: "rush")"#);
+        assert_eq!((e.line, e.column), (3, 83), "{}", e);
+    }
+
+    #[test]
+    fn unknown_enemy_type_points_to_enemy_type() {
+        let e = error_of("enemy_type: 1", "enemy_type: 9");
+        assert_eq!((e.line, e.column), (7, 19), "{}", e);
+    }
+
+    #[test]
+    fn unknown_extra_key_points_to_extra() {
+        let e = error_of(r#"extra: """#, r#"extra: "size=2|""#);
+        assert_eq!((e.line, e.column), (7, 57), "{}", e);
+    }
+
+    /// The pattern and the spawn pattern of "start" are renamed. Both have to unescape the same.
+    #[test]
+    fn pattern_name_can_have_escapes() {
+        let source = LEVEL.replace(r#""wave""#, r#""wave \"a\\b\"""#);
+        let mut enemy_types = EnemyTypeBank::new();
+        enemy_types.setup(&ImageAssets::new(vec![]), &ENEMY_TYPES);
+
+        match parse_level("test.ron", source.as_str(), &enemy_types) {
+            Ok(_) => (),
+            Err(e) => panic!("{}", e),
+        };
+    }
+}
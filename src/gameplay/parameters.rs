@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Value type of a parameter in "extra" text.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ParameterType {
+    Int,
+    UInt,
+    Float,
+    /// Accept 1/0 and true/false
+    Bool,
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterValue {
+    Int(i64),
+    UInt(u128),
+    Float(f32),
+    Bool(bool),
+    Text(String),
+}
+
+/// Declare a key that EnemyType/BulletType/ParticleType read from "extra" text.
+pub struct ParameterSpec {
+    pub key: &'static str,
+    pub parameter_type: ParameterType,
+    /// Value when the key is not in "extra" text. None: no value.
+    pub default: Option<&'static str>,
+}
+
+impl ParameterSpec {
+    pub const fn new(
+        key: &'static str,
+        parameter_type: ParameterType,
+        default: Option<&'static str>,
+    ) -> ParameterSpec {
+        ParameterSpec {
+            key: key,
+            parameter_type: parameter_type,
+            default: default,
+        }
+    }
+}
+
+/// Error from parsing "extra" text. It names the key that cause the error.
+#[derive(Debug)]
+pub struct ParameterError {
+    pub key: String,
+    pub message: String,
+    pub raw_extra: String,
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Parameter \"{}\": {} ({})",
+            self.key, self.message, self.raw_extra
+        )
+    }
+}
+
+/// Typed values parsed from "extra" text. Format: "key=value|key=value|"
+pub struct Parameters {
+    values: HashMap<String, ParameterValue>,
+}
+
+impl Parameters {
+    pub fn new() -> Parameters {
+        Parameters {
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// Replace current values with values from "raw_extra".
+    /// Keys that not in "raw_extra" will use default value from the spec.
+    ///
+    /// # Arguments:
+    ///
+    /// * raw_extra - "key=value|key=value|"
+    /// * specs_list - All keys that allow in "raw_extra".
+    ///
+    pub fn load(
+        &mut self,
+        raw_extra: &str,
+        specs_list: &[&[ParameterSpec]],
+    ) -> Result<(), ParameterError> {
+        self.values.clear();
+
+        let error = |key: &str, message: String| ParameterError {
+            key: String::from(key),
+            message: message,
+            raw_extra: String::from(raw_extra),
+        };
+
+        for text in raw_extra.split('|') {
+            if text.len() == 0 {
+                continue;
+            }
+
+            let parameter: Vec<&str> = text.split('=').collect();
+            if parameter.len() != 2 {
+                return Err(error(text, String::from("Incorrect parameter format")));
+            }
+
+            let key = parameter[0];
+            match find_spec(specs_list, key) {
+                Some(spec) => match parse_value(spec.parameter_type, parameter[1]) {
+                    Some(value) => {
+                        self.values.insert(String::from(key), value);
+                    }
+                    None => {
+                        return Err(error(
+                            key,
+                            format!("\"{}\" is not {:?}", parameter[1], spec.parameter_type),
+                        ));
+                    }
+                },
+                None => return Err(error(key, String::from("Unknown parameter"))),
+            };
+        }
+
+        for specs in specs_list.iter() {
+            for spec in specs.iter() {
+                if self.values.contains_key(spec.key) {
+                    continue;
+                }
+
+                match spec.default {
+                    Some(default) => match parse_value(spec.parameter_type, default) {
+                        Some(value) => {
+                            self.values.insert(String::from(spec.key), value);
+                        }
+                        None => {
+                            return Err(error(
+                                spec.key,
+                                format!("Default \"{}\" is not {:?}", default, spec.parameter_type),
+                            ));
+                        }
+                    },
+                    None => (),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check "raw_extra" without keeping values.
+    pub fn validate(
+        raw_extra: &str,
+        specs_list: &[&[ParameterSpec]],
+    ) -> Result<(), ParameterError> {
+        Parameters::new().load(raw_extra, specs_list)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ParameterValue> {
+        self.values.get(key)
+    }
+
+    pub fn set(&mut self, key: &str, value: ParameterValue) {
+        self.values.insert(String::from(key), value);
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        match self.values.get(key) {
+            Some(ParameterValue::Int(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_uint(&self, key: &str) -> Option<u128> {
+        match self.values.get(key) {
+            Some(ParameterValue::UInt(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_float(&self, key: &str) -> Option<f32> {
+        match self.values.get(key) {
+            Some(ParameterValue::Float(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.values.get(key) {
+            Some(ParameterValue::Bool(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_text(&self, key: &str) -> Option<&str> {
+        match self.values.get(key) {
+            Some(ParameterValue::Text(v)) => Some(v.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut_text(&mut self, key: &str) -> Option<&mut String> {
+        match self.values.get_mut(key) {
+            Some(ParameterValue::Text(v)) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+fn find_spec<'a>(specs_list: &[&'a [ParameterSpec]], key: &str) -> Option<&'a ParameterSpec> {
+    for specs in specs_list.iter() {
+        for spec in specs.iter() {
+            if spec.key == key {
+                return Some(spec);
+            }
+        }
+    }
+
+    None
+}
+
+fn parse_value(parameter_type: ParameterType, text: &str) -> Option<ParameterValue> {
+    match parameter_type {
+        ParameterType::Int => text.parse::<i64>().ok().map(ParameterValue::Int),
+        ParameterType::UInt => text.parse::<u128>().ok().map(ParameterValue::UInt),
+        ParameterType::Float => text.parse::<f32>().ok().map(ParameterValue::Float),
+        ParameterType::Bool => match text {
+            "1" | "true" => Some(ParameterValue::Bool(true)),
+            "0" | "false" => Some(ParameterValue::Bool(false)),
+            _ => None,
+        },
+        ParameterType::Text => Some(ParameterValue::Text(String::from(text))),
+    }
+}
@@ -3,9 +3,15 @@ use std::collections::HashMap;
 use tetra::math::Vec2;
 use tetra::Context;
 
+use crate::gameplay::parameters::{ParameterError, ParameterSpec, ParameterType, Parameters};
+use crate::gameplay::world::World;
 use crate::image_assets::ImageAssets;
 use crate::sprite::Sprite;
 
+/// Keys in particle.extra that use by every ParticleType.
+pub const COMMON_PARAMETERS: [ParameterSpec; 1] =
+    [ParameterSpec::new("scale", ParameterType::Float, Some("2.5"))];
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum ParticleDrawLayer {
     Bottomest,
@@ -53,8 +59,6 @@ impl ParticleManager {
             inactive_particles.push(Particle::new());
         }
 
-        clear_particle_spawn_nodes();
-
         ParticleManager {
             active_particles_layer: HashMap::new(),
             inactive_particles: inactive_particles,
@@ -62,10 +66,18 @@ impl ParticleManager {
         }
     }
 
-    pub fn update(&mut self, image_assets: &ImageAssets) {
+    /// Spawn particles in world.particle_spawn_nodes then update active particles.
+    pub fn update(
+        &mut self,
+        particle_type_bank: &mut ParticleTypeBank,
+        world: &mut World,
+        image_assets: &ImageAssets,
+    ) {
         update_spawn_nodes_list(
+            &mut world.particle_spawn_nodes,
             &mut self.inactive_particles,
             &mut self.active_particles_layer,
+            particle_type_bank,
             image_assets,
         );
 
@@ -74,6 +86,7 @@ impl ParticleManager {
             &mut self.active_particles_layer,
             &mut self.remove_inactive_particle_list,
             &mut self.inactive_particles,
+            particle_type_bank,
         );
 
         update_active_particles(
@@ -81,6 +94,7 @@ impl ParticleManager {
             &mut self.active_particles_layer,
             &mut self.remove_inactive_particle_list,
             &mut self.inactive_particles,
+            particle_type_bank,
         );
 
         update_active_particles(
@@ -88,12 +102,14 @@ impl ParticleManager {
             &mut self.active_particles_layer,
             &mut self.remove_inactive_particle_list,
             &mut self.inactive_particles,
+            particle_type_bank,
         );
         update_active_particles(
             ParticleDrawLayer::FiringBullet,
             &mut self.active_particles_layer,
             &mut self.remove_inactive_particle_list,
             &mut self.inactive_particles,
+            particle_type_bank,
         );
 
         update_active_particles(
@@ -101,19 +117,33 @@ impl ParticleManager {
             &mut self.active_particles_layer,
             &mut self.remove_inactive_particle_list,
             &mut self.inactive_particles,
+            particle_type_bank,
         );
     }
 
+    pub fn active_particle_count(&self) -> usize {
+        self.active_particles_layer
+            .values()
+            .map(|particles| particles.len())
+            .sum()
+    }
+
+    /// Number of particles that the manager created. Active and inactive.
+    pub fn capacity(&self) -> usize {
+        self.active_particle_count() + self.inactive_particles.len()
+    }
+
     pub fn draw(
         &mut self,
         draw_layer: ParticleDrawLayer,
         ctx: &mut Context,
+        particle_type_bank: &ParticleTypeBank,
         image_assets: &ImageAssets,
     ) {
         match self.active_particles_layer.get_mut(&draw_layer) {
             Some(active_particles) => {
                 for particle in active_particles.iter_mut() {
-                    particle.draw(ctx, image_assets);
+                    particle.draw(ctx, particle_type_bank, image_assets);
                 }
             }
             None => (),
@@ -126,13 +156,14 @@ pub fn update_active_particles(
     active_particles_layer: &mut HashMap<ParticleDrawLayer, Vec<Particle>>,
     remove_inactive_particle_list: &mut Vec<usize>,
     inactive_particles: &mut Vec<Particle>,
+    particle_type_bank: &ParticleTypeBank,
 ) {
     match active_particles_layer.get_mut(&draw_layer) {
         Some(active_particles) => {
             let mut index = 0;
             for particle in active_particles.iter_mut() {
                 if particle.active == true {
-                    particle.update();
+                    particle.update(particle_type_bank);
                 } else {
                     remove_inactive_particle_list.push(index);
                 }
@@ -153,26 +184,23 @@ pub fn update_active_particles(
 
 /// Fetching spawn node in the queue and create particle for active list.
 pub fn update_spawn_nodes_list(
+    spawn_node_list: &mut Vec<ParticleSpawnNode>,
     inactive_particles: &mut Vec<Particle>,
     active_particles: &mut HashMap<ParticleDrawLayer, Vec<Particle>>,
+    particle_type_bank: &mut ParticleTypeBank,
     image_assets: &ImageAssets,
 ) {
-    let mut particle_types = crate::PARTICLE_TYPE_BANK.lock().unwrap();
-
-    {
-        let mut spawn_node_list = crate::PARTICLE_SPAWN_NODES.lock().unwrap();
-        for spawn_node in spawn_node_list.iter() {
-            spawn_particle(
-                inactive_particles,
-                active_particles,
-                spawn_node,
-                &mut particle_types,
-                image_assets,
-            );
-        }
-
-        spawn_node_list.clear();
+    for spawn_node in spawn_node_list.iter() {
+        spawn_particle(
+            inactive_particles,
+            active_particles,
+            spawn_node,
+            particle_type_bank,
+            image_assets,
+        );
     }
+
+    spawn_node_list.clear();
 }
 
 fn spawn_particle(
@@ -205,17 +233,22 @@ fn spawn_particle(
                     new_node.active = true;
                     new_node.sprite.set_loop(false);
 
-                    new_node.parsing_extra(spawn_node.extra.as_str());
-
-                    match new_node.extra.get("scale") {
-                        Some(v) => {
-                            let scale = v.parse::<f32>().unwrap_or(2.5);
-                            new_node.sprite.scale = Vec2::new(scale, scale);
-                        }
-                        None => {
-                            new_node.sprite.scale = Vec2::new(2.5, 2.5);
+                    match new_node
+                        .parsing_extra(spawn_node.extra.as_str(), particle_type.parameters())
+                    {
+                        Ok(_) => (),
+                        Err(e) => {
+                            println!(
+                                "Spawn particle type {} error: {}",
+                                spawn_node.particle_type_number, e
+                            );
+                            inactive_particles.push(new_node);
+                            return;
                         }
-                    }
+                    };
+
+                    let scale = new_node.extra.get_float("scale").unwrap_or(2.5);
+                    new_node.sprite.scale = Vec2::new(scale, scale);
 
                     particle_type.init(&mut new_node, image_assets);
                     active_particles.push(new_node);
@@ -226,10 +259,6 @@ fn spawn_particle(
     }
 }
 
-fn clear_particle_spawn_nodes() {
-    crate::PARTICLE_SPAWN_NODES.lock().unwrap().clear();
-}
-
 pub struct Particle {
     /// Object status
     pub active: bool,
@@ -257,7 +286,7 @@ pub struct Particle {
 
     pub face_right: bool,
 
-    pub extra: HashMap<String, String>,
+    pub extra: Parameters,
 }
 
 impl Particle {
@@ -272,7 +301,7 @@ impl Particle {
             frame: 0,
             life_time: 0,
             face_right: true,
-            extra: HashMap::new(),
+            extra: Parameters::new(),
         }
     }
 
@@ -287,9 +316,8 @@ impl Particle {
         self.sprite.reset();
     }
 
-    pub fn update(&mut self) {
-        let particle_types = crate::PARTICLE_TYPE_BANK.lock().unwrap();
-        match particle_types.get(self.particle_type_number) {
+    pub fn update(&mut self, particle_type_bank: &ParticleTypeBank) {
+        match particle_type_bank.get(self.particle_type_number) {
             Some(particle_type) => {
                 particle_type.update(self);
             }
@@ -297,9 +325,13 @@ impl Particle {
         };
     }
 
-    pub fn draw(&mut self, ctx: &mut Context, image_assets: &ImageAssets) {
-        let particle_types = crate::PARTICLE_TYPE_BANK.lock().unwrap();
-        match particle_types.get(self.particle_type_number) {
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        particle_type_bank: &ParticleTypeBank,
+        image_assets: &ImageAssets,
+    ) {
+        match particle_type_bank.get(self.particle_type_number) {
             Some(particle_type) => {
                 particle_type.draw(ctx, self, image_assets);
             }
@@ -307,31 +339,20 @@ impl Particle {
         };
     }
 
-    pub fn parsing_extra(&mut self, raw_extra: &str) {
-        if raw_extra.len() == 0 {
-            return;
-        }
-
-        let split: Vec<&str> = raw_extra.split('|').collect();
-
-        for text in split.iter() {
-            if text.len() == 0 {
-                continue;
-            }
-
-            let parameter: Vec<&str> = text.split('=').collect();
-            if parameter.len() == 2 {
-                self.extra
-                    .insert(String::from(parameter[0]), String::from(parameter[1]));
-            } else {
-                panic!("Incorrect parameter format: {} ({})", text, raw_extra);
-            }
-        }
+    /// Parse "raw_extra" into particle.extra. Accept only COMMON_PARAMETERS and keys in "specs".
+    pub fn parsing_extra(
+        &mut self,
+        raw_extra: &str,
+        specs: &[ParameterSpec],
+    ) -> Result<(), ParameterError> {
+        self.extra.load(raw_extra, &[&COMMON_PARAMETERS, specs])
     }
 }
 
 pub trait ParticleType {
     fn particle_type_id(&self) -> u128;
+    /// Keys that this type read from particle.extra. (Beside COMMON_PARAMETERS)
+    fn parameters(&self) -> &'static [ParameterSpec];
     fn init(&mut self, particle: &mut Particle, image_assets: &ImageAssets);
     fn update(&self, particle: &mut Particle);
     fn draw(&self, ctx: &mut Context, particle: &mut Particle, image_assets: &ImageAssets);
@@ -361,6 +382,10 @@ impl ParticleTypeBank {
         let particle_type =
             crate::gameplay::particle_types::explosion::ExplosionParticleType::new(image_assets);
         self.add(particle_type.particle_type_id(), Box::new(particle_type));
+
+        let particle_type =
+            crate::gameplay::particle_types::dissolve::DissolveParticleType::new(image_assets);
+        self.add(particle_type.particle_type_id(), Box::new(particle_type));
     }
 
     pub fn clear(&mut self) {
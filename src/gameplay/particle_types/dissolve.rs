@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use tetra::Context;
+
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
+use crate::gameplay::particle_manager::{Particle, ParticleType};
+use crate::image_assets::ImageAssets;
+use crate::sprite::AnimationMultiTextures;
+
+pub const DISSOLVE_PARTICLE_TYPE: u128 = 2;
+
+pub const PARAMETERS: [ParameterSpec; 5] = [
+    ParameterSpec::new("idle_animation", ParameterType::Text, None),
+    // Frame of "idle_animation" that dissolves.
+    ParameterSpec::new("frame", ParameterType::UInt, Some("0")),
+    ParameterSpec::new("flip_x", ParameterType::Bool, Some("0")),
+    // Time until every texel is removed. (ms)
+    ParameterSpec::new("time", ParameterType::UInt, Some("400")),
+    ParameterSpec::new("palette", ParameterType::Text, Some("")),
+];
+
+/// One frame of a sprite that dissolves away. Ex: dying enemy
+pub struct DissolveParticleType {
+    animations: HashMap<String, AnimationMultiTextures>,
+}
+
+impl DissolveParticleType {
+    pub fn new(_image_assets: &ImageAssets) -> DissolveParticleType {
+        DissolveParticleType {
+            animations: HashMap::new(),
+        }
+    }
+}
+
+impl ParticleType for DissolveParticleType {
+    fn particle_type_id(&self) -> u128 {
+        DISSOLVE_PARTICLE_TYPE
+    }
+
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
+    fn init(&mut self, particle: &mut Particle, image_assets: &ImageAssets) {
+        let animation_name = particle.extra.get_text("idle_animation").unwrap_or("");
+
+        if self.animations.contains_key(animation_name) == false {
+            match image_assets.get_animation_object(animation_name) {
+                Some(animation) => {
+                    self.animations
+                        .insert(String::from(animation_name), animation);
+                }
+                None => (),
+            };
+        }
+
+        match self.animations.get(animation_name) {
+            Some(animation) => {
+                particle.sprite.play(&animation);
+                particle
+                    .sprite
+                    .stop_at_frame(particle.extra.get_uint("frame").unwrap_or(0) as usize);
+                particle.life_time = particle.extra.get_uint("time").unwrap_or(400).max(1);
+            }
+            None => {
+                particle.active = false;
+            }
+        };
+
+        particle
+            .sprite
+            .flip_x(particle.extra.get_bool("flip_x").unwrap_or(false));
+        particle.sprite.effects.palette =
+            String::from(particle.extra.get_text("palette").unwrap_or(""));
+    }
+
+    fn update(&self, particle: &mut Particle) {
+        match particle.life_time.checked_sub(crate::ONE_FRAME.as_millis()) {
+            Some(v) => particle.life_time = v,
+            None => particle.life_time = 0,
+        };
+
+        if particle.life_time == 0 {
+            particle.active = false;
+        }
+
+        let time = particle.extra.get_uint("time").unwrap_or(400).max(1);
+        particle.sprite.effects.dissolve = 1.0 - particle.life_time as f32 / time as f32;
+    }
+
+    fn draw(&self, ctx: &mut Context, particle: &mut Particle, image_assets: &ImageAssets) {
+        particle
+            .sprite
+            .draw(ctx, particle.position, particle.rotation, image_assets);
+    }
+}
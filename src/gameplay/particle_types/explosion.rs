@@ -3,10 +3,16 @@ use std::collections::HashMap;
 use tetra::math::Vec2;
 use tetra::Context;
 
+use crate::gameplay::parameters::{ParameterSpec, ParameterType};
 use crate::gameplay::particle_manager::{Particle, ParticleSpawnNode, ParticleType};
 use crate::image_assets::ImageAssets;
 use crate::sprite::{AnimationMultiTextures, Sprite};
 
+pub const PARAMETERS: [ParameterSpec; 2] = [
+    ParameterSpec::new("idle_animation", ParameterType::Text, None),
+    ParameterSpec::new("flip_x", ParameterType::Bool, Some("0")),
+];
+
 pub struct ExplosionParticleType {
     animations: HashMap<String, AnimationMultiTextures>,
 }
@@ -36,11 +42,12 @@ impl ParticleType for ExplosionParticleType {
         1
     }
 
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &PARAMETERS
+    }
+
     fn init(&mut self, particle: &mut Particle, image_assets: &ImageAssets) {
-        let animation_name = match particle.extra.get("idle_animation") {
-            Some(name) => name.as_str(),
-            None => "",
-        };
+        let animation_name = particle.extra.get_text("idle_animation").unwrap_or("");
 
         self.prepare_explosion_animation(animation_name, image_assets);
         match self.animations.get(animation_name) {
@@ -53,18 +60,9 @@ impl ParticleType for ExplosionParticleType {
             }
         }
 
-        match particle.extra.get("flip_x") {
-            Some(v) => {
-                let value = v.parse::<u8>().unwrap_or(0);
-
-                if value == 1 {
-                    particle.sprite.flip_x(true);
-                } else {
-                    particle.sprite.flip_x(false);
-                }
-            }
-            None => (),
-        };
+        particle
+            .sprite
+            .flip_x(particle.extra.get_bool("flip_x").unwrap_or(false));
     }
 
     fn update(&self, particle: &mut Particle) {
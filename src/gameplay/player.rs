@@ -1,25 +1,50 @@
-use tetra::graphics::{self, Color, GeometryBuilder, Mesh, Rectangle, ShapeStyle};
+use tetra::graphics::{self, Color, Rectangle};
 use tetra::math::Vec2;
 use tetra::Context;
 
+use crate::animation_state::{AnimationGraph, AnimationStateMachine, TransitionCondition};
 use crate::gameplay::bullet_pool::{BulletOwner, BulletSpawnNode};
-use crate::gameplay::input::{Input, Keyboard};
+use crate::gameplay::input::{Input, InputState, Keyboard};
 use crate::image_assets::ImageAssets;
 use crate::sprite::Sprite;
 use crate::gameplay::enemy_manager::Enemy;
+use crate::gameplay::random::GameRng;
+use crate::gameplay::world::World;
 
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum WeaponType {
     Melee,
     Range,
 }
 
-pub enum PlayerState {
-    Jump,
-    Stand,
-    Run,
+lazy_static! {
+    /// Conditions: "dead", "air" (not on the ground), "running"
+    static ref PLAYER_ANIMATION_GRAPH: AnimationGraph = AnimationGraph::new("stand")
+        .state("stand", "player-stand", None)
+        .state("run", "player-run", None)
+        .state("jump", "player-jump", None)
+        .state("die", "player-die", Some(false))
+        .transition(&[], "die", vec![TransitionCondition::When("dead")])
+        .transition(&["stand", "run"], "jump", vec![TransitionCondition::When("air")])
+        .transition(
+            &["stand", "jump"],
+            "run",
+            vec![
+                TransitionCondition::Unless("air"),
+                TransitionCondition::When("running"),
+            ],
+        )
+        .transition(
+            &["run", "jump"],
+            "stand",
+            vec![
+                TransitionCondition::Unless("air"),
+                TransitionCondition::Unless("running"),
+            ],
+        );
 }
 
-const HIT_POINT_RADIUS: f32 = 4.0;
+pub const HIT_POINT_RADIUS: f32 = 4.0;
 const MELEE_ATTACK_RADIUS: f32 = 40.0;
 
 pub struct Player {
@@ -28,11 +53,10 @@ pub struct Player {
     max_health: u32,
     hit_frame: u128,
     is_dead: bool,
-    animation_state: PlayerState,
+    /// State of "skin" and "slash" overlay
+    animation: AnimationStateMachine,
     /// Skin
     pub skin: Sprite,
-    slash: Sprite,
-    hit_point: Mesh,
 
     /// Input
     controller: Box<dyn Input>,
@@ -58,14 +82,7 @@ pub struct Player {
 }
 
 impl Player {
-    pub fn new(ctx: &mut Context, player_number: i32) -> Player {
-        let hit_point = GeometryBuilder::new()
-            .set_color(Color::RED)
-            .circle(ShapeStyle::Fill, Vec2::zero(), HIT_POINT_RADIUS)
-            .unwrap()
-            .build_mesh(ctx)
-            .unwrap();
-
+    pub fn new(player_number: i32) -> Player {
         let keyboard = Keyboard::new_with_preset_keys();
 
         Player {
@@ -74,11 +91,9 @@ impl Player {
             max_health: 20,
             is_dead: false,
             hit_frame: 0,
-            animation_state: PlayerState::Stand,
+            animation: AnimationStateMachine::new(),
 
             skin: Sprite::new(),
-            slash: Sprite::new(),
-            hit_point: hit_point,
             controller: Box::new(keyboard),
             position: Vec2::zero(),
             direction: 1,
@@ -99,38 +114,43 @@ impl Player {
     }
 
     pub fn setup(&mut self, image_assets: &ImageAssets) {
-        match image_assets.get_animation_object("player-stand") {
-            Some(animation) => {
-                self.skin.play(&animation);
-            }
-            None => (),
-        };
+        self.animation
+            .start(&PLAYER_ANIMATION_GRAPH, &mut self.skin, image_assets);
     }
 
-    pub fn update(&mut self, ctx: &mut Context, image_assets: &ImageAssets) {
+    /// Replace input device of the player. Ex: RecordingInput, ReplayInput
+    pub fn set_controller(&mut self, controller: Box<dyn Input>) {
+        self.controller = controller;
+    }
+
+    /// Read input device. Call it before update().
+    pub fn update_input(&mut self, ctx: &mut Context) {
         self.controller.update(ctx);
-        self.skin.update();
-        self.slash.update();
-        update_movement(self, image_assets);
+    }
+
+    /// Stop the input device at the end of the run. Ex: close the replay file of RecordingInput
+    pub fn finish_input(&mut self) {
+        self.controller.finish();
+    }
+
+    /// Use "state" as input of this frame. Call it before update().
+    pub fn update_input_with_state(&mut self, state: &InputState) {
+        self.controller.update_with_state(state);
+    }
+
+    pub fn update(&mut self, world: &mut World, image_assets: &ImageAssets) {
+        self.animation.update_sprites(&mut self.skin);
+        update_movement(self, world, image_assets);
 
         Player::decrease_values_over_time(self);
 
         if self.health == 0 && self.is_dead == false {
-            self.die();
-
-            if self.skin.get_current_animation_name() != "player-die"
-            {
-                match image_assets.get_animation_object("player-die")
-                {
-                    Some(animation) => {
-                        self.skin.set_loop(false);
-                        self.skin.play(&animation);
-                    },
-                    None => {}
-                };
-            }
-            
+            self.die(world);
         }
+
+        self.animation.set_condition("dead", self.is_dead);
+        self.animation
+            .update_state(&PLAYER_ANIMATION_GRAPH, &mut self.skin, image_assets);
     }
 
     pub fn draw(&mut self, ctx: &mut Context, image_assets: &ImageAssets) {
@@ -144,33 +164,66 @@ impl Player {
             .draw(ctx, self.get_hit_point_position(), 0.0, image_assets);
 
         if self.melee_attack_time > 0 {
-            self.slash.flip_x(self.direction == -1);
-            self.slash
-                .draw(ctx, self.get_melee_attack_position(), 0.0, image_assets);
+            self.animation.draw_overlay(
+                ctx,
+                "slash",
+                self.get_melee_attack_position(),
+                self.direction == -1,
+                image_assets,
+            );
         }
 
         if self.is_dead == false {
-            graphics::draw(ctx, &self.hit_point, self.get_hit_point_position());
+            match image_assets.get_mesh("player-hit-point") {
+                Some(hit_point) => {
+                    graphics::draw(ctx, hit_point, self.get_hit_point_position());
+                }
+                None => (),
+            };
         }
     }
 
-    pub fn get_hit(&mut self, damage: u32) {
+    /// Return health that player lost. 0: player is dead or invincible at the moment.
+    pub fn get_hit(&mut self, damage: u32, world: &mut World, rng: &mut GameRng) -> u32 {
+        if self.is_dead {
+            return 0;
+        }
+
         if self.hit_frame == 0 && self.melee_attack_time <= 10 && self.dash_speed == 0 {
+            let previous_health = self.health;
             match self.health.checked_sub(damage) {
                 Some(v) => self.health = v,
                 None => self.health = 0,
             }
             self.hit_frame = 90;
+            self.animation
+                .tint(self.hit_frame, Color::rgb(1.0, 0.35, 0.35));
             // println!("Hit: {}", self.health);
 
-            Enemy::spawn_random_splash_particle(self.get_hit_point_position(), 1.5);
-            Enemy::spawn_random_splash_particle(self.get_hit_point_position(), 1.5);
+            Enemy::spawn_random_splash_particle(self.get_hit_point_position(), 1.5, world, rng);
+            Enemy::spawn_random_splash_particle(self.get_hit_point_position(), 1.5, world, rng);
 
-            {
-                let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-                play_sound_nodes.insert(String::from("player_hit"), (String::from("./resources/sfx/player_hit.mp3"), 0.8 ) );
-            }
+            world.play_sound("player_hit", 0.8);
+            world.add_trauma(0.35);
+            world.hit_stop(70);
+
+            return previous_health - self.health;
         }
+
+        0
+    }
+
+    pub fn player_number(&self) -> i32 {
+        self.player_number
+    }
+
+    pub fn set_position(&mut self, position: Vec2<f32>) {
+        self.position = position;
+        self.crosshair_position = position;
+    }
+
+    pub fn get_position(&self) -> Vec2<f32> {
+        self.position
     }
 
     pub fn get_hit_point_position(&self) -> Vec2<f32> {
@@ -181,16 +234,13 @@ impl Player {
         HIT_POINT_RADIUS
     }
 
-    fn die(&mut self) {
+    fn die(&mut self, world: &mut World) {
         println!("Player {} die", self.player_number);
         self.is_dead = true;
         self.jump_speed = 30;
         self.fall_time = 0;
 
-        {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.insert(String::from("player_die"), (String::from("./resources/sfx/player_die.mp3"), 0.8 ) );
-        }
+        world.play_sound("player_die", 0.8);
     }
 
     pub fn alive(&self) -> bool {
@@ -282,50 +332,69 @@ impl Player {
         2
     }
 
-    pub fn melee_attack_hit_enemy(&mut self) {
+    pub fn melee_attack_hit_enemy(&mut self, world: &mut World) {
         self.falling_slow_time = 300;
 
         if self.melee_attack_cooldown >= 320
         {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.insert(String::from("melee_hit_target"), (String::from("./resources/sfx/melee_hit_target.mp3"), 0.8 ) );
+            world.play_sound("melee_hit_target", 0.8);
         }
     }
 }
 
-fn apply_gravity(position: &mut Vec2<f32>, falling_slow_time: u128) {
+/// Living player that closest to "position". None: every player is dead.
+pub fn nearest_living_player(players: &[Player], position: Vec2<f32>) -> Option<&Player> {
+    let mut nearest: Option<&Player> = None;
+    let mut nearest_distance = f32::MAX;
+    for player in players.iter() {
+        if player.alive() == false {
+            continue;
+        }
+
+        let distance = Vec2::distance_squared(player.get_hit_point_position(), position);
+        if distance < nearest_distance {
+            nearest = Some(player);
+            nearest_distance = distance;
+        }
+    }
+
+    nearest
+}
+
+fn apply_gravity(position: &mut Vec2<f32>, falling_slow_time: u128, ground: f32) {
     if falling_slow_time == 0 {
         position.y += crate::GRAVITY;
     } else {
         position.y += crate::GRAVITY * 0.2;
     }
 
-    position.y = position.y.min(crate::GROUND);
+    position.y = position.y.min(ground);
 }
 
-fn on_the_ground(position: Vec2<f32>) -> bool {
-    position.y >= crate::GROUND - 10.0
+/// ground: World::ground()
+fn on_the_ground(position: Vec2<f32>, ground: f32) -> bool {
+    position.y >= ground - 10.0
 }
 
-fn update_movement(player: &mut Player, image_assets: &ImageAssets) {
+fn update_movement(player: &mut Player, world: &mut World, image_assets: &ImageAssets) {
     if player.is_dead == false {
         match player.weapon_type {
             WeaponType::Melee => {
-                melee_movement(player, image_assets);
+                melee_movement(player, world, image_assets);
             }
             WeaponType::Range => {
-                range_movement(player);
+                range_movement(player, world);
             }
         };
     }
 
-    if on_the_ground(player.position) == false {
-        player.animation_state = PlayerState::Jump;
-    }
+    player
+        .animation
+        .set_condition("air", on_the_ground(player.position, world.ground()) == false);
 
     if player.jump_speed == 0 {
         if player.fall_time > 50 {
-            apply_gravity(&mut player.position, player.falling_slow_time);
+            apply_gravity(&mut player.position, player.falling_slow_time, world.ground());
         }
 
         player.fall_time += crate::ONE_FRAME.as_millis();
@@ -340,37 +409,26 @@ fn update_movement(player: &mut Player, image_assets: &ImageAssets) {
 
     
 
-    if player.is_dead == false
-    {
-        let animation_name = match player.animation_state {
-            PlayerState::Jump => "player-jump",
-            PlayerState::Stand => "player-stand",
-            PlayerState::Run => "player-run",
-        };
-
-        match image_assets.get_animation_object(animation_name) {
-            Some(animation) => {
-                if player.skin.get_current_animation_name() != animation_name {
-                    player.skin.play(&animation);
-                }
-            }
-            None => (),
-        };
-    }
-
-    
-
-    crate::gameplay::utils::clamp_position_inside_camera_area(&mut player.position);
-    crate::gameplay::utils::clamp_position_inside_camera_area(&mut player.crosshair_position);
+    let camera_area = world.camera_area();
+    crate::gameplay::utils::clamp_position_inside_camera_area(
+        &camera_area,
+        &mut player.position,
+        8.0,
+    );
+    crate::gameplay::utils::clamp_position_inside_camera_area(
+        &camera_area,
+        &mut player.crosshair_position,
+        8.0,
+    );
 }
 
-fn melee_movement(player: &mut Player, image_assets: &ImageAssets) {
+fn melee_movement(player: &mut Player, world: &mut World, image_assets: &ImageAssets) {
     let speed = match player.falling_slow_time == 0 {
         true => 6.0,
         false => 2.0,
     };
 
-    player.animation_state = PlayerState::Stand;
+    player.animation.set_condition("running", false);
     if player.dash_speed == 0
     {
         if player.controller.right() {
@@ -379,14 +437,14 @@ fn melee_movement(player: &mut Player, image_assets: &ImageAssets) {
                 player.direction = 1;
             }
     
-            player.animation_state = PlayerState::Run;
+            player.animation.set_condition("running", true);
         } else if player.controller.left() {
             player.position.x -= speed;
             if player.is_attacking() == false {
                 player.direction = -1;
             }
     
-            player.animation_state = PlayerState::Run;
+            player.animation.set_condition("running", true);
         }
     }
     else
@@ -394,11 +452,11 @@ fn melee_movement(player: &mut Player, image_assets: &ImageAssets) {
         player.dash_speed -= 1;
         player.dash_speed = player.dash_speed.max(0);
         player.position.x += (player.dash_speed * player.direction) as f32;
-        player.animation_state = PlayerState::Run;
+        player.animation.set_condition("running", true);
     }
     
 
-    if player.controller.up() && on_the_ground(player.position) {
+    if player.controller.up() && on_the_ground(player.position, world.ground()) {
         player.jump_speed = 200;
         player.fall_time = 0;
     }
@@ -416,20 +474,13 @@ fn melee_movement(player: &mut Player, image_assets: &ImageAssets) {
 
         if player.is_dead == false
         {
-            match image_assets.get_animation_object("player-slash") {
-                Some(animation) => {
-                    player.slash.play(&animation);
-                    player.slash.set_loop(false);
-                }
-                None => (),
-            };
+            player
+                .animation
+                .play_overlay("slash", "player-slash", image_assets);
         }
         
 
-        {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.insert(String::from("melee_hit"), (String::from("./resources/sfx/melee_hit.mp3"), 0.6 ) );
-        }
+        world.play_sound("melee_hit", 0.6);
     }
 
     if player.controller.attack_holding() > 150 && player.dash_speed == 0{
@@ -441,9 +492,9 @@ fn melee_movement(player: &mut Player, image_assets: &ImageAssets) {
     }
 }
 
-fn range_movement(player: &mut Player) {
+fn range_movement(player: &mut Player, world: &mut World) {
     let speed = 8.0;
-    player.animation_state = PlayerState::Stand;
+    player.animation.set_condition("running", false);
     if player.crosshair_position.x < player.position.x {
         player.direction = -1;
     } else {
@@ -478,6 +529,7 @@ fn range_movement(player: &mut Player) {
     if player.range_attack_time > 0 && player.range_attack_cooldown == 0 {
         player.range_attack_cooldown = 60;
         spawn_bullet(
+            world,
             player.player_number,
             player.get_hit_point_position() + Vec2::new(0.0, -8.0),
             player.crosshair_position,
@@ -485,11 +537,10 @@ fn range_movement(player: &mut Player) {
     }
 }
 
-fn spawn_bullet(player_number: i32, from: Vec2<f32>, target: Vec2<f32>) {
+fn spawn_bullet(world: &mut World, player_number: i32, from: Vec2<f32>, target: Vec2<f32>) {
     let rotation = (target.y - from.y).atan2(target.x - from.x).to_degrees() / 360.0;
 
-    let mut bullet_spawn_nodes = crate::BULLET_SPAWN_NODES.lock().unwrap();
-    bullet_spawn_nodes.push(BulletSpawnNode {
+    world.spawn_bullet(BulletSpawnNode {
         bullet_type: 1,
         position: from,
         owner_type: BulletOwner::PLAYER(player_number),
@@ -0,0 +1,60 @@
+use rand::distributions::{Distribution, Standard};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// Seeded random number generator of a gameplay session.
+/// Every gameplay randomness has to come from it. Same seed and same input give the same game.
+/// ChaCha20Rng is named here, not StdRng. StdRng may change its algorithm in a rand release
+/// and replays keep only the seed.
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha20Rng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        GameRng {
+            seed: seed,
+            rng: ChaCha20Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// New seed from system entropy. Use it when no seed is given.
+    pub fn random_seed() -> u64 {
+        rand::random::<u64>()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Same as rand::random() but use this generator.
+    pub fn random<T>(&mut self) -> T
+    where
+        Standard: Distribution<T>,
+    {
+        self.rng.gen()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameRng;
+
+    /// Replays keep only the seed. They break when these numbers change.
+    #[test]
+    fn same_seed_gives_the_same_numbers() {
+        let mut rng = GameRng::new(42);
+        let numbers: Vec<u64> = (0..4).map(|_| rng.random::<u64>()).collect();
+
+        assert_eq!(
+            numbers,
+            vec![
+                9482535800248027256,
+                7566832397956113305,
+                1804347359131428821,
+                3088291667719571736
+            ]
+        );
+    }
+}
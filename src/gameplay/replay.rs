@@ -0,0 +1,522 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use tetra::Context;
+
+use crate::gameplay::input::{Input, InputState, Keyboard};
+
+/// First line of every replay file.
+const REPLAY_MAGIC: &str = "one-man-replay";
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Error from reading or writing replay file.
+/// line starts from 1. 0: error is not from a line in the file.
+#[derive(Debug)]
+pub struct ReplayError {
+    pub path: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path, self.line, self.message)
+    }
+}
+
+/// Everything that need for running the same game again beside the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayHeader {
+    pub version: u32,
+    pub seed: u64,
+    /// Level name. Ex: "level-1"
+    pub level_id: String,
+}
+
+impl ReplayHeader {
+    pub fn new(seed: u64, level_id: &str) -> ReplayHeader {
+        ReplayHeader {
+            version: REPLAY_VERSION,
+            seed: seed,
+            level_id: String::from(level_id),
+        }
+    }
+}
+
+/// Replay file content.
+///
+/// Format:
+/// ```text
+/// one-man-replay 1
+/// seed=1234
+/// level=level-1
+///
+/// 000100
+/// 000110
+/// ```
+/// After the empty line. One line per frame: up, down, left, right, attack, switch. (1: down)
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub frames: Vec<InputState>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, ReplayError> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => Replay::parse(path, source.as_str()),
+            Err(e) => Err(ReplayError {
+                path: String::from(path),
+                line: 0,
+                message: format!("Can not read replay file. {}", e),
+            }),
+        }
+    }
+
+    /// Parse replay from text. "path" only use for error message.
+    pub fn parse(path: &str, source: &str) -> Result<Replay, ReplayError> {
+        let error = |line: usize, message: String| ReplayError {
+            path: String::from(path),
+            line: line,
+            message: message,
+        };
+
+        let mut lines = source.lines().enumerate();
+
+        let version = match lines.next() {
+            Some((_, text)) => {
+                let values: Vec<&str> = text.split(' ').collect();
+                if values.len() != 2 || values[0] != REPLAY_MAGIC {
+                    return Err(error(1, String::from("Not a replay file")));
+                }
+
+                match values[1].parse::<u32>() {
+                    Ok(v) => v,
+                    Err(_) => return Err(error(1, format!("Invalid version \"{}\"", values[1]))),
+                }
+            }
+            None => return Err(error(1, String::from("Empty replay file"))),
+        };
+
+        if version != REPLAY_VERSION {
+            return Err(error(1, format!("Unsupported replay version {}", version)));
+        }
+
+        let mut seed = None;
+        let mut level_id = None;
+        for (index, text) in &mut lines {
+            if text.len() == 0 {
+                break;
+            }
+
+            let values: Vec<&str> = text.splitn(2, '=').collect();
+            if values.len() != 2 {
+                return Err(error(index + 1, String::from("Incorrect header format")));
+            }
+
+            match values[0] {
+                "seed" => match values[1].parse::<u64>() {
+                    Ok(v) => seed = Some(v),
+                    Err(_) => {
+                        return Err(error(index + 1, format!("Invalid seed \"{}\"", values[1])));
+                    }
+                },
+                "level" => level_id = Some(String::from(values[1])),
+                _ => return Err(error(index + 1, format!("Unknown header \"{}\"", values[0]))),
+            };
+        }
+
+        let seed = match seed {
+            Some(v) => v,
+            None => return Err(error(0, String::from("No seed in header"))),
+        };
+
+        let level_id = match level_id {
+            Some(v) => v,
+            None => return Err(error(0, String::from("No level in header"))),
+        };
+
+        let mut frames = vec![];
+        for (index, text) in lines {
+            match parse_frame(text) {
+                Some(state) => frames.push(state),
+                None => return Err(error(index + 1, format!("Invalid frame \"{}\"", text))),
+            };
+        }
+
+        Ok(Replay {
+            header: ReplayHeader {
+                version: version,
+                seed: seed,
+                level_id: level_id,
+            },
+            frames: frames,
+        })
+    }
+}
+
+fn write_header(writer: &mut impl Write, header: &ReplayHeader) -> std::io::Result<()> {
+    writeln!(writer, "{} {}", REPLAY_MAGIC, header.version)?;
+    writeln!(writer, "seed={}", header.seed)?;
+    writeln!(writer, "level={}", header.level_id)?;
+    writeln!(writer)
+}
+
+fn format_frame(state: &InputState) -> String {
+    [
+        state.up,
+        state.down,
+        state.left,
+        state.right,
+        state.attack,
+        state.switch,
+    ]
+    .iter()
+    .map(|is_down| if *is_down { '1' } else { '0' })
+    .collect()
+}
+
+fn parse_frame(text: &str) -> Option<InputState> {
+    let mut values = vec![];
+    for c in text.chars() {
+        match c {
+            '0' => values.push(false),
+            '1' => values.push(true),
+            _ => return None,
+        };
+    }
+
+    if values.len() != 6 {
+        return None;
+    }
+
+    Some(InputState {
+        up: values[0],
+        down: values[1],
+        left: values[2],
+        right: values[3],
+        attack: values[4],
+        switch: values[5],
+    })
+}
+
+/// Replay file of the nth run when the game restarts with recording. The first run uses "path" as it is.
+/// Ex: "replays/run.txt", 3 -> "replays/run-3.txt"
+pub fn run_path(path: &str, run: usize) -> String {
+    if run <= 1 {
+        return String::from(path);
+    }
+
+    let path = std::path::Path::new(path);
+    let stem = match path.file_stem() {
+        Some(stem) => stem.to_string_lossy().into_owned(),
+        None => String::from("replay"),
+    };
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, run, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, run),
+    };
+
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Pass input through and write button states of every frame into replay file.
+pub struct RecordingInput {
+    input: Box<dyn Input>,
+    path: String,
+    writer: Option<BufWriter<File>>,
+}
+
+impl RecordingInput {
+    /// Create replay file and write header. The file will be replaced if it exists.
+    pub fn new(
+        input: Box<dyn Input>,
+        path: &str,
+        header: &ReplayHeader,
+    ) -> Result<RecordingInput, ReplayError> {
+        let io_error = |e: std::io::Error| ReplayError {
+            path: String::from(path),
+            line: 0,
+            message: format!("Can not write replay file. {}", e),
+        };
+
+        match std::path::Path::new(path).parent() {
+            Some(directory) => std::fs::create_dir_all(directory).map_err(io_error)?,
+            None => (),
+        };
+
+        let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+        write_header(&mut writer, header).map_err(io_error)?;
+
+        Ok(RecordingInput {
+            input: input,
+            path: String::from(path),
+            writer: Some(writer),
+        })
+    }
+
+    /// Flush and close the replay file. Frames after this are not recorded.
+    fn close(&mut self) {
+        let result = match self.writer.take() {
+            Some(mut writer) => writer.flush(),
+            None => return,
+        };
+
+        match result {
+            Ok(_) => println!("Replay saved: {}", self.path),
+            Err(e) => println!("Recording error: {} {}", self.path, e),
+        };
+    }
+
+    fn record(&mut self) {
+        let line = format_frame(&self.input.state());
+        let result = match self.writer.as_mut() {
+            Some(writer) => writeln!(writer, "{}", line),
+            None => return,
+        };
+
+        match result {
+            Ok(_) => (),
+            Err(e) => {
+                // Stop recording. The file would not match the game anymore.
+                println!("Recording error: {} {}", self.path, e);
+                self.writer = None;
+            }
+        };
+    }
+}
+
+impl Input for RecordingInput {
+    fn up(&self) -> bool {
+        self.input.up()
+    }
+
+    fn down(&self) -> bool {
+        self.input.down()
+    }
+
+    fn left(&self) -> bool {
+        self.input.left()
+    }
+
+    fn right(&self) -> bool {
+        self.input.right()
+    }
+
+    fn attack(&self) -> bool {
+        self.input.attack()
+    }
+
+    fn attack_holding(&self) -> u128 {
+        self.input.attack_holding()
+    }
+
+    fn switch(&self) -> bool {
+        self.input.switch()
+    }
+
+    fn update(&mut self, ctx: &mut Context) {
+        self.input.update(ctx);
+        self.record();
+    }
+
+    fn update_with_state(&mut self, state: &InputState) {
+        self.input.update_with_state(state);
+        self.record();
+    }
+
+    fn state(&self) -> InputState {
+        self.input.state()
+    }
+
+    fn finish(&mut self) {
+        self.close();
+    }
+}
+
+/// Feed button states from replay file. Input device and given states are ignored.
+/// Buttons are released after the last frame.
+pub struct ReplayInput {
+    buttons: Keyboard,
+    frames: VecDeque<InputState>,
+}
+
+impl ReplayInput {
+    pub fn new(replay: Replay) -> ReplayInput {
+        ReplayInput {
+            buttons: Keyboard::new_with_preset_keys(),
+            frames: replay.frames.into_iter().collect(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames.len() == 0
+    }
+
+    fn next_frame(&mut self) {
+        let state = self.frames.pop_front().unwrap_or_default();
+        self.buttons.update_with_state(&state);
+    }
+}
+
+impl Input for ReplayInput {
+    fn up(&self) -> bool {
+        self.buttons.up()
+    }
+
+    fn down(&self) -> bool {
+        self.buttons.down()
+    }
+
+    fn left(&self) -> bool {
+        self.buttons.left()
+    }
+
+    fn right(&self) -> bool {
+        self.buttons.right()
+    }
+
+    fn attack(&self) -> bool {
+        self.buttons.attack()
+    }
+
+    fn attack_holding(&self) -> u128 {
+        self.buttons.attack_holding()
+    }
+
+    fn switch(&self) -> bool {
+        self.buttons.switch()
+    }
+
+    fn update(&mut self, _: &mut Context) {
+        self.next_frame();
+    }
+
+    fn update_with_state(&mut self, _: &InputState) {
+        self.next_frame();
+    }
+
+    fn state(&self) -> InputState {
+        self.buttons.state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_frame, write_header, RecordingInput, Replay, ReplayHeader, ReplayInput};
+    use crate::gameplay::input::{InputState, Keyboard};
+    use crate::gameplay::simulation::{Observation, Simulation};
+    use crate::image_assets::ImageAssets;
+
+    fn scripted_input(frame: usize) -> InputState {
+        InputState {
+            left: frame % 200 < 50,
+            right: frame % 200 >= 100 && frame % 200 < 150,
+            up: frame % 70 < 8,
+            attack: frame % 30 < 20,
+            switch: frame % 250 == 0,
+            ..InputState::default()
+        }
+    }
+
+    fn setup_simulation(header: &ReplayHeader) -> (Simulation, ImageAssets) {
+        let mut image_assets = ImageAssets::new(vec![]);
+        crate::scenes::gameplay::setup_textures(&mut image_assets);
+        image_assets.loading_without_textures();
+        crate::scenes::gameplay::setup_animations(&mut image_assets);
+
+        let level = crate::scenes::gameplay::create_level(header.level_id.as_str(), &image_assets);
+        let mut simulation = Simulation::new(level, header.seed, 1);
+        simulation.setup(&mut image_assets);
+
+        (simulation, image_assets)
+    }
+
+    /// Observation after every frame. Input of the player come from its controller.
+    fn run_frames(
+        simulation: &mut Simulation,
+        image_assets: &ImageAssets,
+        frames: usize,
+    ) -> Vec<Observation> {
+        let mut observations = vec![];
+        for frame in 0..frames {
+            simulation.step_with_inputs(&[scripted_input(frame)], image_assets);
+            observations.push(simulation.observe());
+        }
+
+        observations
+    }
+
+    #[test]
+    fn parse_reads_what_recording_writes() {
+        let header = ReplayHeader::new(1234, "level-1");
+        let frames: Vec<InputState> = (0..20).map(scripted_input).collect();
+
+        let mut source = vec![];
+        write_header(&mut source, &header).unwrap();
+        for state in frames.iter() {
+            source.extend_from_slice(format!("{}\n", format_frame(state)).as_bytes());
+        }
+        let replay = Replay::parse("test.replay", &String::from_utf8(source).unwrap()).unwrap();
+
+        assert_eq!(replay.header, header);
+        assert_eq!(replay.frames, frames);
+    }
+
+    #[test]
+    fn malformed_line_is_an_error_on_its_line() {
+        let header_error = Replay::parse(
+            "test.replay",
+            "one-man-replay 1\nseed:1234\nlevel=level-1\n\n",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(header_error.line, 2);
+        assert_eq!(header_error.message, "Incorrect header format");
+
+        let frame_error = Replay::parse(
+            "test.replay",
+            "one-man-replay 1\nseed=1234\nlevel=level-1\n\n000100\n00010\n",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(frame_error.line, 6);
+        assert_eq!(frame_error.message, "Invalid frame \"00010\"");
+    }
+
+    #[test]
+    fn recorded_run_replays_the_same_game() {
+        let frames = 900;
+        let path = std::env::temp_dir().join(format!("one-man-replay-{}.txt", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let header = ReplayHeader::new(42, crate::scenes::gameplay::DEFAULT_LEVEL_ID);
+
+        let (mut simulation, image_assets) = setup_simulation(&header);
+        let recording = RecordingInput::new(
+            Box::new(Keyboard::new_with_preset_keys()),
+            path.as_str(),
+            &header,
+        )
+        .unwrap();
+        simulation.players[0].set_controller(Box::new(recording));
+        let recorded = run_frames(&mut simulation, &image_assets, frames);
+        simulation.players[0].finish_input();
+
+        let replay = Replay::load(path.as_str()).unwrap();
+        std::fs::remove_file(path.as_str()).unwrap();
+        assert_eq!(replay.header, header);
+        assert_eq!(replay.frames.len(), frames);
+
+        let (mut simulation, image_assets) = setup_simulation(&replay.header);
+        simulation.players[0].set_controller(Box::new(ReplayInput::new(replay)));
+        // ReplayInput ignores the scripted input.
+        let replayed = run_frames(&mut simulation, &image_assets, frames);
+
+        for (frame, (expected, actual)) in recorded.iter().zip(replayed.iter()).enumerate() {
+            assert_eq!(expected, actual, "frame {}", frame + 1);
+        }
+    }
+}
@@ -0,0 +1,576 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::PathBuf;
+
+/// First word of every save file.
+const SAVE_MAGIC: &str = "one-man-save";
+pub const SAVE_VERSION: u32 = 2;
+/// Number of entries in the high score table.
+pub const MAX_HIGH_SCORES: usize = 10;
+/// Maximum characters of a name in the high score table.
+pub const MAX_NAME_LENGTH: usize = 8;
+
+/// Error from reading or writing save file.
+/// line starts from 1. 0: error is not from a line in the file.
+#[derive(Debug)]
+pub struct SaveError {
+    pub path: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path, self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u64,
+    /// Level name. Ex: "level-1"
+    pub level_id: String,
+    /// Clear time. (ms)
+    pub time: u128,
+}
+
+/// Volumes are 0.0 - 1.0. Final volume = master * music (or sfx) * volume of the sound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+        }
+    }
+}
+
+/// Everything that keep between runs.
+///
+/// Format:
+/// ```text
+/// one-man-save 2
+/// [settings]
+/// master_volume=1
+/// [high_scores]
+/// OAB|12345|level-1|180000
+/// [best_times]
+/// level-1=180000
+/// [unlocked]
+/// level-1
+/// checksum=0123abcd
+/// ```
+/// checksum is FNV-1a of every line before it.
+///
+/// Version 1 had no checksum and no level and time in high score lines. (name|score)
+/// Its high scores are migrated to DEFAULT_LEVEL_ID without time.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SaveData {
+    /// Sorted by score. Highest first.
+    pub high_scores: Vec<HighScore>,
+    /// Level name -> best clear time (ms)
+    pub best_clear_times: BTreeMap<String, u128>,
+    pub settings: Settings,
+    pub unlocked: BTreeSet<String>,
+}
+
+impl SaveData {
+    /// Save file in the user data directory.
+    /// Linux: $XDG_DATA_HOME/one-man/save.txt, OSX: ~/Library/Application Support/one-man/save.txt,
+    /// Windows: %APPDATA%\one-man\save.txt. "./save.txt" when the directory is unknown.
+    pub fn default_path() -> String {
+        let data_dir = if cfg!(target_os = "windows") {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+        } else {
+            match std::env::var_os("XDG_DATA_HOME") {
+                Some(v) => Some(PathBuf::from(v)),
+                None => std::env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".local").join("share")),
+            }
+        };
+
+        match data_dir {
+            Some(dir) => dir
+                .join("one-man")
+                .join("save.txt")
+                .to_string_lossy()
+                .into_owned(),
+            None => String::from("./save.txt"),
+        }
+    }
+
+    /// Load save file. Use the backup file when the save file is broken.
+    /// Keep every line that still can be read when both are broken.
+    /// Empty save data when there is no save file.
+    pub fn load_or_default(path: &str) -> SaveData {
+        if std::path::Path::new(path).exists() == false {
+            return SaveData::default();
+        }
+
+        let error = match SaveData::load(path) {
+            Ok(save_data) => return save_data,
+            Err(e) => e,
+        };
+        println!("Load save error: {}", error);
+
+        let backup_path = backup_path(path);
+        match SaveData::load(backup_path.as_str()) {
+            Ok(save_data) => {
+                println!("Use backup save file: {}", backup_path);
+                return save_data;
+            }
+            Err(e) => println!("Load save error: {}", e),
+        };
+
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                println!("Use readable data in save file");
+                SaveData::parse_lenient(path, source.as_str())
+            }
+            Err(_) => SaveData::default(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<SaveData, SaveError> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => SaveData::parse(path, source.as_str()),
+            Err(e) => Err(SaveError {
+                path: String::from(path),
+                line: 0,
+                message: format!("Can not read save file. {}", e),
+            }),
+        }
+    }
+
+    /// Write the file next to "path" then replace it. The previous save file become the backup file.
+    pub fn save(&self, path: &str) -> Result<(), SaveError> {
+        let error = |message: String| SaveError {
+            path: String::from(path),
+            line: 0,
+            message: message,
+        };
+
+        match std::path::Path::new(path).parent() {
+            Some(dir) => match std::fs::create_dir_all(dir) {
+                Ok(_) => (),
+                Err(e) => return Err(error(format!("Can not create save directory. {}", e))),
+            },
+            None => (),
+        };
+
+        let temp_path = format!("{}.tmp", path);
+        match std::fs::write(temp_path.as_str(), self.to_text()) {
+            Ok(_) => (),
+            Err(e) => return Err(error(format!("Can not write save file. {}", e))),
+        };
+
+        if std::path::Path::new(path).exists() {
+            match std::fs::copy(path, backup_path(path)) {
+                Ok(_) => (),
+                Err(e) => println!("Backup save file error: {}", e),
+            };
+        }
+
+        match std::fs::rename(temp_path.as_str(), path) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(error(format!("Can not replace save file. {}", e))),
+        }
+    }
+
+    /// Parse save file. Any broken line or wrong checksum is an error. "path" only use for error message.
+    pub fn parse(path: &str, source: &str) -> Result<SaveData, SaveError> {
+        parse_save(path, source, false)
+    }
+
+    /// Parse save file and skip every broken line.
+    pub fn parse_lenient(path: &str, source: &str) -> SaveData {
+        match parse_save(path, source, true) {
+            Ok(save_data) => save_data,
+            Err(_) => SaveData::default(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![];
+        lines.push(format!("{} {}", SAVE_MAGIC, SAVE_VERSION));
+
+        lines.push(String::from("[settings]"));
+        lines.push(format!("master_volume={}", self.settings.master_volume));
+        lines.push(format!("music_volume={}", self.settings.music_volume));
+        lines.push(format!("sfx_volume={}", self.settings.sfx_volume));
+
+        lines.push(String::from("[high_scores]"));
+        for high_score in self.high_scores.iter() {
+            lines.push(format!(
+                "{}|{}|{}|{}",
+                high_score.name, high_score.score, high_score.level_id, high_score.time
+            ));
+        }
+
+        lines.push(String::from("[best_times]"));
+        for (level_id, time) in self.best_clear_times.iter() {
+            lines.push(format!("{}={}", level_id, time));
+        }
+
+        lines.push(String::from("[unlocked]"));
+        for content in self.unlocked.iter() {
+            lines.push(content.clone());
+        }
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text.push_str(format!("checksum={:08x}\n", checksum(text.as_str())).as_str());
+        text
+    }
+
+    /// Return true when the score get into the high score table.
+    pub fn is_high_score(&self, score: u64) -> bool {
+        if score == 0 {
+            return false;
+        }
+
+        self.high_scores.len() < MAX_HIGH_SCORES
+            || self.high_scores.iter().any(|high_score| score > high_score.score)
+    }
+
+    /// Add score into the table. Name is cut to MAX_NAME_LENGTH.
+    /// Return rank of the score. (0: the first) None: the score is not in the table.
+    pub fn add_high_score(
+        &mut self,
+        name: &str,
+        score: u64,
+        level_id: &str,
+        time: u128,
+    ) -> Option<usize> {
+        if self.is_high_score(score) == false {
+            return None;
+        }
+
+        let rank = self
+            .high_scores
+            .iter()
+            .position(|high_score| score > high_score.score)
+            .unwrap_or(self.high_scores.len());
+
+        self.high_scores.insert(
+            rank,
+            HighScore {
+                name: clean_name(name),
+                score: score,
+                level_id: String::from(level_id),
+                time: time,
+            },
+        );
+        self.high_scores.truncate(MAX_HIGH_SCORES);
+
+        Some(rank)
+    }
+
+    /// Return true when "time" is the new best clear time of the level.
+    pub fn record_clear_time(&mut self, level_id: &str, time: u128) -> bool {
+        match self.best_clear_times.get(level_id) {
+            Some(best_time) if *best_time <= time => false,
+            _ => {
+                self.best_clear_times.insert(String::from(level_id), time);
+                true
+            }
+        }
+    }
+
+    pub fn unlock(&mut self, content: &str) {
+        self.unlocked.insert(String::from(content));
+    }
+
+    pub fn is_unlocked(&self, content: &str) -> bool {
+        self.unlocked.contains(content)
+    }
+}
+
+/// Upper case letters, digits and space. Cut to MAX_NAME_LENGTH.
+pub fn clean_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == ' ')
+        .map(|c| c.to_ascii_uppercase())
+        .take(MAX_NAME_LENGTH)
+        .collect()
+}
+
+fn backup_path(path: &str) -> String {
+    format!("{}.bak", path)
+}
+
+/// FNV-1a 32 bits
+fn checksum(text: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in text.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+enum Section {
+    None,
+    Settings,
+    HighScores,
+    BestTimes,
+    Unlocked,
+}
+
+/// # Arguments:
+///
+/// * lenient - true: skip broken lines and ignore checksum. false: return the first error
+///
+fn parse_save(path: &str, source: &str, lenient: bool) -> Result<SaveData, SaveError> {
+    let error = |line: usize, message: String| SaveError {
+        path: String::from(path),
+        line: line,
+        message: message,
+    };
+
+    let version = match source.lines().next() {
+        Some(text) => {
+            let values: Vec<&str> = text.split(' ').collect();
+            if values.len() != 2 || values[0] != SAVE_MAGIC {
+                return Err(error(1, String::from("Not a save file")));
+            }
+
+            match values[1].parse::<u32>() {
+                Ok(v) => v,
+                Err(_) => return Err(error(1, format!("Invalid version \"{}\"", values[1]))),
+            }
+        }
+        None => return Err(error(1, String::from("Empty save file"))),
+    };
+
+    if version == 0 || version > SAVE_VERSION {
+        return Err(error(1, format!("Unsupported save version {}", version)));
+    }
+
+    let mut save_data = SaveData::default();
+    let mut section = Section::None;
+    let mut has_checksum = false;
+    // Length of text before the current line. For checking checksum.
+    let mut offset = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let line_offset = offset;
+        offset += text.len() + 1;
+
+        if index == 0 || text.trim().len() == 0 {
+            continue;
+        }
+
+        let result = match text {
+            "[settings]" => {
+                section = Section::Settings;
+                Ok(())
+            }
+            "[high_scores]" => {
+                section = Section::HighScores;
+                Ok(())
+            }
+            "[best_times]" => {
+                section = Section::BestTimes;
+                Ok(())
+            }
+            "[unlocked]" => {
+                section = Section::Unlocked;
+                Ok(())
+            }
+            _ if text.starts_with("checksum=") => {
+                has_checksum = true;
+                let expected = checksum(&source[..line_offset]);
+                match u32::from_str_radix(&text["checksum=".len()..], 16) {
+                    Ok(v) if v == expected => Ok(()),
+                    _ => Err(String::from("Checksum does not match. The file is broken")),
+                }
+            }
+            _ => match section {
+                Section::None => Err(format!("Line is outside of any section \"{}\"", text)),
+                Section::Settings => parse_setting(&mut save_data.settings, text),
+                Section::HighScores => match parse_high_score(text, version) {
+                    Ok(high_score) => {
+                        save_data.high_scores.push(high_score);
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                },
+                Section::BestTimes => match parse_best_time(text) {
+                    Ok((level_id, time)) => {
+                        save_data.best_clear_times.insert(level_id, time);
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                },
+                Section::Unlocked => {
+                    save_data.unlocked.insert(String::from(text.trim()));
+                    Ok(())
+                }
+            },
+        };
+
+        match result {
+            Ok(_) => (),
+            Err(message) => {
+                if lenient {
+                    println!("Skip save line {}: {}", index + 1, message);
+                } else {
+                    return Err(error(index + 1, message));
+                }
+            }
+        };
+    }
+
+    // Version 1 has no checksum.
+    if has_checksum == false && version > 1 && lenient == false {
+        return Err(error(0, String::from("No checksum. The file is incomplete")));
+    }
+
+    save_data
+        .high_scores
+        .sort_by(|a, b| b.score.cmp(&a.score));
+    save_data.high_scores.truncate(MAX_HIGH_SCORES);
+
+    Ok(save_data)
+}
+
+fn parse_setting(settings: &mut Settings, text: &str) -> Result<(), String> {
+    let values: Vec<&str> = text.splitn(2, '=').collect();
+    if values.len() != 2 {
+        return Err(format!("Incorrect setting format \"{}\"", text));
+    }
+
+    let value = match values[1].parse::<f32>() {
+        Ok(v) => v.max(0.0).min(1.0),
+        Err(_) => return Err(format!("Invalid volume \"{}\"", values[1])),
+    };
+
+    match values[0] {
+        "master_volume" => settings.master_volume = value,
+        "music_volume" => settings.music_volume = value,
+        "sfx_volume" => settings.sfx_volume = value,
+        // Setting from newer game. Keep the other settings.
+        _ => println!("Unknown setting \"{}\"", values[0]),
+    };
+
+    Ok(())
+}
+
+fn parse_best_time(text: &str) -> Result<(String, u128), String> {
+    let values: Vec<&str> = text.splitn(2, '=').collect();
+    if values.len() != 2 {
+        return Err(format!("Incorrect best time format \"{}\"", text));
+    }
+
+    match values[1].parse::<u128>() {
+        Ok(time) => Ok((String::from(values[0]), time)),
+        Err(_) => Err(format!("Invalid time \"{}\"", values[1])),
+    }
+}
+
+/// Version 1: name|score. Version 2: name|score|level|time
+fn parse_high_score(text: &str, version: u32) -> Result<HighScore, String> {
+    let values: Vec<&str> = text.split('|').collect();
+    let expected_length = if version == 1 { 2 } else { 4 };
+    if values.len() != expected_length {
+        return Err(format!("Incorrect high score format \"{}\"", text));
+    }
+
+    let score = match values[1].parse::<u64>() {
+        Ok(v) => v,
+        Err(_) => return Err(format!("Invalid score \"{}\"", values[1])),
+    };
+
+    if version == 1 {
+        return Ok(HighScore {
+            name: clean_name(values[0]),
+            score: score,
+            level_id: String::from(crate::scenes::gameplay::DEFAULT_LEVEL_ID),
+            time: 0,
+        });
+    }
+
+    let time = match values[3].parse::<u128>() {
+        Ok(v) => v,
+        Err(_) => return Err(format!("Invalid time \"{}\"", values[3])),
+    };
+
+    Ok(HighScore {
+        name: clean_name(values[0]),
+        score: score,
+        level_id: String::from(values[2]),
+        time: time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HighScore, SaveData, Settings, SAVE_VERSION};
+    use crate::scenes::gameplay::DEFAULT_LEVEL_ID;
+
+    fn high_score(name: &str, score: u64, level_id: &str, time: u128) -> HighScore {
+        HighScore {
+            name: String::from(name),
+            score: score,
+            level_id: String::from(level_id),
+            time: time,
+        }
+    }
+
+    #[test]
+    fn load_reads_what_save_writes() {
+        let mut save_data = SaveData::default();
+        save_data.settings = Settings {
+            master_volume: 0.5,
+            music_volume: 0.25,
+            sfx_volume: 0.75,
+        };
+        save_data.add_high_score("abc", 300, "level-1", 180000);
+        save_data.add_high_score("zed", 500, "boss", 95000);
+        save_data.record_clear_time("level-1", 180000);
+        save_data.unlock("boss");
+
+        let directory = std::env::temp_dir().join(format!("one-man-save-{}", std::process::id()));
+        let path = directory.join("save.txt").to_string_lossy().into_owned();
+        save_data.save(path.as_str()).unwrap();
+        let loaded = SaveData::load(path.as_str());
+        std::fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(loaded.unwrap(), save_data);
+    }
+
+    #[test]
+    fn version_1_high_scores_move_to_the_default_level() {
+        let source =
+            "one-man-save 1\n[settings]\nmusic_volume=0.5\n[high_scores]\nabc|300\nZed 2|500\n";
+        let save_data = SaveData::parse("save.txt", source).unwrap();
+
+        assert_eq!(save_data.settings.music_volume, 0.5);
+        assert_eq!(
+            save_data.high_scores,
+            vec![
+                high_score("ZED 2", 500, DEFAULT_LEVEL_ID, 0),
+                high_score("ABC", 300, DEFAULT_LEVEL_ID, 0),
+            ]
+        );
+
+        let text = save_data.to_text();
+        assert!(text.starts_with(format!("one-man-save {}\n", SAVE_VERSION).as_str()));
+        assert_eq!(
+            SaveData::parse("save.txt", text.as_str()).unwrap(),
+            save_data
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+
+use crate::gameplay::player::WeaponType;
+
+/// Combo is gone when there is no kill or melee hit within this time. (ms)
+pub const COMBO_TIME: u128 = 2000;
+/// Every COMBO_STEP combo count increase the multiplier by 1.
+pub const COMBO_STEP: u32 = 5;
+pub const MAX_MULTIPLIER: u32 = 8;
+/// Extra combo count for a kill with the other weapon than the previous kill.
+pub const WEAPON_SWITCH_BONUS: u32 = 2;
+/// Points for landing a melee attack on an enemy. (before multiplier)
+pub const MELEE_HIT_SCORE: u32 = 10;
+
+/// Statistics of one run. All players share it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RunStats {
+    /// Enemy type number -> number of kills
+    pub kills_by_type: BTreeMap<i32, u32>,
+    pub damage_taken: u32,
+    pub bullets_fired: u32,
+    pub bullets_hit: u32,
+    /// Time spent on each camera node in visiting order. (node name, ms)
+    pub node_times: Vec<(String, u128)>,
+}
+
+impl RunStats {
+    pub fn total_kills(&self) -> u32 {
+        self.kills_by_type.values().sum()
+    }
+
+    pub fn total_time(&self) -> u128 {
+        self.node_times.iter().map(|(_, time)| time).sum()
+    }
+
+    /// Percentage of player bullets that hit an enemy. 0 when no bullet is fired.
+    pub fn accuracy(&self) -> f32 {
+        if self.bullets_fired == 0 {
+            return 0.0;
+        }
+
+        self.bullets_hit as f32 / self.bullets_fired as f32 * 100.0
+    }
+}
+
+/// Score, combo and statistics of a run.
+///
+/// Kill give enemy type's score value times multiplier.
+/// Multiplier = 1 + combo / COMBO_STEP (up to MAX_MULTIPLIER).
+/// Combo count increase on every kill and get WEAPON_SWITCH_BONUS when the weapon is changed from the previous kill.
+pub struct ScoreBoard {
+    score: u64,
+    combo: u32,
+    max_combo: u32,
+    combo_time: u128,
+    last_kill_weapon: Option<WeaponType>,
+    stats: RunStats,
+}
+
+impl ScoreBoard {
+    pub fn new() -> ScoreBoard {
+        ScoreBoard {
+            score: 0,
+            combo: 0,
+            max_combo: 0,
+            combo_time: 0,
+            last_kill_weapon: None,
+            stats: RunStats::default(),
+        }
+    }
+
+    /// Call it once per frame while playing.
+    ///
+    /// # Arguments:
+    ///
+    /// * current_node - name of camera target node. None: level has no node left
+    ///
+    pub fn update(&mut self, current_node: Option<&str>) {
+        match self.combo_time.checked_sub(crate::ONE_FRAME.as_millis()) {
+            Some(v) => self.combo_time = v,
+            None => self.combo_time = 0,
+        };
+
+        if self.combo_time == 0 {
+            self.combo = 0;
+            self.last_kill_weapon = None;
+        }
+
+        match current_node {
+            Some(name) => {
+                let is_same_node = match self.stats.node_times.last() {
+                    Some((last_name, _)) => last_name == name,
+                    None => false,
+                };
+
+                if is_same_node == false {
+                    self.stats.node_times.push((String::from(name), 0));
+                }
+
+                match self.stats.node_times.last_mut() {
+                    Some((_, time)) => *time += crate::ONE_FRAME.as_millis(),
+                    None => (),
+                };
+            }
+            None => (),
+        };
+    }
+
+    /// # Arguments:
+    ///
+    /// * enemy_type - enemy type number
+    /// * score_value - points of the enemy type before multiplier
+    /// * weapon_type - weapon that make the last hit
+    ///
+    pub fn enemy_killed(&mut self, enemy_type: i32, score_value: u32, weapon_type: WeaponType) {
+        self.score += score_value as u64 * self.multiplier() as u64;
+
+        let is_switched = match self.last_kill_weapon {
+            Some(last_weapon) => last_weapon != weapon_type,
+            None => false,
+        };
+
+        self.combo += if is_switched {
+            1 + WEAPON_SWITCH_BONUS
+        } else {
+            1
+        };
+        self.max_combo = self.max_combo.max(self.combo);
+        self.combo_time = COMBO_TIME;
+        self.last_kill_weapon = Some(weapon_type);
+
+        *self.stats.kills_by_type.entry(enemy_type).or_insert(0) += 1;
+    }
+
+    /// Melee attack hit an enemy. Keep the combo going.
+    pub fn melee_hit(&mut self) {
+        self.score += MELEE_HIT_SCORE as u64 * self.multiplier() as u64;
+        if self.combo > 0 {
+            self.combo_time = COMBO_TIME;
+        }
+    }
+
+    pub fn player_damaged(&mut self, damage: u32) {
+        self.stats.damage_taken += damage;
+    }
+
+    pub fn bullets_fired(&mut self, count: u32) {
+        self.stats.bullets_fired += count;
+    }
+
+    pub fn bullet_hit(&mut self) {
+        self.stats.bullets_hit += 1;
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    pub fn max_combo(&self) -> u32 {
+        self.max_combo
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.combo / COMBO_STEP).min(MAX_MULTIPLIER)
+    }
+
+    pub fn stats(&self) -> &RunStats {
+        &self.stats
+    }
+
+    /// Text for LevelCleared and GameOver screen. One line per item.
+    pub fn summary(&self) -> String {
+        let kills = self
+            .stats
+            .kills_by_type
+            .iter()
+            .map(|(enemy_type, count)| {
+                format!(
+                    "{} {}",
+                    crate::gameplay::enemy_manager::EnemyTypeBank::name(*enemy_type),
+                    count
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("  ");
+
+        let node_times = self
+            .stats
+            .node_times
+            .iter()
+            .map(|(name, time)| format!("{} {}", name, format_time(*time)))
+            .collect::<Vec<String>>()
+            .join("  ");
+
+        format!(
+            "SCORE {}  MAX COMBO {}  TIME {}\nKILLS {}  {}\nDAMAGE TAKEN {}  SHOTS HIT {}/{} ({:.0}%)\n{}",
+            self.score,
+            self.max_combo,
+            format_time(self.stats.total_time()),
+            self.stats.total_kills(),
+            kills,
+            self.stats.damage_taken,
+            self.stats.bullets_hit,
+            self.stats.bullets_fired,
+            self.stats.accuracy(),
+            node_times
+        )
+    }
+}
+
+/// ms -> "m:ss"
+fn format_time(time: u128) -> String {
+    let seconds = time / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
@@ -0,0 +1,1062 @@
+use tetra::math::Vec2;
+
+use crate::image_assets::ImageAssets;
+
+use crate::gameplay::broadphase::Broadphase;
+use crate::gameplay::bullet_pattern::PatternEmitter;
+use crate::gameplay::bullet_pool::{Bullet, BulletOwner, BulletPool, BulletTypeBank};
+use crate::gameplay::camera_effects::CameraEffects;
+use crate::gameplay::camera_path::{CameraPath, NodeTravel};
+use crate::gameplay::collider::{self, ColliderShape};
+use crate::gameplay::enemy_manager::{Enemy, EnemyManager, EnemyTypeBank};
+use crate::gameplay::input::InputState;
+use crate::gameplay::level::Level;
+use crate::gameplay::particle_manager::{ParticleManager, ParticleTypeBank};
+use crate::gameplay::player::{Player, WeaponType};
+use crate::gameplay::random::GameRng;
+use crate::gameplay::score::ScoreBoard;
+use crate::gameplay::world::World;
+
+/// Game state of the simulation.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SimulationState {
+    Playing,
+    LevelCleared,
+    GameOver,
+}
+
+/// Snapshot of the simulation after a step. Use it for checking a level frame by frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub frame: u64,
+    pub state: SimulationState,
+    /// Name of camera target node. None: level has no node left.
+    pub current_node: Option<String>,
+    pub camera_position: Vec2<f32>,
+    pub players: Vec<PlayerObservation>,
+    pub active_enemies: usize,
+    pub player_bullets: usize,
+    pub enemy_bullets: usize,
+    pub score: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerObservation {
+    pub player_number: i32,
+    pub position: Vec2<f32>,
+    pub health: f32,
+    pub alive: bool,
+}
+
+/// Game logic of GamePlayScene without tetra Context. (No window, no audio, no input device)
+/// Every step() advances Level, EnemyManager, BulletPool, ParticleManager and Players by ONE_FRAME.
+pub struct Simulation {
+    /// Player number start from 1. Game is over when every player is dead.
+    pub players: Vec<Player>,
+    pub enemy_manager: EnemyManager,
+    pub particle_manager: ParticleManager,
+    pub bullet_pool: BulletPool,
+    pub level: Level,
+    /// Types of this simulation. They are filled in setup().
+    pub enemy_type_bank: EnemyTypeBank,
+    pub bullet_type_bank: BulletTypeBank,
+    pub particle_type_bank: ParticleTypeBank,
+    /// Bullet patterns from the world. (origin, emitter)
+    pattern_emitters: Vec<(Vec2<f32>, PatternEmitter)>,
+    /// Grids of enemies and enemy bullets for the hit checks. Rebuilt every frame.
+    broadphase: Broadphase,
+    world: World,
+    rng: GameRng,
+    score: ScoreBoard,
+    state: SimulationState,
+    frame: u64,
+
+    camera_target_position: Vec2<f32>,
+    /// Travel of the target node.
+    camera_travel: NodeTravel,
+    /// Path to the target. None: NodeTravel::is_step()
+    camera_path: Option<CameraPath>,
+    /// Time since the travel started and the whole travel time. (ms)
+    camera_travel_time: u128,
+    camera_travel_duration: u128,
+    camera_effects: CameraEffects,
+    reach_camera_target: bool,
+    waiting_time: u128,
+
+    /// Sounds that requested since the last drain_sounds(). (sound key, volume)
+    sounds: Vec<(String, f32)>,
+    /// Music tracks that requested since the last drain_music().
+    music: Vec<String>,
+}
+
+impl Simulation {
+    /// Create simulation with empty type banks.
+    /// All randomness in the simulation come from "seed". The seed is printed in log.
+    ///
+    /// # Arguments:
+    ///
+    /// * total_players - number of players. At least 1.
+    ///
+    pub fn new(level: Level, seed: u64, total_players: usize) -> Simulation {
+        println!("Game seed: {}", seed);
+
+        let camera_position = Vec2::new(0.0, -crate::SCREEN_HEIGHT * 0.3);
+
+        let mut players = vec![];
+        for index in 0..total_players.max(1) {
+            let mut player = Player::new(index as i32 + 1);
+            player.set_position(Vec2::new(index as f32 * 32.0, 0.0));
+            players.push(player);
+        }
+
+        Simulation {
+            players: players,
+            enemy_manager: EnemyManager::new(),
+            particle_manager: ParticleManager::new(),
+            bullet_pool: BulletPool::new(500),
+            level: level,
+            enemy_type_bank: EnemyTypeBank::new(),
+            bullet_type_bank: BulletTypeBank::new(),
+            particle_type_bank: ParticleTypeBank::new(),
+            pattern_emitters: vec![],
+            broadphase: Broadphase::new(),
+            world: World::new(camera_position),
+            rng: GameRng::new(seed),
+            score: ScoreBoard::new(),
+            state: SimulationState::Playing,
+            frame: 0,
+            camera_target_position: camera_position,
+            camera_travel: NodeTravel::new(),
+            camera_path: None,
+            camera_travel_time: 0,
+            camera_travel_duration: 0,
+            camera_effects: CameraEffects::new(),
+            reach_camera_target: false,
+            waiting_time: 1500,
+            sounds: vec![],
+            music: vec![],
+        }
+    }
+
+    /// Setup type banks and player. Animations in "image_assets" have to be loaded before call it.
+    pub fn setup(&mut self, image_assets: &mut ImageAssets) {
+        self.bullet_type_bank.setup(image_assets);
+
+        self.world.bullet_pattern_bank.setup();
+
+        for player in self.players.iter_mut() {
+            player.setup(image_assets);
+        }
+
+        // Setup particle manager, particle type bank
+        self.particle_type_bank.setup(image_assets);
+
+        self.enemy_type_bank
+            .setup(image_assets, &crate::gameplay::enemy_manager::ENEMY_TYPES);
+
+        // Set to camera target node to "start"
+        self.level.set_current_node("start");
+    }
+
+    /// Advance the game by ONE_FRAME. inputs[0] is input of player 1 and so on.
+    /// Players without input get no button down.
+    pub fn step_with_inputs(&mut self, inputs: &[InputState], image_assets: &ImageAssets) {
+        let mut index = 0;
+        for player in self.players.iter_mut() {
+            let input = inputs.get(index).copied().unwrap_or_default();
+            player.update_input_with_state(&input);
+            index += 1;
+        }
+
+        self.step(image_assets);
+    }
+
+    /// Advance the game by ONE_FRAME. Player input have to be updated before call it.
+    /// Nothing but the camera moves while hit-stop is running.
+    pub fn step(&mut self, image_assets: &ImageAssets) {
+        self.frame += 1;
+
+        if self.camera_effects.is_hit_stop() {
+            self.update_camera_effects();
+            return;
+        }
+
+        self.world.player_positions = self
+            .players
+            .iter()
+            .filter(|player| player.alive())
+            .map(|player| player.get_hit_point_position())
+            .collect();
+
+        match self.state {
+            SimulationState::Playing => {
+                self.update_camera_position();
+
+                for player in self.players.iter_mut() {
+                    player.update(&mut self.world, image_assets);
+                }
+                // Update active enemy and remove inactive enemy
+                self.enemy_manager.update_active_enemies(
+                    &self.players,
+                    &self.enemy_type_bank,
+                    &mut self.world,
+                    image_assets,
+                    &mut self.rng,
+                );
+
+                self.particle_manager.update(
+                    &mut self.particle_type_bank,
+                    &mut self.world,
+                    image_assets,
+                );
+
+                // Update active bullets and remove inactive bullets
+                self.bullet_pool
+                    .update_active_enemies_bullets(&self.bullet_type_bank, &mut self.world);
+                self.bullet_pool
+                    .update_active_player_bullets(&self.bullet_type_bank, &mut self.world);
+
+                // Check enemy spawn patter for this cameranode
+                self.level.update(&mut self.world);
+                // Spawn enemy that put in the queue by Level
+
+                self.spawn_enemy_in_the_queue(image_assets);
+
+                // Fire bullet patterns from level nodes and bullets
+                self.update_pattern_emitters();
+
+                // Spawn bullet
+                self.count_player_bullets_in_the_queue();
+                self.bullet_pool.spawn_bullets_from_queue(
+                    &mut self.bullet_type_bank,
+                    &mut self.world,
+                    image_assets,
+                    &mut self.rng,
+                );
+
+                // Check GameOver game state
+                if self.players.iter().all(|player| player.alive() == false) {
+                    self.state = SimulationState::GameOver;
+                }
+
+                // Do collision detects between objects
+                self.call_hit_checks();
+
+                self.score
+                    .update(self.level.get_current_node().map(|node| node.name.as_str()));
+
+                // Check that all enemies killed and no pattern for spawning enemy before fetch the next node.
+                self.fetching_next_camera_target();
+
+                self.sounds.append(&mut self.world.play_sound_nodes);
+            }
+            SimulationState::LevelCleared | SimulationState::GameOver => {
+                for player in self.players.iter_mut() {
+                    player.update(&mut self.world, image_assets);
+                }
+                // Update active enemy and remove inactive enemy
+                self.enemy_manager.update_active_enemies(
+                    &self.players,
+                    &self.enemy_type_bank,
+                    &mut self.world,
+                    image_assets,
+                    &mut self.rng,
+                );
+
+                self.particle_manager.update(
+                    &mut self.particle_type_bank,
+                    &mut self.world,
+                    image_assets,
+                );
+
+                // Update active bullets and remove inactive bullets
+                self.bullet_pool
+                    .update_active_enemies_bullets(&self.bullet_type_bank, &mut self.world);
+                self.bullet_pool
+                    .update_active_player_bullets(&self.bullet_type_bank, &mut self.world);
+
+                // Nothing spawns after the game is over.
+                self.world.enemy_spawn_nodes.clear();
+                self.world.bullet_spawn_nodes.clear();
+                self.world.pattern_spawn_nodes.clear();
+                self.world.play_sound_nodes.clear();
+            }
+        }
+
+        self.update_camera_effects();
+    }
+
+    pub fn observe(&self) -> Observation {
+        let player_bullets = self.bullet_pool.player_active_bullets.len();
+        let enemy_bullets = self.bullet_pool.enemy_active_bullets.len();
+
+        Observation {
+            frame: self.frame,
+            state: self.state,
+            current_node: self.level.get_current_node().map(|node| node.name.clone()),
+            camera_position: self.world.camera_position,
+            players: self
+                .players
+                .iter()
+                .map(|player| PlayerObservation {
+                    player_number: player.player_number(),
+                    position: player.get_position(),
+                    health: player.get_health_percentage(),
+                    alive: player.alive(),
+                })
+                .collect(),
+            active_enemies: self.enemy_manager.active_enemy_count(),
+            player_bullets: player_bullets,
+            enemy_bullets: enemy_bullets,
+            score: self.score.score(),
+        }
+    }
+
+    pub fn state(&self) -> SimulationState {
+        self.state
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Score, combo and statistics of this run.
+    pub fn score(&self) -> &ScoreBoard {
+        &self.score
+    }
+
+    pub fn camera_position(&self) -> Vec2<f32> {
+        self.world.camera_position
+    }
+
+    /// Bullet patterns that are fired from the world. (origin, emitter)
+    /// Patterns of enemies are in Enemy::bullet_pattern.
+    pub fn pattern_emitters(&self) -> &Vec<(Vec2<f32>, PatternEmitter)> {
+        &self.pattern_emitters
+    }
+
+    /// Offset from camera shake. Add it to camera position for drawing only.
+    pub fn camera_shake_offset(&self) -> Vec2<f32> {
+        self.camera_effects.offset()
+    }
+
+    /// Shake, hit-stop and zoom of the camera. Use it for drawing only.
+    pub fn camera_effects(&self) -> &CameraEffects {
+        &self.camera_effects
+    }
+
+    /// Take sounds that requested since the last call. (sound key, volume)
+    pub fn drain_sounds(&mut self) -> Vec<(String, f32)> {
+        self.sounds.drain(..).collect()
+    }
+
+    /// Take music tracks that requested since the last call. The last one is the newest.
+    pub fn drain_music(&mut self) -> Vec<String> {
+        self.music.drain(..).collect()
+    }
+}
+
+impl Simulation {
+    /// interporate between current camera position and latest target position.
+    /// NodeTravel::is_step() moves "move_speed" per frame on each axis.
+    /// Other travels move along CameraPath at the node speed with the travel curve.
+    fn update_camera_position(&mut self) {
+        if self.reach_camera_target == false {
+            let move_speed = 2.0;
+            let camera_target_position = self.camera_target_position;
+            let camera_position = &mut self.world.camera_position;
+
+            match self.camera_path.as_ref() {
+                None => {
+                    if camera_position.x < camera_target_position.x {
+                        camera_position.x += move_speed
+                    } else if camera_position.x > camera_target_position.x {
+                        camera_position.x -= move_speed;
+                    }
+
+                    if camera_position.y < camera_target_position.y {
+                        camera_position.y += move_speed;
+                    } else if camera_position.y > camera_target_position.y {
+                        camera_position.y -= move_speed;
+                    }
+                }
+                Some(path) => {
+                    self.camera_travel_time += crate::ONE_FRAME.as_millis();
+
+                    let t = match self.camera_travel_duration {
+                        0 => 1.0,
+                        duration => self.camera_travel_time as f32 / duration as f32,
+                    };
+                    *camera_position =
+                        path.position_at(self.camera_travel.curve.apply(t) * path.length());
+                }
+            };
+
+            let distance_sqr = Vec2::distance_squared(*camera_position, camera_target_position);
+            if distance_sqr <= (move_speed * move_speed) * 1.0
+                && (self.camera_path.is_none()
+                    || self.camera_travel_time >= self.camera_travel_duration)
+            {
+                self.reach_camera_target = true;
+                *camera_position = camera_target_position;
+
+                match self.level.get_current_node() {
+                    Some(node) => {
+                        match node.music.as_ref() {
+                            Some(music) => self.music.push(music.clone()),
+                            None => (),
+                        };
+                        match node.stinger.as_ref() {
+                            Some(stinger) => self.world.play_sound(stinger, 1.0),
+                            None => (),
+                        };
+                    }
+                    None => (),
+                };
+            }
+        }
+    }
+
+    /// Start bullet patterns from the world and fire every running pattern.
+    /// Aimed volleys turn to the nearest living player. (Straight down without players)
+    fn update_pattern_emitters(&mut self) {
+        for node in self.world.pattern_spawn_nodes.drain(..) {
+            self.pattern_emitters
+                .push((node.position, PatternEmitter::new(&node.pattern, node.delay)));
+        }
+
+        if self.pattern_emitters.len() == 0 {
+            return;
+        }
+
+        for (origin, emitter) in self.pattern_emitters.iter_mut() {
+            let target = match crate::gameplay::player::nearest_living_player(&self.players, *origin)
+            {
+                Some(player) => player.get_hit_point_position(),
+                None => *origin + Vec2::new(0.0, 1.0),
+            };
+
+            for node in emitter.update(&self.world.bullet_pattern_bank, *origin, target) {
+                self.world.spawn_bullet(node);
+            }
+        }
+
+        self.pattern_emitters
+            .retain(|(_, emitter)| emitter.is_finished() == false);
+    }
+
+    /// Take camera requests from the world and move shake, hit-stop and zoom.
+    fn update_camera_effects(&mut self) {
+        for effect in self.world.camera_effects.drain(..) {
+            self.camera_effects.apply(effect);
+        }
+
+        self.camera_effects.update(self.frame);
+    }
+
+    fn fetching_next_camera_target(&mut self) {
+        // Go next node if possible. Auto-scroll node does not wait for enemies.
+        if self.reach_camera_target == true {
+            let auto_scroll = match self.level.get_current_node() {
+                Some(node) => node.travel.auto_scroll && node.next_node.is_some(),
+                None => false,
+            };
+            if auto_scroll
+                || (self.enemy_manager.has_active_enemy() == false
+                    && self.level.is_spawn_queue_empty() == true)
+            {
+                if self.waiting_time == 0 {
+                    match self.level.get_next_node() {
+                        Some(node) => {
+                            self.camera_target_position = node.position;
+                            self.camera_travel = node.travel.clone();
+                            self.camera_path = match node.travel.is_step() {
+                                true => None,
+                                false => {
+                                    let mut points = vec![self.world.camera_position];
+                                    points.extend_from_slice(&node.travel.waypoints);
+                                    points.push(node.position);
+                                    Some(CameraPath::new(&points, node.travel.spline))
+                                }
+                            };
+                            self.camera_travel_time = 0;
+                            self.camera_travel_duration = match self.camera_path.as_ref() {
+                                Some(path) => {
+                                    (path.length() / node.travel.speed.max(0.1)) as u128
+                                        * crate::ONE_FRAME.as_millis()
+                                }
+                                None => 0,
+                            };
+                            self.reach_camera_target = false;
+                            let next_node_name = String::from(node.name.as_str());
+                            self.waiting_time = node.waiting_time;
+
+                            self.level.set_current_node(&next_node_name);
+                        }
+                        None => {
+                            self.level.set_current_node("");
+                            self.waiting_time = 1500;
+                        }
+                    };
+                } else {
+                    match self.waiting_time.checked_sub(crate::ONE_FRAME.as_millis()) {
+                        Some(v) => self.waiting_time = v,
+                        None => self.waiting_time = 0,
+                    };
+                }
+            }
+        }
+    }
+
+    fn update_hit_check_between_player_melee_attack_with_enemies_bullets(
+        player: &mut Player,
+        bullet_pool: &mut BulletPool,
+        broadphase: &mut Broadphase,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
+        match player.get_weapon_type() {
+            WeaponType::Range => {
+                return;
+            }
+            WeaponType::Melee => {
+                if player.is_attacking() == false {
+                    return;
+                }
+            }
+        }
+
+        broadphase.enemy_bullets.query(
+            player.get_melee_attack_position(),
+            Player::get_melee_attack_radius(),
+            &mut broadphase.candidates,
+        );
+        for index in broadphase.candidates.iter() {
+            let bullet = &mut bullet_pool.enemy_active_bullets[*index];
+            if bullet.active {
+                let total_radius = bullet.radius + Player::get_melee_attack_radius();
+                let distance =
+                    Vec2::distance_squared(player.get_melee_attack_position(), bullet.position);
+                if distance < total_radius * total_radius {
+                    bullet.health = 0;
+
+                    match bullet.extra.get_text("kill_animation") {
+                        Some(name) => {
+                            let random_size = 8.0;
+                            let random_position = Vec2::new(
+                                random_size / 2.0 - rng.random::<f32>() * random_size,
+                                random_size / 2.0 - rng.random::<f32>() * random_size,
+                            );
+
+                            Bullet::spawn_hitting_particle(
+                                world,
+                                bullet.position + random_position,
+                                format!("idle_animation={}|flip_x=0|", name).as_str(),
+                            );
+                        }
+                        None => (),
+                    };
+                }
+            }
+        }
+    }
+
+    fn update_hit_check_between_player_melee_attack_with_enemies(
+        player: &mut Player,
+        active_enemies: &mut Vec<Enemy>,
+        enemy_type_bank: &EnemyTypeBank,
+        broadphase: &mut Broadphase,
+        score: &mut ScoreBoard,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
+        match player.get_weapon_type() {
+            WeaponType::Range => {
+                return;
+            }
+            WeaponType::Melee => {
+                if player.is_attacking() == false {
+                    return;
+                }
+            }
+        }
+
+        let attack_position = player.get_melee_attack_position();
+        broadphase.enemies.query(
+            attack_position,
+            Player::get_melee_attack_radius(),
+            &mut broadphase.candidates,
+        );
+        for index in broadphase.candidates.iter() {
+            let enemy = &mut active_enemies[*index];
+            if enemy.active {
+                let result = enemy.hit_check(
+                    &attack_position,
+                    &attack_position,
+                    Player::get_melee_attack_radius(),
+                );
+                let damage = collider::damage_of(result, player.melee_attack_damage());
+
+                if damage > 0 {
+                    let hit_position = enemy.position;
+                    let was_alive = enemy.health > 0;
+                    enemy.get_hit(&hit_position, damage, world, rng);
+                    player.melee_attack_hit_enemy(world);
+
+                    score.melee_hit();
+                    if was_alive && enemy.health == 0 {
+                        score.enemy_killed(
+                            enemy.enemy_type,
+                            enemy.score_value(enemy_type_bank),
+                            WeaponType::Melee,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_hit_check_between_player_bullet_and_enemies(
+        bullet_pool: &mut BulletPool,
+        active_enemies: &mut Vec<Enemy>,
+        enemy_type_bank: &EnemyTypeBank,
+        broadphase: &mut Broadphase,
+        score: &mut ScoreBoard,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
+        for bullet in bullet_pool.player_active_bullets.iter_mut() {
+            if bullet.active == false
+                || broadphase.is_inside_camera_area(&bullet.position, bullet.radius) == false
+            {
+                continue;
+            }
+
+            // Check the path of this frame. Fast bullets do not pass through small enemies.
+            let (center, radius) =
+                collider::sweep_bounds(bullet.previous_position, bullet.position, bullet.radius);
+            broadphase
+                .enemies
+                .query(center, radius, &mut broadphase.candidates);
+            for index in broadphase.candidates.iter() {
+                let enemy = &mut active_enemies[*index];
+                if bullet.active == true && enemy.active {
+                    let mut break_loop = false;
+                    {
+                        let (result, checking_position) = match enemy.collider.sweep(
+                            enemy.position,
+                            bullet.previous_position,
+                            bullet.position,
+                            bullet.radius,
+                        ) {
+                            Some((part, time)) => (
+                                part.zone.hit_result(),
+                                Vec2::lerp(bullet.previous_position, bullet.position, time),
+                            ),
+                            None => (collider::HIT_NONE, bullet.position),
+                        };
+
+                        if result != collider::HIT_NONE {
+                            bullet.health -= 1;
+                            score.bullet_hit();
+
+                            let bullet_rotation = if bullet.rotation < 0.0 {
+                                bullet.rotation + 1.0
+                            } else {
+                                bullet.rotation
+                            };
+
+                            match bullet.extra.get_text("hit_animation") {
+                                Some(name) => {
+                                    let random_size = 8.0;
+                                    let random_position = Vec2::new(
+                                        random_size / 2.0 - rng.random::<f32>() * random_size,
+                                        random_size / 2.0 - rng.random::<f32>() * random_size,
+                                    );
+
+                                    let flip_x = if bullet_rotation > 0.25 && bullet_rotation < 0.75
+                                    {
+                                        "flip_x=1"
+                                    } else {
+                                        "flip_x=0"
+                                    };
+
+                                    Bullet::spawn_hitting_particle(
+                                        world,
+                                        checking_position + random_position,
+                                        format!("idle_animation={}|{}|", name, flip_x).as_str(),
+                                    );
+                                }
+                                None => (),
+                            };
+
+                            // Armour stops bullets without damage, same as shields.
+                            let damage = collider::damage_of(result, bullet.damage);
+                            if damage > 0 {
+                                let was_alive = enemy.health > 0;
+                                enemy.get_hit(&checking_position, damage, world, rng);
+                                if was_alive && enemy.health == 0 {
+                                    score.enemy_killed(
+                                        enemy.enemy_type,
+                                        enemy.score_value(enemy_type_bank),
+                                        WeaponType::Range,
+                                    );
+                                }
+
+                                {
+                                    let sfx_list = ["bullet_hit_1", "bullet_hit_2", "bullet_hit_3"];
+
+                                    let name = sfx_list[rng.random::<usize>() % sfx_list.len()];
+                                    world.play_sound(name, 0.15);
+                                }
+                            }
+
+                            if enemy.health <= 0 {
+                                enemy.active = false;
+                            }
+
+                            if bullet.health <= 0 {
+                                bullet.active = false;
+                                break_loop = true;
+                            }
+
+                            break;
+                        }
+                    }
+
+                    if break_loop {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_hit_check_between_player_and_enemies(
+        player: &mut Player,
+        active_enemies: &mut Vec<Enemy>,
+        broadphase: &mut Broadphase,
+        score: &mut ScoreBoard,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
+        let player_hit_point_position = player.get_hit_point_position();
+        let player_hit_point_radius = player.get_hit_point_radius();
+
+        broadphase.enemies.query(
+            player_hit_point_position,
+            player_hit_point_radius,
+            &mut broadphase.candidates,
+        );
+        for index in broadphase.candidates.iter() {
+            let enemy = &mut active_enemies[*index];
+            if enemy.hit_check(
+                &player_hit_point_position,
+                &player_hit_point_position,
+                player_hit_point_radius,
+            ) != collider::HIT_NONE
+            {
+                let damage = player.get_hit(2, world, rng);
+                score.player_damaged(damage);
+            }
+        }
+    }
+
+    fn update_hit_check_between_player_and_enemies_bullets(
+        player: &mut Player,
+        bullet_pool: &mut BulletPool,
+        broadphase: &mut Broadphase,
+        score: &mut ScoreBoard,
+        world: &mut World,
+        rng: &mut GameRng,
+    ) {
+        let player_hit_point_position = player.get_hit_point_position();
+        let player_hit_point_radius = player.get_hit_point_radius();
+
+        broadphase.enemy_bullets.query(
+            player_hit_point_position,
+            player_hit_point_radius,
+            &mut broadphase.candidates,
+        );
+        for index in broadphase.candidates.iter() {
+            let bullet = &mut bullet_pool.enemy_active_bullets[*index];
+            if bullet.active {
+                let mut break_loop = false;
+                {
+                    let hit_point = ColliderShape::Circle {
+                        center: Vec2::zero(),
+                        radius: player_hit_point_radius,
+                    };
+
+                    if hit_point
+                        .sweep(
+                            player_hit_point_position,
+                            bullet.previous_position,
+                            bullet.position,
+                            bullet.radius,
+                        )
+                        .is_some()
+                    {
+                        bullet.health -= 1;
+
+                        let bullet_rotation = if bullet.rotation < 0.0 {
+                            bullet.rotation + 1.0
+                        } else {
+                            bullet.rotation
+                        };
+
+                        match bullet.extra.get_text("hit_animation") {
+                            Some(name) => {
+                                let random_size = 8.0;
+                                let random_position = Vec2::new(
+                                    random_size / 2.0 - rng.random::<f32>() * random_size,
+                                    random_size / 2.0 - rng.random::<f32>() * random_size,
+                                );
+
+                                let flip_x = if bullet_rotation > 0.25 && bullet_rotation < 0.75 {
+                                    "flip_x=1"
+                                } else {
+                                    "flip_x=0"
+                                };
+
+                                Bullet::spawn_hitting_particle(
+                                    world,
+                                    bullet.position + random_position,
+                                    format!("idle_animation={}|{}|", name, flip_x).as_str(),
+                                );
+                            }
+                            None => (),
+                        };
+
+                        let damage = player.get_hit(1, world, rng);
+                        score.player_damaged(damage);
+
+                        if bullet.health <= 0 {
+                            bullet.active = false;
+                            break_loop = true;
+                        }
+
+                        break;
+                    }
+                }
+
+                if break_loop {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Simulation {
+    fn spawn_enemy_in_the_queue(&mut self, image_assets: &ImageAssets) {
+        // check spawn queue in Level
+        let need_to_spawn_enemy_list = std::mem::take(&mut self.world.enemy_spawn_nodes);
+        if need_to_spawn_enemy_list.len() > 0 {
+            for spawn_node in need_to_spawn_enemy_list.iter() {
+                let world_position = spawn_node.position;
+
+                // Add enemy into active list
+                self.enemy_manager.spawn_enemy(
+                    spawn_node.enemy_type,
+                    world_position,
+                    spawn_node.extra.as_str(),
+                    &mut self.enemy_type_bank,
+                    &mut self.world,
+                    image_assets,
+                    &mut self.rng,
+                );
+            }
+        }
+        // Check level cleared game state.
+        else if self.level.is_spawn_queue_empty() == true
+            && self.enemy_manager.has_active_enemy() == false
+            && self.level.get_current_node().is_none() == true
+        {
+            self.state = SimulationState::LevelCleared;
+        }
+    }
+
+    fn call_hit_checks(&mut self) {
+        self.broadphase.rebuild(
+            self.world.camera_position,
+            self.enemy_manager.get_mut_active_enemy(),
+            &self.bullet_pool.enemy_active_bullets,
+        );
+
+        Simulation::update_hit_check_between_player_bullet_and_enemies(
+            &mut self.bullet_pool,
+            self.enemy_manager.get_mut_active_enemy(),
+            &self.enemy_type_bank,
+            &mut self.broadphase,
+            &mut self.score,
+            &mut self.world,
+            &mut self.rng,
+        );
+
+        for player in self.players.iter_mut() {
+            // Dead players in co-op stay in the level. Bullets and enemies pass through them.
+            if player.alive() == false {
+                continue;
+            }
+
+            Simulation::update_hit_check_between_player_and_enemies(
+                player,
+                self.enemy_manager.get_mut_active_enemy(),
+                &mut self.broadphase,
+                &mut self.score,
+                &mut self.world,
+                &mut self.rng,
+            );
+            Simulation::update_hit_check_between_player_and_enemies_bullets(
+                player,
+                &mut self.bullet_pool,
+                &mut self.broadphase,
+                &mut self.score,
+                &mut self.world,
+                &mut self.rng,
+            );
+
+            Simulation::update_hit_check_between_player_melee_attack_with_enemies_bullets(
+                player,
+                &mut self.bullet_pool,
+                &mut self.broadphase,
+                &mut self.world,
+                &mut self.rng,
+            );
+            Simulation::update_hit_check_between_player_melee_attack_with_enemies(
+                player,
+                self.enemy_manager.get_mut_active_enemy(),
+                &self.enemy_type_bank,
+                &mut self.broadphase,
+                &mut self.score,
+                &mut self.world,
+                &mut self.rng,
+            );
+        }
+    }
+
+    /// Count bullets that players fire in this frame. Call it before BulletPool::spawn_bullets_from_queue.
+    fn count_player_bullets_in_the_queue(&mut self) {
+        let count = self
+            .world
+            .bullet_spawn_nodes
+            .iter()
+            .filter(|node| match node.owner_type {
+                BulletOwner::PLAYER(_) => true,
+                _ => false,
+            })
+            .count();
+
+        self.score.bullets_fired(count as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Simulation;
+    use crate::gameplay::input::InputState;
+    use crate::gameplay::score::RunStats;
+    use crate::image_assets::ImageAssets;
+
+    const SEED: u64 = 7;
+
+    /// Assets and simulation of the default level, same as headless::run().
+    fn setup_simulation(use_grid: bool) -> (Simulation, ImageAssets) {
+        let mut image_assets = ImageAssets::new(vec![]);
+        crate::scenes::gameplay::setup_textures(&mut image_assets);
+        image_assets.loading_without_textures();
+        crate::scenes::gameplay::setup_animations(&mut image_assets);
+
+        let level = crate::scenes::gameplay::create_level(
+            crate::scenes::gameplay::DEFAULT_LEVEL_ID,
+            &image_assets,
+        );
+        let mut simulation = Simulation::new(level, SEED, 1);
+        simulation.setup(&mut image_assets);
+        simulation.broadphase.set_use_grid(use_grid);
+
+        (simulation, image_assets)
+    }
+
+    /// Player walks back and forth and keeps attacking. It switches weapon every 5 seconds,
+    /// so both bullets and melee attacks hit.
+    fn scripted_input(frame: u64) -> InputState {
+        InputState {
+            left: frame % 240 < 60,
+            right: frame % 240 >= 120 && frame % 240 < 180,
+            up: frame % 90 < 10,
+            attack: frame % 20 < 15,
+            switch: frame % 300 == 0,
+            ..InputState::default()
+        }
+    }
+
+    /// Observation, score statistics and health of every active enemy after every frame.
+    fn run_frames(use_grid: bool, frames: u64) -> (Vec<String>, RunStats) {
+        let (mut simulation, image_assets) = setup_simulation(use_grid);
+
+        let mut results = vec![];
+        for frame in 0..frames {
+            simulation.step_with_inputs(&[scripted_input(frame)], &image_assets);
+
+            let enemy_health: Vec<u32> = simulation
+                .enemy_manager
+                .get_active_enemy()
+                .iter()
+                .map(|enemy| enemy.health)
+                .collect();
+            results.push(format!(
+                "{:?} {:?} {:?}",
+                simulation.observe(),
+                simulation.score().stats(),
+                enemy_health
+            ));
+        }
+
+        (results, simulation.score().stats().clone())
+    }
+
+    #[test]
+    fn hit_checks_give_the_same_game_with_and_without_the_grid() {
+        let (without_grid, _) = run_frames(false, 1800);
+        let (with_grid, stats) = run_frames(true, 1800);
+
+        assert!(
+            stats.bullets_hit > 0 && stats.total_kills() > 0,
+            "{:?}",
+            stats
+        );
+        for (frame, (expected, actual)) in without_grid.iter().zip(with_grid.iter()).enumerate() {
+            assert_eq!(expected, actual, "frame {}", frame + 1);
+        }
+    }
+
+    /// Time of the same run with and without the grid. Only hit checks are different.
+    /// `cargo test --release benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn benchmark_hit_checks() {
+        let frames = 3600;
+        for use_grid in [false, true].iter() {
+            let (mut simulation, image_assets) = setup_simulation(*use_grid);
+            let start = std::time::Instant::now();
+            for frame in 0..frames {
+                simulation.step_with_inputs(&[scripted_input(frame)], &image_assets);
+            }
+            let time = start.elapsed();
+
+            println!(
+                "grid {}: {:.1} us/frame, {} hits, score {}",
+                use_grid,
+                time.as_secs_f64() * 1000000.0 / frames as f64,
+                simulation.score().stats().bullets_hit,
+                simulation.score().score()
+            );
+        }
+    }
+}
@@ -4,9 +4,14 @@ use tetra::math::Vec2;
 use tetra::Context;
 
 use crate::gameplay::player::WeaponType;
+use crate::gameplay::save::SaveData;
+use crate::gameplay::score::ScoreBoard;
 use crate::image_assets::ImageAssets;
 use crate::sprite::Sprite;
 
+/// Horizontal space of one player's energy bar and weapon icon.
+const PLAYER_HUD_WIDTH: f32 = 160.0;
+
 pub struct UI {
     circle: Sprite,
     melee: Sprite,
@@ -22,6 +27,10 @@ pub struct UI {
     level_cleared_text: Option<Text>,
     credits_text: Option<Text>,
 
+    score_text: Option<Text>,
+    stats_text: Option<Text>,
+    high_scores_text: Option<Text>,
+
     intro_text: Option<Text>,
     warning: Option<Text>,
 }
@@ -41,6 +50,10 @@ impl UI {
             level_cleared_text: None,
             credits_text: None,
 
+            score_text: None,
+            stats_text: None,
+            high_scores_text: None,
+
             intro_text: None,
             warning: None,
         }
@@ -120,7 +133,20 @@ impl UI {
             Err(_) => panic!("Load font error for loading screen"),
         };
         self.restart_text = Some(Text::new("PRESS 'Z' TO RESTART", font.clone()));
-        self.credits_text = Some(Text::new("A GAME BY OAB", font));
+        self.credits_text = Some(Text::new("A GAME BY OAB", font.clone()));
+        self.score_text = Some(Text::new("", font));
+
+        let font = match Font::vector(ctx, "./resources/fonts/D-DINCondensed.ttf", 20.0) {
+            Ok(v) => v,
+            Err(_) => panic!("Load font error for loading screen"),
+        };
+        self.stats_text = Some(Text::new("", font));
+
+        let font = match Font::vector(ctx, "./resources/fonts/D-DINCondensed.ttf", 16.0) {
+            Ok(v) => v,
+            Err(_) => panic!("Load font error for loading screen"),
+        };
+        self.high_scores_text = Some(Text::new("", font));
     }
 
     pub fn update(&mut self, _ctx: &mut Context) {
@@ -141,7 +167,8 @@ impl UI {
     }
 
     pub fn draw_game_over(&mut self,
-        ctx: &mut Context        
+        ctx: &mut Context,
+        score: &ScoreBoard
         )
     {
 
@@ -157,11 +184,13 @@ impl UI {
             (crate::SCREEN_HEIGHT * 0.7).ceil()
         );   
         UI::draw_3_colors_text(ctx, position, self.restart_text.as_ref().unwrap());
+
+        self.draw_stats(ctx, score);
     }
 
     pub fn draw_level_cleared(&mut self,
-        ctx: &mut Context
-        
+        ctx: &mut Context,
+        score: &ScoreBoard
         )
     {
 
@@ -177,24 +206,90 @@ impl UI {
             (crate::SCREEN_HEIGHT * 0.7).ceil()
         );   
         UI::draw_3_colors_text(ctx, position, self.credits_text.as_ref().unwrap());
+
+        self.draw_stats(ctx, score);
+    }
+
+    /// Score and combo at the top right corner of the screen.
+    pub fn draw_score(&mut self, ctx: &mut Context, score: &ScoreBoard) {
+        let score_text = self.score_text.as_mut().unwrap();
+        if score.combo() > 0 {
+            score_text.set_content(format!("{}  x{} ({})", score.score(), score.multiplier(), score.combo()));
+        } else {
+            score_text.set_content(format!("{}", score.score()));
+        }
+
+        let position = Vec2::new(
+            (crate::SCREEN_WIDTH - score_text.get_bounds(ctx).unwrap().width - 16.0).ceil(),
+            8.0
+        );
+        UI::draw_3_colors_text(ctx, position, self.score_text.as_ref().unwrap());
+    }
+
+    /// Name entry of the high score table at the left side of LevelCleared screen.
+    pub fn draw_name_entry(&mut self, ctx: &mut Context, name: &str) {
+        let high_scores_text = self.high_scores_text.as_mut().unwrap();
+        high_scores_text.set_content(format!("NEW HIGH SCORE\nENTER YOUR NAME\n{}_", name));
+
+        UI::draw_3_colors_text(ctx, Vec2::new(16.0, 8.0), self.high_scores_text.as_ref().unwrap());
+    }
+
+    /// High score table at the left side of LevelCleared screen.
+    ///
+    /// # Arguments:
+    ///
+    /// * rank - rank of this run. It is marked in the table. None: this run is not in the table
+    ///
+    pub fn draw_high_scores(&mut self, ctx: &mut Context, save_data: &SaveData, rank: Option<usize>) {
+        let mut lines = vec![String::from("HIGH SCORES")];
+        let mut index = 0;
+        for high_score in save_data.high_scores.iter() {
+            let mark = if rank == Some(index) { ">" } else { " " };
+            lines.push(format!("{}{:2}. {:<8} {}", mark, index + 1, high_score.name, high_score.score));
+            index += 1;
+        }
+
+        let high_scores_text = self.high_scores_text.as_mut().unwrap();
+        high_scores_text.set_content(lines.join("\n"));
+
+        UI::draw_3_colors_text(ctx, Vec2::new(16.0, 8.0), self.high_scores_text.as_ref().unwrap());
     }
 
+    /// Statistics of the run between the title and the bottom text of LevelCleared and GameOver screen.
+    fn draw_stats(&mut self, ctx: &mut Context, score: &ScoreBoard) {
+        let stats_text = self.stats_text.as_mut().unwrap();
+        stats_text.set_content(score.summary());
+
+        let position = Vec2::new(
+            ((crate::SCREEN_WIDTH - stats_text.get_bounds(ctx).unwrap().width)/2.0).ceil(),
+            (crate::SCREEN_HEIGHT * 0.45).ceil()
+        );
+        UI::draw_3_colors_text(ctx, position, self.stats_text.as_ref().unwrap());
+    }
+
+    /// # Arguments:
+    ///
+    /// * player_index - 0: player 1. Each player's HUD is next to the previous one
+    ///
     pub fn draw_weapon(
         &mut self,
         ctx: &mut Context,
         image_assets: &ImageAssets,
         weapon_type: &WeaponType,
+        player_index: usize,
     ) {
+        let offset_x = player_index as f32 * PLAYER_HUD_WIDTH;
+
         self.circle.draw(
             ctx,
-            Vec2::new(24.0, crate::SCREEN_HEIGHT - 24.0),
+            Vec2::new(offset_x + 24.0, crate::SCREEN_HEIGHT - 24.0),
             0.0,
             image_assets,
         );
 
         self.z_button.draw(
             ctx,
-            Vec2::new(36.0, crate::SCREEN_HEIGHT - 10.0),
+            Vec2::new(offset_x + 36.0, crate::SCREEN_HEIGHT - 10.0),
             0.0,
             image_assets,
         );
@@ -203,7 +298,7 @@ impl UI {
             WeaponType::Melee => {
                 self.melee.draw(
                     ctx,
-                    Vec2::new(24.0, crate::SCREEN_HEIGHT - 24.0),
+                    Vec2::new(offset_x + 24.0, crate::SCREEN_HEIGHT - 24.0),
                     0.0,
                     image_assets,
                 );
@@ -211,7 +306,7 @@ impl UI {
             WeaponType::Range => {
                 self.range.draw(
                     ctx,
-                    Vec2::new(24.0, crate::SCREEN_HEIGHT - 24.0),
+                    Vec2::new(offset_x + 24.0, crate::SCREEN_HEIGHT - 24.0),
                     0.0,
                     image_assets,
                 );
@@ -219,13 +314,19 @@ impl UI {
         }
     }
 
+    /// # Arguments:
+    ///
+    /// * player_index - 0: player 1. Each player's HUD is next to the previous one
+    ///
     pub fn draw_energy_bar(
         &mut self,
         ctx: &mut Context,
         image_assets: &ImageAssets,
         energy_percentage: f32,
+        player_index: usize,
     ) {
-        let position = Vec2::new(40.0, crate::SCREEN_HEIGHT - 30.0);
+        let offset_x = player_index as f32 * PLAYER_HUD_WIDTH;
+        let position = Vec2::new(offset_x + 40.0, crate::SCREEN_HEIGHT - 30.0);
 
         match image_assets.get("ui-bar-bg") {
             Some(texture) => {
@@ -236,7 +337,7 @@ impl UI {
 
         self.energy.draw_with_clipping(
             ctx,
-            Vec2::new(72.0, crate::SCREEN_HEIGHT - 16.0),
+            Vec2::new(offset_x + 72.0, crate::SCREEN_HEIGHT - 16.0),
             0.0,
             image_assets,
             Vec2::new(energy_percentage, 1.0),
@@ -1,56 +1,67 @@
 use tetra::graphics::Rectangle;
 use tetra::math::Vec2;
 
-use rand::prelude::*;
-
-pub fn clamp_position_inside_camera_area(position: &mut Vec2<f32>) {
-    let camera_position = crate::CAMERA_POSITION.lock().unwrap();
+use crate::gameplay::random::GameRng;
+
+/// Area that the camera at "camera_position" shows in world position.
+pub fn camera_area(camera_position: Vec2<f32>) -> Rectangle {
+    Rectangle::new(
+        camera_position.x - crate::SCREEN_WIDTH / 2.0,
+        camera_position.y - crate::SCREEN_HEIGHT / 2.0,
+        crate::SCREEN_WIDTH,
+        crate::SCREEN_HEIGHT,
+    )
+}
 
-    let half_size = 16.0 / 2.0;
-    let left = camera_position.x - crate::SCREEN_WIDTH / 2.0 - half_size;
-    let right = camera_position.x + crate::SCREEN_WIDTH / 2.0 + half_size;
-    let up = camera_position.y - crate::SCREEN_HEIGHT / 2.0;
-    let down = camera_position.y + crate::SCREEN_HEIGHT / 2.0;
+/// Keep "position" inside "camera_area". Use World::camera_area() so the area follows the camera
+/// on paths and auto-scroll.
+///
+/// # Arguments:
+///
+/// * margin - distance that position can go out of the left and right side. (pixel)
+///
+pub fn clamp_position_inside_camera_area(
+    camera_area: &Rectangle,
+    position: &mut Vec2<f32>,
+    margin: f32,
+) {
+    let left = camera_area.left() - margin;
+    let right = camera_area.right() + margin;
 
     position.x = position.x.max(left).min(right);
-    position.y = position.y.max(up).min(down);
+    position.y = position.y.max(camera_area.top()).min(camera_area.bottom());
 }
 
-pub fn is_inside_camera_area(position: &Vec2<f32>, radius: f32) -> bool {
-    let camera_position = crate::CAMERA_POSITION.lock().unwrap();
-
-    let left = camera_position.x - crate::SCREEN_WIDTH / 2.0 - radius;
-    let right = camera_position.x + crate::SCREEN_WIDTH / 2.0 + radius;
-
-    if position.x >= left && position.x <= right {
-        let up = camera_position.y - crate::SCREEN_HEIGHT / 2.0 - radius;
-        let down = camera_position.y + crate::SCREEN_HEIGHT / 2.0 + radius;
+pub fn is_inside_camera_area(camera_position: Vec2<f32>, position: &Vec2<f32>, radius: f32) -> bool {
+    is_inside_area(&camera_area(camera_position), position, radius)
+}
 
-        if position.y >= up && position.y <= down {
-            return true;
-        }
-    }
-    false
+/// True: the circle touches "area".
+pub fn is_inside_area(area: &Rectangle, position: &Vec2<f32>, radius: f32) -> bool {
+    position.x >= area.left() - radius
+        && position.x <= area.right() + radius
+        && position.y >= area.top() - radius
+        && position.y <= area.bottom() + radius
 }
 
 /// Random position inside camera.
 /// top,left,width,height values in percentage. 0.0 - 1.0
 pub fn random_position_inside_camera_area(
+    camera_position: Vec2<f32>,
     left: f32,
     top: f32,
     width: f32,
     height: f32,
+    rng: &mut GameRng,
 ) -> Vec2<f32> {
-    let camera_position = crate::CAMERA_POSITION.lock().unwrap();
-
     let width = width.min(crate::SCREEN_WIDTH);
     let height = height.min(crate::SCREEN_HEIGHT);
 
     let top = (camera_position.y - crate::SCREEN_HEIGHT / 2.0) + (top * crate::SCREEN_HEIGHT);
     let left = (camera_position.x - crate::SCREEN_WIDTH / 2.0) + (left * crate::SCREEN_WIDTH);
 
-    let value_x = random::<f32>() * (crate::SCREEN_WIDTH * width);
-    let value_y = random::<f32>() * (crate::SCREEN_HEIGHT * height);
+    let value_x = rng.random::<f32>() * (crate::SCREEN_WIDTH * width);
+    let value_y = rng.random::<f32>() * (crate::SCREEN_HEIGHT * height);
 
     let mut random_position = Vec2::new(left + value_x, top + value_y);
 
@@ -67,9 +78,11 @@ pub fn random_position_inside_camera_area(
     return random_position;
 }
 
-pub fn convert_screen_position_to_world_position(screen_position: Vec2<f32>) -> Vec2<f32> {
-    let camera_position = crate::CAMERA_POSITION.lock().unwrap();
-    screen_position + *camera_position
+pub fn convert_screen_position_to_world_position(
+    camera_position: Vec2<f32>,
+    screen_position: Vec2<f32>,
+) -> Vec2<f32> {
+    screen_position + camera_position
 }
 
 pub fn distance_sqr(x1: i128, y1: i128, x2: i128, y2: i128) -> i128 {
@@ -107,3 +120,31 @@ pub fn angle_lerp(angle_1: f32, angle_2: f32, t: f32) -> f32 {
 
     return lerp(angle_1, angle_2, t) % 1.0;
 }
+
+/// Turn "angle" to "target" by "max_turn" or less. Angles are rotation values. (0.0 - 1.0)
+pub fn turn_angle(angle: f32, target: f32, max_turn: f32) -> f32 {
+    let angle = angle.rem_euclid(1.0);
+    let target = target.rem_euclid(1.0);
+
+    let mut difference = (target - angle).rem_euclid(1.0);
+    if difference > 0.5 {
+        difference -= 1.0;
+    }
+
+    if difference.abs() <= max_turn {
+        return target;
+    }
+
+    angle_lerp(angle, target, max_turn / difference.abs()).rem_euclid(1.0)
+}
+
+/// Unit vector of rotation value. (0.0 - 1.0)
+pub fn rotation_to_direction(rotation: f32) -> Vec2<f32> {
+    let radian = (rotation * 360.0).to_radians();
+    Vec2::new(radian.cos(), radian.sin())
+}
+
+/// Rotation value (0.0 - 1.0) of the direction from "from" to "to".
+pub fn rotation_between(from: Vec2<f32>, to: Vec2<f32>) -> f32 {
+    ((to.y - from.y).atan2(to.x - from.x).to_degrees() / 360.0).rem_euclid(1.0)
+}
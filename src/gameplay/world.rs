@@ -0,0 +1,133 @@
+use tetra::graphics::Rectangle;
+use tetra::math::Vec2;
+
+use crate::gameplay::bullet_pattern::{BulletPatternBank, PatternSpawnNode};
+use crate::gameplay::bullet_pool::BulletSpawnNode;
+use crate::gameplay::camera_effects::CameraEffect;
+use crate::gameplay::level::EnemySpawnNode;
+use crate::gameplay::particle_manager::ParticleSpawnNode;
+
+/// Space between the bottom of the camera and the floor of the players. (pixel)
+const GROUND_MARGIN: f32 = 24.0;
+
+/// Shared state and command queues of one Simulation.
+/// Players, enemies, bullets and level push commands into it during update.
+/// Simulation executes the commands after every object is updated.
+pub struct World {
+    /// Center of the camera in world position.
+    pub camera_position: Vec2<f32>,
+    /// Hit points of living players. Simulation updates it before anything moves.
+    pub player_positions: Vec<Vec2<f32>>,
+
+    pub enemy_spawn_nodes: Vec<EnemySpawnNode>,
+    pub bullet_spawn_nodes: Vec<BulletSpawnNode>,
+    /// Bullet patterns from level nodes and bullets. Simulation runs them until they finish.
+    pub pattern_spawn_nodes: Vec<PatternSpawnNode>,
+    pub particle_spawn_nodes: Vec<ParticleSpawnNode>,
+    /// (sound key, volume). AudioManager limits instances of the same sound.
+    pub play_sound_nodes: Vec<(String, f32)>,
+    /// Shake, hit-stop and zoom requests. See CameraEffects
+    pub camera_effects: Vec<CameraEffect>,
+    /// Patterns that enemies and levels fire by name. Simulation fills it in setup().
+    pub bullet_pattern_bank: BulletPatternBank,
+}
+
+impl World {
+    pub fn new(camera_position: Vec2<f32>) -> World {
+        World {
+            camera_position: camera_position,
+            player_positions: vec![],
+            enemy_spawn_nodes: vec![],
+            bullet_spawn_nodes: vec![],
+            pattern_spawn_nodes: vec![],
+            particle_spawn_nodes: vec![],
+            play_sound_nodes: vec![],
+            camera_effects: vec![],
+            bullet_pattern_bank: BulletPatternBank::new(),
+        }
+    }
+
+    /// Area that the camera shows in world position. It moves with the camera on every path.
+    pub fn camera_area(&self) -> Rectangle {
+        crate::gameplay::utils::camera_area(self.camera_position)
+    }
+
+    /// Floor of the players. It rises with the bottom of the camera in vertical sections.
+    pub fn ground(&self) -> f32 {
+        let area = self.camera_area();
+        crate::GROUND.min(area.bottom() - GROUND_MARGIN)
+    }
+
+    /// Hit point of the nearest living player. None: every player is dead.
+    pub fn nearest_player_position(&self, position: Vec2<f32>) -> Option<Vec2<f32>> {
+        let mut nearest = None;
+        let mut nearest_distance = f32::MAX;
+        for player_position in self.player_positions.iter() {
+            let distance = Vec2::distance_squared(*player_position, position);
+            if distance < nearest_distance {
+                nearest_distance = distance;
+                nearest = Some(*player_position);
+            }
+        }
+
+        nearest
+    }
+
+    pub fn spawn_enemy(&mut self, spawn_node: EnemySpawnNode) {
+        self.enemy_spawn_nodes.push(spawn_node);
+    }
+
+    pub fn spawn_bullet(&mut self, spawn_node: BulletSpawnNode) {
+        self.bullet_spawn_nodes.push(spawn_node);
+    }
+
+    /// Fire bullet pattern from a fixed position. Its aimed volleys turn to the nearest player.
+    pub fn fire_pattern(&mut self, spawn_node: PatternSpawnNode) {
+        self.pattern_spawn_nodes.push(spawn_node);
+    }
+
+    pub fn spawn_particle(&mut self, spawn_node: ParticleSpawnNode) {
+        self.particle_spawn_nodes.push(spawn_node);
+    }
+
+    /// # Arguments:
+    ///
+    /// * name - key of the sound in AudioManager. Ex: "player_hit"
+    ///
+    pub fn play_sound(&mut self, name: &str, volume: f32) {
+        self.play_sound_nodes.push((String::from(name), volume));
+    }
+
+    /// Shake the camera. Trauma from every request adds up to 1.0 and fades out.
+    ///
+    /// # Arguments:
+    ///
+    /// * trauma - 0.1: small hit, 0.5: big explosion, 1.0: the strongest shake.
+    ///
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.camera_effects.push(CameraEffect::Trauma(trauma));
+    }
+
+    /// Freeze players, enemies, bullets and particles for "duration". (ms)
+    pub fn hit_stop(&mut self, duration: u128) {
+        self.camera_effects.push(CameraEffect::HitStop(duration));
+    }
+
+    /// Zoom in by "amount" (0.1 = 10%) and ease back in "duration". (ms)
+    pub fn zoom_punch(&mut self, amount: f32, duration: u128) {
+        self.camera_effects.push(CameraEffect::Zoom {
+            amount: amount,
+            duration: duration,
+        });
+    }
+
+    /// Remove every command in the queues.
+    pub fn clear(&mut self) {
+        self.enemy_spawn_nodes.clear();
+        self.bullet_spawn_nodes.clear();
+        self.pattern_spawn_nodes.clear();
+        self.particle_spawn_nodes.clear();
+        self.play_sound_nodes.clear();
+        self.camera_effects.clear();
+    }
+}
@@ -0,0 +1,91 @@
+use crate::audio_manager::{AudioManager, NullAudioBackend};
+use crate::gameplay::random::GameRng;
+use crate::gameplay::replay::Replay;
+use crate::gameplay::simulation::{Simulation, SimulationState};
+use crate::image_assets::ImageAssets;
+
+/// Frames to run when "--frames" is not given. (5 minutes)
+const DEFAULT_FRAMES: u64 = 60 * 60 * 5;
+
+/// Run the level without window, audio and input device.
+/// Stop when the level is cleared, the player died or "frames" reached.
+///
+/// # Arguments:
+///
+/// * frames - maximum frames to run. None: DEFAULT_FRAMES or every frame in the replay
+/// * seed - seed of the game. None: random seed. (Replay use seed from the file)
+/// * replay_path - replay file for player input. None: no input
+///
+pub fn run(
+    frames: Option<u64>,
+    seed: Option<u64>,
+    replay_path: Option<&str>,
+) -> SimulationState {
+    let replay = match replay_path {
+        Some(path) => match Replay::load(path) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                println!("Load replay error: {}", e);
+                return SimulationState::Playing;
+            }
+        },
+        None => None,
+    };
+
+    let (seed, level_id, inputs) = match replay {
+        Some(replay) => (replay.header.seed, replay.header.level_id, replay.frames),
+        None => (
+            seed.unwrap_or(GameRng::random_seed()),
+            String::from(crate::scenes::gameplay::DEFAULT_LEVEL_ID),
+            vec![],
+        ),
+    };
+
+    let frames = match frames {
+        Some(v) => v,
+        None if replay_path.is_some() => inputs.len() as u64,
+        None => DEFAULT_FRAMES,
+    };
+
+    let mut image_assets = ImageAssets::new(vec![]);
+    crate::scenes::gameplay::setup_textures(&mut image_assets);
+    image_assets.loading_without_textures();
+    crate::scenes::gameplay::setup_animations(&mut image_assets);
+
+    let mut audio = AudioManager::new(
+        Box::new(NullAudioBackend::new()),
+        crate::scenes::gameplay::setup_sounds(&image_assets.take_sounds()),
+    );
+    let mut music = crate::scenes::gameplay::setup_music();
+
+    let level = crate::scenes::gameplay::create_level(level_id.as_str(), &image_assets);
+    let mut simulation = Simulation::new(level, seed, 1);
+    simulation.setup(&mut image_assets);
+
+    while simulation.frame() < frames && simulation.state() == SimulationState::Playing {
+        let input = inputs
+            .get(simulation.frame() as usize)
+            .copied()
+            .unwrap_or_default();
+        simulation.step_with_inputs(&[input], &image_assets);
+        for (key, volume) in simulation.drain_sounds().iter() {
+            audio.play(key, *volume);
+        }
+        for track in simulation.drain_music().iter() {
+            println!("Music: {}", track);
+            music.play(&mut audio, track, 0);
+        }
+        music.update(&mut audio, crate::ONE_FRAME.as_millis());
+        audio.update(crate::ONE_FRAME.as_millis());
+
+        if simulation.frame() % 600 == 0 {
+            println!("{:?}", simulation.observe());
+        }
+    }
+
+    let observation = simulation.observe();
+    println!("Headless result: {:?}", observation);
+    println!("{}", simulation.score().summary());
+
+    observation.state
+}
@@ -1,10 +1,15 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
-use tetra::graphics::{Mesh, Shader, Texture};
+use tetra::graphics::{Color, Mesh, Rectangle, Shader, Texture};
+use tetra::math::Vec2;
 use tetra::Context;
 
-use crate::sprite::AnimationMultiTextures;
+use crate::asset_manifest::{AnimationDefinition, AssetError, AssetManifest};
+use crate::sprite::{AnimationMultiTextures, FrameRectangle};
+use crate::sprite_effects::{Palette, MAX_PALETTE_COLORS};
+use crate::sprite_sheet::{SliceKey, SpriteSheet};
+use crate::texture_atlas::{self, ATLAS_SIZE};
 
 /// Struct for a content data. One object per file.
 pub struct ContentPath {
@@ -44,11 +49,47 @@ pub struct ImageAssets {
 
     texture_ids: HashMap<String, u128>,
 
-    animations: HashMap<String, Vec<u128>>,
+    /// Area of the texture inside its atlas. Texture without area uses the whole texture.
+    texture_regions: HashMap<String, Rectangle>,
+
+    /// Animation frames from manifest are packed into atlases before other textures are loaded.
+    atlas_packed: bool,
+
+    animations: HashMap<String, Vec<FrameRectangle>>,
 
     animations_frame_length: HashMap<String, u64>,
 
+    /// Loop flag from sidecar files. Animation without it keeps loop flag of the Sprite.
+    animations_loop: HashMap<String, bool>,
+
+    /// Anchor from slice pivot of sprite sheets. Animation without it keeps anchor of the Sprite.
+    animations_anchor: HashMap<String, Vec2<f32>>,
+
+    /// Frame events from sidecar files or add_animation_event().
+    animations_events: HashMap<String, Vec<(usize, String)>>,
+
+    /// Sprite sheets by texture key. Their slices are used by get_animation_slice().
+    sheets: HashMap<String, SpriteSheet>,
+
+    /// Animation name -> (sheet key, sheet frame index of every animation frame)
+    sheet_animations: HashMap<String, (String, Vec<usize>)>,
+
+    /// Sprite sheets from manifest. They are built by load_manifest_animations() after textures loaded.
+    sheet_loading_list: Vec<SpriteSheet>,
+
+    /// Animations from manifest. They are built by load_manifest_animations() after textures loaded.
+    animation_loading_list: Vec<AnimationDefinition>,
+
+    /// Problems found while loading. See take_errors().
+    errors: Vec<AssetError>,
+
     shaders: HashMap<String, Shader>,
+    /// (key, fragment shader path) from manifest. They are loaded by loading().
+    shader_loading_list: Vec<(String, String)>,
+    /// Palettes for SpriteEffects::palette by name.
+    palettes: HashMap<String, Palette>,
+    /// (key, sound path) from manifest. See take_sounds().
+    sound_loading_list: Vec<(String, String)>,
 
     tick: Duration,
 
@@ -69,24 +110,51 @@ impl ImageAssets {
             texture_loading_list: texture_loading_list,
             textures: HashMap::new(),
             texture_ids: HashMap::new(),
+            texture_regions: HashMap::new(),
+            atlas_packed: false,
             animations: HashMap::new(),
             animations_frame_length: HashMap::new(),
+            animations_loop: HashMap::new(),
+            animations_anchor: HashMap::new(),
+            animations_events: HashMap::new(),
+            sheets: HashMap::new(),
+            sheet_animations: HashMap::new(),
+            sheet_loading_list: vec![],
+            animation_loading_list: vec![],
+            errors: vec![],
             shaders: HashMap::new(),
+            shader_loading_list: vec![],
+            palettes: HashMap::new(),
+            sound_loading_list: vec![],
             meshes: HashMap::new(),
             tick: Duration::from_millis(0),
         }
     }
 
-    pub fn hit_shader(&self) -> Option<&Shader> {
-        self.shaders.get("hit-frame")
+    pub fn get_shader(&self, key: &str) -> Option<&Shader> {
+        self.shaders.get(key)
     }
 
-    pub fn flash_red_shader(&self) -> Option<&Shader> {
-        self.shaders.get("flash-red")
+    /// Add palette for SpriteEffects. Only the first MAX_PALETTE_COLORS pairs are used.
+    pub fn add_palette(&mut self, name: &str, colors: Vec<(Color, Color)>) {
+        if colors.len() > MAX_PALETTE_COLORS {
+            self.errors.push(AssetError {
+                path: String::from(name),
+                line: 0,
+                message: format!(
+                    "Palette has {} colors. Only {} colors are used",
+                    colors.len(),
+                    MAX_PALETTE_COLORS
+                ),
+            });
+        }
+
+        self.palettes
+            .insert(String::from(name), Palette { colors: colors });
     }
 
-    pub fn get_shader(&self, key: &str) -> Option<&Shader> {
-        self.shaders.get(key)
+    pub fn get_palette(&self, name: &str) -> Option<&Palette> {
+        self.palettes.get(name)
     }
 
     pub fn get_id(&self, key: &String) -> u128 {
@@ -101,25 +169,30 @@ impl ImageAssets {
     }
 
     pub fn load_animations(&mut self, animations: &Vec<(&str, Vec<String>, u64)>) -> usize {
-        if self.textures.len() == 0 {
+        if self.texture_ids.len() == 0 {
             panic!("ImageAssets try to load animation before load textures.");
         }
         for anim in animations.iter() {
-            let name = anim.0.clone();
-            let mut frame_ids = Vec::new();
+            let name = anim.0;
+            let mut frames = Vec::new();
             for texture_name in anim.1.iter() {
                 let texture_id = self.get_id(texture_name);
                 if texture_id > 0 {
-                    frame_ids.push(texture_id);
+                    let rect = match self.texture_regions.get(texture_name) {
+                        Some(v) => *v,
+                        None => Rectangle::new(0.0, 0.0, 0.0, 0.0),
+                    };
+                    frames.push(FrameRectangle::new(texture_id, rect));
                 } else {
-                    println!(
-                        "load_animations()::Texture missing ({}) for {}",
-                        texture_name, name
-                    );
+                    self.errors.push(AssetError {
+                        path: String::from(name),
+                        line: 0,
+                        message: format!("Texture missing ({}) for animation", texture_name),
+                    });
                 }
             }
 
-            self.animations.insert(String::from(name), frame_ids);
+            self.animations.insert(String::from(name), frames);
             self.animations_frame_length
                 .insert(String::from(anim.0), anim.2);
 
@@ -129,6 +202,17 @@ impl ImageAssets {
         self.animations.len()
     }
 
+    /// Load fragment shaders. Shader that is missing or does not compile is left out and
+    /// its error is in take_errors(). Sprite effects draw without shader in that case.
+    ///
+    /// # Arguments:
+    ///
+    /// * shaders - (key, fragment shader path)
+    ///
+    /// # Return:
+    ///
+    /// Number of loaded shaders.
+    ///
     pub fn load_shaders(&mut self, ctx: &mut Context, shaders: &Vec<(String, String)>) -> usize {
         let mut loaded_count = 0;
         for shader_path in shaders.iter() {
@@ -136,8 +220,23 @@ impl ImageAssets {
                 Ok(v) => {
                     self.shaders.insert(shader_path.0.to_owned(), v);
                     loaded_count += 1;
+
+                    println!(
+                        "Loaded shader \"{}\" : \"{}\"",
+                        shader_path.0, shader_path.1
+                    );
+                }
+                Err(e) => {
+                    println!(
+                        "Shader error ({}). Use fallback effects. {}",
+                        shader_path.0, e
+                    );
+                    self.errors.push(AssetError {
+                        path: shader_path.1.to_owned(),
+                        line: 0,
+                        message: format!("Shader error. {}", e),
+                    });
                 }
-                Err(e) => panic!("Shader file missing. {}", e),
             };
         }
 
@@ -148,7 +247,7 @@ impl ImageAssets {
         self.animations.keys().collect()
     }
 
-    pub fn get_animation_frames(&self, animation_name: &String) -> Option<&Vec<u128>> {
+    pub fn get_animation_frames(&self, animation_name: &String) -> Option<&Vec<FrameRectangle>> {
         self.animations.get(animation_name)
     }
 
@@ -162,11 +261,18 @@ impl ImageAssets {
     pub fn get_animation_object(&self, animation_name: &str) -> Option<AnimationMultiTextures> {
         match self.animations.get(animation_name) {
             Some(frames) => {
-                let mut animation_object = AnimationMultiTextures::new_with_frames(frames.clone());
+                let mut animation_object = AnimationMultiTextures::new();
+                animation_object.frames = frames.clone();
                 animation_object.name = String::from(animation_name);
                 animation_object.frame_length = Duration::from_millis(
                     1000 / self.get_animation_frame_length(&animation_object.name),
                 );
+                animation_object.is_loop = self.animations_loop.get(animation_name).copied();
+                animation_object.anchor = self.animations_anchor.get(animation_name).copied();
+                match self.animations_events.get(animation_name) {
+                    Some(events) => animation_object.events = events.clone(),
+                    None => (),
+                };
                 return Some(animation_object);
             }
             None => (),
@@ -175,6 +281,173 @@ impl ImageAssets {
         None
     }
 
+    /// Add textures, animations and sounds from manifest file into the loading lists.
+    /// Call load_manifest_animations() after textures are loaded.
+    ///
+    /// # Return:
+    ///
+    /// Number of textures in the manifest. Missing frames and files are in take_errors().
+    ///
+    pub fn add_manifest(&mut self, path: &str) -> Result<usize, AssetError> {
+        let mut manifest = AssetManifest::load(path)?;
+
+        let count = manifest.textures.len();
+        self.texture_loading_list.append(&mut manifest.textures);
+        self.animation_loading_list.append(&mut manifest.animations);
+        self.sheet_loading_list.append(&mut manifest.sheets);
+        self.shader_loading_list.append(&mut manifest.shaders);
+        self.sound_loading_list.append(&mut manifest.sounds);
+        for (name, colors) in manifest.palettes.drain(..) {
+            self.add_palette(name.as_str(), colors);
+        }
+        self.errors.append(&mut manifest.errors);
+
+        Ok(count)
+    }
+
+    /// Build animations from manifest. Textures have to be loaded first.
+    pub fn load_manifest_animations(&mut self) -> usize {
+        let definitions: Vec<AnimationDefinition> = self.animation_loading_list.drain(..).collect();
+
+        let mut animations = vec![];
+        for definition in definitions.iter() {
+            animations.push((
+                definition.name.as_str(),
+                definition.frames.clone(),
+                definition.frame_length,
+            ));
+
+            match definition.is_loop {
+                Some(v) => {
+                    self.animations_loop.insert(definition.name.clone(), v);
+                }
+                None => (),
+            };
+        }
+
+        self.load_animations(&animations);
+
+        for definition in definitions.iter() {
+            match self.animations.get_mut(&definition.name) {
+                Some(frames) if frames.len() == definition.durations.len() => {
+                    for (frame, duration) in frames.iter_mut().zip(definition.durations.iter()) {
+                        frame.duration = Some(Duration::from_millis(*duration));
+                    }
+                }
+                _ => (),
+            };
+
+            for (frame_index, name) in definition.events.iter() {
+                self.add_animation_event(&definition.name, *frame_index, name);
+            }
+        }
+
+        let sheets: Vec<SpriteSheet> = self.sheet_loading_list.drain(..).collect();
+        for sheet in sheets.into_iter() {
+            self.load_sheet(sheet);
+        }
+
+        self.animations.len()
+    }
+
+    /// Build animations of the sprite sheet. Its texture has to be loaded first.
+    /// Every frame keeps its own duration. Frame area is the area in the sheet image.
+    pub fn load_sheet(&mut self, sheet: SpriteSheet) {
+        let texture_id = self.get_id(&sheet.key);
+        if texture_id == 0 {
+            self.errors.push(AssetError {
+                path: sheet.image.clone(),
+                line: 0,
+                message: format!("Texture missing ({}) for sprite sheet", sheet.key),
+            });
+            return;
+        }
+
+        for animation in sheet.animations.iter() {
+            let frames = animation
+                .frames
+                .iter()
+                .map(|index| {
+                    let frame = &sheet.frames[*index];
+                    FrameRectangle::new_with_duration(
+                        texture_id,
+                        frame.rect,
+                        Duration::from_millis(frame.duration),
+                    )
+                })
+                .collect();
+
+            self.animations.insert(animation.name.clone(), frames);
+            match sheet.anchor(animation) {
+                Some(v) => {
+                    self.animations_anchor.insert(animation.name.clone(), v);
+                }
+                None => (),
+            };
+            self.sheet_animations.insert(
+                animation.name.clone(),
+                (sheet.key.clone(), animation.frames.clone()),
+            );
+
+            println!("Loaded animation: {}", animation.name);
+        }
+
+        self.sheets.insert(sheet.key.clone(), sheet);
+    }
+
+    /// Add event that Sprite::update() returns when the frame of the animation starts.
+    ///
+    /// # Arguments:
+    ///
+    /// * animation_name - name of the animation. Ex: "enemy-spawner-1-spawning"
+    /// * frame_index - frame index in the animation. (0 = first frame)
+    /// * name - event name. Ex: "spawn"
+    ///
+    pub fn add_animation_event(&mut self, animation_name: &str, frame_index: usize, name: &str) {
+        self.animations_events
+            .entry(String::from(animation_name))
+            .or_insert(vec![])
+            .push((frame_index, String::from(name)));
+    }
+
+    /// Slice of a sprite sheet animation at the frame. Use it for hitbox or anchor hints.
+    ///
+    /// # Arguments:
+    ///
+    /// * animation_name - name of the animation. Ex: Sprite::get_current_animation_name()
+    /// * frame_index - frame index in the animation. Ex: Sprite::get_current_frame_index()
+    /// * slice_name - name of the slice in Aseprite. Ex: "hitbox"
+    ///
+    /// # Return:
+    ///
+    /// Slice bounds and pivot relative to the top-left of the frame. None: animation is not from a sheet or no slice.
+    ///
+    pub fn get_animation_slice(
+        &self,
+        animation_name: &str,
+        frame_index: usize,
+        slice_name: &str,
+    ) -> Option<&SliceKey> {
+        let (sheet_key, frames) = self.sheet_animations.get(animation_name)?;
+        let frame = *frames.get(frame_index)?;
+        let sheet = self.sheets.get(sheet_key)?;
+
+        match sheet.slices.iter().find(|slice| slice.name == slice_name) {
+            Some(slice) => slice.at(frame),
+            None => None,
+        }
+    }
+
+    /// Take sounds from manifest for the audio backend. (key, sound path)
+    pub fn take_sounds(&mut self) -> Vec<(String, String)> {
+        self.sound_loading_list.drain(..).collect()
+    }
+
+    /// Take errors that found since the last call.
+    pub fn take_errors(&mut self) -> Vec<AssetError> {
+        self.errors.drain(..).collect()
+    }
+
     pub fn add_content_list(&mut self, list: &Vec<ContentPath>) {
         for ref_node in list.iter() {
             self.texture_loading_list.push(ref_node.clone());
@@ -201,8 +474,14 @@ impl ImageAssets {
     ///
     pub fn loading(&mut self, ctx: &mut Context) {
         if self.is_loading() {
-            if self.shaders.len() == 0 {
-                // Load shader files here
+            if self.shader_loading_list.len() > 0 {
+                let shaders: Vec<(String, String)> = self.shader_loading_list.drain(..).collect();
+                self.load_shaders(ctx, &shaders);
+            }
+
+            if self.atlas_packed == false {
+                self.pack_atlases(ctx);
+                self.atlas_packed = true;
             }
 
             let accumelated_time = tetra::time::get_accumulator(ctx).as_millis();
@@ -220,7 +499,11 @@ impl ImageAssets {
 
                                     println!("Loaded \"{}\" : \"{}\"", content.key, content.path);
                                 }
-                                Err(e) => println!("Load texture error : {}", e),
+                                Err(e) => self.errors.push(AssetError {
+                                    path: content.path.clone(),
+                                    line: 0,
+                                    message: format!("Load texture error. {}", e),
+                                }),
                             };
                         }
                         None => (),
@@ -232,6 +515,91 @@ impl ImageAssets {
         }
     }
 
+    /// Pack frames of manifest animations into atlas textures and remove them from the loading list.
+    /// Frame that does not fit into an atlas stays in the list and is loaded as its own texture.
+    fn pack_atlases(&mut self, ctx: &mut Context) {
+        let keys = texture_atlas::atlas_texture_keys(&self.animation_loading_list);
+        if keys.len() == 0 {
+            return;
+        }
+
+        let (contents, others): (Vec<ContentPath>, Vec<ContentPath>) = self
+            .texture_loading_list
+            .drain(..)
+            .partition(|content| keys.contains(&content.key));
+        self.texture_loading_list = others;
+
+        let (images, mut errors) = texture_atlas::load_images(&contents);
+        self.errors.append(&mut errors);
+
+        let packed = texture_atlas::pack(&images, ATLAS_SIZE);
+        let mut atlas_ids = vec![];
+        for (index, atlas) in packed.images.iter().enumerate() {
+            let key = format!("atlas-{}", index);
+            match Texture::from_rgba(
+                ctx,
+                atlas.width() as i32,
+                atlas.height() as i32,
+                atlas.as_raw(),
+            ) {
+                Ok(texture) => {
+                    self.add(&key, texture);
+                    atlas_ids.push(self.get_id(&key));
+                }
+                Err(e) => {
+                    self.errors.push(AssetError {
+                        path: key,
+                        line: 0,
+                        message: format!("Create atlas error. {}", e),
+                    });
+                    atlas_ids.push(0);
+                }
+            };
+        }
+
+        for (key, region) in packed.regions.iter() {
+            let texture_id = atlas_ids[region.atlas];
+            if texture_id > 0 {
+                self.texture_ids.insert(key.clone(), texture_id);
+                self.texture_regions.insert(
+                    key.clone(),
+                    Rectangle::new(
+                        region.x as f32,
+                        region.y as f32,
+                        region.width as f32,
+                        region.height as f32,
+                    ),
+                );
+            }
+        }
+
+        // Too big for the atlas. Load it as a texture.
+        for (key, _) in images.iter() {
+            if self.texture_ids.contains_key(key) == false {
+                match contents.iter().find(|content| &content.key == key) {
+                    Some(content) => self.texture_loading_list.push(content.clone()),
+                    None => (),
+                };
+            }
+        }
+
+        println!(
+            "Packed {} textures into {} atlases",
+            packed.regions.len(),
+            packed.images.len()
+        );
+    }
+
+    /// Give texture id to every content in the loading list without loading Texture.
+    /// Animations can use these ids but nothing will be drawn. Use it for running without window.
+    pub fn loading_without_textures(&mut self) {
+        while let Some(content) = self.texture_loading_list.pop() {
+            self.texture_ids
+                .insert(content.key.to_string(), self.texture_id_counter);
+            self.texture_id_counter += 1;
+        }
+    }
+
     /// Add texture into texture bank with key
     fn add(&mut self, key: &String, texture: Texture) {
         self.texture_ids
@@ -1,6 +1,3 @@
-use std::collections::HashMap;
-
-use std::sync::Mutex;
 use std::time::Duration;
 
 use tetra::graphics::scaling::{ScalingMode, ScreenScaler};
@@ -8,14 +5,8 @@ use tetra::graphics::{self, Color};
 use tetra::math::Vec2;
 use tetra::{Context, ContextBuilder, Event, State};
 
-use crate::gameplay::bullet_pool::{BulletPool, BulletSpawnNode, BulletTypeBank};
-
-use crate::gameplay::enemy_manager::EnemyTypeBank;
-use crate::gameplay::level::EnemySpawnNode;
-use crate::gameplay::particle_manager::{ParticleSpawnNode, ParticleTypeBank};
-
 use crate::scene::{Scene, Transition};
-use crate::scenes::gameplay::GamePlayScene;
+use crate::scenes::gameplay::{GamePlayScene, InputSource};
 
 /// Game screen resolution : Width
 pub const SCREEN_WIDTH: f32 = 480.0;
@@ -31,25 +22,17 @@ pub const ONE_FRAME: Duration = Duration::from_millis(1000 / 60);
 #[macro_use]
 extern crate lazy_static;
 
-lazy_static! {
-    pub static ref CAMERA_POSITION: Mutex<Vec2<f32>> = Mutex::new(Vec2::zero());
-    pub static ref BULLET_POOL: Mutex<BulletPool> = Mutex::new(BulletPool::new(500));
-    pub static ref BULLET_SPAWN_NODES: Mutex<Vec<BulletSpawnNode>> = Mutex::new(vec![]);
-    pub static ref BULLET_TYPE_BANK: Mutex<BulletTypeBank> = Mutex::new(BulletTypeBank::new());
-    pub static ref ENEMY_TYPE_BANK: Mutex<EnemyTypeBank> = Mutex::new(EnemyTypeBank::new());
-    pub static ref ENEMY_SPAWN_NODES: Mutex<Vec<EnemySpawnNode>> = Mutex::new(Vec::new());
-    pub static ref PARTICLE_TYPE_BANK: Mutex<ParticleTypeBank> =
-        Mutex::new(ParticleTypeBank::new());
-    pub static ref PARTICLE_SPAWN_NODES: Mutex<Vec<ParticleSpawnNode>> = Mutex::new(Vec::new());
-    pub static ref PLAY_SOUND_NODES: Mutex<HashMap<String, (String, f32)>> = Mutex::new(HashMap::new());
-}
-
 struct GameState {
     scenes: Vec<Box<dyn Scene>>,
     scaler: ScreenScaler,
 }
 
+pub mod animation_state;
+pub mod asset_manifest;
+pub mod audio_manager;
+pub mod headless;
 pub mod image_assets;
+pub mod music;
 
 pub mod scene;
 pub mod scenes {
@@ -57,19 +40,44 @@ pub mod scenes {
 }
 
 pub mod sprite;
+pub mod sprite_effects;
+pub mod sprite_sheet;
+pub mod texture_atlas;
 
 pub mod gameplay {
+    pub mod broadphase;
+    pub mod bullet_pattern;
+    #[cfg(feature = "level-files")]
+    pub mod bullet_pattern_file;
     pub mod bullet_pool;
+    pub mod bullet_trajectory;
+    pub mod camera_effects;
+    pub mod camera_path;
+    pub mod collider;
+    pub mod debug_overlay;
     pub mod enemy_manager;
     pub mod input;
     pub mod level;
+    #[cfg(feature = "level-files")]
+    pub mod level_file;
+    pub mod parameters;
     pub mod particle_manager;
     pub mod player;
+    pub mod random;
+    pub mod replay;
+    pub mod save;
+    pub mod score;
+    pub mod simulation;
     pub mod ui;
     pub mod utils;
+    pub mod world;
 
     pub mod bullet_types {
+        pub mod accelerating;
+        pub mod bouncing;
         pub mod constant_velocity;
+        pub mod homing;
+        pub mod sine_wave;
     }
 
     pub mod enemy_types {
@@ -80,16 +88,21 @@ pub mod gameplay {
     }
 
     pub mod particle_types {
+        pub mod dissolve;
         pub mod explosion;
     }
 }
 
 impl GameState {
-    fn new(ctx: &mut Context) -> tetra::Result<GameState> {
+    fn new(
+        ctx: &mut Context,
+        input_source: InputSource,
+        total_players: usize,
+    ) -> tetra::Result<GameState> {
         // let initial_scene = second_scene::SecondScene::new(ctx)?;
         // let initial_scene = AnimationPreview::new(ctx)?;
         // let initial_scene = EnemySandboxScene::new()?;
-        let initial_scene = GamePlayScene::new(ctx)?;
+        let initial_scene = GamePlayScene::new(ctx, input_source, total_players)?;
 
         Ok(GameState {
             scenes: vec![Box::new(initial_scene)],
@@ -158,8 +171,77 @@ impl State for GameState {
 }
 
 use tetra::time::Timestep;
+/// Read value after "name" from command line arguments. Ex: "--frames 600"
+fn value_from_args<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => args.get(index + 1).map(|v| v.as_str()),
+        None => None,
+    }
+}
+
+fn number_from_args(args: &[String], name: &str) -> Option<u64> {
+    match value_from_args(args, name) {
+        Some(value) => match value.parse::<u64>() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                println!("Invalid {} value: {}", name, value);
+                None
+            }
+        },
+        None => None,
+    }
+}
+
 /// Setup window size, window title, etc.
+///
+/// Arguments:
+/// * --headless [--frames N] [--seed N] [--replay PATH]: run without window
+/// * --bless-patterns: write bullet pattern spawn lists into golden files. `cargo test` compares them
+/// * --bless-bullets: write trajectories of every BulletType into golden files. `cargo test` compares them
+/// * --record PATH: record player input into replay file. Runs after a restart go into PATH with a run number. Ex: run-2.replay
+/// * --replay PATH: play replay file
+/// * --players N: local co-op. Player 2, 3, ... use gamepad
 fn main() -> tetra::Result {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--bless-bullets") {
+        if gameplay::bullet_trajectory::bless_trajectories() == false {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    #[cfg(feature = "level-files")]
+    {
+        if args.iter().any(|arg| arg == "--bless-patterns") {
+            if gameplay::bullet_pattern_file::bless_patterns(
+                gameplay::bullet_pattern::BULLET_PATTERN_PATH,
+            ) == false
+            {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+    }
+
+    if args.iter().any(|arg| arg == "--headless") {
+        headless::run(
+            number_from_args(&args, "--frames"),
+            number_from_args(&args, "--seed"),
+            value_from_args(&args, "--replay"),
+        );
+        return Ok(());
+    }
+
+    let input_source = match value_from_args(&args, "--replay") {
+        Some(path) => InputSource::Replay(String::from(path)),
+        None => match value_from_args(&args, "--record") {
+            Some(path) => InputSource::Recording(String::from(path)),
+            None => InputSource::Keyboard,
+        },
+    };
+
+    let total_players = number_from_args(&args, "--players").unwrap_or(1) as usize;
+
     let scale = 1;
     ContextBuilder::new(
         "One man",
@@ -173,5 +255,5 @@ fn main() -> tetra::Result {
     .borderless(false)
     .fullscreen(false)
     .build()?
-    .run(GameState::new)
+    .run(|ctx| GameState::new(ctx, input_source, total_players))
 }
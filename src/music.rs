@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use crate::audio_manager::AudioManager;
+
+/// Music made from sounds in AudioManager. Sounds should be on AudioBus::Music.
+#[derive(Debug, Clone)]
+pub struct MusicTrack {
+    /// Sound that plays once before the loop. None: start with the loop.
+    pub intro: Option<String>,
+    /// Sound that repeats until the track is stopped.
+    pub loop_section: String,
+    pub volume: f32,
+}
+
+impl MusicTrack {
+    pub fn new(intro: &str, loop_section: &str, volume: f32) -> MusicTrack {
+        let intro = match intro.len() {
+            0 => None,
+            _ => Some(String::from(intro)),
+        };
+
+        MusicTrack {
+            intro: intro,
+            loop_section: String::from(loop_section),
+            volume: volume,
+        }
+    }
+}
+
+/// A track that is playing or fading out.
+struct PlayingTrack {
+    name: String,
+    track: MusicTrack,
+    /// The intro is playing. The loop starts after it finished.
+    in_intro: bool,
+    /// Fade level. 0.0 - 1.0
+    level: f32,
+    /// Level change per ms. Negative: fading out.
+    fade_speed: f32,
+}
+
+impl PlayingTrack {
+    fn playing_key(&self) -> &str {
+        match (self.in_intro, self.track.intro.as_ref()) {
+            (true, Some(intro)) => intro.as_str(),
+            _ => self.track.loop_section.as_str(),
+        }
+    }
+
+    fn stop(&self, audio: &mut AudioManager) {
+        match self.track.intro.as_ref() {
+            Some(intro) => audio.stop(intro),
+            None => (),
+        };
+        audio.stop(self.track.loop_section.as_str());
+    }
+}
+
+/// Play one music track at a time. Changing track crossfades the old one into the new one.
+pub struct MusicController {
+    tracks: HashMap<String, MusicTrack>,
+    current: Option<PlayingTrack>,
+    fading_out: Vec<PlayingTrack>,
+}
+
+impl MusicController {
+    pub fn new() -> MusicController {
+        MusicController {
+            tracks: HashMap::new(),
+            current: None,
+            fading_out: vec![],
+        }
+    }
+
+    pub fn add_track(&mut self, name: &str, track: MusicTrack) {
+        self.tracks.insert(String::from(name), track);
+    }
+
+    /// Name of the track that is playing. None: no music or the music is fading out.
+    pub fn current_track(&self) -> Option<&str> {
+        match self.current.as_ref() {
+            Some(playing) => Some(playing.name.as_str()),
+            None => None,
+        }
+    }
+
+    /// Start the track. Nothing changes if the track is already playing.
+    ///
+    /// # Arguments:
+    ///
+    /// * name - name of the track in add_track()
+    /// * crossfade - fade time between the current track and the new track. (ms) 0: switch immediately
+    ///
+    pub fn play(&mut self, audio: &mut AudioManager, name: &str, crossfade: u128) {
+        if self.current_track() == Some(name) {
+            return;
+        }
+
+        let track = match self.tracks.get(name) {
+            Some(v) => v.clone(),
+            None => {
+                println!("Play music error: unknown track {}", name);
+                return;
+            }
+        };
+
+        self.fade_out(audio, crossfade);
+
+        // The same sound can not fade in and out at the same time.
+        let mut index = 0;
+        while index < self.fading_out.len() {
+            if self.fading_out[index].track.loop_section == track.loop_section {
+                let old = self.fading_out.remove(index);
+                old.stop(audio);
+            } else {
+                index += 1;
+            }
+        }
+
+        let playing = PlayingTrack {
+            name: String::from(name),
+            in_intro: track.intro.is_some(),
+            level: if crossfade == 0 { 1.0 } else { 0.0 },
+            fade_speed: MusicController::fade_speed(crossfade),
+            track: track,
+        };
+
+        let volume = playing.track.volume * playing.level;
+        if playing.in_intro {
+            audio.play(playing.playing_key(), volume);
+        } else {
+            audio.play_repeating(playing.playing_key(), volume);
+        }
+
+        self.current = Some(playing);
+    }
+
+    /// Fade out the current track in "duration" (ms). 0: stop immediately.
+    pub fn fade_out(&mut self, audio: &mut AudioManager, duration: u128) {
+        match self.current.take() {
+            Some(mut playing) => {
+                if duration == 0 {
+                    playing.stop(audio);
+                } else {
+                    playing.fade_speed = -MusicController::fade_speed(duration);
+                    self.fading_out.push(playing);
+                }
+            }
+            None => (),
+        };
+    }
+
+    /// Move fades by "dt" (ms) and start the loop after the intro finished.
+    pub fn update(&mut self, audio: &mut AudioManager, dt: u128) {
+        match self.current.as_mut() {
+            Some(playing) => {
+                playing.level = (playing.level + playing.fade_speed * dt as f32).min(1.0);
+
+                if playing.in_intro && audio.is_playing(playing.playing_key()) == false {
+                    playing.in_intro = false;
+                    audio.play_repeating(
+                        playing.track.loop_section.as_str(),
+                        playing.track.volume * playing.level,
+                    );
+                }
+
+                audio.set_volume(playing.playing_key(), playing.track.volume * playing.level);
+            }
+            None => (),
+        };
+
+        for playing in self.fading_out.iter_mut() {
+            playing.level = (playing.level + playing.fade_speed * dt as f32).max(0.0);
+            audio.set_volume(playing.playing_key(), playing.track.volume * playing.level);
+            if playing.level == 0.0 {
+                playing.stop(audio);
+            }
+        }
+        self.fading_out.retain(|playing| playing.level > 0.0);
+    }
+
+    /// Level change per ms for fade in "duration" (ms).
+    fn fade_speed(duration: u128) -> f32 {
+        match duration {
+            0 => 1.0,
+            _ => 1.0 / duration as f32,
+        }
+    }
+}
@@ -1,22 +1,46 @@
-use rand::prelude::*;
 use std::collections::VecDeque;
 
 use tetra::graphics::{self, Camera, Color, GeometryBuilder, Rectangle, ShapeStyle};
 use tetra::input::{self, Key};
 use tetra::math::Vec2;
 use tetra::Context;
-use tetra::audio::{Sound, SoundInstance, SoundState};
-
+use crate::audio_manager::{AudioBus, AudioManager, SoundDefinition, TetraAudioBackend};
 use crate::image_assets::{ ImageAssets};
+use crate::music::{MusicController, MusicTrack};
 use crate::scene::{Scene, Transition};
 
-use crate::gameplay::bullet_pool::{Bullet, BulletPool};
-use crate::gameplay::enemy_manager::{Enemy, EnemyManager};
+use crate::gameplay::camera_effects::TravelCurve;
+use crate::gameplay::camera_path::NodeTravel;
+use crate::gameplay::debug_overlay::DebugOverlay;
 use crate::gameplay::level::{EnemySpawnNode, Level,  PatternNode};
-use crate::gameplay::particle_manager::{ParticleDrawLayer, ParticleManager};
-use crate::gameplay::player::{Player, WeaponType};
+use crate::gameplay::particle_manager::ParticleDrawLayer;
+use crate::gameplay::player::HIT_POINT_RADIUS;
+use crate::gameplay::input::{Bindings, Button, GamepadInput, Keyboard, BINDINGS_PATH};
+use crate::gameplay::random::GameRng;
+use crate::gameplay::replay::{run_path, RecordingInput, Replay, ReplayHeader, ReplayInput};
+use crate::gameplay::save::{SaveData, MAX_NAME_LENGTH};
+use crate::gameplay::simulation::{Simulation, SimulationState};
 use crate::gameplay::ui::UI;
 
+/// Level that the game start with.
+pub const DEFAULT_LEVEL_ID: &str = "level-1";
+/// Crossfade time when a camera node changes the music. (ms)
+const MUSIC_CROSSFADE: u128 = 2000;
+/// Fade out time of the music on LevelCleared and GameOver. (ms)
+const MUSIC_FADE_OUT: u128 = 3000;
+/// Show and hide hitboxes, camera nodes, pool usage and frame times.
+const DEBUG_OVERLAY_KEY: Key = Key::F3;
+
+/// Where the player input come from.
+#[derive(Clone)]
+pub enum InputSource {
+    Keyboard,
+    /// Keyboard. Every frame is recorded into the replay file.
+    Recording(String),
+    /// Play the replay file. It uses seed and level from the file.
+    Replay(String),
+}
+
 enum GamePlayState {
     Loading,
     Preparing,
@@ -26,72 +50,55 @@ enum GamePlayState {
 }
 
 pub struct GamePlayScene {
-    reach_camera_target: bool,
-    camera_target_position: Vec2<f32>,
     camera: Camera,
-    player: Player,
+    simulation: Simulation,
     image_assets: ImageAssets,
     state: GamePlayState,
-    enemy_manager: EnemyManager,
-    particle_manager: ParticleManager,
-    level: Level,
-    waiting_time: u128,
-
-    bgm: Option<SoundInstance>,
+    input_source: InputSource,
+    /// Number of this run since the game started. (1: the first) Recording uses a new replay file per run.
+    run: usize,
+    total_players: usize,
+    /// Restart button of every player with its gamepad id. Any player can restart after game over.
+    restart_buttons: Vec<(usize, Button)>,
+    level_id: String,
+
+    save_data: SaveData,
+    save_path: String,
+    /// Name that is typing on LevelCleared screen. None: no name entry.
+    name_entry: Option<String>,
+    /// Rank of this run in the high score table. (0: the first)
+    high_score_rank: Option<usize>,
+
+    audio: AudioManager,
+    music: MusicController,
     ui: UI,
+    debug_overlay: DebugOverlay,
 }
 
 impl GamePlayScene {
-    pub fn new(ctx: &mut Context) -> tetra::Result<GamePlayScene> {
+    /// # Arguments:
+    ///
+    /// * input_source - input of player 1
+    /// * total_players - player 1 use keyboard. Player 2, 3, ... use gamepad 1, 2, ...
+    ///   Recording and replay support only 1 player.
+    ///
+    pub fn new(
+        ctx: &mut Context,
+        input_source: InputSource,
+        total_players: usize,
+    ) -> tetra::Result<GamePlayScene> {
+        GamePlayScene::new_run(ctx, input_source, total_players, 1)
+    }
+
+    /// Start the nth run. The replay file of the recording gets a run number after the first run.
+    fn new_run(
+        ctx: &mut Context,
+        input_source: InputSource,
+        total_players: usize,
+        run: usize,
+    ) -> tetra::Result<GamePlayScene> {
         let camera = Camera::new(crate::SCREEN_WIDTH, crate::SCREEN_HEIGHT);
 
-        {
-            let mut camera_position = crate::CAMERA_POSITION.lock().unwrap();
-            camera_position.x = 0.0;
-            camera_position.y = -crate::SCREEN_HEIGHT * 0.3;
-        }
-
-        {
-            let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-            play_sound_nodes.clear();
-        }
-
-        {
-            let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
-            bullet_pool.clear();
-        }
-        
-        {
-            let mut bullet_spawn_nodes = crate::BULLET_SPAWN_NODES.lock().unwrap();
-            bullet_spawn_nodes.clear();
-        }
-        
-        {
-            let mut bullet_type_bank = crate::BULLET_TYPE_BANK.lock().unwrap();
-            bullet_type_bank.clear();
-        }
-    
-        {
-            let mut enemy_type_bank = crate::ENEMY_TYPE_BANK.lock().unwrap();
-            enemy_type_bank.clear();
-        }
-        
-        {
-            let mut enemy_spawn_nodes = crate::ENEMY_SPAWN_NODES.lock().unwrap();
-            enemy_spawn_nodes.clear();
-        }
-
-        {
-            let mut partcie_type_bank = crate::PARTICLE_TYPE_BANK.lock().unwrap();
-            partcie_type_bank.clear();
-        }
-        
-        {
-            let mut particle_spawn_nodes = crate::PARTICLE_SPAWN_NODES.lock().unwrap();
-            particle_spawn_nodes.clear();
-        }
-  
-
         let texture_list = vec![];
         let mut image_assets = ImageAssets::new(texture_list);
 
@@ -130,58 +137,223 @@ impl GamePlayScene {
             .unwrap();
         image_assets.add_mesh("player-rect", simple);
 
+        let player_hit_point = GeometryBuilder::new()
+            .set_color(Color::RED)
+            .circle(ShapeStyle::Fill, Vec2::zero(), HIT_POINT_RADIUS)
+            .unwrap()
+            .build_mesh(ctx)
+            .unwrap();
+        image_assets.add_mesh("player-hit-point", player_hit_point);
+
         setup_textures(&mut image_assets);
 
-        let mut level = Level::new();
-        setup_level_for_spawning_enemies(&mut level);
-        
-       
+        let replay = match &input_source {
+            InputSource::Replay(path) => match Replay::load(path) {
+                Ok(replay) => Some(replay),
+                Err(e) => {
+                    println!("Load replay error: {}", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        let (seed, level_id) = match replay.as_ref() {
+            Some(replay) => (replay.header.seed, replay.header.level_id.clone()),
+            None => (GameRng::random_seed(), String::from(DEFAULT_LEVEL_ID)),
+        };
+
+        let total_players = match input_source {
+            InputSource::Keyboard => total_players.max(1),
+            _ => {
+                if total_players > 1 {
+                    println!("Recording and replay support only 1 player");
+                }
+                1
+            }
+        };
+
+        let bindings = Bindings::load_or_preset(BINDINGS_PATH);
+        // Gamepads belong to player 2, 3, ... in multiplayer
+        let keyboard_bindings = match total_players {
+            1 => bindings.clone(),
+            _ => bindings.keys_only(),
+        };
+
+        let save_path = SaveData::default_path();
+        let save_data = SaveData::load_or_default(save_path.as_str());
+
+        let sound_list = setup_sounds(&image_assets.take_sounds());
+        let mut audio = AudioManager::new(
+            Box::new(TetraAudioBackend::new(ctx, &sound_list)),
+            sound_list,
+        );
+        audio.set_volumes(
+            save_data.settings.master_volume,
+            save_data.settings.music_volume,
+            save_data.settings.sfx_volume,
+        );
+
+        let level = create_level(level_id.as_str(), &image_assets);
+        let mut simulation = Simulation::new(level, seed, total_players);
+
+        let mut restart_buttons = vec![(0, Button::new(keyboard_bindings.restart.clone()))];
+        let mut index = 0;
+        for player in simulation.players.iter_mut() {
+            if index > 0 {
+                player.set_controller(Box::new(GamepadInput::new(index - 1, &bindings)));
+                restart_buttons.push((
+                    index - 1,
+                    Button::new(bindings.gamepad_only().restart.clone()),
+                ));
+            }
+            index += 1;
+        }
+
+        match replay {
+            Some(replay) => {
+                simulation.players[0].set_controller(Box::new(ReplayInput::new(replay)));
+            }
+            None => {
+                let keyboard = Box::new(Keyboard::new_with_bindings(&keyboard_bindings));
+                match &input_source {
+                    InputSource::Recording(path) => {
+                        let header = ReplayHeader::new(seed, level_id.as_str());
+                        let path = run_path(path, run);
+                        match RecordingInput::new(keyboard, path.as_str(), &header) {
+                            Ok(recording) => simulation.players[0].set_controller(Box::new(recording)),
+                            Err(e) => {
+                                println!("Recording error: {}", e);
+                                simulation.players[0].set_controller(Box::new(
+                                    Keyboard::new_with_bindings(&keyboard_bindings),
+                                ));
+                            }
+                        };
+                    }
+                    _ => simulation.players[0].set_controller(keyboard),
+                };
+            }
+        };
+
         Ok(GamePlayScene {
-            reach_camera_target: false,
-            camera_target_position: Vec2::new(0.0, -crate::SCREEN_HEIGHT * 0.3),
             camera: camera,
-            player: Player::new(ctx, 1),
+            simulation: simulation,
             image_assets: image_assets,
             state: GamePlayState::Loading,
-            enemy_manager: EnemyManager::new(),
-            particle_manager: ParticleManager::new(),
-            level: level,
-            waiting_time: 1500,
-            bgm: None,
+            input_source: input_source,
+            run: run,
+            total_players: total_players,
+            restart_buttons: restart_buttons,
+            level_id: level_id,
+            save_data: save_data,
+            save_path: save_path,
+            name_entry: None,
+            high_score_rank: None,
+            audio: audio,
+            music: setup_music(),
             ui: UI::new(),
+            debug_overlay: DebugOverlay::new(),
         })
     }
 
-    fn play_sound(ctx: &mut Context, path: &str, volume: f32) -> Option<SoundInstance>
-    {
-        match Sound::new(path)
-        {
-            Ok(s) => {
-
-                match s.play_with(ctx, volume, 1.0)
-                {
-                    Ok(instance) => {
-                        Some(instance)
-                    },
-                    Err(e) =>
-                    {
-                        println!("Play sound error: {} {}", path, e);
-                        None
+    /// Step the simulation and keep the time it takes for the debug overlay.
+    fn step_simulation(&mut self) {
+        let start = std::time::Instant::now();
+        self.simulation.step(&self.image_assets);
+        self.debug_overlay.set_step_time(start.elapsed());
+    }
+
+    /// The run is over. Save the replay file before the game can restart.
+    fn finish_inputs(&mut self) {
+        for player in self.simulation.players.iter_mut() {
+            player.finish_input();
+        }
+    }
+
+    /// Follow the simulation camera with shake, rotation and zoom from its camera effects.
+    fn update_camera(&mut self) {
+        let camera_effects = self.simulation.camera_effects();
+        self.camera.position = self.simulation.camera_position() + camera_effects.offset();
+        self.camera.rotation = camera_effects.rotation();
+        self.camera.zoom = camera_effects.zoom();
+        self.camera.update();
+    }
+
+    /// Record clear time and unlock content of the level. Start name entry when the score get into the high score table.
+    /// Replay does not change the save file.
+    fn level_cleared(&mut self) {
+        match self.input_source {
+            InputSource::Replay(_) => return,
+            _ => (),
+        };
+
+        let score = self.simulation.score();
+        let time = score.stats().total_time();
+        if self.save_data.record_clear_time(self.level_id.as_str(), time) {
+            println!("New best time of {}: {} ms", self.level_id, time);
+        }
+        self.save_data.unlock(self.level_id.as_str());
+
+        if self.save_data.is_high_score(score.score()) {
+            self.name_entry = Some(String::new());
+        } else {
+            self.write_save_file();
+        }
+    }
+
+    /// Read typed text, Backspace and Enter for the name in high score table.
+    fn update_name_entry(&mut self, ctx: &mut Context) {
+        let name = match self.name_entry.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
+
+        match input::get_text_input(ctx) {
+            Some(text) => {
+                for c in text.chars() {
+                    if (c.is_ascii_alphanumeric() || c == ' ') && name.len() < MAX_NAME_LENGTH {
+                        name.push(c.to_ascii_uppercase());
                     }
                 }
             }
-            , 
-            Err(e) =>
-            {
-                println!("Play sound error: {} {}", path, e);
-                None
-            }
+            None => (),
+        };
+
+        if input::is_key_pressed(ctx, Key::Backspace) {
+            name.pop();
+        }
+
+        if input::is_key_pressed(ctx, Key::Enter) && name.trim().len() > 0 {
+            let name = name.clone();
+            let score = self.simulation.score();
+            self.high_score_rank = self.save_data.add_high_score(
+                name.as_str(),
+                score.score(),
+                self.level_id.as_str(),
+                score.stats().total_time(),
+            );
+            self.name_entry = None;
+            self.write_save_file();
         }
     }
+
+    fn write_save_file(&self) {
+        match self.save_data.save(self.save_path.as_str()) {
+            Ok(_) => (),
+            Err(e) => println!("Save error: {}", e),
+        };
+    }
 }
 
 impl Scene for GamePlayScene {
     fn update(&mut self, ctx: &mut Context) -> tetra::Result<Transition> {
+        if input::is_key_pressed(ctx, DEBUG_OVERLAY_KEY) {
+            match self.state {
+                GamePlayState::Loading | GamePlayState::Preparing => (),
+                _ => self.debug_overlay.toggle(),
+            };
+        }
+
         match self.state {
             GamePlayState::Loading => {
                 if self.image_assets.is_loading() == true {
@@ -196,129 +368,90 @@ impl Scene for GamePlayScene {
                 // Load all animations in the scene
                 setup_animations(&mut self.image_assets);
 
-                self.bgm = GamePlayScene::play_sound(ctx, "./resources/bgm/a.mp3", 0.25);
+                self.music.play(&mut self.audio, "level", 0);
 
-                {
-                    let mut bullet_type_bank = crate::BULLET_TYPE_BANK.lock().unwrap();
-                    bullet_type_bank.setup(ctx, &self.image_assets);
-                }
-
-                self.player.setup(&mut self.image_assets);
+                self.simulation.setup(&mut self.image_assets);
 
                 // Animations have to load before setup UI.
                 self.ui.setup(ctx, &self.image_assets);
+                self.debug_overlay.setup(ctx);
 
-                // Setup particle manager, particle type bank
-                {
-                    let mut particle_type_bank = crate::PARTICLE_TYPE_BANK.lock().unwrap();
-                    particle_type_bank.setup(&self.image_assets);
-                }
-
-                {
-                    let mut required_list = vec![];
-                    required_list.push(0);
-                    required_list.push(1);
-                    required_list.push(2);
-                    required_list.push(3);
-
-                    let mut enemy_type_bank = crate::ENEMY_TYPE_BANK.lock().unwrap();
-                    enemy_type_bank.setup(&self.image_assets, &required_list);
-                }
-
-                // Set to camera target node to "start"
-                self.level.set_current_node("start");
-
-                {
-                    let camera_target_position = crate::CAMERA_POSITION.lock().unwrap();
-                    self.camera.position = *camera_target_position;
-                }
+                self.camera.position = self.simulation.camera_position();
 
                 self.state = GamePlayState::Playing;
             }
             GamePlayState::Playing => {
-                self.update_camera_position();
-                
-                self.player.update(ctx, &self.image_assets);
-                // Update active enemy and remove inactive enemy
-                self.enemy_manager
-                    .update_active_enemies(Some(&self.player), &self.image_assets);
-
-                self.particle_manager.update(&self.image_assets);
-
-                // Update active bullets and remove inactive bullets
-                BulletPool::update_active_enemies_bullets();
-                BulletPool::update_active_player_bullets();
-
-                // Check enemy spawn patter for this cameranode
-                self.level.update();
-                // Spawn enemy that put in the queue by Level
-
-                self.spawn_enemy_in_the_queue();
-
-                // Spawn bullet
-                BulletPool::spawn_bullets_from_queue(&self.image_assets);
-
-                // Check GameOver game state
-                if self.player.alive() == false {
-                    self.state = GamePlayState::GameOver;
+                for player in self.simulation.players.iter_mut() {
+                    player.update_input(ctx);
                 }
+                self.step_simulation();
 
-                // Do collision detects between objects
-                self.call_hit_checks();
+                self.update_camera();
 
-                // Check that all enemies killed and no pattern for spawning enemy before fetch the next node.
-                self.fetching_next_camera_target();
+                for (key, volume) in self.simulation.drain_sounds().iter() {
+                    self.audio.play(key, *volume);
+                }
+                for track in self.simulation.drain_music().iter() {
+                    self.music.play(&mut self.audio, track, MUSIC_CROSSFADE);
+                }
 
-                {
-                    let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-                    for (_, (path, volume)) in play_sound_nodes.iter()
-                    {
-                        GamePlayScene::play_sound(ctx, path, *volume);
+                match self.simulation.state() {
+                    SimulationState::Playing => (),
+                    SimulationState::LevelCleared => {
+                        self.state = GamePlayState::LevelCleared;
+                        self.finish_inputs();
+                        self.music.fade_out(&mut self.audio, MUSIC_FADE_OUT);
+                        self.level_cleared();
                     }
-                    play_sound_nodes.clear();
-                }
+                    SimulationState::GameOver => {
+                        self.state = GamePlayState::GameOver;
+                        self.finish_inputs();
+                        self.music.fade_out(&mut self.audio, MUSIC_FADE_OUT);
+                    }
+                };
 
                 self.ui.update(ctx);
             }
             GamePlayState::LevelCleared => {
                 self.ui.update(ctx);
-                self.player.update(ctx, &self.image_assets);
-                // Update active enemy and remove inactive enemy
-                self.enemy_manager
-                    .update_active_enemies(Some(&self.player), &self.image_assets);
-
-                self.particle_manager.update(&self.image_assets);
-
-                // Update active bullets and remove inactive bullets
-                BulletPool::update_active_enemies_bullets();
-                BulletPool::update_active_player_bullets();
+                self.update_name_entry(ctx);
+                for player in self.simulation.players.iter_mut() {
+                    player.update_input(ctx);
+                }
+                self.step_simulation();
+                self.update_camera();
             }
             GamePlayState::GameOver => {
                 self.ui.update(ctx);
-                self.player.update(ctx, &self.image_assets);
-                // Update active enemy and remove inactive enemy
-                self.enemy_manager
-                    .update_active_enemies(Some(&self.player), &self.image_assets);
-
-                self.particle_manager.update(&self.image_assets);
-
-                // Update active bullets and remove inactive bullets
-                BulletPool::update_active_enemies_bullets();
-                BulletPool::update_active_player_bullets();
-
-                if input::is_key_released(ctx, Key::Z)
-                {
-                    if self.bgm.is_some()
-                    {
-                        self.bgm.as_mut().unwrap().stop();
-                    }
+                for player in self.simulation.players.iter_mut() {
+                    player.update_input(ctx);
+                }
+                self.step_simulation();
+                self.update_camera();
+
+                let mut restart = false;
+                for (gamepad_id, button) in self.restart_buttons.iter_mut() {
+                    button.update(ctx, *gamepad_id);
+                    restart = restart || button.is_released();
+                }
 
-                    return Ok(Transition::Replace(Box::new(GamePlayScene::new(ctx)?)));
+                if restart {
+                    self.audio.stop_all();
+
+                    return Ok(Transition::Replace(Box::new(GamePlayScene::new_run(
+                        ctx,
+                        self.input_source.clone(),
+                        self.total_players,
+                        self.run + 1,
+                    )?)));
                 }
                 
             }
         }
 
+        self.music.update(&mut self.audio, crate::ONE_FRAME.as_millis());
+        self.audio.update(crate::ONE_FRAME.as_millis());
+
         Ok(Transition::None)
     }
 
@@ -327,7 +460,7 @@ impl Scene for GamePlayScene {
         graphics::clear(ctx, Color::rgb8(255, 241, 232));
 
         let bg_key = String::from("bg");
-        for (_, camera_node) in self.level.all_nodes().iter() {
+        for (_, camera_node) in self.simulation.level.all_nodes().iter() {
             match self.image_assets.get(&bg_key) {
                 Some(bg) => {
                     graphics::draw(
@@ -351,446 +484,146 @@ impl Scene for GamePlayScene {
         };
         
 
-        self.particle_manager
-            .draw(ParticleDrawLayer::Bottomest, ctx, &self.image_assets);
-
-        self.enemy_manager.draw(ctx, &self.image_assets);
+        self.simulation.particle_manager.draw(
+            ParticleDrawLayer::Bottomest,
+            ctx,
+            &self.simulation.particle_type_bank,
+            &self.image_assets,
+        );
 
-        self.particle_manager
-            .draw(ParticleDrawLayer::Explosion, ctx, &self.image_assets);
+        self.simulation.enemy_manager.draw(
+            ctx,
+            &self.simulation.enemy_type_bank,
+            &self.image_assets,
+        );
 
-        self.player.draw(ctx, &self.image_assets);
+        self.simulation.particle_manager.draw(
+            ParticleDrawLayer::Explosion,
+            ctx,
+            &self.simulation.particle_type_bank,
+            &self.image_assets,
+        );
 
-        self.particle_manager
-            .draw(ParticleDrawLayer::BulletHit, ctx, &self.image_assets);
+        for player in self.simulation.players.iter_mut() {
+            player.draw(ctx, &self.image_assets);
+        }
 
-        self.particle_manager
-            .draw(ParticleDrawLayer::FiringBullet, ctx, &self.image_assets);
+        self.simulation.particle_manager.draw(
+            ParticleDrawLayer::BulletHit,
+            ctx,
+            &self.simulation.particle_type_bank,
+            &self.image_assets,
+        );
 
-        BulletPool::draw_active_player_bullets(ctx, &mut self.image_assets);
-        BulletPool::draw_active_enemies_bullets(ctx, &mut self.image_assets);
+        self.simulation.particle_manager.draw(
+            ParticleDrawLayer::FiringBullet,
+            ctx,
+            &self.simulation.particle_type_bank,
+            &self.image_assets,
+        );
 
-        self.particle_manager
-            .draw(ParticleDrawLayer::Topest, ctx, &self.image_assets);
-        self.ui.draw_crosshair(
+        self.simulation.bullet_pool.draw_active_player_bullets(
             ctx,
+            &self.simulation.bullet_type_bank,
             &mut self.image_assets,
-            self.player.get_weapon_type(),
-            self.player.get_crosshair_position(),
         );
-        graphics::reset_transform_matrix(ctx);
-
-        self.ui.draw_energy_bar(
+        self.simulation.bullet_pool.draw_active_enemies_bullets(
             ctx,
+            &self.simulation.bullet_type_bank,
             &mut self.image_assets,
-            self.player.get_health_percentage(),
         );
 
-        self.ui
-            .draw_weapon(ctx, &mut self.image_assets, self.player.get_weapon_type());
+        self.simulation.particle_manager.draw(
+            ParticleDrawLayer::Topest,
+            ctx,
+            &self.simulation.particle_type_bank,
+            &self.image_assets,
+        );
+        for player in self.simulation.players.iter() {
+            if player.alive() {
+                self.ui.draw_crosshair(
+                    ctx,
+                    &mut self.image_assets,
+                    player.get_weapon_type(),
+                    player.get_crosshair_position(),
+                );
+            }
+        }
+        self.debug_overlay.draw_world(ctx, &self.simulation);
+        graphics::reset_transform_matrix(ctx);
+
+        let mut player_index = 0;
+        for player in self.simulation.players.iter() {
+            self.ui.draw_energy_bar(
+                ctx,
+                &mut self.image_assets,
+                player.get_health_percentage(),
+                player_index,
+            );
+
+            self.ui.draw_weapon(
+                ctx,
+                &mut self.image_assets,
+                player.get_weapon_type(),
+                player_index,
+            );
+
+            player_index += 1;
+        }
 
         match self.state
         {
             GamePlayState::GameOver => {
-                self.ui.draw_game_over(ctx);
+                self.ui.draw_game_over(ctx, self.simulation.score());
             },
             GamePlayState::LevelCleared => {
-                self.ui.draw_level_cleared(ctx);
+                self.ui.draw_level_cleared(ctx, self.simulation.score());
+                match self.name_entry.as_ref() {
+                    Some(name) => self.ui.draw_name_entry(ctx, name),
+                    None => self.ui.draw_high_scores(ctx, &self.save_data, self.high_score_rank),
+                };
             }
             _ => {
-                
+                self.ui.draw_score(ctx, self.simulation.score());
             }
         };
-    }
-}
-
-impl GamePlayScene {
-    /// interporate between current camera position and latest target position. (from CAMERA_POSITION)
-    fn update_camera_position(&mut self) {
-        self.camera.update();
-
-        if self.reach_camera_target == false {
-            let move_speed = 2.0;
-            let camera_target_position = self.camera_target_position;
-
-            if self.camera.position.x < camera_target_position.x {
-                self.camera.position.x += move_speed
-            } else if self.camera.position.x > camera_target_position.x {
-                self.camera.position.x -= move_speed;
-            }
-
-            if self.camera.position.y < camera_target_position.y {
-                self.camera.position.y += move_speed;
-            } else if self.camera.position.y > camera_target_position.y {
-                self.camera.position.y -= move_speed;
-            }
-
-            let distance_sqr =
-                Vec2::distance_squared(self.camera.position, self.camera_target_position);
-            if distance_sqr <= (move_speed * move_speed) * 1.0 {
-                self.reach_camera_target = true;
-                self.camera.position = self.camera_target_position;
-                println!("camera reach target");
-            }
-
-            let mut camera_position = crate::CAMERA_POSITION.lock().unwrap();
-            *camera_position = self.camera.position;
-        }
-    }
-
-    fn fetching_next_camera_target(&mut self) {
-        // Go next node if possible
-        if self.reach_camera_target == true {
-            if self.enemy_manager.has_active_enemy() == false
-                && self.level.is_spawn_queue_empty() == true
-            {
-                if self.waiting_time == 0 {
-                    match self.level.get_next_node() {
-                        Some(node) => {
-                            self.camera_target_position = node.position;
-                            self.reach_camera_target = false;
-                            let next_node_name = String::from(node.name.as_str());
-                            self.waiting_time = node.waiting_time;
-
-                            self.level.set_current_node(&next_node_name);
-                        }
-                        None => {
-                            self.level.set_current_node("");
-                            self.waiting_time = 1500;
-                        }
-                    };
-                } else {
-                    match self.waiting_time.checked_sub(crate::ONE_FRAME.as_millis()) {
-                        Some(v) => self.waiting_time = v,
-                        None => self.waiting_time = 0,
-                    };
-                }
-            }
-        }
-    }
-
-    fn update_hit_check_between_player_melee_attack_with_enemies_bullets(player: &mut Player) {
-        let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
-        match player.get_weapon_type() {
-            WeaponType::Range => {
-                return;
-            }
-            WeaponType::Melee => {
-                if player.is_attacking() == false {
-                    return;
-                }
-            }
-        }
-
-        for bullet in bullet_pool.enemy_active_bullets.iter_mut() {
-            if bullet.active {
-                if crate::gameplay::utils::is_inside_camera_area(&bullet.position, bullet.radius)
-                    == false
-                {
-                    continue;
-                }
-
-                let total_radius = bullet.radius + Player::get_melee_attack_radius();
-                let distance =
-                    Vec2::distance_squared(player.get_melee_attack_position(), bullet.position);
-                if distance < total_radius * total_radius {
-                    bullet.health = 0;
-
-                    match bullet.extra.get("kill_animation") {
-                        Some(name) => {
-                            let random_size = 8.0;
-                            let random_position = Vec2::new(
-                                random_size / 2.0 - random::<f32>() * random_size,
-                                random_size / 2.0 - random::<f32>() * random_size,
-                            );
-
-                            Bullet::spawn_hitting_particle(
-                                bullet.position + random_position,
-                                format!("idle_animation={}|flip_x=0|", name).as_str(),
-                            );
-                        }
-                        None => (),
-                    };
-                }
-            }
-        }
-    }
-
-    fn update_hit_check_between_player_melee_attack_with_enemies(
-        player: &mut Player,
-        active_enemies: &mut Vec<Enemy>,
-    ) {
-        match player.get_weapon_type() {
-            WeaponType::Range => {
-                return;
-            }
-            WeaponType::Melee => {
-                if player.is_attacking() == false {
-                    return;
-                }
-            }
-        }
-
-        for enemy in active_enemies.iter_mut() {
-            if enemy.active {
-                if crate::gameplay::utils::is_inside_camera_area(&enemy.position, enemy.radius)
-                    == false
-                {
-                    continue;
-                }
-
-                let total_radius = enemy.radius + Player::get_melee_attack_radius();
-                let distance = crate::gameplay::utils::distance_sqr(
-                    player.get_melee_attack_position().x as i128,
-                    player.get_melee_attack_position().y as i128,
-                    enemy.position.x as i128,
-                    enemy.position.y as i128,
-                ) as f32;
-
-                if distance < total_radius * total_radius {
-                    let hit_position = enemy.position;
-                    enemy.get_hit(&hit_position, player.melee_attack_damage());
-                    player.melee_attack_hit_enemy();
-                }
-            }
-        }
-    }
-
-    fn update_hit_check_between_player_bullet_and_enemies(active_enemies: &mut Vec<Enemy>) {
-        let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
-
-        for bullet in bullet_pool.player_active_bullets.iter_mut() {
-            for enemy in active_enemies.iter_mut() {
-                if bullet.active == true && enemy.active {
-                    if crate::gameplay::utils::is_inside_camera_area(
-                        &bullet.position,
-                        bullet.radius,
-                    ) == false
-                    {
-                        break;
-                    }
-
-                    if crate::gameplay::utils::is_inside_camera_area(&enemy.position, enemy.radius)
-                        == false
-                    {
-                        continue;
-                    }
-
-                    let mut break_loop = false;
-                    {
-                        let checking_position = bullet.position;
-
-                        let result = enemy.hit_check(&checking_position, bullet.radius);
-
-                        if result == 1 || result == -1 {
-                            bullet.health -= 1;
-
-                            let bullet_rotation = if bullet.rotation < 0.0 {
-                                bullet.rotation + 1.0
-                            } else {
-                                bullet.rotation
-                            };
-
-                            match bullet.extra.get("hit_animation") {
-                                Some(name) => {
-                                    let random_size = 8.0;
-                                    let random_position = Vec2::new(
-                                        random_size / 2.0 - random::<f32>() * random_size,
-                                        random_size / 2.0 - random::<f32>() * random_size,
-                                    );
-
-                                    let flip_x = if bullet_rotation > 0.25 && bullet_rotation < 0.75
-                                    {
-                                        "flip_x=1"
-                                    } else {
-                                        "flip_x=0"
-                                    };
-
-                                    Bullet::spawn_hitting_particle(
-                                        bullet.position + random_position,
-                                        format!("idle_animation={}|{}|", name, flip_x).as_str(),
-                                    );
-                                }
-                                None => (),
-                            };
-
-                            if result == 1 {
-                                enemy.get_hit(&checking_position, bullet.damage);
-
-                                {
-                                    let sfx_list = ["bullet_hit_1", "bullet_hit_2", "bullet_hit_3"];
-
-                                    let name = sfx_list[random::<usize>() % sfx_list.len()];
-                                    let mut play_sound_nodes = crate::PLAY_SOUND_NODES.lock().unwrap();
-                                    play_sound_nodes.insert(String::from(name), (format!("./resources/sfx/{}.mp3", name), 0.15 ) );
-                                }
-                            }
-
-                            if enemy.health <= 0 {
-                                enemy.active = false;
-                            }
-
-                            if bullet.health <= 0 {
-                                bullet.active = false;
-                                break_loop = true;
-                            }
-
-                            break;
-                        }
-                    }
-
-                    if break_loop {
-                        break;
-                    }
-                }
-            }
-        }
-    }
-
-    fn update_hit_check_between_player_and_enemies(
-        player: &mut Player,
-        active_enemies: &mut Vec<Enemy>,
-    ) {
-        let player_hit_point_position = player.get_hit_point_position();
-        let player_hit_point_radius = player.get_hit_point_radius();
-
-        for enemy in active_enemies.iter_mut() {
-            if enemy.active {
-                if crate::gameplay::utils::is_inside_camera_area(&enemy.position, enemy.radius)
-                    == false
-                {
-                    continue;
-                }
-            }
-
-            if enemy.hit_check(&player_hit_point_position, player_hit_point_radius) != 0 {
-                player.get_hit(2);
-            }
-        }
-    }
-
-    fn update_hit_check_between_player_and_enemies_bullets(player: &mut Player) {
-        let mut bullet_pool = crate::BULLET_POOL.lock().unwrap();
-
-        let player_hit_point_position = player.get_hit_point_position();
-        let player_hit_point_radius = player.get_hit_point_radius();
-
-        for bullet in bullet_pool.enemy_active_bullets.iter_mut() {
-            if bullet.active {
-                if crate::gameplay::utils::is_inside_camera_area(&bullet.position, bullet.radius)
-                    == false
-                {
-                    continue;
-                }
 
-                let mut break_loop = false;
-                {
-                    let checking_position = bullet.position;
-                    
-                    let distance = crate::gameplay::utils::distance_sqr(
-                        player_hit_point_position.x as i128,
-                        player_hit_point_position.y as i128,
-                        checking_position.x as i128,
-                        checking_position.y as i128,
-                    );
-                    let total_radius = player_hit_point_radius + bullet.radius;
-
-                    if distance <= (total_radius * total_radius) as i128 {
-                        bullet.health -= 1;
-
-                        let bullet_rotation = if bullet.rotation < 0.0 {
-                            bullet.rotation + 1.0
-                        } else {
-                            bullet.rotation
-                        };
-
-                        match bullet.extra.get("hit_animation") {
-                            Some(name) => {
-                                let random_size = 8.0;
-                                let random_position = Vec2::new(
-                                    random_size / 2.0 - random::<f32>() * random_size,
-                                    random_size / 2.0 - random::<f32>() * random_size,
-                                );
-
-                                let flip_x = if bullet_rotation > 0.25 && bullet_rotation < 0.75 {
-                                    "flip_x=1"
-                                } else {
-                                    "flip_x=0"
-                                };
-
-                                Bullet::spawn_hitting_particle(
-                                    bullet.position + random_position,
-                                    format!("idle_animation={}|{}|", name, flip_x).as_str(),
-                                );
-                            }
-                            None => (),
-                        };
-
-                        player.get_hit(1);
-
-                        if bullet.health <= 0 {
-                            bullet.active = false;
-                            break_loop = true;
-                        }
-
-                        break;
-                    }
-                }
-
-                if break_loop {
-                    break;
-                }
-            }
-        }
+        self.debug_overlay.record_frame(ctx);
+        self.debug_overlay.draw_screen(ctx, &self.simulation);
     }
 }
 
-impl GamePlayScene {
-    fn spawn_enemy_in_the_queue(&mut self) {
-        // check spawn queue in Level
-        let mut need_to_spawn_enemy_list = crate::ENEMY_SPAWN_NODES.lock().unwrap();
-        if need_to_spawn_enemy_list.len() > 0 {
-            for spawn_node in need_to_spawn_enemy_list.iter() {
-                let world_position = spawn_node.position;
-
-                // Add enemy into active list
-                self.enemy_manager.spawn_enemy(
-                    spawn_node.enemy_type,
-                    world_position,
-                    spawn_node.extra.as_str(),
-                    &self.image_assets,
-                );
-            }
-            need_to_spawn_enemy_list.clear();
-        }
-        // Check level cleared game state.
-        else if need_to_spawn_enemy_list.len() == 0
-            && self.level.is_spawn_queue_empty() == true
-            && self.enemy_manager.has_active_enemy() == false
-            && self.level.get_current_node().is_none() == true
-        {
-            self.state = GamePlayState::LevelCleared;
+/// Load level from level file. Use built-in level when level file has error.
+///
+/// # Arguments:
+///
+/// * level_id - level file name without extension. Ex: "level-1"
+/// * image_assets - for creating the enemy types that check spawn nodes. Animations can be not loaded yet.
+///
+#[cfg(feature = "level-files")]
+pub fn create_level(level_id: &str, image_assets: &ImageAssets) -> Level {
+    let mut enemy_types = crate::gameplay::enemy_manager::EnemyTypeBank::new();
+    enemy_types.setup(image_assets, &crate::gameplay::enemy_manager::ENEMY_TYPES);
+
+    let path = format!("./resources/levels/{}.ron", level_id);
+    match crate::gameplay::level_file::load_level(path.as_str(), &enemy_types) {
+        Ok(level) => level,
+        Err(e) => {
+            println!("Load level error: {}", e);
+            println!("Use built-in level");
+
+            let mut level = Level::new();
+            setup_level_for_spawning_enemies(&mut level);
+            level
         }
     }
+}
 
-    fn call_hit_checks(&mut self) {
-        GamePlayScene::update_hit_check_between_player_bullet_and_enemies(
-            self.enemy_manager.get_mut_active_enemy(),
-        );
-        GamePlayScene::update_hit_check_between_player_and_enemies(
-            &mut self.player,
-            self.enemy_manager.get_mut_active_enemy(),
-        );
-        GamePlayScene::update_hit_check_between_player_and_enemies_bullets(&mut self.player);
-
-        GamePlayScene::update_hit_check_between_player_melee_attack_with_enemies_bullets(
-            &mut self.player,
-        );
-        GamePlayScene::update_hit_check_between_player_melee_attack_with_enemies(
-            &mut self.player,
-            self.enemy_manager.get_mut_active_enemy(),
-        );
-    }
+#[cfg(not(feature = "level-files"))]
+pub fn create_level(_level_id: &str, _image_assets: &ImageAssets) -> Level {
+    let mut level = Level::new();
+    setup_level_for_spawning_enemies(&mut level);
+    level
 }
 
 fn setup_level_for_spawning_enemies(level: &mut Level) {
@@ -857,22 +690,37 @@ fn setup_camera_target_nodes(level: &mut Level) {
             "03",
             Vec2::new(pos_x, -crate::SCREEN_HEIGHT * 0.3),
             300,
-            "boss",
+            "04",
             spawn_pattern,
         );
     }
 
+    // Vertical section. The camera climbs while flying pop corns come in.
     pos_x += crate::SCREEN_WIDTH;
     {
         let mut spawn_pattern = VecDeque::new();
+        spawn_pattern.push_back(PatternNode {
+            delay: 1000,
+            pattern: String::from("04"),
+        });
 
         level.add_camera_target_node(
             "04",
-            Vec2::new(pos_x, -crate::SCREEN_HEIGHT * 0.3),
+            Vec2::new(pos_x, -crate::SCREEN_HEIGHT * 1.3),
             0,
             "05",
             spawn_pattern,
         );
+
+        let mut travel = NodeTravel::new();
+        travel.curve = TravelCurve::Linear;
+        travel.waypoints = vec![Vec2::new(
+            pos_x - crate::SCREEN_WIDTH * 0.5,
+            -crate::SCREEN_HEIGHT * 0.3,
+        )];
+        travel.speed = 1.5;
+        travel.auto_scroll = true;
+        level.set_node_travel("04", travel);
     }
 
     pos_x += crate::SCREEN_WIDTH;
@@ -886,6 +734,16 @@ fn setup_camera_target_nodes(level: &mut Level) {
             "boss",
             spawn_pattern,
         );
+
+        let mut travel = NodeTravel::new();
+        travel.curve = TravelCurve::EaseOut;
+        travel.waypoints = vec![
+            Vec2::new(pos_x - crate::SCREEN_WIDTH * 0.75, -crate::SCREEN_HEIGHT * 1.15),
+            Vec2::new(pos_x - crate::SCREEN_WIDTH * 0.33, -crate::SCREEN_HEIGHT * 0.5),
+        ];
+        travel.spline = true;
+        travel.auto_scroll = true;
+        level.set_node_travel("05", travel);
     }
 
     pos_x += crate::SCREEN_WIDTH;
@@ -904,6 +762,12 @@ fn setup_camera_target_nodes(level: &mut Level) {
             spawn_pattern,
         );
     }
+
+    level.set_node_music("start", "level", "");
+    level.set_node_music("boss", "boss", "");
+    let mut travel = NodeTravel::new();
+    travel.curve = TravelCurve::EaseInOut;
+    level.set_node_travel("boss", travel);
 }
 
 fn setup_level_patterns(level: &mut Level) {
@@ -947,7 +811,7 @@ fn setup_level_patterns(level: &mut Level) {
                 3500,
                 2,
                 Vec2::new(pos_x + crate::SCREEN_WIDTH * 0.5, crate::GROUND - 52.0),
-                "rotation=0.35|",
+                "rotation=0.35|palette=pop-corn-caramel|",
             ));
             level.add_pattern("02", pattern);
         }
@@ -986,6 +850,27 @@ fn setup_level_patterns(level: &mut Level) {
     }
 
     pos_x += crate::SCREEN_WIDTH;
+    {
+        {
+            let mut pattern = VecDeque::new();
+
+            pattern.push_back(EnemySpawnNode::new(
+                0,
+                1,
+                Vec2::new(pos_x - crate::SCREEN_WIDTH * 0.3, -crate::SCREEN_HEIGHT * 0.9),
+                "palette=pop-corn-mint|",
+            ));
+
+            pattern.push_back(EnemySpawnNode::new(
+                1500,
+                1,
+                Vec2::new(pos_x + crate::SCREEN_WIDTH * 0.3, -crate::SCREEN_HEIGHT * 1.4),
+                "palette=pop-corn-mint|",
+            ));
+
+            level.add_pattern("04", pattern);
+        }
+    }
     pos_x += crate::SCREEN_WIDTH;
     pos_x += crate::SCREEN_WIDTH;
     {
@@ -1004,598 +889,70 @@ fn setup_level_patterns(level: &mut Level) {
     }
 }
 
-fn setup_textures(image_assets: &mut ImageAssets) {
-    image_assets.add_content("bg", "./resources/bg.png");
-
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("ui-bar-{}", index).as_str(),
-            format!("./resources/ui/bar/{}.png", index).as_str(),
-        );
-    }
-    image_assets.add_content("ui-bar-bg", "./resources/ui/bar/bg.png");
-    image_assets.add_content("ui-bar-fg", "./resources/ui/bar/fg.png");
-
-    for index in 1..9 {
-        image_assets.add_content(
-            format!("ui-circle-{}", index).as_str(),
-            format!("./resources/ui/circle/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("ui-crosshair-{}", index).as_str(),
-            format!("./resources/ui/crosshair/{}.png", index).as_str(),
-        );
-    }
-    image_assets.add_content(
-        "ui-crosshair-inactive",
-        "./resources/ui/crosshair/inactive.png",
-    );
-
-    image_assets.add_content("ui-weapon-melee", "./resources/ui/weapons/melee.png");
-    image_assets.add_content("ui-weapon-range", "./resources/ui/weapons/range.png");
-
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("ui-z-{}", index).as_str(),
-            format!("./resources/ui/z/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..12 {
-        image_assets.add_content(
-            format!("player-run-{}", index).as_str(),
-            format!("./resources/player/run/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("player-stand-{}", index).as_str(),
-            format!("./resources/player/stand/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..6 {
-        image_assets.add_content(
-            format!("player-jump-{}", index).as_str(),
-            format!("./resources/player/jump/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..10 {
-        image_assets.add_content(
-            format!("player-die-{}", index).as_str(),
-            format!("./resources/player/die/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..7 {
-        image_assets.add_content(
-            format!("player-slash-{}", index).as_str(),
-            format!("./resources/player/slash/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("player-bullet-idle-{}", index).as_str(),
-            format!("./resources/player/bullet/idle/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..4 {
-        image_assets.add_content(
-            format!("player-bullet-firing-{}", index).as_str(),
-            format!("./resources/player/bullet/firing/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..4 {
-        image_assets.add_content(
-            format!("player-bullet-hit-{}", index).as_str(),
-            format!("./resources/player/bullet/hit/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("enemy-bullet-1-idle-{}", index).as_str(),
-            format!("./resources/enemies/bullet-1/idle/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("enemy-bullet-1-hit-{}", index).as_str(),
-            format!("./resources/enemies/bullet-1/hit/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..3 {
-        image_assets.add_content(
-            format!("enemy-bullet-1-firing-{}", index).as_str(),
-            format!("./resources/enemies/bullet-1/firing/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..4 {
-        image_assets.add_content(
-            format!("enemy-bullet-1-kill-{}", index).as_str(),
-            format!("./resources/enemies/bullet-1/kill/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..9 {
-        image_assets.add_content(
-            format!("enemy-flying-idle-{}", index).as_str(),
-            format!("./resources/enemies/flying/idle/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..11 {
-        image_assets.add_content(
-            format!("enemy-flying-spawn-{}", index).as_str(),
-            format!("./resources/enemies/flying/spawn/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..7 {
-        image_assets.add_content(
-            format!("splash-1-{}", index).as_str(),
-            format!("./resources/splashes/splash-1/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..7 {
-        image_assets.add_content(
-            format!("splash-2-{}", index).as_str(),
-            format!("./resources/splashes/splash-2/{}.png", index).as_str(),
-        );
-    }
-
-    for index in 1..8 {
-        image_assets.add_content(
-            format!("splash-3-{}", index).as_str(),
-            format!("./resources/splashes/splash-3/{}.png", index).as_str(),
-        );
-    }
+/// Sound definitions for the sounds in "sound_paths". (key, path) See ImageAssets::take_sounds()
+/// A sound without a manifest entry is not loaded. Missing files are asset errors of the manifest.
+pub fn setup_sounds(sound_paths: &[(String, String)]) -> Vec<SoundDefinition> {
+    // (key, bus, instances that play at the same time, pitch variation)
+    let definition_list = [
+        ("bgm", AudioBus::Music, 1, 0.0),
+        ("bgm_boss", AudioBus::Music, 1, 0.0),
+        ("bullet_hit_1", AudioBus::Sfx, 3, 0.1),
+        ("bullet_hit_2", AudioBus::Sfx, 3, 0.1),
+        ("bullet_hit_3", AudioBus::Sfx, 3, 0.1),
+        ("melee_hit", AudioBus::Sfx, 2, 0.1),
+        ("melee_hit_target", AudioBus::Sfx, 2, 0.1),
+        ("player_hit", AudioBus::Sfx, 2, 0.0),
+        ("player_die", AudioBus::Sfx, 1, 0.0),
+        ("flying_explode", AudioBus::Sfx, 4, 0.15),
+        ("crawl_explode", AudioBus::Sfx, 4, 0.15),
+        ("spawner", AudioBus::Sfx, 2, 0.05),
+        ("spawner_explode", AudioBus::Sfx, 2, 0.05),
+        ("boss_spawn", AudioBus::Sfx, 1, 0.0),
+        ("boss_explode", AudioBus::Sfx, 1, 0.0),
+        ("boss_enter", AudioBus::Sfx, 1, 0.0),
+    ];
+
+    let mut sound_list = vec![];
+    for (key, bus, max_instances, pitch_variation) in definition_list.iter() {
+        let path = match sound_paths.iter().find(|(sound_key, _)| sound_key == key) {
+            Some((_, path)) => path,
+            None => continue,
+        };
 
-    for index in 1..10 {
-        image_assets.add_content(
-            format!("splash-4-{}", index).as_str(),
-            format!("./resources/splashes/splash-4/{}.png", index).as_str(),
-        );
+        let definition = SoundDefinition::new(key, path, *bus, *max_instances, *pitch_variation);
+        sound_list.push(match *key {
+            "boss_enter" => definition.with_ducking(0.3, 3000),
+            _ => definition,
+        });
     }
 
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("enemy-spawner-1-spawning-{}", index).as_str(),
-            format!("./resources/enemies/spawner-1/spawning/{}.png", index).as_str(),
-        );
-    }
+    sound_list
+}
 
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("enemy-spawner-1-idle-{}", index).as_str(),
-            format!("./resources/enemies/spawner-1/idle/{}.png", index).as_str(),
-        );
-    }
+/// Music tracks that camera nodes can start. (NodePoint.music)
+pub fn setup_music() -> MusicController {
+    let mut music = MusicController::new();
+    music.add_track("level", MusicTrack::new("", "bgm", 0.25));
+    music.add_track("boss", MusicTrack::new("", "bgm_boss", 0.25));
 
-    for index in 1..5 {
-        image_assets.add_content(
-            format!("enemy-spawner-2-spawning-{}", index).as_str(),
-            format!("./resources/enemies/spawner-2/spawning/{}.png", index).as_str(),
-        );
-    }
+    music
+}
 
-    for index in 1..2 {
-        image_assets.add_content(
-            format!("enemy-spawner-2-idle-{}", index).as_str(),
-            format!("./resources/enemies/spawner-2/idle/{}.png", index).as_str(),
-        );
-    }
+/// Texture, animation and sound manifest. See AssetManifest for the format.
+pub const ASSET_MANIFEST_PATH: &str = "./resources/assets.txt";
 
-    for index in 1..8 {
-        image_assets.add_content(
-            format!("enemy-crawler-air-{}", index).as_str(),
-            format!("./resources/enemies/crawler/air/{}.png", index).as_str(),
-        );
-    }
+pub fn setup_textures(image_assets: &mut ImageAssets) {
+    match image_assets.add_manifest(ASSET_MANIFEST_PATH) {
+        Ok(count) => println!("Asset manifest: {} textures", count),
+        Err(e) => println!("Asset manifest error: {}", e),
+    };
+}
 
-    for index in 1..10 {
-        image_assets.add_content(
-            format!("enemy-crawler-idle-{}", index).as_str(),
-            format!("./resources/enemies/crawler/idle/{}.png", index).as_str(),
-        );
-    }
+/// Build animations from the manifest and print every asset error. Call it after textures are loaded.
+pub fn setup_animations(image_assets: &mut ImageAssets) {
+    image_assets.load_manifest_animations();
 
-    for index in 1..10 {
-        image_assets.add_content(
-            format!("enemy-boss-idle-{}", index).as_str(),
-            format!("./resources/enemies/boss/idle/{}.png", index).as_str(),
-        );
+    for e in image_assets.take_errors().iter() {
+        println!("Asset error: {}", e);
     }
 }
-
-fn setup_animations(image_assets: &mut ImageAssets) {
-    let mut animations = vec![];
-
-    animations.push((
-        "enemy-boss-idle",
-        vec![
-            "enemy-boss-idle-1".to_string(),
-            "enemy-boss-idle-2".to_string(),
-            "enemy-boss-idle-3".to_string(),
-            "enemy-boss-idle-4".to_string(),
-            "enemy-boss-idle-5".to_string(),
-            "enemy-boss-idle-6".to_string(),
-            "enemy-boss-idle-7".to_string(),
-            "enemy-boss-idle-8".to_string(),
-            "enemy-boss-idle-9".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-crawler-idle",
-        vec![
-            "enemy-crawler-idle-1".to_string(),
-            "enemy-crawler-idle-2".to_string(),
-            "enemy-crawler-idle-3".to_string(),
-            "enemy-crawler-idle-4".to_string(),
-            "enemy-crawler-idle-5".to_string(),
-            "enemy-crawler-idle-6".to_string(),
-            "enemy-crawler-idle-7".to_string(),
-            "enemy-crawler-idle-8".to_string(),
-            "enemy-crawler-idle-9".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-crawler-air",
-        vec![
-            "enemy-crawler-air-1".to_string(),
-            "enemy-crawler-air-2".to_string(),
-            "enemy-crawler-air-3".to_string(),
-            "enemy-crawler-air-4".to_string(),
-            "enemy-crawler-air-5".to_string(),
-            "enemy-crawler-air-6".to_string(),
-            "enemy-crawler-air-7".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-spawner-2-idle",
-        vec!["enemy-spawner-2-idle-1".to_string()],
-        1,
-    ));
-
-    animations.push((
-        "enemy-spawner-2-spawning",
-        vec![
-            "enemy-spawner-2-spawning-1".to_string(),
-            "enemy-spawner-2-spawning-2".to_string(),
-            "enemy-spawner-2-spawning-3".to_string(),
-            "enemy-spawner-2-spawning-4".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-spawner-1-idle",
-        vec![
-            "enemy-spawner-1-idle-1".to_string(),
-            "enemy-spawner-1-idle-2".to_string(),
-            "enemy-spawner-1-idle-3".to_string(),
-            "enemy-spawner-1-idle-4".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-spawner-1-spawning",
-        vec![
-            "enemy-spawner-1-spawning-1".to_string(),
-            "enemy-spawner-1-spawning-2".to_string(),
-            "enemy-spawner-1-spawning-3".to_string(),
-            "enemy-spawner-1-spawning-4".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-flying-idle",
-        vec![
-            "enemy-flying-idle-1".to_string(),
-            "enemy-flying-idle-2".to_string(),
-            "enemy-flying-idle-3".to_string(),
-            "enemy-flying-idle-4".to_string(),
-            "enemy-flying-idle-5".to_string(),
-            "enemy-flying-idle-6".to_string(),
-            "enemy-flying-idle-7".to_string(),
-            "enemy-flying-idle-8".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-flying-spawn",
-        vec![
-            "enemy-flying-spawn-1".to_string(),
-            "enemy-flying-spawn-2".to_string(),
-            "enemy-flying-spawn-3".to_string(),
-            "enemy-flying-spawn-4".to_string(),
-            "enemy-flying-spawn-5".to_string(),
-            "enemy-flying-spawn-6".to_string(),
-            "enemy-flying-spawn-7".to_string(),
-            "enemy-flying-spawn-8".to_string(),
-            "enemy-flying-spawn-9".to_string(),
-            "enemy-flying-spawn-10".to_string(),
-        ],
-        12,
-    ));
-
-    animations.push((
-        "splash-1",
-        vec![
-            "splash-1-1".to_string(),
-            "splash-1-2".to_string(),
-            "splash-1-3".to_string(),
-            "splash-1-4".to_string(),
-            "splash-1-5".to_string(),
-            "splash-1-6".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "splash-2",
-        vec![
-            "splash-2-1".to_string(),
-            "splash-2-2".to_string(),
-            "splash-2-3".to_string(),
-            "splash-2-4".to_string(),
-            "splash-2-5".to_string(),
-            "splash-2-6".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "splash-3",
-        vec![
-            "splash-3-1".to_string(),
-            "splash-3-2".to_string(),
-            "splash-3-3".to_string(),
-            "splash-3-4".to_string(),
-            "splash-3-5".to_string(),
-            "splash-3-6".to_string(),
-            "splash-3-7".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "splash-4",
-        vec![
-            "splash-4-1".to_string(),
-            "splash-4-2".to_string(),
-            "splash-4-3".to_string(),
-            "splash-4-4".to_string(),
-            "splash-4-5".to_string(),
-            "splash-4-6".to_string(),
-            "splash-4-7".to_string(),
-            "splash-4-8".to_string(),
-            "splash-4-9".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-bullet-1-idle",
-        vec![
-            "enemy-bullet-1-idle-1".to_string(),
-            "enemy-bullet-1-idle-2".to_string(),
-            "enemy-bullet-1-idle-3".to_string(),
-            "enemy-bullet-1-idle-4".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-bullet-1-hit",
-        vec![
-            "enemy-bullet-1-hit-1".to_string(),
-            "enemy-bullet-1-hit-2".to_string(),
-            "enemy-bullet-1-hit-3".to_string(),
-            "enemy-bullet-1-hit-4".to_string(),
-        ],
-        14,
-    ));
-
-    animations.push((
-        "enemy-bullet-1-firing",
-        vec![
-            "enemy-bullet-1-firing-1".to_string(),
-            "enemy-bullet-1-firing-2".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "enemy-bullet-1-kill",
-        vec![
-            "enemy-bullet-1-kill-1".to_string(),
-            "enemy-bullet-1-kill-2".to_string(),
-            "enemy-bullet-1-kill-3".to_string(),
-        ],
-        14,
-    ));
-
-    animations.push((
-        "player-run",
-        vec![
-            "player-run-1".to_string(),
-            "player-run-2".to_string(),
-            "player-run-3".to_string(),
-            "player-run-4".to_string(),
-            "player-run-5".to_string(),
-            "player-run-6".to_string(),
-            "player-run-7".to_string(),
-            "player-run-8".to_string(),
-            "player-run-9".to_string(),
-            "player-run-10".to_string(),
-            "player-run-11".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "player-slash",
-        vec![
-            "player-slash-1".to_string(),
-            "player-slash-2".to_string(),
-            "player-slash-2".to_string(),
-            "player-slash-3".to_string(),
-            "player-slash-4".to_string(),
-            "player-slash-5".to_string(),
-            "player-slash-6".to_string(),
-        ],
-        14,
-    ));
-
-    animations.push((
-        "player-stand",
-        vec![
-            "player-stand-1".to_string(),
-            "player-stand-2".to_string(),
-            "player-stand-3".to_string(),
-            "player-stand-4".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "player-jump",
-        vec![
-            "player-jump-1".to_string(),
-            "player-jump-2".to_string(),
-            "player-jump-3".to_string(),
-            "player-jump-4".to_string(),
-            "player-jump-5".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "player-die",
-        vec![
-            "player-die-1".to_string(),
-            "player-die-2".to_string(),
-            "player-die-3".to_string(),
-            "player-die-4".to_string(),
-            "player-die-5".to_string(),
-            "player-die-6".to_string(),
-            "player-die-7".to_string(),
-            "player-die-8".to_string(),
-            "player-die-9".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "player-bullet-idle",
-        vec![
-            "player-bullet-idle-1".to_string(),
-            "player-bullet-idle-2".to_string(),
-            "player-bullet-idle-3".to_string(),
-            "player-bullet-idle-4".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "player-bullet-hit",
-        vec![
-            "player-bullet-hit-1".to_string(),
-            "player-bullet-hit-2".to_string(),
-            "player-bullet-hit-3".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "player-bullet-firing",
-        vec![
-            "player-bullet-firing-1".to_string(),
-            "player-bullet-firing-2".to_string(),
-            "player-bullet-firing-3".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "ui-circle",
-        vec![
-            "ui-circle-1".to_string(),
-            "ui-circle-2".to_string(),
-            "ui-circle-3".to_string(),
-            "ui-circle-4".to_string(),
-            "ui-circle-5".to_string(),
-            "ui-circle-6".to_string(),
-            "ui-circle-7".to_string(),
-            "ui-circle-8".to_string(),
-        ],
-        24,
-    ));
-
-    animations.push((
-        "ui-z",
-        vec![
-            "ui-z-1".to_string(),
-            "ui-z-2".to_string(),
-            "ui-z-3".to_string(),
-            "ui-z-4".to_string(),
-        ],
-        24,
-    ));
-
-    animations.push(("ui-weapon-melee", vec!["ui-weapon-melee".to_string()], 1));
-
-    animations.push(("ui-weapon-range", vec!["ui-weapon-range".to_string()], 1));
-
-    animations.push((
-        "ui-bar",
-        vec![
-            "ui-bar-1".to_string(),
-            "ui-bar-2".to_string(),
-            "ui-bar-3".to_string(),
-            "ui-bar-4".to_string(),
-        ],
-        18,
-    ));
-
-    animations.push((
-        "ui-crosshair",
-        vec![
-            "ui-crosshair-1".to_string(),
-            "ui-crosshair-2".to_string(),
-            "ui-crosshair-3".to_string(),
-            "ui-crosshair-4".to_string(),
-        ],
-        24,
-    ));
-
-    animations.push((
-        "ui-crosshair-inactive",
-        vec!["ui-crosshair-inactive".to_string()],
-        1,
-    ));
-
-    image_assets.load_animations(&animations);
-}
@@ -7,6 +7,7 @@ use tetra::Context;
 use std::time::Duration;
 
 use crate::image_assets::ImageAssets;
+use crate::sprite_effects::SpriteEffects;
 
 pub struct Sprite {
     /// True: Looping animation.
@@ -20,6 +21,10 @@ pub struct Sprite {
     frame_duration: Duration,
     /// Internally use it for pausing animation when it reach last frame and is_loop = false.
     pause: bool,
+    /// True: events of the first frame are not sent yet. It is set when animation starts.
+    first_frame_events: bool,
+    /// Events of the frames that started in this update.
+    events: Vec<String>,
 
     /// position
     pub position: Vec2<f32>,
@@ -37,6 +42,9 @@ pub struct Sprite {
     pub size: Vec2<f32>,
 
     color: Color,
+
+    /// Flash, tint, dissolve and palette. They are drawn by shader or its fallback.
+    pub effects: SpriteEffects,
 }
 
 impl Sprite {
@@ -51,8 +59,11 @@ impl Sprite {
             anchor: Vec2::new(0.5, 0.5),
             alpha: 1.0,
             pause: false,
+            first_frame_events: true,
+            events: vec![],
             size: Vec2::one(),
             color: Color::rgba(1.0, 1.0, 1.0, 1.0),
+            effects: SpriteEffects::new(),
         }
     }
 
@@ -65,7 +76,11 @@ impl Sprite {
         self.scale = Vec2::one();
         self.anchor = Vec2::new(0.5, 0.5);
         self.alpha = 1.0;
+        self.color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+        self.effects.reset();
         self.pause = false;
+        self.first_frame_events = true;
+        self.events.clear();
         self.animation = AnimationMultiTextures::new();
     }
 
@@ -101,7 +116,18 @@ impl Sprite {
         self.animation = new_animation.clone();
         self.frame_duration = Duration::from_millis(0);
         self.frame_index = 0;
-        self.pause = false
+        self.pause = false;
+        self.first_frame_events = true;
+
+        match new_animation.is_loop {
+            Some(v) => self.is_loop = v,
+            None => (),
+        };
+
+        match new_animation.anchor {
+            Some(v) => self.set_anchor(v.x, v.y),
+            None => (),
+        };
     }
 
     /// Restart animation
@@ -109,36 +135,68 @@ impl Sprite {
         self.frame_duration = Duration::from_millis(0);
         self.frame_index = 0;
         self.pause = false;
+        self.first_frame_events = true;
+    }
+
+    /// Show a frame of the current animation and stop playing. It does not send frame events.
+    pub fn stop_at_frame(&mut self, frame_index: usize) {
+        let total_frames = self.animation.frames.len();
+        if total_frames == 0 {
+            return;
+        }
+
+        self.frame_duration = Duration::from_millis(0);
+        self.frame_index = frame_index.min(total_frames - 1);
+        self.pause = true;
+        self.first_frame_events = false;
     }
 
     /// Use for update animation frame_index.
+    /// Every frame that starts adds its events into "events".
     fn advance(&mut self) -> bool {
-        let frame_length = self.animation.frame_length;
+        let total_frames = self.animation.frames.len();
+        if self.first_frame_events {
+            self.first_frame_events = false;
+            self.add_frame_events(self.frame_index);
+        }
 
         match self.frame_duration.checked_add(crate::ONE_FRAME) {
             Some(v) => self.frame_duration = v,
             None => self.frame_duration = Duration::from_millis(0),
         };
 
-        if self.frame_duration >= frame_length && self.pause == false {
-            while self.frame_duration >= frame_length {
-                self.frame_duration -= frame_length;
-                self.frame_index += 1;
-            }
+        if self.pause || total_frames == 0 {
+            return false;
+        }
+
+        let mut is_end = false;
+        while self.frame_duration >= self.current_frame_length() {
+            self.frame_duration -= self.current_frame_length();
+            self.frame_index += 1;
 
-            if self.is_end_of_animation() == true {
+            if self.frame_index >= total_frames {
+                is_end = true;
                 if self.is_loop == true {
                     self.continue_loop();
                 } else {
-                    self.frame_index = self.animation.frames.len() - 1;
+                    self.frame_index = total_frames - 1;
                     self.pause = true;
+                    break;
                 }
-
-                return true;
             }
+
+            self.add_frame_events(self.frame_index);
         }
 
-        false
+        is_end
+    }
+
+    fn add_frame_events(&mut self, frame_index: usize) {
+        for (index, name) in self.animation.events.iter() {
+            if *index == frame_index {
+                self.events.push(name.clone());
+            }
+        }
     }
 
     /// Draw sprite on screen.
@@ -168,17 +226,14 @@ impl Sprite {
                         self.size.x = rect.width;
                         self.size.y = rect.height;
 
-                        tetra::graphics::draw(
-                            ctx,
-                            texture,
-                            DrawParams::new()
-                                .position(position + self.position)
-                                .origin(origin)
-                                .rotation((rotation * 360.0).to_radians())
-                                .scale(self.scale)
-                                .clip(rect)
-                                .color(Color::rgba(1.0, 1.0, 1.0, self.alpha)),
-                        );
+                        let params = DrawParams::new()
+                            .position(position + self.position)
+                            .origin(origin)
+                            .rotation((rotation * 360.0).to_radians())
+                            .scale(self.scale)
+                            .clip(rect)
+                            .color(self.color);
+                        self.draw_texture(ctx, texture, params, image_assets);
                     }
                     None => (),
                 };
@@ -216,17 +271,14 @@ impl Sprite {
                         rect.width = rect.width * clipping.x;
                         rect.height = rect.height * clipping.y;
 
-                        tetra::graphics::draw(
-                            ctx,
-                            texture,
-                            DrawParams::new()
-                                .position(position)
-                                .origin(origin)
-                                .rotation((rotation * 360.0).to_radians())
-                                .scale(self.scale)
-                                .clip(rect)
-                                .color(Color::rgba(1.0, 1.0, 1.0, self.alpha)),
-                        );
+                        let params = DrawParams::new()
+                            .position(position)
+                            .origin(origin)
+                            .rotation((rotation * 360.0).to_radians())
+                            .scale(self.scale)
+                            .clip(rect)
+                            .color(self.color);
+                        self.draw_texture(ctx, texture, params, image_assets);
                     }
                     None => (),
                 };
@@ -235,8 +287,29 @@ impl Sprite {
         };
     }
 
-    pub fn update(&mut self) -> bool {
-        self.advance()
+    fn draw_texture(
+        &self,
+        ctx: &mut Context,
+        texture: &Texture,
+        params: DrawParams,
+        image_assets: &ImageAssets,
+    ) {
+        if self.effects.is_active() {
+            self.effects.draw(ctx, texture, params, image_assets);
+        } else {
+            tetra::graphics::draw(ctx, texture, params);
+        }
+    }
+
+    /// Move animation by one frame of the game.
+    ///
+    /// # Return:
+    ///
+    /// Events of the animation frames that started in this update. Ex: "spawn", "fire"
+    ///
+    pub fn update(&mut self) -> Vec<String> {
+        self.advance();
+        std::mem::replace(&mut self.events, vec![])
     }
 
     pub fn is_end_of_animation(&self) -> bool {
@@ -259,6 +332,20 @@ impl Sprite {
     pub fn get_current_frame_index(&self) -> usize {
         self.frame_index
     }
+
+    /// Duration of the current frame. Frame without duration uses frame_length of the animation.
+    fn current_frame_length(&self) -> Duration {
+        let frame_length = match self.animation.frames.get(self.frame_index) {
+            Some(frame) => match frame.duration {
+                Some(v) => v,
+                None => self.animation.frame_length,
+            },
+            None => self.animation.frame_length,
+        };
+
+        // Zero length frame never ends.
+        frame_length.max(Duration::from_millis(1))
+    }
 }
 
 // scale
@@ -326,6 +413,15 @@ pub struct AnimationMultiTextures {
     pub frames: Vec<FrameRectangle>,
 
     pub name: String,
+
+    /// Loop flag that Sprite use when it plays the animation. None: keep loop flag of the Sprite.
+    pub is_loop: Option<bool>,
+
+    /// Anchor that Sprite use when it plays the animation. None: keep anchor of the Sprite.
+    pub anchor: Option<Vec2<f32>>,
+
+    /// (frame index, event name). Sprite::update() returns the event when the frame starts.
+    pub events: Vec<(usize, String)>,
 }
 
 impl Clone for AnimationMultiTextures {
@@ -334,6 +430,9 @@ impl Clone for AnimationMultiTextures {
             frame_length: self.frame_length.clone(),
             frames: self.frames.clone(),
             name: self.name.clone(),
+            is_loop: self.is_loop,
+            anchor: self.anchor,
+            events: self.events.clone(),
         }
     }
 }
@@ -344,6 +443,9 @@ impl AnimationMultiTextures {
             frame_length: Duration::from_millis(1000 / 8),
             frames: Vec::new(),
             name: String::from(""),
+            is_loop: None,
+            anchor: None,
+            events: vec![],
         }
     }
 
@@ -352,6 +454,9 @@ impl AnimationMultiTextures {
             frame_length: Duration::from_millis(1000 / 12),
             frames: Vec::new(),
             name: String::from(""),
+            is_loop: None,
+            anchor: None,
+            events: vec![],
         };
 
         anim_obj.add_frames(&frames);
@@ -374,6 +479,11 @@ impl AnimationMultiTextures {
         }
     }
 
+    /// Add event that Sprite::update() returns when the frame starts.
+    pub fn add_event(&mut self, frame_index: usize, name: &str) {
+        self.events.push((frame_index, String::from(name)));
+    }
+
     /// Add one frame with size into animation frames.
     pub fn add_with_rectangle(&mut self, texture_id: u128, rect: Rectangle) {
         self.frames.push(FrameRectangle::new(texture_id, rect));
@@ -388,6 +498,8 @@ pub struct FrameRectangle {
     pub texture_id: u128,
     /// Frame area
     pub rect: Rectangle,
+    /// Time that the frame is shown. None: use frame_length of the animation.
+    pub duration: Option<Duration>,
 }
 
 impl FrameRectangle {
@@ -395,6 +507,19 @@ impl FrameRectangle {
         FrameRectangle {
             texture_id: texture_id,
             rect: rect,
+            duration: None,
+        }
+    }
+
+    pub fn new_with_duration(
+        texture_id: u128,
+        rect: Rectangle,
+        duration: Duration,
+    ) -> FrameRectangle {
+        FrameRectangle {
+            texture_id: texture_id,
+            rect: rect,
+            duration: Some(duration),
         }
     }
 }
@@ -404,6 +529,7 @@ impl Clone for FrameRectangle {
         FrameRectangle {
             texture_id: self.texture_id,
             rect: self.rect,
+            duration: self.duration,
         }
     }
 }
@@ -0,0 +1,156 @@
+use tetra::graphics::{self, Color, DrawParams, Texture};
+use tetra::math::{Vec2, Vec4};
+use tetra::Context;
+
+use crate::image_assets::ImageAssets;
+
+/// Shader key of the effects pipeline. It is loaded from "shader sprite-effects <file>" in the manifest.
+pub const SPRITE_EFFECTS_SHADER: &str = "sprite-effects";
+
+/// Color pairs that a palette can swap. It is the array size in the shader.
+pub const MAX_PALETTE_COLORS: usize = 8;
+
+/// Colors of a sprite variant. Texels that match "from" are drawn with "to".
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub colors: Vec<(Color, Color)>,
+}
+
+/// Effects of a sprite. Sprite::draw() uses SPRITE_EFFECTS_SHADER when an effect is active.
+///
+/// Without the shader (missing or failed to compile) the effects fall back to the draw color:
+/// * flash - draw color blends toward flash_color with half alpha
+/// * tint - multiply color
+/// * dissolve - fade out
+/// * palette - ignored
+#[derive(Debug, Clone)]
+pub struct SpriteEffects {
+    /// Mix with flash_color. (0.0, none - 1.0, solid flash_color)
+    pub flash: f32,
+    pub flash_color: Color,
+    /// Multiply color.
+    pub tint: Color,
+    /// Part of the texels that are removed. (0.0, none - 1.0, all)
+    pub dissolve: f32,
+    /// Palette name in ImageAssets. "": original colors.
+    pub palette: String,
+}
+
+impl SpriteEffects {
+    pub fn new() -> SpriteEffects {
+        SpriteEffects {
+            flash: 0.0,
+            flash_color: Color::rgb(1.0, 1.0, 1.0),
+            tint: Color::rgb(1.0, 1.0, 1.0),
+            dissolve: 0.0,
+            palette: String::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.flash = 0.0;
+        self.flash_color = Color::rgb(1.0, 1.0, 1.0);
+        self.tint = Color::rgb(1.0, 1.0, 1.0);
+        self.dissolve = 0.0;
+        self.palette.clear();
+    }
+
+    /// True: draw needs the shader or the fallback.
+    pub fn is_active(&self) -> bool {
+        self.flash > 0.0
+            || self.dissolve > 0.0
+            || self.palette.len() > 0
+            || self.tint.r < 1.0
+            || self.tint.g < 1.0
+            || self.tint.b < 1.0
+    }
+
+    /// Draw "texture" with the effects.
+    ///
+    /// # Arguments:
+    ///
+    /// * params - draw params of the sprite. Its color is multiplied with tint.
+    ///
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        texture: &Texture,
+        params: DrawParams,
+        image_assets: &ImageAssets,
+    ) {
+        let shader = match image_assets.get_shader(SPRITE_EFFECTS_SHADER) {
+            Some(v) => v,
+            None => {
+                self.draw_fallback(ctx, texture, params);
+                return;
+            }
+        };
+
+        let color = params.color;
+        let params = params.color(Color::rgba(
+            color.r * self.tint.r,
+            color.g * self.tint.g,
+            color.b * self.tint.b,
+            color.a,
+        ));
+
+        // Uniforms belong to the shader, so every sprite is drawn in its own batch.
+        graphics::set_shader(ctx, shader);
+        shader.set_uniform(ctx, "u_flash", self.flash.min(1.0).max(0.0));
+        shader.set_uniform(ctx, "u_flash_color", self.flash_color);
+        shader.set_uniform(ctx, "u_dissolve", self.dissolve.min(1.0).max(0.0));
+        shader.set_uniform(
+            ctx,
+            "u_texture_size",
+            Vec2::new(texture.width() as f32, texture.height() as f32),
+        );
+
+        let colors = match image_assets.get_palette(self.palette.as_str()) {
+            Some(palette) => &palette.colors[..],
+            None => &[],
+        };
+        let count = colors.len().min(MAX_PALETTE_COLORS);
+        shader.set_uniform(ctx, "u_palette_size", count as i32);
+        for (index, (from, to)) in colors.iter().take(count).enumerate() {
+            let from: Vec4<f32> = (*from).into();
+            let to: Vec4<f32> = (*to).into();
+            shader.set_uniform(ctx, format!("u_palette_from[{}]", index).as_str(), from);
+            shader.set_uniform(ctx, format!("u_palette_to[{}]", index).as_str(), to);
+        }
+
+        graphics::draw(ctx, texture, params);
+        graphics::reset_shader(ctx);
+    }
+
+    fn draw_fallback(&self, ctx: &mut Context, texture: &Texture, params: DrawParams) {
+        let color = params.color;
+        let mut alpha = color.a * (1.0 - self.dissolve.min(1.0).max(0.0));
+        let mut r = color.r * self.tint.r;
+        let mut g = color.g * self.tint.g;
+        let mut b = color.b * self.tint.b;
+        if self.flash > 0.0 {
+            // Blend toward flash_color. Draw color only multiplies the texture.
+            let flash = self.flash.min(1.0);
+            r = r + (self.flash_color.r - r) * flash;
+            g = g + (self.flash_color.g - g) * flash;
+            b = b + (self.flash_color.b - b) * flash;
+            alpha = alpha * 0.5;
+        }
+
+        graphics::draw(ctx, texture, params.color(Color::rgba(r, g, b, alpha)));
+    }
+}
+
+/// Parse "RRGGBB" hex color.
+pub fn parse_hex_color(text: &str) -> Option<Color> {
+    if text.len() != 6 {
+        return None;
+    }
+
+    let channel = |index: usize| -> Option<f32> {
+        let value = u8::from_str_radix(text.get(index..index + 2)?, 16).ok()?;
+        Some(value as f32 / 255.0)
+    };
+
+    Some(Color::rgb(channel(0)?, channel(2)?, channel(4)?))
+}
@@ -0,0 +1,504 @@
+use std::path::Path;
+
+use tetra::graphics::Rectangle;
+use tetra::math::Vec2;
+
+use crate::asset_manifest::AssetError;
+
+/// Play order of a tag in Aseprite.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+    /// Forward then back without repeating the first and the last frame. Ex: 0 1 2 1
+    PingPong,
+    /// Backward then forward. Ex: 2 1 0 1
+    PingPongReverse,
+}
+
+impl Direction {
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "forward" => Some(Direction::Forward),
+            "reverse" => Some(Direction::Reverse),
+            "pingpong" => Some(Direction::PingPong),
+            "pingpong_reverse" => Some(Direction::PingPongReverse),
+            _ => None,
+        }
+    }
+
+    /// Frame indexes from "from" to "to" (inclusive) in play order.
+    pub fn frame_order(&self, from: usize, to: usize) -> Vec<usize> {
+        let forward: Vec<usize> = (from..=to).collect();
+        let backward: Vec<usize> = forward.iter().rev().copied().collect();
+        let (first, second) = match self {
+            Direction::Forward => return forward,
+            Direction::Reverse => return backward,
+            Direction::PingPong => (forward, backward),
+            Direction::PingPongReverse => (backward, forward),
+        };
+
+        let mut order = first;
+        if second.len() > 2 {
+            order.extend_from_slice(&second[1..second.len() - 1]);
+        }
+        order
+    }
+}
+
+/// One frame of the sheet image.
+#[derive(Debug, Copy, Clone)]
+pub struct SheetFrame {
+    /// Frame area in the sheet image. (pixel)
+    pub rect: Rectangle,
+    /// (ms)
+    pub duration: u64,
+}
+
+/// Slice bounds from a frame until the next key.
+#[derive(Debug, Copy, Clone)]
+pub struct SliceKey {
+    /// Index of the sheet frame where the key starts.
+    pub frame: usize,
+    /// Area relative to the top-left of the frame. (pixel)
+    pub bounds: Rectangle,
+    /// Pivot relative to the top-left of the frame. (pixel) None: slice has no pivot.
+    pub pivot: Option<Vec2<f32>>,
+}
+
+/// Named area of the sprite. Ex: "hitbox", "muzzle"
+#[derive(Debug, Clone)]
+pub struct SheetSlice {
+    pub name: String,
+    pub keys: Vec<SliceKey>,
+}
+
+impl SheetSlice {
+    /// Key that is active at the sheet frame. None: the slice starts after the frame.
+    pub fn at(&self, frame: usize) -> Option<&SliceKey> {
+        self.keys.iter().filter(|key| key.frame <= frame).last()
+    }
+}
+
+/// Animation from a tag. Sheet without tags has one animation with every frame.
+#[derive(Debug, Clone)]
+pub struct SheetAnimation {
+    /// "<sheet key>-<tag name>" or "<sheet key>" for sheet without tags.
+    pub name: String,
+    /// Index of the sheet frames in play order.
+    pub frames: Vec<usize>,
+}
+
+/// Sheet image with frame data that is exported from Aseprite. ("Array" or "Hash" JSON data)
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    /// Texture key of the sheet image.
+    pub key: String,
+    /// Path to the sheet image.
+    pub image: String,
+    pub frames: Vec<SheetFrame>,
+    pub animations: Vec<SheetAnimation>,
+    pub slices: Vec<SheetSlice>,
+}
+
+impl SpriteSheet {
+    /// Anchor of the animation from the first slice that has a pivot at the first frame of the animation.
+    /// (0.0, 0.0) = top-left of the frame. None: no pivot.
+    pub fn anchor(&self, animation: &SheetAnimation) -> Option<Vec2<f32>> {
+        let frame = *animation.frames.first()?;
+        let rect = self.frames.get(frame)?.rect;
+        if rect.width == 0.0 || rect.height == 0.0 {
+            return None;
+        }
+
+        for slice in self.slices.iter() {
+            match slice.at(frame).and_then(|key| key.pivot) {
+                Some(pivot) => return Some(Vec2::new(pivot.x / rect.width, pivot.y / rect.height)),
+                None => (),
+            };
+        }
+
+        None
+    }
+
+    /// Load Aseprite JSON file. The sheet image path is relative to the JSON file.
+    ///
+    /// # Arguments:
+    ///
+    /// * key - texture key of the sheet image and prefix of the animation names.
+    /// * path - path to JSON file.
+    ///
+    #[cfg(feature = "sprite-sheets")]
+    pub fn load(key: &str, path: &Path) -> Result<SpriteSheet, AssetError> {
+        let source = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(AssetError {
+                    path: path.display().to_string(),
+                    line: 0,
+                    message: format!("Can not read sprite sheet. {}", e),
+                })
+            }
+        };
+
+        let base = match path.parent() {
+            Some(v) => v,
+            None => Path::new("."),
+        };
+        aseprite::parse(
+            key,
+            path.display().to_string().as_str(),
+            source.as_str(),
+            base,
+        )
+    }
+
+    #[cfg(not(feature = "sprite-sheets"))]
+    pub fn load(_key: &str, path: &Path) -> Result<SpriteSheet, AssetError> {
+        Err(AssetError {
+            path: path.display().to_string(),
+            line: 0,
+            message: String::from("Sprite sheets need feature \"sprite-sheets\""),
+        })
+    }
+}
+
+#[cfg(feature = "sprite-sheets")]
+mod aseprite {
+    use std::fmt;
+    use std::path::Path;
+
+    use serde::de::{MapAccess, Visitor};
+    use serde::{Deserialize, Deserializer};
+    use tetra::graphics::Rectangle;
+    use tetra::math::Vec2;
+
+    use super::{Direction, SheetAnimation, SheetFrame, SheetSlice, SliceKey, SpriteSheet};
+    use crate::asset_manifest::AssetError;
+
+    #[derive(Deserialize)]
+    struct SheetData {
+        frames: FramesData,
+        meta: MetaData,
+    }
+
+    /// Aseprite writes frames as an array or as an object with frame file names.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FramesData {
+        Array(Vec<FrameData>),
+        Hash(FrameHash),
+    }
+
+    #[derive(Deserialize)]
+    struct FrameData {
+        frame: RectData,
+        #[serde(default)]
+        rotated: bool,
+        #[serde(default)]
+        trimmed: bool,
+        #[serde(default = "default_duration")]
+        duration: u64,
+    }
+
+    fn default_duration() -> u64 {
+        100
+    }
+
+    #[derive(Deserialize, Copy, Clone)]
+    struct RectData {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    }
+
+    #[derive(Deserialize)]
+    struct PointData {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Deserialize)]
+    struct MetaData {
+        image: String,
+        #[serde(default, rename = "frameTags")]
+        frame_tags: Vec<TagData>,
+        #[serde(default)]
+        slices: Vec<SliceData>,
+    }
+
+    #[derive(Deserialize)]
+    struct TagData {
+        name: String,
+        from: usize,
+        to: usize,
+        #[serde(default = "default_direction")]
+        direction: String,
+    }
+
+    fn default_direction() -> String {
+        String::from("forward")
+    }
+
+    #[derive(Deserialize)]
+    struct SliceData {
+        name: String,
+        keys: Vec<SliceKeyData>,
+    }
+
+    #[derive(Deserialize)]
+    struct SliceKeyData {
+        frame: usize,
+        bounds: RectData,
+        #[serde(default)]
+        pivot: Option<PointData>,
+    }
+
+    /// Frames of "Hash" JSON data in file order.
+    struct FrameHash(Vec<FrameData>);
+
+    impl<'de> Deserialize<'de> for FrameHash {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FrameHash, D::Error> {
+            struct FrameHashVisitor;
+
+            impl<'de> Visitor<'de> for FrameHashVisitor {
+                type Value = FrameHash;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "an object of frames")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<FrameHash, A::Error> {
+                    let mut frames = vec![];
+                    while let Some((_, frame)) = map.next_entry::<String, FrameData>()? {
+                        frames.push(frame);
+                    }
+                    Ok(FrameHash(frames))
+                }
+            }
+
+            deserializer.deserialize_map(FrameHashVisitor)
+        }
+    }
+
+    fn to_rectangle(rect: &RectData) -> Rectangle {
+        Rectangle::new(rect.x, rect.y, rect.w, rect.h)
+    }
+
+    pub fn parse(
+        key: &str,
+        path: &str,
+        source: &str,
+        base: &Path,
+    ) -> Result<SpriteSheet, AssetError> {
+        let data: SheetData = match serde_json::from_str(source) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(AssetError {
+                    path: String::from(path),
+                    line: e.line(),
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        let frame_list = match data.frames {
+            FramesData::Array(v) => v,
+            FramesData::Hash(v) => v.0,
+        };
+
+        let mut frames = vec![];
+        for (index, frame) in frame_list.iter().enumerate() {
+            if frame.rotated || frame.trimmed {
+                return Err(AssetError {
+                    path: String::from(path),
+                    line: 0,
+                    message: format!(
+                        "Frame {} is rotated or trimmed. Export the sheet without trim and rotation",
+                        index
+                    ),
+                });
+            }
+
+            frames.push(SheetFrame {
+                rect: to_rectangle(&frame.frame),
+                duration: frame.duration,
+            });
+        }
+
+        if frames.len() == 0 {
+            return Err(AssetError {
+                path: String::from(path),
+                line: 0,
+                message: String::from("Sprite sheet has no frames"),
+            });
+        }
+
+        let mut animations = vec![];
+        for tag in data.meta.frame_tags.iter() {
+            let direction = match Direction::from_name(tag.direction.as_str()) {
+                Some(v) => v,
+                None => {
+                    return Err(AssetError {
+                        path: String::from(path),
+                        line: 0,
+                        message: format!(
+                            "Unknown direction \"{}\" of tag \"{}\"",
+                            tag.direction, tag.name
+                        ),
+                    })
+                }
+            };
+
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err(AssetError {
+                    path: String::from(path),
+                    line: 0,
+                    message: format!(
+                        "Tag \"{}\" has invalid frames {} - {}",
+                        tag.name, tag.from, tag.to
+                    ),
+                });
+            }
+
+            animations.push(SheetAnimation {
+                name: format!("{}-{}", key, tag.name),
+                frames: direction.frame_order(tag.from, tag.to),
+            });
+        }
+
+        if animations.len() == 0 {
+            animations.push(SheetAnimation {
+                name: String::from(key),
+                frames: (0..frames.len()).collect(),
+            });
+        }
+
+        let slices = data
+            .meta
+            .slices
+            .iter()
+            .map(|slice| SheetSlice {
+                name: slice.name.clone(),
+                keys: slice
+                    .keys
+                    .iter()
+                    .map(|key| SliceKey {
+                        frame: key.frame,
+                        bounds: to_rectangle(&key.bounds),
+                        pivot: key
+                            .pivot
+                            .as_ref()
+                            .map(|v| Vec2::new(key.bounds.x + v.x, key.bounds.y + v.y)),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(SpriteSheet {
+            key: String::from(key),
+            image: base.join(data.meta.image.as_str()).display().to_string(),
+            frames: frames,
+            animations: animations,
+            slices: slices,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "sprite-sheets"))]
+mod tests {
+    use std::path::Path;
+
+    use tetra::math::Vec2;
+
+    use super::{Direction, SpriteSheet};
+
+    /// Aseprite "Hash" data. 4 frames of 16x24, one tag per direction and two slices.
+    /// Slice "feet" moves at frame 2.
+    const SHEET: &str = r#"{
+        "frames": {
+            "boss 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 24 }, "duration": 100 },
+            "boss 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 16, "h": 24 }, "duration": 50 },
+            "boss 2.aseprite": { "frame": { "x": 32, "y": 0, "w": 16, "h": 24 }, "duration": 50 },
+            "boss 3.aseprite": { "frame": { "x": 48, "y": 0, "w": 16, "h": 24 }, "duration": 200 }
+        },
+        "meta": {
+            "image": "boss.png",
+            "frameTags": [
+                { "name": "walk", "from": 0, "to": 2, "direction": "forward" },
+                { "name": "back", "from": 0, "to": 2, "direction": "reverse" },
+                { "name": "bounce", "from": 0, "to": 3, "direction": "pingpong" }
+            ],
+            "slices": [
+                { "name": "hitbox", "keys": [
+                    { "frame": 0, "bounds": { "x": 2, "y": 2, "w": 12, "h": 20 } }
+                ] },
+                { "name": "feet", "keys": [
+                    { "frame": 0, "bounds": { "x": 4, "y": 8, "w": 8, "h": 16 }, "pivot": { "x": 4, "y": 12 } },
+                    { "frame": 2, "bounds": { "x": 2, "y": 4, "w": 8, "h": 16 }, "pivot": { "x": 2, "y": 16 } }
+                ] }
+            ]
+        }
+    }"#;
+
+    fn parse_sheet() -> SpriteSheet {
+        super::aseprite::parse("boss", "boss.json", SHEET, Path::new("sheets")).unwrap()
+    }
+
+    #[test]
+    fn ping_pong_does_not_repeat_the_ends() {
+        assert_eq!(
+            Direction::PingPong.frame_order(0, 3),
+            vec![0, 1, 2, 3, 2, 1]
+        );
+        assert_eq!(
+            Direction::PingPongReverse.frame_order(0, 2),
+            vec![2, 1, 0, 1]
+        );
+        assert_eq!(Direction::PingPong.frame_order(1, 2), vec![1, 2]);
+    }
+
+    #[test]
+    fn tags_give_frames_in_play_order() {
+        let sheet = parse_sheet();
+
+        assert_eq!(
+            sheet.image,
+            Path::new("sheets").join("boss.png").display().to_string()
+        );
+        let durations: Vec<u64> = sheet.frames.iter().map(|v| v.duration).collect();
+        assert_eq!(durations, vec![100, 50, 50, 200]);
+        assert_eq!(sheet.frames[1].rect.x, 16.0);
+
+        let animations: Vec<(&str, Vec<usize>)> = sheet
+            .animations
+            .iter()
+            .map(|v| (v.name.as_str(), v.frames.clone()))
+            .collect();
+        assert_eq!(
+            animations,
+            vec![
+                ("boss-walk", vec![0, 1, 2]),
+                ("boss-back", vec![2, 1, 0]),
+                ("boss-bounce", vec![0, 1, 2, 3, 2, 1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn anchor_is_the_pivot_of_the_first_frame() {
+        let sheet = parse_sheet();
+
+        // Key of frame 0: pivot (4 + 4, 8 + 12) in a 16x24 frame. "hitbox" has no pivot.
+        assert_eq!(
+            sheet.anchor(&sheet.animations[0]),
+            Some(Vec2::new(0.5, 20.0 / 24.0))
+        );
+        // "back" starts at frame 2. Key of frame 2: pivot (2 + 2, 4 + 16)
+        assert_eq!(
+            sheet.anchor(&sheet.animations[1]),
+            Some(Vec2::new(0.25, 20.0 / 24.0))
+        );
+    }
+}
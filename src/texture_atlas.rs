@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+
+use image::RgbaImage;
+
+use crate::asset_manifest::{AnimationDefinition, AssetError};
+use crate::image_assets::ContentPath;
+
+/// Width and height of an atlas. (pixel)
+pub const ATLAS_SIZE: u32 = 1024;
+/// Transparent space between frames. It stops a frame from drawing pixels of its neighbor.
+/// There is no padding after the last frame of a shelf and after the last shelf.
+const PADDING: u32 = 1;
+
+/// Where a texture is inside an atlas. (pixel)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AtlasRegion {
+    /// Index of the atlas in PackedAtlases.images
+    pub atlas: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct PackedAtlases {
+    pub images: Vec<RgbaImage>,
+    /// Texture key -> region. Texture that is bigger than the atlas is not in the list.
+    pub regions: HashMap<String, AtlasRegion>,
+}
+
+/// Texture keys that go into atlases: frames of animations from frame directories.
+/// A texture that is also a one frame animation with its own name can be drawn without Sprite. It is not packed.
+pub fn atlas_texture_keys(animations: &Vec<AnimationDefinition>) -> HashSet<String> {
+    let mut keys = HashSet::new();
+    for animation in animations.iter() {
+        if animation.frames.len() == 1 && animation.frames[0] == animation.name {
+            continue;
+        }
+
+        for frame in animation.frames.iter() {
+            keys.insert(frame.clone());
+        }
+    }
+
+    keys
+}
+
+/// Read png files of the contents. Unreadable file is an error and is not in the result.
+pub fn load_images(contents: &Vec<ContentPath>) -> (Vec<(String, RgbaImage)>, Vec<AssetError>) {
+    let mut images = vec![];
+    let mut errors = vec![];
+    for content in contents.iter() {
+        match image::open(content.path.as_str()) {
+            Ok(v) => images.push((content.key.clone(), v.to_rgba8())),
+            Err(e) => errors.push(AssetError {
+                path: content.path.clone(),
+                line: 0,
+                message: format!("Load texture error. {}", e),
+            }),
+        };
+    }
+
+    (images, errors)
+}
+
+/// Put images into atlases with shelf packing. The tallest images go first.
+/// The result is the same for the same images.
+///
+/// # Arguments:
+///
+/// * images - (texture key, image)
+/// * size - width and height of each atlas. The last atlas is cut to the used height.
+///
+pub fn pack(images: &Vec<(String, RgbaImage)>, size: u32) -> PackedAtlases {
+    let mut order: Vec<usize> = (0..images.len()).collect();
+    order.sort_by(|a, b| {
+        images[*b]
+            .1
+            .height()
+            .cmp(&images[*a].1.height())
+            .then(images[*a].0.cmp(&images[*b].0))
+    });
+
+    let mut atlases: Vec<RgbaImage> = vec![];
+    let mut regions = HashMap::new();
+    let mut x = 0;
+    let mut y = 0;
+    let mut shelf_height = 0;
+
+    for index in order.iter() {
+        let (key, image) = &images[*index];
+        let width = image.width();
+        let height = image.height();
+        if width > size || height > size {
+            continue;
+        }
+
+        if x + width > size {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+
+        if atlases.len() == 0 || y + height > size {
+            atlases.push(RgbaImage::new(size, size));
+            x = 0;
+            y = 0;
+            shelf_height = 0;
+        }
+
+        let atlas_index = atlases.len() - 1;
+        image::imageops::replace(&mut atlases[atlas_index], image, x, y);
+        regions.insert(
+            key.clone(),
+            AtlasRegion {
+                atlas: atlas_index,
+                x: x,
+                y: y,
+                width: image.width(),
+                height: image.height(),
+            },
+        );
+
+        x += width + PADDING;
+        shelf_height = shelf_height.max(height + PADDING);
+    }
+
+    // Remove unused rows of the last atlas.
+    match atlases.pop() {
+        Some(last) => {
+            let used_height = (y + shelf_height).max(1).min(size);
+            atlases.push(image::imageops::crop_imm(&last, 0, 0, size, used_height).to_image());
+        }
+        None => (),
+    };
+
+    PackedAtlases {
+        images: atlases,
+        regions: regions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::{atlas_texture_keys, load_images, pack, AtlasRegion, PackedAtlases, ATLAS_SIZE};
+    use crate::asset_manifest::AssetManifest;
+    use crate::image_assets::ContentPath;
+
+    /// Image with a different color in every pixel. "seed" makes images different from each other.
+    fn known_image(seed: u8, width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba([seed, x as u8, y as u8, 255]))
+    }
+
+    fn overlaps(a: &AtlasRegion, b: &AtlasRegion) -> bool {
+        a.atlas == b.atlas
+            && a.x < b.x + b.width
+            && b.x < a.x + a.width
+            && a.y < b.y + b.height
+            && b.y < a.y + a.height
+    }
+
+    /// Check every packed image: inside its atlas, no other region on it and the same pixels as the source.
+    fn assert_packed(packed: &PackedAtlases, images: &Vec<(String, RgbaImage)>) {
+        for (key, image) in images.iter() {
+            let region = match packed.regions.get(key) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            assert_eq!((region.width, region.height), image.dimensions(), "{}", key);
+
+            let atlas = &packed.images[region.atlas];
+            assert!(
+                region.x + region.width <= atlas.width()
+                    && region.y + region.height <= atlas.height(),
+                "{} is outside atlas {}: {:?}",
+                key,
+                region.atlas,
+                region
+            );
+
+            for (other_key, other) in packed.regions.iter() {
+                assert!(
+                    other_key == key || overlaps(region, other) == false,
+                    "{} overlaps {}",
+                    key,
+                    other_key
+                );
+            }
+
+            for (x, y, pixel) in image.enumerate_pixels() {
+                assert_eq!(
+                    atlas.get_pixel(region.x + x, region.y + y),
+                    pixel,
+                    "{}: pixel ({}, {}) in atlas {}",
+                    key,
+                    x,
+                    y,
+                    region.atlas
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pack_keeps_every_frame_in_bounds_without_overlap() {
+        let sizes = [
+            (8, 8),
+            (16, 4),
+            (4, 16),
+            (12, 12),
+            (31, 2),
+            (1, 1),
+            (10, 20),
+            (20, 10),
+            (7, 9),
+            (9, 7),
+        ];
+        let mut images: Vec<(String, RgbaImage)> = sizes
+            .iter()
+            .enumerate()
+            .map(|(index, (width, height))| {
+                (
+                    format!("frame-{}", index),
+                    known_image(index as u8, *width, *height),
+                )
+            })
+            .collect();
+        // Bigger than the atlas. It is left out.
+        images.push((String::from("too-big"), known_image(99, 40, 4)));
+
+        let packed = pack(&images, 32);
+
+        assert!(packed.images.len() > 1);
+        assert_eq!(packed.regions.len(), sizes.len());
+        assert!(packed.regions.contains_key("too-big") == false);
+        assert_packed(&packed, &images);
+    }
+
+    #[test]
+    fn pack_fits_frames_up_to_the_atlas_edge() {
+        let images = vec![
+            (String::from("full"), known_image(1, 32, 32)),
+            (String::from("left"), known_image(2, 15, 4)),
+            (String::from("right"), known_image(3, 16, 4)),
+        ];
+
+        let packed = pack(&images, 32);
+
+        assert_eq!(packed.images.len(), 2);
+        assert_eq!(
+            packed.regions["full"],
+            AtlasRegion {
+                atlas: 0,
+                x: 0,
+                y: 0,
+                width: 32,
+                height: 32,
+            }
+        );
+        // One pixel between the neighbors and none after the last one.
+        assert_eq!(
+            (packed.regions["left"].atlas, packed.regions["left"].x),
+            (1, 0)
+        );
+        assert_eq!(
+            (packed.regions["right"].atlas, packed.regions["right"].x),
+            (1, 16)
+        );
+        assert_packed(&packed, &images);
+    }
+
+    #[test]
+    fn pack_gives_the_same_regions_for_the_same_images() {
+        let images: Vec<(String, RgbaImage)> = (0..6)
+            .map(|index| {
+                (
+                    format!("frame-{}", index),
+                    known_image(index, 6 + index as u32, 10 - index as u32),
+                )
+            })
+            .collect();
+        let mut reversed = images.clone();
+        reversed.reverse();
+
+        assert_eq!(pack(&images, 24).regions, pack(&reversed, 24).regions);
+    }
+
+    #[test]
+    fn manifest_frames_are_the_same_as_their_png_files() {
+        let manifest = AssetManifest::load(crate::scenes::gameplay::ASSET_MANIFEST_PATH).unwrap();
+        let keys = atlas_texture_keys(&manifest.animations);
+        let contents: Vec<ContentPath> = manifest
+            .textures
+            .iter()
+            .filter(|content| keys.contains(&content.key))
+            .cloned()
+            .collect();
+
+        let (images, errors) = load_images(&contents);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(images.len() > 0);
+
+        let packed = pack(&images, ATLAS_SIZE);
+        assert_eq!(packed.regions.len(), images.len());
+        assert_packed(&packed, &images);
+    }
+}